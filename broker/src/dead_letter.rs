@@ -0,0 +1,253 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use errors::{Result, Status, STATUS_HEADER};
+
+use crate::{
+    event::{Acker, Event},
+    message::Message,
+    options::{DeadLetterPolicy, SubscribeOptions},
+    Broker, Subscriber,
+};
+
+/// the header of a dead letter carrying the topic it was delivered on
+pub const TOPIC_HEADER: &str = "vine-topic";
+
+/// the header of a dead letter carrying the times it was delivered
+pub const ATTEMPTS_HEADER: &str = "vine-attempts";
+
+/// the header of a dead letter carrying the JSON of the [`Status`] it
+/// failed with, its code is in [`STATUS_HEADER`]
+pub const ERROR_HEADER: &str = "vine-error";
+
+/// DeadLetterSubscriber wraps a [`Subscriber`] of a subscription without
+/// `auto_ack`, diverting the events nacked on their last delivery allowed by
+/// its [`DeadLetterPolicy`] onto the dead letter topic instead of delivering
+/// them again
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// # use broker::{dead_letter::*, memory::MemoryBroker, options::*, Broker, Subscriber};
+/// # use errors::Status;
+/// # async fn run() -> errors::Result<()> {
+/// let broker = Arc::new(MemoryBroker::new(None));
+/// let dlq = broker.subscribe("jobs.dlq").await?;
+/// let mut opts = SubscribeOptions::new();
+/// opts.with_auto_ack(false).with_requeue_on_error(true);
+/// let mut policy = DeadLetterPolicy::new("jobs.dlq");
+/// policy.with_max_deliveries(1);
+/// let sub = DeadLetterSubscriber::subscribe(broker.clone(), "jobs", opts, policy).await?;
+///
+/// broker.publish("jobs", b"poison").await?;
+/// let event = sub.next_event().await?;
+/// event.nack_with(&Status::bad_request("io.vine.jobs", "poison")).await?;
+/// let dead = dlq.next_message().await?;
+/// assert_eq!(dead.header(TOPIC_HEADER), Some("jobs"));
+/// assert_eq!(dead.header(errors::STATUS_HEADER), Some("400"));
+/// # Ok(())
+/// # }
+/// ```
+pub struct DeadLetterSubscriber {
+    broker: Arc<dyn Broker + Sync>,
+    inner: Box<dyn Subscriber + Send + Sync>,
+    policy: DeadLetterPolicy,
+}
+
+impl DeadLetterSubscriber {
+    pub fn new(
+        broker: Arc<dyn Broker + Sync>,
+        inner: Box<dyn Subscriber + Send + Sync>,
+        policy: DeadLetterPolicy,
+    ) -> Self {
+        DeadLetterSubscriber {
+            broker,
+            inner,
+            policy,
+        }
+    }
+
+    /// subscribes to `topic` of `broker` with `opts` and wraps the subscriber
+    pub async fn subscribe(
+        broker: Arc<dyn Broker + Sync>,
+        topic: &str,
+        opts: SubscribeOptions,
+        policy: DeadLetterPolicy,
+    ) -> Result<Self> {
+        let inner = broker.subscribe_with(topic, opts).await?;
+        Ok(Self::new(broker, inner, policy))
+    }
+}
+
+#[async_trait]
+impl Subscriber for DeadLetterSubscriber {
+    fn topic(&self) -> &str {
+        self.inner.topic()
+    }
+
+    /// the body of the next event, acked as read
+    async fn next(&self) -> Result<Vec<u8>> {
+        let m = self.next_message().await?;
+        Ok(match m.header.is_empty() {
+            true => m.body.to_vec(),
+            false => m.to_bytes().to_vec(),
+        })
+    }
+
+    async fn unsubscribe(&self) {
+        self.inner.unsubscribe().await
+    }
+
+    /// the message of the next event, acked as read. The events to nack
+    /// under the policy are read with [`Subscriber::next_event`].
+    async fn next_message(&self) -> Result<Message> {
+        let event = self.next_event().await?;
+        let m = event.message.clone();
+        event.ack().await?;
+        Ok(m)
+    }
+
+    async fn next_event(&self) -> Result<Event> {
+        let mut event = self.inner.next_event().await?;
+        let acker = DeadLetterAcker {
+            inner: event.take_acker(),
+            broker: self.broker.clone(),
+            policy: self.policy.clone(),
+            topic: event.topic.clone(),
+            message: event.message.clone(),
+            attempts: event.attempts,
+        };
+        Ok(event.with_acker(Box::new(acker)))
+    }
+}
+
+/// the [`Acker`] of a [`DeadLetterSubscriber`], settling the event with the
+/// acker of the wrapped subscriber once diverted
+struct DeadLetterAcker {
+    inner: Option<Box<dyn Acker + Send + Sync>>,
+    broker: Arc<dyn Broker + Sync>,
+    policy: DeadLetterPolicy,
+    topic: String,
+    message: Message,
+    attempts: u32,
+}
+
+impl DeadLetterAcker {
+    async fn fail(&self, status: Option<&Status>) -> Result<()> {
+        if self.attempts < self.policy.max_deliveries {
+            return match (&self.inner, status) {
+                (Some(inner), Some(status)) => inner.nack_with(status).await,
+                (Some(inner), None) => inner.nack().await,
+                (None, _) => Ok(()),
+            };
+        }
+
+        let mut m = self
+            .message
+            .clone()
+            .with_header(TOPIC_HEADER, self.topic.as_str())
+            .with_header(ATTEMPTS_HEADER, self.attempts.to_string());
+        if let Some(status) = status {
+            m = m
+                .with_header(STATUS_HEADER, (status.code() as i32).to_string())
+                .with_header(ERROR_HEADER, status.to_string());
+        }
+        self.broker.publish_message(&self.policy.topic, &m).await?;
+        self.ack().await
+    }
+}
+
+#[async_trait]
+impl Acker for DeadLetterAcker {
+    async fn ack(&self) -> Result<()> {
+        match &self.inner {
+            Some(inner) => inner.ack().await,
+            None => Ok(()),
+        }
+    }
+
+    async fn nack(&self) -> Result<()> {
+        self.fail(None).await
+    }
+
+    async fn nack_with(&self, status: &Status) -> Result<()> {
+        self.fail(Some(status)).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use errors::{Code, Result, Status, STATUS_HEADER};
+
+    use super::{DeadLetterSubscriber, ATTEMPTS_HEADER, ERROR_HEADER, TOPIC_HEADER};
+    use crate::{
+        memory::MemoryBroker,
+        options::{DeadLetterPolicy, SubscribeOptions},
+        Broker, Subscriber,
+    };
+
+    #[tokio::test]
+    async fn test_dead_letter() -> Result<()> {
+        let b = Arc::new(MemoryBroker::new(None));
+        let dlq = b.subscribe("jobs.dlq").await?;
+        let mut opts = SubscribeOptions::new();
+        opts.with_auto_ack(false).with_requeue_on_error(true);
+        let mut policy = DeadLetterPolicy::new("jobs.dlq");
+        policy.with_max_deliveries(3);
+        let sub = DeadLetterSubscriber::subscribe(b.clone(), "jobs", opts, policy).await?;
+        assert_eq!(sub.topic(), "jobs");
+
+        b.publish("jobs", b"poison").await?;
+        b.publish("jobs", b"fine").await?;
+        let failed = Status::internal_server_error("io.vine.jobs", "boom");
+        for attempts in 1..=3 {
+            let event = sub.next_event().await?;
+            assert_eq!(event.message.body, &b"poison"[..]);
+            assert_eq!(event.attempts, attempts);
+            event.nack_with(&failed).await?;
+        }
+        let fine = sub.next_event().await?;
+        assert_eq!(fine.message.body, &b"fine"[..]);
+        fine.ack().await?;
+
+        let dead = dlq.next_message().await?;
+        assert_eq!(dead.body, &b"poison"[..]);
+        assert_eq!(dead.header(TOPIC_HEADER), Some("jobs"));
+        assert_eq!(dead.header(ATTEMPTS_HEADER), Some("3"));
+        assert_eq!(dead.header(STATUS_HEADER), Some("500"));
+        let status = Status::from_str(dead.header(ERROR_HEADER).unwrap())?;
+        assert_eq!(status.code(), Code::InternalServerError);
+        assert_eq!(status.detail(), "boom");
+
+        // diverted and settled, not delivered again
+        b.publish("jobs", b"next").await?;
+        assert_eq!(sub.next_event().await?.message.body, &b"next"[..]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_next_settles() -> Result<()> {
+        let b = Arc::new(MemoryBroker::new(None));
+        let mut opts = SubscribeOptions::new();
+        opts.with_auto_ack(false).with_requeue_on_error(true);
+        let sub = DeadLetterSubscriber::subscribe(
+            b.clone(),
+            "jobs",
+            opts,
+            DeadLetterPolicy::new("jobs.dlq"),
+        )
+        .await?;
+
+        b.publish("jobs", b"1").await?;
+        b.publish("jobs", b"2").await?;
+        // acked as read, not delivered again
+        assert_eq!(sub.next().await?, b"1");
+        assert_eq!(sub.next_message().await?.body, &b"2"[..]);
+        b.publish("jobs", b"3").await?;
+        let event = sub.next_event().await?;
+        assert_eq!(event.message.body, &b"3"[..]);
+        assert_eq!(event.attempts, 1);
+        Ok(())
+    }
+}
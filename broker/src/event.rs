@@ -0,0 +1,102 @@
+use async_trait::async_trait;
+use errors::{Result, Status};
+
+use crate::message::Message;
+
+/// Acker settles an [`Event`] with the broker that delivered it. Only the
+/// memory and MQTT brokers implement it, which honour `requeue_on_error`,
+/// the gRPC client forwards the settlements to the broker of its server.
+/// The other brokers have no acknowledgements and refuse subscriptions
+/// without `auto_ack`.
+#[async_trait]
+pub trait Acker {
+    /// the event is processed and must not be delivered again
+    async fn ack(&self) -> Result<()>;
+    /// the event failed, it is delivered again if the subscription requeues
+    /// on errors
+    async fn nack(&self) -> Result<()>;
+
+    /// nacks the event for `status`, the error processing it
+    async fn nack_with(&self, status: &Status) -> Result<()> {
+        let _ = status;
+        self.nack().await
+    }
+}
+
+/// Event is a message delivered to a subscriber by
+/// [`Subscriber::next_event`], to be settled with [`Event::ack`] or
+/// [`Event::nack`] when its subscription does not acknowledge automatically.
+/// An event without an [`Acker`] was settled on delivery: acking or nacking
+/// it does nothing and guarantees nothing, it is not delivered again.
+///
+/// [`Subscriber::next_event`]: crate::Subscriber::next_event
+pub struct Event {
+    pub topic: String,
+    pub message: Message,
+    /// the times the event was delivered, this time included
+    pub attempts: u32,
+    acker: Option<Box<dyn Acker + Send + Sync>>,
+}
+
+impl Event {
+    /// an event settled already, with no acker
+    pub fn new(topic: impl Into<String>, message: Message) -> Self {
+        Event {
+            topic: topic.into(),
+            message,
+            attempts: 1,
+            acker: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    #[inline]
+    pub fn with_acker(mut self, acker: Box<dyn Acker + Send + Sync>) -> Self {
+        self.acker = Some(acker);
+        self
+    }
+
+    pub async fn ack(mut self) -> Result<()> {
+        match self.acker.take() {
+            Some(acker) => acker.ack().await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn nack(mut self) -> Result<()> {
+        match self.acker.take() {
+            Some(acker) => acker.nack().await,
+            None => Ok(()),
+        }
+    }
+
+    /// nacks the event for the error `status`
+    pub async fn nack_with(mut self, status: &Status) -> Result<()> {
+        match self.acker.take() {
+            Some(acker) => acker.nack_with(status).await,
+            None => Ok(()),
+        }
+    }
+
+    /// the acker settling the event, none when settled already
+    #[inline]
+    pub fn take_acker(&mut self) -> Option<Box<dyn Acker + Send + Sync>> {
+        self.acker.take()
+    }
+}
+
+impl std::fmt::Debug for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Event")
+            .field("topic", &self.topic)
+            .field("message", &self.message)
+            .field("attempts", &self.attempts)
+            .field("settled", &self.acker.is_none())
+            .finish()
+    }
+}
@@ -0,0 +1,397 @@
+pub mod server;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use errors::{err, Result, Status};
+use tokio::sync::{mpsc, Mutex as AsyncMutex, Notify};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tonic::Streaming;
+
+pub use self::server::serve;
+use crate::event::{Acker, Event};
+use crate::message::Message;
+use crate::options::{Options, SubscribeOptions};
+use crate::proto::{
+    self, broker_client::BrokerClient, subscribe_request::Request as SubscribeRequest, Settlement,
+};
+use crate::{Broker, Subscriber};
+
+/// how long connecting to the broker service may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// the implement of [`Broker`] over the gRPC broker service of a remote
+/// broker, see [`serve`], for the processes without access to the backend
+/// itself
+///
+/// ```rust
+/// # use broker::{grpc::GrpcBroker, options::Options, Broker};
+/// # async fn run() -> errors::Result<()> {
+/// let mut opts = Options::new();
+/// opts.with_addrs(vec!["127.0.0.1:11600".to_string()]);
+/// let broker = GrpcBroker::new(Some(opts)).await?;
+/// broker.publish("events", b"hello").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct GrpcBroker {
+    client: BrokerClient<Channel>,
+    options: Options,
+}
+
+impl GrpcBroker {
+    pub async fn new(opt: Option<Options>) -> Result<Self> {
+        let options = opt.unwrap_or_default();
+        let client = connect(&options).await?;
+        Ok(GrpcBroker { client, options })
+    }
+}
+
+/// connects to every address of `opts`, balancing the calls between them
+async fn connect(opts: &Options) -> Result<BrokerClient<Channel>> {
+    if opts.addrs.is_empty() {
+        return Err(err!("require at lease one broker address"));
+    }
+
+    let mut endpoints = Vec::with_capacity(opts.addrs.len());
+    for addr in &opts.addrs {
+        let uri = if addr.contains("://") {
+            addr.clone()
+        } else if opts.secure {
+            format!("https://{}", addr)
+        } else {
+            format!("http://{}", addr)
+        };
+
+        let mut endpoint = Endpoint::from_shared(uri)?;
+        if opts.secure {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new())?;
+        }
+        endpoints.push(endpoint);
+    }
+
+    let channel = if endpoints.len() == 1 {
+        let endpoint = endpoints.remove(0);
+        tokio::time::timeout(CONNECT_TIMEOUT, endpoint.connect())
+            .await
+            .map_err(Status::from)??
+    } else {
+        Channel::balance_list(endpoints.into_iter())
+    };
+
+    Ok(BrokerClient::new(channel))
+}
+
+/// the remote status of a failed call
+fn remote(s: tonic::Status) -> anyhow::Error {
+    Status::from(s).into()
+}
+
+#[async_trait]
+impl Broker for GrpcBroker {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        let opts = opt.unwrap_or_default();
+        self.client = connect(&opts).await?;
+        self.options = opts;
+        Ok(())
+    }
+
+    #[inline]
+    async fn options(&self) -> Options {
+        self.options.clone()
+    }
+
+    /// the headers of a `body` written by [`Message::to_bytes`] travel as
+    /// the headers of the proto message
+    async fn publish(&self, topic: &str, body: &[u8]) -> Result<()> {
+        self.publish_message(topic, &Message::from_bytes(body.to_vec()))
+            .await
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<Box<dyn Subscriber + Send + Sync>> {
+        self.subscribe_with(topic, SubscribeOptions::default())
+            .await
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        "grpc"
+    }
+
+    async fn publish_message(&self, topic: &str, m: &Message) -> Result<()> {
+        let req = proto::PublishRequest {
+            topic: topic.to_string(),
+            message: Some(m.into()),
+        };
+        self.client.clone().publish(req).await.map_err(remote)?;
+        Ok(())
+    }
+
+    async fn subscribe_with(
+        &self,
+        topic: &str,
+        opts: SubscribeOptions,
+    ) -> Result<Box<dyn Subscriber + Send + Sync>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let subscribe = SubscribeRequest::Subscribe(proto::Subscription {
+            topic: topic.to_string(),
+            auto_ack: opts.auto_ack,
+            requeue_on_error: opts.requeue_on_error,
+        });
+        // the receiver is alive until the call below
+        let _ = tx.send(proto::SubscribeRequest {
+            request: Some(subscribe),
+        });
+
+        let stream = self
+            .client
+            .clone()
+            .subscribe(UnboundedReceiverStream::new(rx))
+            .await
+            .map_err(remote)?
+            .into_inner();
+
+        Ok(Box::new(GrpcSubscriber {
+            topic: topic.to_string(),
+            opts,
+            stream: AsyncMutex::new(stream),
+            requests: Mutex::new(Some(tx)),
+            stopped: AtomicBool::new(false),
+            exit: Notify::new(),
+        }))
+    }
+}
+
+type Requests = mpsc::UnboundedSender<proto::SubscribeRequest>;
+
+/// the implement of [`Subscriber`] over the Subscribe stream of a
+/// [`GrpcBroker`], the remote subscription ends once unsubscribed and its
+/// events settled
+pub struct GrpcSubscriber {
+    topic: String,
+    opts: SubscribeOptions,
+    stream: AsyncMutex<Streaming<proto::Event>>,
+    /// the settlements, taken by unsubscribe
+    requests: Mutex<Option<Requests>>,
+    stopped: AtomicBool,
+    exit: Notify,
+}
+
+impl GrpcSubscriber {
+    async fn recv(&self) -> Result<proto::Event> {
+        let mut stream = self.stream.lock().await;
+        loop {
+            if self.stopped.load(Ordering::SeqCst) {
+                return Err(err!("subscriber of {} unsubscribed", self.topic));
+            }
+
+            let event = tokio::select! {
+                event = stream.message() => event,
+                _ = self.exit.notified() => continue,
+            };
+
+            return match event {
+                Ok(Some(event)) => Ok(event),
+                Ok(None) => Err(err!("subscriber of {} unsubscribed", self.topic)),
+                Err(e) => Err(remote(e)),
+            };
+        }
+    }
+
+    fn acker(&self, id: u64) -> Option<GrpcAcker> {
+        let requests = self
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()?;
+        Some(GrpcAcker {
+            id,
+            requests,
+            settled: AtomicBool::new(false),
+        })
+    }
+}
+
+#[async_trait]
+impl Subscriber for GrpcSubscriber {
+    fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    async fn next(&self) -> Result<Vec<u8>> {
+        let m = self.next_message().await?;
+        Ok(match m.header.is_empty() {
+            true => m.body.to_vec(),
+            false => m.to_bytes().to_vec(),
+        })
+    }
+
+    async fn unsubscribe(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        self.exit.notify_one();
+    }
+
+    async fn next_message(&self) -> Result<Message> {
+        let event = self.next_event().await?;
+        let m = event.message.clone();
+        event.ack().await?;
+        Ok(m)
+    }
+
+    async fn next_event(&self) -> Result<Event> {
+        let event = self.recv().await?;
+        let message = event.message.map(Message::from).unwrap_or_default();
+        let out = Event::new(event.topic, message).with_attempts(event.attempts);
+        if self.opts.auto_ack {
+            return Ok(out);
+        }
+        match self.acker(event.id) {
+            Some(acker) => Ok(out.with_acker(Box::new(acker))),
+            None => Err(err!("subscriber of {} unsubscribed", self.topic)),
+        }
+    }
+}
+
+/// the implement of [`Acker`] of a [`GrpcSubscriber`], settling the event
+/// with the server, which releases one dropped unsettled to the backend
+struct GrpcAcker {
+    id: u64,
+    requests: Requests,
+    settled: AtomicBool,
+}
+
+impl GrpcAcker {
+    fn settle(&self, settlement: Settlement, status: Option<&Status>) -> Result<()> {
+        self.settled.store(true, Ordering::SeqCst);
+        let settle = proto::Settle {
+            id: self.id,
+            settlement: settlement as i32,
+            status: status.map(|s| s.to_string()).unwrap_or_default(),
+        };
+        self.requests
+            .send(proto::SubscribeRequest {
+                request: Some(SubscribeRequest::Settle(settle)),
+            })
+            .map_err(|_| err!("the subscription of event {} ended", self.id))
+    }
+}
+
+#[async_trait]
+impl Acker for GrpcAcker {
+    async fn ack(&self) -> Result<()> {
+        self.settle(Settlement::Ack, None)
+    }
+
+    async fn nack(&self) -> Result<()> {
+        self.settle(Settlement::Nack, None)
+    }
+
+    async fn nack_with(&self, status: &Status) -> Result<()> {
+        self.settle(Settlement::Nack, Some(status))
+    }
+}
+
+impl Drop for GrpcAcker {
+    fn drop(&mut self) {
+        if !self.settled.load(Ordering::SeqCst) {
+            let _ = self.settle(Settlement::Release, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::Duration;
+
+    use errors::{Result, Status};
+
+    use super::{serve, GrpcBroker};
+    use crate::{
+        memory::MemoryBroker,
+        message::Message,
+        options::{Options, SubscribeOptions},
+        Broker,
+    };
+
+    async fn client(addr: SocketAddr) -> GrpcBroker {
+        let mut opts = Options::new();
+        opts.with_addrs(vec![addr.to_string()]);
+        // the server task may not be listening yet
+        for _ in 0..50 {
+            if let Ok(b) = GrpcBroker::new(Some(opts.clone())).await {
+                return b;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("could not connect to {}", addr);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_broker() -> Result<()> {
+        let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let backend = MemoryBroker::new(None);
+        let server = tokio::spawn(serve(Box::new(backend.clone()), addr));
+        let b = client(addr).await;
+        assert_eq!(b.string().await, "grpc");
+
+        let sub = b.subscribe("events").await?;
+        let local = backend.subscribe("events").await?;
+        b.publish("events", b"hello").await?;
+        assert_eq!(sub.next().await?, b"hello");
+        // raw on the backend as well
+        assert_eq!(local.next().await?, b"hello");
+
+        let m = Message::new(&b"1"[..]).with_header("X-Sensor", "t1");
+        backend.publish_message("events", &m).await?;
+        let got = sub.next_message().await?;
+        assert_eq!(got, m);
+        assert_eq!(local.next_message().await?, m);
+
+        sub.unsubscribe().await;
+        assert!(sub.next().await.is_err());
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grpc_ack() -> Result<()> {
+        let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let server = tokio::spawn(serve(Box::new(MemoryBroker::new(None)), addr));
+        let b = client(addr).await;
+
+        let mut opts = SubscribeOptions::new();
+        opts.with_auto_ack(false).with_requeue_on_error(true);
+        let sub = b.subscribe_with("jobs", opts).await?;
+        // one at a time, the server reads ahead of the settlements
+        b.publish("jobs", b"1").await?;
+
+        let first = sub.next_event().await?;
+        assert_eq!(first.topic, "jobs");
+        assert_eq!(first.message.body, &b"1"[..]);
+        first
+            .nack_with(&Status::internal_server_error("io.vine.jobs", "boom"))
+            .await?;
+        // dropped unsettled
+        drop(sub.next_event().await?);
+        let again = sub.next_event().await?;
+        assert_eq!(again.message.body, &b"1"[..]);
+        assert_eq!(again.attempts, 3);
+        again.ack().await?;
+        b.publish("jobs", b"2").await?;
+        let again = sub.next_event().await?;
+        assert_eq!(again.message.body, &b"2"[..]);
+        again.ack().await?;
+
+        server.abort();
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use errors::{Result, Status};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Request, Response, Streaming};
+
+use crate::event::Acker;
+use crate::message::Message;
+use crate::options::SubscribeOptions;
+use crate::proto::{
+    self,
+    broker_server::{Broker as BrokerRpc, BrokerServer},
+    subscribe_request::Request as SubscribeRequest,
+    Settlement,
+};
+use crate::Broker;
+
+/// how many events may queue for a slow client
+const SUBSCRIBE_BUFFER: usize = 64;
+
+/// serves `inner` as the gRPC broker service on `addr` until the server fails
+pub async fn serve(inner: Box<dyn Broker + Sync>, addr: SocketAddr) -> Result<()> {
+    let service = BrokerService {
+        inner: Arc::new(inner),
+    };
+
+    Server::builder()
+        .add_service(BrokerServer::new(service))
+        .serve(addr)
+        .await?;
+
+    Ok(())
+}
+
+/// exposes a [`Broker`] backend over the proto
+struct BrokerService {
+    inner: Arc<Box<dyn Broker + Sync>>,
+}
+
+/// the grpc status of a failed backend call
+fn status(e: anyhow::Error) -> tonic::Status {
+    match e.downcast::<Status>() {
+        Ok(s) => s.into(),
+        Err(e) => Status::internal_server_error("io.vine.broker".to_string(), e.to_string()).into(),
+    }
+}
+
+/// settles the event of `settle` delivered to the client, a failed settlement
+/// is left to the backend to deliver again
+async fn settle(ackers: &mut HashMap<u64, Box<dyn Acker + Send + Sync>>, settle: proto::Settle) {
+    let acker = match ackers.remove(&settle.id) {
+        Some(acker) => acker,
+        None => return,
+    };
+    let _ = match Settlement::from_i32(settle.settlement) {
+        Some(Settlement::Ack) => acker.ack().await,
+        Some(Settlement::Nack) if !settle.status.is_empty() => {
+            match Status::from_str(settle.status) {
+                Ok(s) => acker.nack_with(&s).await,
+                Err(_) => acker.nack().await,
+            }
+        }
+        Some(Settlement::Nack) => acker.nack().await,
+        // dropped unsettled by the client, as the acker is here
+        _ => Ok(()),
+    };
+}
+
+#[tonic::async_trait]
+impl BrokerRpc for BrokerService {
+    async fn publish(
+        &self,
+        request: Request<proto::PublishRequest>,
+    ) -> std::result::Result<Response<proto::Empty>, tonic::Status> {
+        let request = request.into_inner();
+        let m = Message::from(request.message.unwrap_or_default());
+        // raw bodies stay raw for the subscribers of the backend
+        let r = match m.header.is_empty() {
+            true => self.inner.publish(&request.topic, &m.body).await,
+            false => self.inner.publish_message(&request.topic, &m).await,
+        };
+        r.map_err(status)?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    type SubscribeStream = ReceiverStream<std::result::Result<proto::Event, tonic::Status>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<Streaming<proto::SubscribeRequest>>,
+    ) -> std::result::Result<Response<Self::SubscribeStream>, tonic::Status> {
+        let mut requests = request.into_inner();
+        let subscription = match requests.message().await? {
+            Some(proto::SubscribeRequest {
+                request: Some(SubscribeRequest::Subscribe(s)),
+            }) => s,
+            _ => {
+                return Err(tonic::Status::invalid_argument(
+                    "the first request must subscribe",
+                ))
+            }
+        };
+
+        let mut opts = SubscribeOptions::new();
+        opts.with_auto_ack(subscription.auto_ack)
+            .with_requeue_on_error(subscription.requeue_on_error);
+        let sub = self
+            .inner
+            .subscribe_with(&subscription.topic, opts)
+            .await
+            .map_err(status)?;
+
+        let (tx, rx) = mpsc::channel(SUBSCRIBE_BUFFER);
+        tokio::spawn(async move {
+            // the events delivered to the client and not settled yet
+            let mut ackers = HashMap::new();
+            let mut id = 0;
+            loop {
+                tokio::select! {
+                    event = sub.next_event() => {
+                        let mut event = match event {
+                            Ok(event) => event,
+                            Err(e) => {
+                                let _ = tx.send(Err(status(e))).await;
+                                break;
+                            }
+                        };
+                        id += 1;
+                        if let Some(acker) = event.take_acker() {
+                            ackers.insert(id, acker);
+                        }
+                        let item = proto::Event {
+                            id,
+                            topic: event.topic.clone(),
+                            message: Some((&event.message).into()),
+                            attempts: event.attempts,
+                        };
+                        if tx.send(Ok(item)).await.is_err() {
+                            break;
+                        }
+                    }
+                    r = requests.message() => match r {
+                        Ok(Some(proto::SubscribeRequest {
+                            request: Some(SubscribeRequest::Settle(s)),
+                        })) => settle(&mut ackers, s).await,
+                        Ok(Some(_)) => {}
+                        // unsubscribed or gone
+                        Ok(None) | Err(_) => break,
+                    },
+                    // stop as soon as the client goes away
+                    _ = tx.closed() => break,
+                }
+            }
+            sub.unsubscribe().await;
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
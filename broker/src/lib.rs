@@ -1,4 +1,97 @@
+pub mod dead_letter;
 
+pub mod event;
+
+pub mod grpc;
+
+pub mod memory;
+
+pub mod message;
+
+pub mod mqtt;
+
+pub mod options;
+
+pub mod proto;
+
+pub mod typed;
+
+use async_trait::async_trait;
+use errors::{bail, Result};
+
+use self::{
+    event::Event,
+    message::Message,
+    options::{Options, SubscribeOptions},
+};
+
+/// Broker publishes messages onto topics and delivers them to the
+/// subscribers of those topics
+#[async_trait]
+pub trait Broker: Send {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()>;
+    async fn options(&self) -> Options;
+    /// delivers `body` to every current subscriber of `topic`, having none
+    /// is not an error
+    async fn publish(&self, topic: &str, body: &[u8]) -> Result<()>;
+    async fn subscribe(&self, topic: &str) -> Result<Box<dyn Subscriber + Send + Sync>>;
+    async fn string(&self) -> &'static str;
+
+    /// publishes `m` with its headers, read back by
+    /// [`Subscriber::next_message`]
+    async fn publish_message(&self, topic: &str, m: &Message) -> Result<()>
+    where
+        Self: Sync,
+    {
+        self.publish(topic, &m.to_bytes()).await
+    }
+
+    /// subscribes to `topic` as [`Broker::subscribe`] with the settling of
+    /// `opts`, the events are read with [`Subscriber::next_event`]. A broker
+    /// without acknowledgements only subscribes with `auto_ack`.
+    async fn subscribe_with(
+        &self,
+        topic: &str,
+        opts: SubscribeOptions,
+    ) -> Result<Box<dyn Subscriber + Send + Sync>>
+    where
+        Self: Sync,
+    {
+        if !opts.auto_ack {
+            bail!("broker {} has no acknowledgements", self.string().await);
+        }
+        self.subscribe(topic).await
+    }
+}
+
+/// Subscriber receives the messages published onto one topic after it
+/// subscribed
+#[async_trait]
+pub trait Subscriber {
+    fn topic(&self) -> &str;
+    /// the next message, an error once unsubscribed
+    async fn next(&self) -> Result<Vec<u8>>;
+    async fn unsubscribe(&self);
+
+    /// the next message with its headers, as published by
+    /// [`Broker::publish_message`], a raw body without any
+    async fn next_message(&self) -> Result<Message>
+    where
+        Self: Sync,
+    {
+        Ok(Message::from_bytes(self.next().await?))
+    }
+
+    /// the next message as an [`Event`] to settle, one settled already
+    /// unless subscribed by [`Broker::subscribe_with`] without `auto_ack`.
+    /// [`Subscriber::next`] settles the messages it returns.
+    async fn next_event(&self) -> Result<Event>
+    where
+        Self: Sync,
+    {
+        Ok(Event::new(self.topic(), self.next_message().await?))
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,326 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+use async_trait::async_trait;
+use errors::{bail, Result};
+use tokio::sync::{mpsc, Mutex as AsyncMutex, Notify};
+
+use crate::event::{Acker, Event};
+use crate::message::Message;
+use crate::options::{Options, SubscribeOptions};
+use crate::{Broker, Subscriber};
+
+/// topic -> (subscriber id, sender)
+type Subscribers = HashMap<String, Vec<(u64, mpsc::UnboundedSender<Vec<u8>>)>>;
+
+fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// the implement of [`Broker`] within the process, clones share the topics
+///
+/// ```rust
+/// # use broker::{memory::MemoryBroker, Broker};
+/// # async fn run() -> errors::Result<()> {
+/// let broker = MemoryBroker::new(None);
+/// let sub = broker.subscribe("events").await?;
+/// broker.publish("events", b"hello").await?;
+/// assert_eq!(sub.next().await?, b"hello");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MemoryBroker {
+    options: Options,
+    subscribers: Arc<Mutex<Subscribers>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl MemoryBroker {
+    pub fn new(opt: Option<Options>) -> Self {
+        MemoryBroker {
+            options: opt.unwrap_or_default(),
+            subscribers: Arc::new(Mutex::new(HashMap::new())),
+            next_id: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn subscriber(&self, topic: &str, opts: SubscribeOptions) -> MemorySubscriber {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        lock(&self.subscribers)
+            .entry(topic.to_string())
+            .or_default()
+            .push((id, tx));
+
+        MemorySubscriber {
+            topic: topic.to_string(),
+            id,
+            opts,
+            rx: AsyncMutex::new(rx),
+            redelivered: Arc::new(Redelivered::default()),
+            subscribers: Arc::downgrade(&self.subscribers),
+            stopped: AtomicBool::new(false),
+            exit: Notify::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Broker for MemoryBroker {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.options = opt.unwrap_or_default();
+        Ok(())
+    }
+
+    #[inline]
+    async fn options(&self) -> Options {
+        self.options.clone()
+    }
+
+    async fn publish(&self, topic: &str, body: &[u8]) -> Result<()> {
+        let mut subscribers = lock(&self.subscribers);
+        if let Some(subs) = subscribers.get_mut(topic) {
+            // the subscribers dropped without unsubscribing go here
+            subs.retain(|(_, tx)| tx.send(body.to_vec()).is_ok());
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<Box<dyn Subscriber + Send + Sync>> {
+        Ok(Box::new(
+            self.subscriber(topic, SubscribeOptions::default()),
+        ))
+    }
+
+    async fn subscribe_with(
+        &self,
+        topic: &str,
+        opts: SubscribeOptions,
+    ) -> Result<Box<dyn Subscriber + Send + Sync>> {
+        Ok(Box::new(self.subscriber(topic, opts)))
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        "memory"
+    }
+}
+
+/// the events of a [`MemorySubscriber`] to deliver again, before the
+/// published ones, with the times they were delivered
+#[derive(Default)]
+struct Redelivered {
+    bodies: Mutex<VecDeque<(Vec<u8>, u32)>>,
+    notify: Notify,
+}
+
+impl Redelivered {
+    fn push(&self, body: Vec<u8>, attempts: u32) {
+        lock(&self.bodies).push_back((body, attempts));
+        self.notify.notify_one();
+    }
+}
+
+/// the implement of [`Subscriber`] of a [`MemoryBroker`]
+pub struct MemorySubscriber {
+    topic: String,
+    id: u64,
+    opts: SubscribeOptions,
+    rx: AsyncMutex<mpsc::UnboundedReceiver<Vec<u8>>>,
+    redelivered: Arc<Redelivered>,
+    subscribers: Weak<Mutex<Subscribers>>,
+    stopped: AtomicBool,
+    exit: Notify,
+}
+
+#[async_trait]
+impl Subscriber for MemorySubscriber {
+    fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    async fn next(&self) -> Result<Vec<u8>> {
+        Ok(self.recv().await?.0)
+    }
+
+    async fn unsubscribe(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(subscribers) = self.subscribers.upgrade() {
+            if let Some(subs) = lock(&subscribers).get_mut(&self.topic) {
+                subs.retain(|(id, _)| *id != self.id);
+            }
+        }
+        self.exit.notify_one();
+    }
+
+    async fn next_event(&self) -> Result<Event> {
+        let (body, attempts) = self.recv().await?;
+        let event =
+            Event::new(&self.topic, Message::from_bytes(body.clone())).with_attempts(attempts + 1);
+        if self.opts.auto_ack {
+            return Ok(event);
+        }
+        Ok(event.with_acker(Box::new(MemoryAcker {
+            body: Mutex::new(Some(body)),
+            attempts: attempts + 1,
+            requeue_on_error: self.opts.requeue_on_error,
+            redelivered: Arc::downgrade(&self.redelivered),
+        })))
+    }
+}
+
+impl MemorySubscriber {
+    /// the next body with the times it was delivered before
+    async fn recv(&self) -> Result<(Vec<u8>, u32)> {
+        let mut rx = self.rx.lock().await;
+        loop {
+            if self.stopped.load(Ordering::SeqCst) {
+                bail!("subscriber of {} unsubscribed", self.topic);
+            }
+            if let Some(body) = lock(&self.redelivered.bodies).pop_front() {
+                return Ok(body);
+            }
+
+            let body = tokio::select! {
+                body = rx.recv() => body,
+                _ = self.exit.notified() => continue,
+                _ = self.redelivered.notify.notified() => continue,
+            };
+
+            match body {
+                Some(body) => return Ok((body, 0)),
+                None => bail!("subscriber of {} unsubscribed", self.topic),
+            }
+        }
+    }
+}
+
+/// the implement of [`Acker`] of a [`MemorySubscriber`], an event dropped
+/// unsettled is delivered again
+struct MemoryAcker {
+    /// the body until settled
+    body: Mutex<Option<Vec<u8>>>,
+    attempts: u32,
+    requeue_on_error: bool,
+    redelivered: Weak<Redelivered>,
+}
+
+impl MemoryAcker {
+    fn redeliver(&self) {
+        let body = lock(&self.body).take();
+        if let (Some(body), Some(redelivered)) = (body, self.redelivered.upgrade()) {
+            redelivered.push(body, self.attempts);
+        }
+    }
+}
+
+#[async_trait]
+impl Acker for MemoryAcker {
+    async fn ack(&self) -> Result<()> {
+        lock(&self.body).take();
+        Ok(())
+    }
+
+    async fn nack(&self) -> Result<()> {
+        if self.requeue_on_error {
+            self.redeliver();
+        } else {
+            lock(&self.body).take();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MemoryAcker {
+    fn drop(&mut self) {
+        self.redeliver();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use errors::Result;
+
+    use super::MemoryBroker;
+    use crate::{message::Message, options::SubscribeOptions, Broker};
+
+    #[tokio::test]
+    async fn test_publish_subscribe() -> Result<()> {
+        let b = MemoryBroker::new(None);
+        // nobody listens yet
+        b.publish("events", b"lost").await?;
+
+        let first = b.subscribe("events").await?;
+        let second = b.subscribe("events").await?;
+        let other = b.subscribe("other").await?;
+        b.publish("events", b"1").await?;
+        b.publish("other", b"2").await?;
+
+        assert_eq!(first.next().await?, b"1");
+        assert_eq!(second.next().await?, b"1");
+        assert_eq!(other.next().await?, b"2");
+        assert_eq!(first.topic(), "events");
+
+        first.unsubscribe().await;
+        assert!(first.next().await.is_err());
+        b.publish("events", b"3").await?;
+        assert_eq!(second.next().await?, b"3");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_messages() -> Result<()> {
+        let b = MemoryBroker::new(None);
+        let sub = b.subscribe("readings").await?;
+        let m = Message::encode("application/json", &[21.5])?.with_header("X-Sensor", "t1");
+        b.publish_message("readings", &m).await?;
+        b.publish("readings", b"[1.0]").await?;
+
+        let got = sub.next_message().await?;
+        assert_eq!(got.header("x-sensor"), Some("t1"));
+        assert_eq!(got.decode::<Vec<f64>>()?, vec![21.5]);
+        // a raw body decodes as JSON
+        assert_eq!(sub.next_message().await?.decode::<Vec<f64>>()?, vec![1.0]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ack() -> Result<()> {
+        let b = MemoryBroker::new(None);
+        let mut opts = SubscribeOptions::new();
+        opts.with_auto_ack(false).with_requeue_on_error(true);
+        let sub = b.subscribe_with("jobs", opts).await?;
+        b.publish("jobs", b"1").await?;
+        b.publish("jobs", b"2").await?;
+
+        let first = sub.next_event().await?;
+        assert_eq!(first.topic, "jobs");
+        assert_eq!(first.message.body, &b"1"[..]);
+        first.nack().await?;
+        // dropped unsettled
+        drop(sub.next_event().await?);
+        let again = sub.next_event().await?;
+        assert_eq!(again.message.body, &b"1"[..]);
+        assert_eq!(again.attempts, 3);
+        again.ack().await?;
+        let again = sub.next_event().await?;
+        assert_eq!(again.message.body, &b"2"[..]);
+        again.ack().await?;
+
+        // dropped on nack without requeue_on_error, settled with auto_ack
+        let mut opts = SubscribeOptions::new();
+        opts.with_auto_ack(false);
+        let sub = b.subscribe_with("jobs", opts).await?;
+        let auto = b.subscribe("jobs").await?;
+        b.publish("jobs", b"3").await?;
+        b.publish("jobs", b"4").await?;
+        sub.next_event().await?.nack().await?;
+        drop(auto.next_event().await?);
+        assert_eq!(sub.next_event().await?.message.body, &b"4"[..]);
+        assert_eq!(auto.next_event().await?.message.body, &b"4"[..]);
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use codec::header::CONTENT_TYPE;
+use errors::{Code, Result, Status};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// the start of an encoded [`Message`], telling it from a raw body
+const MAGIC: &[u8] = b"VINE";
+
+/// the content type of a body without one
+const DEFAULT_CONTENT_TYPE: &str = "application/json";
+
+/// the content type of the bodies of [`Message::encode_proto`]
+const PROTOBUF: &str = "application/protobuf";
+
+/// Message is an event published onto a topic: its headers, as the content
+/// type of its body, and its body
+///
+/// ```rust
+/// # use broker::message::Message;
+/// let m = Message::encode("application/json", &["vine"])?;
+/// assert_eq!(m.content_type(), Some("application/json"));
+/// assert_eq!(m.decode::<Vec<String>>()?, vec!["vine"]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Message {
+    pub header: HashMap<String, String>,
+    pub body: Bytes,
+}
+
+impl Message {
+    pub fn new(body: impl Into<Bytes>) -> Self {
+        Message {
+            header: HashMap::new(),
+            body: body.into(),
+        }
+    }
+
+    /// encodes `t` with the codec registered for `content_type`, see
+    /// [`codec::registry`], and sets the content type header
+    pub fn encode<T: Serialize + ?Sized>(content_type: &str, t: &T) -> Result<Self> {
+        let codec =
+            codec::from_content_type(content_type).ok_or_else(|| unsupported(content_type))?;
+        let body = codec.encode_body(serde_json::to_value(t)?)?;
+        Ok(Message::new(body).with_header(CONTENT_TYPE, codec.content_type()))
+    }
+
+    /// decodes the body with the codec of its content type, JSON without
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T> {
+        let ct = self.content_type().unwrap_or(DEFAULT_CONTENT_TYPE);
+        let codec = codec::from_content_type(ct).ok_or_else(|| unsupported(ct))?;
+        let v = codec.decode_body(self.body.clone())?;
+        Ok(serde_json::from_value(v)?)
+    }
+
+    /// encodes the prost message `t` into the body as is, without the
+    /// detour through JSON of [`Message::encode`] which needs serde, and
+    /// sets the `application/protobuf` content type
+    pub fn encode_proto<T: prost::Message>(t: &T) -> Self {
+        Message::new(t.encode_to_vec()).with_header(CONTENT_TYPE, PROTOBUF)
+    }
+
+    /// decodes the body as the prost message `T`, a body without a content
+    /// type taken as protobuf
+    pub fn decode_proto<T: prost::Message + Default>(&self) -> Result<T> {
+        if let Some(ct) = self.content_type() {
+            if !codec::proto::CONTENT_TYPES.contains(&codec::registry::essence(ct).as_str()) {
+                return Err(unsupported(ct).into());
+            }
+        }
+        T::decode(self.body.clone())
+            .map_err(|e| Status::bad_request("io.vine.broker", &e.to_string()).into())
+    }
+
+    #[inline]
+    pub fn with_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.header.insert(key.into(), value.into());
+        self
+    }
+
+    /// the header `key`, whatever the case of its name
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.header
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// the [`CONTENT_TYPE`] header
+    pub fn content_type(&self) -> Option<&str> {
+        self.header(CONTENT_TYPE)
+    }
+
+    /// the message as the payload of a broker carrying bytes only: `VINE`,
+    /// the length of the JSON of the headers as a big endian u32, that JSON
+    /// and the body
+    pub fn to_bytes(&self) -> Bytes {
+        let header = serde_json::to_vec(&self.header).unwrap_or_default();
+        let mut out = BytesMut::with_capacity(MAGIC.len() + 4 + header.len() + self.body.len());
+        out.put_slice(MAGIC);
+        out.put_u32(header.len() as u32);
+        out.put_slice(&header);
+        out.put_slice(&self.body);
+        out.freeze()
+    }
+
+    /// the message of a payload written by [`Message::to_bytes`], or one
+    /// without headers for a raw body published as is
+    pub fn from_bytes(payload: impl Into<Bytes>) -> Self {
+        let mut payload = payload.into();
+        if !payload.starts_with(MAGIC) || payload.len() < MAGIC.len() + 4 {
+            return Message::new(payload);
+        }
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&payload[MAGIC.len()..MAGIC.len() + 4]);
+        let len = u32::from_be_bytes(len) as usize;
+        let start = MAGIC.len() + 4;
+        if payload.len() < start + len {
+            return Message::new(payload);
+        }
+        let header = match serde_json::from_slice(&payload[start..start + len]) {
+            Ok(header) => header,
+            Err(_) => return Message::new(payload),
+        };
+        Message {
+            header,
+            body: payload.split_off(start + len),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Message {
+    fn from(body: Vec<u8>) -> Self {
+        Message::new(body)
+    }
+}
+
+impl From<&[u8]> for Message {
+    fn from(body: &[u8]) -> Self {
+        Message::new(Bytes::copy_from_slice(body))
+    }
+}
+
+fn unsupported(content_type: &str) -> Status {
+    Status::new(
+        "io.vine.broker".to_string(),
+        format!("no codec for content type {}", content_type),
+        Code::UnsupportedMediaType,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use errors::{Code, Result, Status};
+    use serde::{Deserialize, Serialize};
+
+    use super::Message;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: String,
+        value: f64,
+    }
+
+    #[test]
+    fn test_codec() -> Result<()> {
+        let r = Reading {
+            sensor: "t1".to_string(),
+            value: 21.5,
+        };
+        let m = Message::encode("application/json; charset=utf-8", &r)?;
+        assert_eq!(m.header("content-type"), Some("application/json"));
+        assert_eq!(m.decode::<Reading>()?, r);
+
+        // JSON without a content type
+        let raw = Message::new(&br#"{"sensor":"t2","value":1}"#[..]);
+        assert_eq!(raw.decode::<Reading>()?.sensor, "t2");
+
+        let e = Message::encode("application/x-unknown", &r).unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<Status>().unwrap().code(),
+            Code::UnsupportedMediaType
+        );
+        Ok(())
+    }
+
+    /// a message without serde, only prost can encode it
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Sample {
+        #[prost(string, tag = "1")]
+        sensor: String,
+        #[prost(double, tag = "2")]
+        value: f64,
+    }
+
+    #[test]
+    fn test_proto() -> Result<()> {
+        let s = Sample {
+            sensor: "t1".to_string(),
+            value: 21.5,
+        };
+        let m = Message::encode_proto(&s);
+        assert_eq!(m.content_type(), Some("application/protobuf"));
+        assert_eq!(m.body, prost::Message::encode_to_vec(&s));
+        assert_eq!(m.decode_proto::<Sample>()?, s);
+        // through the bytes of a broker
+        assert_eq!(
+            Message::from_bytes(m.to_bytes()).decode_proto::<Sample>()?,
+            s
+        );
+        assert_eq!(Message::new(m.body).decode_proto::<Sample>()?, s);
+
+        let json = Message::encode("application/json", &["vine"])?;
+        let e = json.decode_proto::<Sample>().unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<Status>().unwrap().code(),
+            Code::UnsupportedMediaType
+        );
+        let e = Message::new(&b"\xff"[..])
+            .decode_proto::<Sample>()
+            .unwrap_err();
+        assert_eq!(e.downcast_ref::<Status>().unwrap().code(), Code::BadRequest);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes() {
+        let m = Message::new(&b"hello"[..]).with_header("X-Request-Id", "1");
+        assert_eq!(Message::from_bytes(m.to_bytes()), m);
+
+        // a raw body has no headers
+        let raw = Message::from_bytes(&b"hello"[..]);
+        assert!(raw.header.is_empty());
+        assert_eq!(raw.body, &b"hello"[..]);
+        let short = Message::from_bytes(&b"VINE\0\0\0\x09{}"[..]);
+        assert_eq!(short.body, &b"VINE\0\0\0\x09{}"[..]);
+    }
+}
@@ -0,0 +1,1052 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use errors::{bail, err, Result, Status};
+use rumqttc::tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use rumqttc::v5::mqttbytes::v5::{Packet, Publish, PublishProperties};
+use rumqttc::v5::mqttbytes::{self, QoS as MqttQoS};
+use rumqttc::v5::{
+    AsyncClient, ClientError, ConnectionError, Event as MqttEvent, EventLoop, MqttOptions,
+};
+use rumqttc::Transport;
+use tokio::sync::{mpsc, oneshot, Mutex as AsyncMutex, Notify};
+
+use crate::event::{Acker, Event};
+use crate::message::Message;
+use crate::options::{Options, QoS, SubscribeOptions};
+use crate::{Broker, Subscriber};
+
+/// how long connecting to the broker may take
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// how long to wait before connecting again to a broker lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// the port of an address without one, plain and over tls
+const DEFAULT_PORT: u16 = 1883;
+const DEFAULT_SECURE_PORT: u16 = 8883;
+
+/// the requests to the broker buffered before a publish waits
+const REQUEST_CAPACITY: usize = 64;
+
+fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn failed(e: ClientError) -> anyhow::Error {
+    err!("mqtt client stopped: {}", e)
+}
+
+/// the MQTT topic of a vine topic, its dot separated levels separated by
+/// slashes. The filters of subscriptions may have the `+` and `#`
+/// wildcards of MQTT, as `sensors.+.temperature`.
+fn mqtt_topic(topic: &str, filter: bool) -> Result<String> {
+    if topic.contains('/') {
+        bail!(
+            "topic {} has a /, the levels of a topic are split by dots",
+            topic
+        );
+    }
+    let out = topic.replace('.', "/");
+    let valid = match filter {
+        true => mqttbytes::valid_filter(&out),
+        false => !out.is_empty() && mqttbytes::valid_topic(&out),
+    };
+    if !valid {
+        bail!("topic {} is not a valid mqtt topic", topic);
+    }
+    Ok(out)
+}
+
+/// the vine topic of an MQTT topic
+fn vine_topic(topic: &str) -> String {
+    topic.replace('/', ".")
+}
+
+fn mqtt_qos(qos: QoS) -> MqttQoS {
+    match qos {
+        QoS::AtMostOnce => MqttQoS::AtMostOnce,
+        QoS::AtLeastOnce => MqttQoS::AtLeastOnce,
+        QoS::ExactlyOnce => MqttQoS::ExactlyOnce,
+    }
+}
+
+/// the message of a publish, its headers are the user properties
+fn message(p: &Publish) -> Message {
+    let header: std::collections::HashMap<_, _> = p
+        .properties
+        .iter()
+        .flat_map(|props| props.user_properties.iter().cloned())
+        .collect();
+    match header.is_empty() {
+        true => Message::from_bytes(p.payload.clone()),
+        false => Message {
+            header,
+            body: p.payload.clone(),
+        },
+    }
+}
+
+/// the implement of [`Broker`] over an MQTT 5 broker, for the services
+/// consuming the events of devices. The dot separated levels of the vine
+/// topics are the slash separated levels of the MQTT topics, the headers of
+/// a message its user properties. The messages are published at the QoS of
+/// the [`Options`], [`QoS::AtLeastOnce`] unless set, and each subscription
+/// is delivered at the lower of that and the QoS of its
+/// [`SubscribeOptions`].
+///
+/// ```rust
+/// # use broker::{mqtt::MqttBroker, options::*, Broker};
+/// # async fn run() -> errors::Result<()> {
+/// let mut opts = Options::new();
+/// opts.with_addrs(vec!["127.0.0.1:1883".to_string()]);
+/// let broker = MqttBroker::new(Some(opts)).await?;
+/// let mut sub = SubscribeOptions::new();
+/// sub.with_qos(QoS::AtMostOnce);
+/// let readings = broker.subscribe_with("sensors.+.temperature", sub).await?;
+/// broker.publish("sensors.t1.temperature", b"21.5").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MqttBroker {
+    inner: Arc<Inner>,
+    options: Options,
+}
+
+impl MqttBroker {
+    pub async fn new(opt: Option<Options>) -> Result<Self> {
+        let options = opt.unwrap_or_default();
+        let inner = connect(&options).await?;
+        Ok(MqttBroker { inner, options })
+    }
+}
+
+/// connects to the first address of `opts` accepting the connection
+async fn connect(opts: &Options) -> Result<Arc<Inner>> {
+    if opts.addrs.is_empty() {
+        bail!("require at least one broker address");
+    }
+    let mut last = None;
+    for addr in &opts.addrs {
+        match connect_to(addr, opts.secure).await {
+            Ok(inner) => return Ok(inner),
+            Err(e) => last = Some(e),
+        }
+    }
+    Err(last.unwrap_or_else(|| err!("no broker address connected")))
+}
+
+/// the host and port of `addr`, `host[:port]` with an optional `mqtt://`
+/// or `mqtts://`, the latter over tls
+fn parse_addr(addr: &str, secure: bool) -> Result<(String, u16, bool)> {
+    let (rest, secure) = if let Some(rest) = addr.strip_prefix("mqtts://") {
+        (rest, true)
+    } else if let Some(rest) = addr.strip_prefix("mqtt://") {
+        (rest, secure)
+    } else {
+        (addr, secure)
+    };
+    let default_port = match secure {
+        true => DEFAULT_SECURE_PORT,
+        false => DEFAULT_PORT,
+    };
+    let (host, port) = match rest.rsplit_once(':') {
+        // the colons of a v6 address are in brackets
+        Some((host, port)) if !port.contains(']') => {
+            let port = port
+                .parse()
+                .map_err(|_| err!("broker address {} has a malformed port", addr))?;
+            (host, port)
+        }
+        _ => (rest, default_port),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        bail!("broker address {} has no host", addr);
+    }
+    Ok((host.to_string(), port, secure))
+}
+
+/// the tls checking the broker against the roots of the platform
+fn tls() -> Result<Transport> {
+    let mut roots = RootCertStore::empty();
+    let (added, _) =
+        roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+    if added == 0 {
+        bail!("no root certificates to check the mqtt broker with");
+    }
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Transport::tls_with_config(config.into()))
+}
+
+/// a client id of its own for each connection
+fn client_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    format!(
+        "vine-{}-{}-{}",
+        std::process::id(),
+        nanos,
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+async fn connect_to(addr: &str, secure: bool) -> Result<Arc<Inner>> {
+    let (host, port, secure) = parse_addr(addr, secure)?;
+    let mut mqtt = MqttOptions::new(client_id(), host, port);
+    mqtt.set_manual_acks(true)
+        .set_clean_start(true)
+        .set_connection_timeout(CONNECT_TIMEOUT.as_secs());
+    if secure {
+        mqtt.set_transport(tls()?);
+    }
+
+    let (client, eventloop) = AsyncClient::new(mqtt, REQUEST_CAPACITY);
+    let inner = Arc::new(Inner {
+        client,
+        routes: Mutex::new(Vec::new()),
+        next_id: AtomicU64::new(0),
+    });
+    let (tx, rx) = oneshot::channel();
+    tokio::spawn(run(eventloop, Arc::downgrade(&inner), tx));
+    match tokio::time::timeout(CONNECT_TIMEOUT, rx)
+        .await
+        .map_err(Status::from)?
+    {
+        Ok(connected) => connected.map(|_| inner),
+        Err(_) => bail!("mqtt connection to {} stopped", addr),
+    }
+}
+
+/// drives the connection until the broker and its subscribers are dropped,
+/// connecting again when it is lost. Connecting the first time fails the
+/// connection, told to `connected`.
+async fn run(mut eventloop: EventLoop, inner: Weak<Inner>, connected: oneshot::Sender<Result<()>>) {
+    let mut connected = Some(connected);
+    loop {
+        let event = eventloop.poll().await;
+        let inner = match inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        match event {
+            Ok(MqttEvent::Incoming(Packet::ConnAck(ack))) => match connected.take() {
+                Some(tx) => {
+                    let _ = tx.send(Ok(()));
+                }
+                // a session not kept has none of the subscriptions
+                None if !ack.session_present => inner.resubscribe(),
+                None => {}
+            },
+            Ok(MqttEvent::Incoming(Packet::Publish(p))) => inner.route(p),
+            Ok(_) => {}
+            Err(ConnectionError::RequestsDone) => return,
+            Err(e) => {
+                if let Some(tx) = connected.take() {
+                    let _ = tx.send(Err(err!("mqtt connect: {}", e)));
+                    return;
+                }
+                drop(inner);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+/// the subscription of a [`MqttSubscriber`] to the publishes of `filter`
+struct Route {
+    id: u64,
+    filter: String,
+    qos: MqttQoS,
+    tx: mpsc::UnboundedSender<Delivery>,
+}
+
+/// the connection shared by a [`MqttBroker`], its clones and subscribers
+struct Inner {
+    client: AsyncClient,
+    routes: Mutex<Vec<Route>>,
+    next_id: AtomicU64,
+}
+
+impl Inner {
+    /// delivers `p` to the subscribers of the filters matching its topic
+    fn route(&self, p: Publish) {
+        let topic = String::from_utf8_lossy(&p.topic).into_owned();
+        let message = message(&p);
+        let mut routes = lock(&self.routes);
+        let matched = routes
+            .iter()
+            .filter(|r| mqttbytes::matches(&topic, &r.filter))
+            .count();
+        if matched == 0 {
+            let _ = self.client.try_ack(&p);
+            return;
+        }
+
+        let pending = Arc::new(Pending {
+            client: self.client.clone(),
+            publish: p,
+            unsettled: AtomicUsize::new(matched),
+        });
+        // the subscribers dropped without unsubscribing go here, their
+        // deliveries dropped are settled
+        routes.retain(|r| {
+            if !mqttbytes::matches(&topic, &r.filter) {
+                return true;
+            }
+            let delivery = Delivery {
+                topic: vine_topic(&topic),
+                message: message.clone(),
+                pending: Some(pending.clone()),
+            };
+            r.tx.send(delivery).is_ok()
+        });
+    }
+
+    /// the highest QoS of the subscribers of `filter`, none without any
+    fn qos(&self, filter: &str) -> Option<MqttQoS> {
+        lock(&self.routes)
+            .iter()
+            .filter(|r| r.filter == filter)
+            .map(|r| r.qos)
+            .max_by_key(|qos| *qos as u8)
+    }
+
+    /// subscribes to the filters again on a new session
+    fn resubscribe(&self) {
+        let mut filters: Vec<String> = lock(&self.routes)
+            .iter()
+            .map(|r| r.filter.clone())
+            .collect();
+        filters.sort();
+        filters.dedup();
+        for filter in filters {
+            if let Some(qos) = self.qos(&filter) {
+                let _ = self.client.try_subscribe(filter, qos);
+            }
+        }
+    }
+}
+
+/// a publish delivered to the subscribers, acknowledged to the broker once
+/// all its deliveries are settled
+struct Pending {
+    client: AsyncClient,
+    publish: Publish,
+    unsettled: AtomicUsize,
+}
+
+impl Pending {
+    /// settles one delivery, true when it was the last
+    fn settle(&self) -> bool {
+        self.unsettled.fetch_sub(1, Ordering::SeqCst) == 1
+    }
+}
+
+/// a publish delivered to one subscriber, settled when dropped
+struct Delivery {
+    topic: String,
+    message: Message,
+    pending: Option<Arc<Pending>>,
+}
+
+impl Delivery {
+    async fn settle(mut self) -> Result<()> {
+        match self.pending.take() {
+            Some(p) if p.settle() => p.client.ack(&p.publish).await.map_err(failed),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for Delivery {
+    fn drop(&mut self) {
+        if let Some(p) = self.pending.take() {
+            if p.settle() {
+                let _ = p.client.try_ack(&p.publish);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Broker for MqttBroker {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        let opts = opt.unwrap_or_default();
+        self.inner = connect(&opts).await?;
+        self.options = opts;
+        Ok(())
+    }
+
+    #[inline]
+    async fn options(&self) -> Options {
+        self.options.clone()
+    }
+
+    /// the headers of a `body` written by [`Message::to_bytes`] travel as
+    /// the user properties of the publish
+    async fn publish(&self, topic: &str, body: &[u8]) -> Result<()> {
+        self.publish_message(topic, &Message::from_bytes(body.to_vec()))
+            .await
+    }
+
+    async fn subscribe(&self, topic: &str) -> Result<Box<dyn Subscriber + Send + Sync>> {
+        self.subscribe_with(topic, SubscribeOptions::default())
+            .await
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        "mqtt"
+    }
+
+    async fn publish_message(&self, topic: &str, m: &Message) -> Result<()> {
+        let topic = mqtt_topic(topic, false)?;
+        let properties = PublishProperties {
+            user_properties: m
+                .header
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            ..Default::default()
+        };
+        self.inner
+            .client
+            .publish_with_properties(
+                topic,
+                mqtt_qos(self.options.qos),
+                false,
+                m.body.clone(),
+                properties,
+            )
+            .await
+            .map_err(failed)
+    }
+
+    /// the subscribers of the same topic share the subscription to the
+    /// broker, at the highest QoS of theirs
+    async fn subscribe_with(
+        &self,
+        topic: &str,
+        opts: SubscribeOptions,
+    ) -> Result<Box<dyn Subscriber + Send + Sync>> {
+        let filter = mqtt_topic(topic, true)?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        lock(&self.inner.routes).push(Route {
+            id,
+            filter: filter.clone(),
+            qos: mqtt_qos(opts.qos),
+            tx,
+        });
+
+        let sub = MqttSubscriber {
+            topic: topic.to_string(),
+            filter,
+            id,
+            opts,
+            rx: AsyncMutex::new(rx),
+            redelivered: Arc::new(Redelivered::default()),
+            inner: self.inner.clone(),
+            stopped: AtomicBool::new(false),
+            exit: Notify::new(),
+        };
+        let qos = self.inner.qos(&sub.filter).unwrap_or(MqttQoS::AtMostOnce);
+        if let Err(e) = self.inner.client.subscribe(sub.filter.clone(), qos).await {
+            sub.unsubscribe().await;
+            return Err(failed(e));
+        }
+        Ok(Box::new(sub))
+    }
+}
+
+/// the deliveries of a [`MqttSubscriber`] to deliver again, before the
+/// others, with the times they were delivered
+#[derive(Default)]
+struct Redelivered {
+    deliveries: Mutex<VecDeque<(Delivery, u32)>>,
+    notify: Notify,
+}
+
+impl Redelivered {
+    fn push(&self, delivery: Delivery, attempts: u32) {
+        lock(&self.deliveries).push_back((delivery, attempts));
+        self.notify.notify_one();
+    }
+}
+
+/// the implement of [`Subscriber`] of a [`MqttBroker`]. The events nacked
+/// are delivered again by the subscriber, a publish is acknowledged to the
+/// broker once settled.
+pub struct MqttSubscriber {
+    topic: String,
+    filter: String,
+    id: u64,
+    opts: SubscribeOptions,
+    rx: AsyncMutex<mpsc::UnboundedReceiver<Delivery>>,
+    redelivered: Arc<Redelivered>,
+    inner: Arc<Inner>,
+    stopped: AtomicBool,
+    exit: Notify,
+}
+
+impl MqttSubscriber {
+    /// the next delivery with the times it was delivered before
+    async fn recv(&self) -> Result<(Delivery, u32)> {
+        let mut rx = self.rx.lock().await;
+        loop {
+            if self.stopped.load(Ordering::SeqCst) {
+                bail!("subscriber of {} unsubscribed", self.topic);
+            }
+            if let Some(delivery) = lock(&self.redelivered.deliveries).pop_front() {
+                return Ok(delivery);
+            }
+
+            let delivery = tokio::select! {
+                delivery = rx.recv() => delivery,
+                _ = self.exit.notified() => continue,
+                _ = self.redelivered.notify.notified() => continue,
+            };
+
+            match delivery {
+                Some(delivery) => return Ok((delivery, 0)),
+                None => bail!("subscriber of {} unsubscribed", self.topic),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Subscriber for MqttSubscriber {
+    fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    async fn next(&self) -> Result<Vec<u8>> {
+        let m = self.next_message().await?;
+        Ok(match m.header.is_empty() {
+            true => m.body.to_vec(),
+            false => m.to_bytes().to_vec(),
+        })
+    }
+
+    /// stops the subscription, the broker is unsubscribed from the topic
+    /// once its last subscriber is. The events not read are settled.
+    async fn unsubscribe(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        let last = {
+            let mut routes = lock(&self.inner.routes);
+            routes.retain(|r| r.id != self.id);
+            !routes.iter().any(|r| r.filter == self.filter)
+        };
+        if last {
+            let _ = self.inner.client.unsubscribe(self.filter.clone()).await;
+        }
+        lock(&self.redelivered.deliveries).clear();
+        if let Ok(mut rx) = self.rx.try_lock() {
+            rx.close();
+            while rx.try_recv().is_ok() {}
+        }
+        self.exit.notify_one();
+    }
+
+    async fn next_message(&self) -> Result<Message> {
+        let event = self.next_event().await?;
+        let m = event.message.clone();
+        event.ack().await?;
+        Ok(m)
+    }
+
+    async fn next_event(&self) -> Result<Event> {
+        let (delivery, attempts) = self.recv().await?;
+        let event =
+            Event::new(&delivery.topic, delivery.message.clone()).with_attempts(attempts + 1);
+        if self.opts.auto_ack {
+            delivery.settle().await?;
+            return Ok(event);
+        }
+        Ok(event.with_acker(Box::new(MqttAcker {
+            delivery: Mutex::new(Some(delivery)),
+            attempts: attempts + 1,
+            requeue_on_error: self.opts.requeue_on_error,
+            redelivered: Arc::downgrade(&self.redelivered),
+        })))
+    }
+}
+
+/// the implement of [`Acker`] of a [`MqttSubscriber`], an event dropped
+/// unsettled is delivered again
+struct MqttAcker {
+    /// the delivery until settled
+    delivery: Mutex<Option<Delivery>>,
+    attempts: u32,
+    requeue_on_error: bool,
+    redelivered: Weak<Redelivered>,
+}
+
+impl MqttAcker {
+    fn redeliver(&self) {
+        let delivery = lock(&self.delivery).take();
+        if let (Some(delivery), Some(redelivered)) = (delivery, self.redelivered.upgrade()) {
+            redelivered.push(delivery, self.attempts);
+        }
+    }
+}
+
+#[async_trait]
+impl Acker for MqttAcker {
+    async fn ack(&self) -> Result<()> {
+        let delivery = lock(&self.delivery).take();
+        match delivery {
+            Some(delivery) => delivery.settle().await,
+            None => Ok(()),
+        }
+    }
+
+    async fn nack(&self) -> Result<()> {
+        if self.requeue_on_error {
+            self.redeliver();
+            return Ok(());
+        }
+        self.ack().await
+    }
+}
+
+impl Drop for MqttAcker {
+    fn drop(&mut self) {
+        self.redeliver();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use bytes::BytesMut;
+    use errors::Result;
+    use rumqttc::v5::mqttbytes::v5::{
+        ConnAck, ConnectReturnCode, Packet, PingResp, PubAck, PubComp, PubRec, PubRel, Publish,
+        SubAck, SubscribeReasonCode, UnsubAck, UnsubAckReason,
+    };
+    use rumqttc::v5::mqttbytes::{self, Error, QoS as MqttQoS};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+
+    use super::{mqtt_topic, parse_addr, vine_topic, MqttBroker};
+    use crate::{
+        message::Message,
+        options::{Options, QoS, SubscribeOptions},
+        Broker,
+    };
+
+    #[derive(Default)]
+    struct State {
+        /// the writers of the connections by id
+        clients: Vec<(u64, mpsc::UnboundedSender<Packet>)>,
+        /// (connection, filter, qos)
+        subscriptions: Vec<(u64, String, MqttQoS)>,
+        /// the publishes sent to the subscribers not acknowledged yet,
+        /// (connection, pkid) -> topic
+        unacked: Vec<((u64, u16), String)>,
+        /// the topics of the publishes acknowledged by their subscribers
+        acked: Vec<String>,
+        /// the topics and QoS of the publishes of the clients
+        published: Vec<(String, MqttQoS)>,
+        next_pkid: u16,
+    }
+
+    /// a broker of MQTT 5 for the tests, routing the publishes of its
+    /// clients to the connections subscribed and recording the
+    /// acknowledgements of those
+    #[derive(Clone, Default)]
+    struct TestServer {
+        state: Arc<Mutex<State>>,
+    }
+
+    impl TestServer {
+        async fn start() -> (TestServer, SocketAddr) {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let server = TestServer::default();
+            let accept = server.clone();
+            tokio::spawn(async move {
+                for id in 0.. {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    tokio::spawn(accept.clone().serve(id, stream));
+                }
+            });
+            (server, addr)
+        }
+
+        fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+            self.state.lock().unwrap()
+        }
+
+        fn subscriptions(&self) -> Vec<(String, MqttQoS)> {
+            let state = self.lock();
+            state
+                .subscriptions
+                .iter()
+                .map(|(_, filter, qos)| (filter.clone(), *qos))
+                .collect()
+        }
+
+        fn acked(&self) -> Vec<String> {
+            self.lock().acked.clone()
+        }
+
+        fn published(&self) -> Vec<(String, MqttQoS)> {
+            self.lock().published.clone()
+        }
+
+        fn unacked(&self) -> usize {
+            self.lock().unacked.len()
+        }
+
+        fn ack(&self, id: u64, pkid: u16) {
+            let mut state = self.lock();
+            if let Some(i) = state.unacked.iter().position(|(k, _)| *k == (id, pkid)) {
+                let (_, topic) = state.unacked.remove(i);
+                state.acked.push(topic);
+            }
+        }
+
+        fn route(&self, p: &Publish) {
+            let topic = String::from_utf8_lossy(&p.topic).into_owned();
+            let mut state = self.lock();
+            state.published.push((topic.clone(), p.qos));
+            let mut deliveries: Vec<(u64, MqttQoS)> = Vec::new();
+            for (id, filter, qos) in &state.subscriptions {
+                if !mqttbytes::matches(&topic, filter) {
+                    continue;
+                }
+                let qos = std::cmp::min_by_key(*qos, p.qos, |q| *q as u8);
+                match deliveries.iter_mut().find(|(d, _)| d == id) {
+                    Some(d) => d.1 = std::cmp::max_by_key(d.1, qos, |q| *q as u8),
+                    None => deliveries.push((*id, qos)),
+                }
+            }
+            for (id, qos) in deliveries {
+                let mut out = p.clone();
+                out.qos = qos;
+                out.dup = false;
+                out.pkid = 0;
+                if qos != MqttQoS::AtMostOnce {
+                    state.next_pkid = state.next_pkid % 1000 + 1;
+                    out.pkid = state.next_pkid;
+                    state.unacked.push(((id, out.pkid), topic.clone()));
+                }
+                if let Some((_, tx)) = state.clients.iter().find(|(c, _)| *c == id) {
+                    let _ = tx.send(Packet::Publish(out));
+                }
+            }
+        }
+
+        async fn serve(self, id: u64, stream: TcpStream) {
+            let (mut rd, mut wr) = stream.into_split();
+            let (tx, mut rx) = mpsc::unbounded_channel::<Packet>();
+            self.lock().clients.push((id, tx.clone()));
+            tokio::spawn(async move {
+                while let Some(p) = rx.recv().await {
+                    let mut buf = BytesMut::new();
+                    p.write(&mut buf, None).unwrap();
+                    if wr.write_all(&buf).await.is_err() {
+                        return;
+                    }
+                }
+            });
+
+            let mut buf = BytesMut::new();
+            loop {
+                let packet = match Packet::read(&mut buf, None) {
+                    Ok(packet) => packet,
+                    Err(Error::InsufficientBytes(_)) => match rd.read_buf(&mut buf).await {
+                        Ok(n) if n > 0 => continue,
+                        _ => break,
+                    },
+                    Err(e) => panic!("malformed packet: {:?}", e),
+                };
+                let reply = match packet {
+                    Packet::Connect(..) => Packet::ConnAck(ConnAck {
+                        session_present: false,
+                        code: ConnectReturnCode::Success,
+                        properties: None,
+                    }),
+                    Packet::Subscribe(s) => {
+                        let mut state = self.lock();
+                        for f in &s.filters {
+                            state
+                                .subscriptions
+                                .retain(|(c, p, _)| !(*c == id && *p == f.path));
+                            state.subscriptions.push((id, f.path.clone(), f.qos));
+                        }
+                        Packet::SubAck(SubAck {
+                            pkid: s.pkid,
+                            return_codes: s
+                                .filters
+                                .iter()
+                                .map(|f| SubscribeReasonCode::Success(f.qos))
+                                .collect(),
+                            properties: None,
+                        })
+                    }
+                    Packet::Unsubscribe(u) => {
+                        self.lock()
+                            .subscriptions
+                            .retain(|(c, p, _)| !(*c == id && u.filters.contains(p)));
+                        Packet::UnsubAck(UnsubAck {
+                            pkid: u.pkid,
+                            reasons: vec![UnsubAckReason::Success; u.filters.len()],
+                            properties: None,
+                        })
+                    }
+                    Packet::Publish(p) => {
+                        self.route(&p);
+                        match p.qos {
+                            MqttQoS::AtMostOnce => continue,
+                            MqttQoS::AtLeastOnce => Packet::PubAck(PubAck::new(p.pkid, None)),
+                            MqttQoS::ExactlyOnce => Packet::PubRec(PubRec::new(p.pkid, None)),
+                        }
+                    }
+                    Packet::PubRel(r) => Packet::PubComp(PubComp::new(r.pkid, None)),
+                    Packet::PubAck(a) => {
+                        self.ack(id, a.pkid);
+                        continue;
+                    }
+                    Packet::PubRec(r) => {
+                        self.ack(id, r.pkid);
+                        Packet::PubRel(PubRel::new(r.pkid, None))
+                    }
+                    Packet::PingReq(_) => Packet::PingResp(PingResp),
+                    Packet::Disconnect(_) => break,
+                    _ => continue,
+                };
+                let _ = tx.send(reply);
+            }
+            let mut state = self.lock();
+            state.clients.retain(|(c, _)| *c != id);
+            state.subscriptions.retain(|(c, _, _)| *c != id);
+        }
+    }
+
+    async fn broker(addr: SocketAddr) -> Result<MqttBroker> {
+        let mut opts = Options::new();
+        opts.with_addrs(vec![addr.to_string()]);
+        MqttBroker::new(Some(opts)).await
+    }
+
+    /// waits for `f` to hold, the broker sees the settlements after they
+    /// are made
+    async fn eventually(f: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if f() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("condition not met");
+    }
+
+    #[test]
+    fn test_topics() {
+        assert_eq!(
+            mqtt_topic("sensors.t1.temp", false).unwrap(),
+            "sensors/t1/temp"
+        );
+        assert_eq!(
+            mqtt_topic("sensors.+.temp", true).unwrap(),
+            "sensors/+/temp"
+        );
+        assert_eq!(mqtt_topic("sensors.#", true).unwrap(), "sensors/#");
+        assert_eq!(vine_topic("sensors/t1/temp"), "sensors.t1.temp");
+        // no wildcards to publish onto, no slashes in a vine topic
+        assert!(mqtt_topic("sensors.+.temp", false).is_err());
+        assert!(mqtt_topic("sensors/t1", true).is_err());
+        assert!(mqtt_topic("sensors.#.temp", true).is_err());
+        assert!(mqtt_topic("", false).is_err());
+
+        assert_eq!(
+            parse_addr("broker", false).unwrap(),
+            ("broker".to_string(), 1883, false)
+        );
+        assert_eq!(
+            parse_addr("broker:1884", false).unwrap(),
+            ("broker".to_string(), 1884, false)
+        );
+        assert_eq!(
+            parse_addr("mqtts://broker", false).unwrap(),
+            ("broker".to_string(), 8883, true)
+        );
+        assert_eq!(
+            parse_addr("[::1]:1884", false).unwrap(),
+            ("::1".to_string(), 1884, false)
+        );
+        assert!(parse_addr("broker:x", false).is_err());
+        assert!(parse_addr(":1883", false).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mqtt_broker() -> Result<()> {
+        let (server, addr) = TestServer::start().await;
+        let b = broker(addr).await?;
+        assert_eq!(b.string().await, "mqtt");
+
+        let mut opts = SubscribeOptions::new();
+        opts.with_qos(QoS::AtMostOnce);
+        let all = b.subscribe_with("sensors.+.temp", opts).await?;
+        let mut opts = SubscribeOptions::new();
+        opts.with_qos(QoS::ExactlyOnce);
+        let t1 = b.subscribe_with("sensors.t1.temp", opts).await?;
+        let other = b.subscribe("other").await?;
+        eventually(|| server.subscriptions().len() == 3).await;
+        let mut subscriptions = server.subscriptions();
+        subscriptions.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            subscriptions,
+            vec![
+                ("other".to_string(), MqttQoS::AtLeastOnce),
+                ("sensors/+/temp".to_string(), MqttQoS::AtMostOnce),
+                ("sensors/t1/temp".to_string(), MqttQoS::ExactlyOnce),
+            ]
+        );
+
+        let m = Message::new(&b"21.5"[..]).with_header("X-Sensor", "t1");
+        b.publish_message("sensors.t1.temp", &m).await?;
+        b.publish("sensors.t2.temp", b"19").await?;
+        b.publish("other", b"1").await?;
+
+        assert_eq!(t1.next_message().await?, m);
+        let event = all.next_event().await?;
+        assert_eq!(event.topic, "sensors.t1.temp");
+        assert_eq!(event.message, m);
+        let event = all.next_event().await?;
+        assert_eq!(event.topic, "sensors.t2.temp");
+        assert_eq!(event.message.body, &b"19"[..]);
+        assert_eq!(other.next().await?, b"1");
+        // the headers travel as user properties, in the publish of a body
+        // written by to_bytes as well
+        b.publish("other", &m.to_bytes()).await?;
+        assert_eq!(other.next_message().await?, m);
+        assert!(b.publish("sensors.+.temp", b"1").await.is_err());
+
+        // the QoS 0 deliveries are not acknowledged
+        eventually(|| server.unacked() == 0).await;
+        assert_eq!(server.acked(), vec!["sensors/t1/temp", "other", "other"]);
+        assert!(server
+            .published()
+            .iter()
+            .all(|(_, qos)| *qos == MqttQoS::AtLeastOnce));
+
+        t1.unsubscribe().await;
+        assert!(t1.next().await.is_err());
+        eventually(|| server.subscriptions().len() == 2).await;
+        b.publish("sensors.t1.temp", b"22").await?;
+        assert_eq!(all.next().await?, b"22");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mqtt_publish_qos() -> Result<()> {
+        let (server, addr) = TestServer::start().await;
+        let mut opts = Options::new();
+        opts.with_addrs(vec![addr.to_string()])
+            .with_qos(QoS::ExactlyOnce);
+        let b = MqttBroker::new(Some(opts.clone())).await?;
+        let mut sub = SubscribeOptions::new();
+        sub.with_qos(QoS::ExactlyOnce);
+        let jobs = b.subscribe_with("jobs", sub).await?;
+        b.publish("jobs", b"1").await?;
+        assert_eq!(jobs.next().await?, b"1");
+
+        opts.with_qos(QoS::AtMostOnce);
+        let lossy = MqttBroker::new(Some(opts)).await?;
+        lossy.publish("jobs", b"2").await?;
+        assert_eq!(jobs.next().await?, b"2");
+        assert_eq!(
+            server.published(),
+            vec![
+                ("jobs".to_string(), MqttQoS::ExactlyOnce),
+                ("jobs".to_string(), MqttQoS::AtMostOnce),
+            ]
+        );
+        // delivered at the lower QoS of the publish and the subscription
+        eventually(|| server.acked() == vec!["jobs"]).await;
+        assert_eq!(server.unacked(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mqtt_ack() -> Result<()> {
+        let (server, addr) = TestServer::start().await;
+        let b = broker(addr).await?;
+
+        let mut opts = SubscribeOptions::new();
+        opts.with_auto_ack(false).with_requeue_on_error(true);
+        let sub = b.subscribe_with("jobs", opts).await?;
+        eventually(|| server.subscriptions().len() == 1).await;
+        b.publish("jobs", b"1").await?;
+
+        let first = sub.next_event().await?;
+        assert_eq!(first.topic, "jobs");
+        assert_eq!(first.message.body, &b"1"[..]);
+        first.nack().await?;
+        // dropped unsettled
+        drop(sub.next_event().await?);
+        let again = sub.next_event().await?;
+        assert_eq!(again.message.body, &b"1"[..]);
+        assert_eq!(again.attempts, 3);
+        // acknowledged to the broker once settled
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(server.unacked(), 1);
+        assert!(server.acked().is_empty());
+        again.ack().await?;
+        eventually(|| server.acked() == vec!["jobs"]).await;
+
+        // settled on a nack without requeue_on_error, two subscribers of a
+        // publish settle it
+        let mut opts = SubscribeOptions::new();
+        opts.with_auto_ack(false);
+        let drops = b.subscribe_with("jobs", opts).await?;
+        b.publish("jobs", b"2").await?;
+        drops.next_event().await?.nack().await?;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(server.unacked(), 1);
+        sub.next_event().await?.ack().await?;
+        eventually(|| server.acked() == vec!["jobs", "jobs"]).await;
+
+        // the events not read are settled once unsubscribed
+        b.publish("jobs", b"3").await?;
+        let event = drops.next_event().await?;
+        assert_eq!(event.message.body, &b"3"[..]);
+        event.ack().await?;
+        sub.unsubscribe().await;
+        eventually(|| server.unacked() == 0).await;
+        assert_eq!(server.acked().len(), 3);
+        // the other subscriber keeps the subscription
+        assert_eq!(server.subscriptions().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mqtt_connect() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        assert!(broker(addr).await.is_err());
+        assert!(MqttBroker::new(None).await.is_err());
+    }
+}
@@ -1,5 +1,138 @@
 #[derive(Debug, Clone)]
-struct Options {
+pub struct Options {
     pub addrs: Vec<String>,
     pub secure: bool,
-}
\ No newline at end of file
+    /// the guarantee of delivery of the publishes to a broker with levels
+    /// of it, as MQTT, [`QoS::AtLeastOnce`] unless set. The others ignore it.
+    pub qos: QoS,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Options {
+    #[inline]
+    pub fn new() -> Self {
+        Options {
+            addrs: vec![],
+            secure: false,
+            qos: QoS::AtLeastOnce,
+        }
+    }
+
+    #[inline]
+    pub fn with_addrs(&mut self, addrs: Vec<String>) -> &mut Self {
+        self.addrs = addrs;
+        self
+    }
+
+    #[inline]
+    pub fn with_secure(&mut self, b: bool) -> &mut Self {
+        self.secure = b;
+        self
+    }
+
+    #[inline]
+    pub fn with_qos(&mut self, qos: QoS) -> &mut Self {
+        self.qos = qos;
+        self
+    }
+}
+
+/// SubscribeOptions are the options of [`Broker::subscribe_with`]
+///
+/// [`Broker::subscribe_with`]: crate::Broker::subscribe_with
+#[derive(Debug, Clone)]
+pub struct SubscribeOptions {
+    /// settles every event once delivered, so one lost by the consumer is
+    /// not delivered again. Without it an [`Event`] of the memory or MQTT
+    /// broker, directly or through gRPC, is delivered again until
+    /// acknowledged, at least once; the other brokers refuse it.
+    ///
+    /// [`Event`]: crate::event::Event
+    pub auto_ack: bool,
+    /// delivers an event again when [`Event::nack`] is called, drops it
+    /// otherwise, by the brokers with acknowledgements only
+    ///
+    /// [`Event::nack`]: crate::event::Event::nack
+    pub requeue_on_error: bool,
+    /// the guarantee of delivery asked of a broker with levels of it, as
+    /// MQTT, [`QoS::AtLeastOnce`] unless set. The others ignore it.
+    pub qos: QoS,
+}
+
+/// QoS is the guarantee of delivery of a publish or a subscription, the
+/// levels of MQTT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QoS {
+    /// delivered once or lost
+    AtMostOnce,
+    /// delivered until acknowledged, maybe more than once
+    AtLeastOnce,
+    /// delivered once
+    ExactlyOnce,
+}
+
+impl Default for SubscribeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubscribeOptions {
+    #[inline]
+    pub fn new() -> Self {
+        SubscribeOptions {
+            auto_ack: true,
+            requeue_on_error: false,
+            qos: QoS::AtLeastOnce,
+        }
+    }
+
+    #[inline]
+    pub fn with_auto_ack(&mut self, b: bool) -> &mut Self {
+        self.auto_ack = b;
+        self
+    }
+
+    #[inline]
+    pub fn with_requeue_on_error(&mut self, b: bool) -> &mut Self {
+        self.requeue_on_error = b;
+        self
+    }
+
+    #[inline]
+    pub fn with_qos(&mut self, qos: QoS) -> &mut Self {
+        self.qos = qos;
+        self
+    }
+}
+
+/// DeadLetterPolicy is the policy of a [`DeadLetterSubscriber`], diverting
+/// the events failed `max_deliveries` times onto `topic`
+///
+/// [`DeadLetterSubscriber`]: crate::dead_letter::DeadLetterSubscriber
+#[derive(Debug, Clone)]
+pub struct DeadLetterPolicy {
+    pub max_deliveries: u32,
+    pub topic: String,
+}
+
+impl DeadLetterPolicy {
+    #[inline]
+    pub fn new(topic: impl Into<String>) -> Self {
+        DeadLetterPolicy {
+            max_deliveries: 5,
+            topic: topic.into(),
+        }
+    }
+
+    #[inline]
+    pub fn with_max_deliveries(&mut self, n: u32) -> &mut Self {
+        self.max_deliveries = n;
+        self
+    }
+}
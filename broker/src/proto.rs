@@ -0,0 +1,24 @@
+//! the protobuf messages of the vine broker service, along with the gRPC
+//! client and server of its `Broker` service
+
+use crate::message;
+
+tonic::include_proto!("broker");
+
+impl From<&message::Message> for Message {
+    fn from(m: &message::Message) -> Self {
+        Message {
+            header: m.header.clone(),
+            body: m.body.to_vec(),
+        }
+    }
+}
+
+impl From<Message> for message::Message {
+    fn from(m: Message) -> Self {
+        message::Message {
+            header: m.header,
+            body: m.body.into(),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use errors::{Result, Status};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::{message::Message, Broker, Subscriber};
+
+/// the content type of a [`Publisher`] without one
+const DEFAULT_CONTENT_TYPE: &str = "application/json";
+
+/// Publisher publishes the values of `T` onto one topic, encoded with the
+/// codec of its content type
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// # use broker::{memory::MemoryBroker, typed::Publisher, Broker, Subscriber};
+/// # async fn run() -> errors::Result<()> {
+/// let broker = Arc::new(MemoryBroker::new(None));
+/// let sub = broker.subscribe("readings").await?;
+/// let p = Publisher::<f64>::new(broker, "readings");
+/// p.publish(&21.5).await?;
+/// assert_eq!(sub.next_message().await?.decode::<f64>()?, 21.5);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Publisher<T: ?Sized> {
+    broker: Arc<dyn Broker + Sync>,
+    topic: String,
+    content_type: String,
+    _t: PhantomData<fn(&T)>,
+}
+
+impl<T: Serialize + ?Sized> Publisher<T> {
+    pub fn new(broker: Arc<dyn Broker + Sync>, topic: impl Into<String>) -> Self {
+        Publisher {
+            broker,
+            topic: topic.into(),
+            content_type: DEFAULT_CONTENT_TYPE.to_string(),
+            _t: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+
+    #[inline]
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    pub async fn publish(&self, t: &T) -> Result<()> {
+        let m = Message::encode(&self.content_type, t)?;
+        self.broker.publish_message(&self.topic, &m).await
+    }
+}
+
+/// Subscription is the task of [`subscribe_typed`] handling the events of
+/// its topic
+pub struct Subscription {
+    subscriber: Arc<Box<dyn Subscriber + Send + Sync>>,
+    task: JoinHandle<()>,
+}
+
+impl Subscription {
+    #[inline]
+    pub fn topic(&self) -> &str {
+        self.subscriber.topic()
+    }
+
+    /// unsubscribes and waits for the handler of the current event
+    pub async fn unsubscribe(self) {
+        self.subscriber.unsubscribe().await;
+        let _ = self.task.await;
+    }
+}
+
+/// the status an event failed with, the bad request of a body not decoding
+/// as the type of the handler
+fn failure(e: anyhow::Error, decoding: bool) -> Status {
+    match e.downcast::<Status>() {
+        Ok(s) => s,
+        Err(e) if decoding => Status::bad_request("io.vine.broker".to_string(), e.to_string()),
+        Err(e) => Status::internal_server_error("io.vine.broker".to_string(), e.to_string()),
+    }
+}
+
+/// subscribes to `topic` of `broker` and calls `handler` with every event
+/// decoded as a `T` until unsubscribed. An event is acked once handled and
+/// nacked with the [`Status`] of its failure, when its body does not decode
+/// or the handler fails.
+///
+/// ```rust
+/// # use broker::{memory::MemoryBroker, typed::subscribe_typed};
+/// # async fn run() -> errors::Result<()> {
+/// let broker = MemoryBroker::new(None);
+/// let sub = subscribe_typed(&broker, "readings", |value: f64| async move {
+///     println!("read {}", value);
+///     Ok(())
+/// })
+/// .await?;
+/// sub.unsubscribe().await;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn subscribe_typed<T, H, F>(
+    broker: &(dyn Broker + Sync),
+    topic: &str,
+    handler: H,
+) -> Result<Subscription>
+where
+    T: DeserializeOwned + Send + 'static,
+    H: Fn(T) -> F + Send + Sync + 'static,
+    F: Future<Output = Result<()>> + Send,
+{
+    let subscriber = Arc::new(broker.subscribe(topic).await?);
+    let sub = subscriber.clone();
+    let task = tokio::spawn(async move {
+        // ends with the subscription
+        while let Ok(event) = sub.next_event().await {
+            let r = match event.message.decode::<T>() {
+                Ok(t) => handler(t).await.map_err(|e| failure(e, false)),
+                Err(e) => Err(failure(e, true)),
+            };
+            let _ = match r {
+                Ok(()) => event.ack().await,
+                Err(s) => event.nack_with(&s).await,
+            };
+        }
+    });
+
+    Ok(Subscription { subscriber, task })
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use errors::{bail, Result};
+    use serde::{Deserialize, Serialize};
+    use tokio::sync::mpsc;
+
+    use super::{subscribe_typed, Publisher};
+    use crate::{memory::MemoryBroker, Broker};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: String,
+        value: f64,
+    }
+
+    #[tokio::test]
+    async fn test_typed() -> Result<()> {
+        let b = Arc::new(MemoryBroker::new(None));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let sub = subscribe_typed(b.as_ref(), "readings", move |r: Reading| {
+            let tx = tx.clone();
+            async move {
+                if r.value < 0.0 {
+                    bail!("negative reading");
+                }
+                let _ = tx.send(r);
+                Ok(())
+            }
+        })
+        .await?;
+        assert_eq!(sub.topic(), "readings");
+
+        let p = Publisher::<Reading>::new(b.clone(), "readings");
+        assert_eq!(p.topic(), "readings");
+        let r = Reading {
+            sensor: "t1".to_string(),
+            value: 21.5,
+        };
+        p.publish(&Reading {
+            value: -1.0,
+            ..r.clone()
+        })
+        .await?;
+        // neither fails the subscription
+        b.publish("readings", b"not json").await?;
+        p.publish(&r).await?;
+        assert_eq!(rx.recv().await, Some(r));
+
+        let e = Publisher::<Reading>::new(b.clone(), "readings")
+            .with_content_type("application/x-unknown")
+            .publish(&Reading {
+                sensor: "t2".to_string(),
+                value: 0.0,
+            })
+            .await;
+        assert!(e.is_err());
+
+        sub.unsubscribe().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use broker::{message::Message, Broker};
+use bytes::{Buf, BufMut, Bytes};
+use errors::{err, Result, Status};
+use registry::selector::{RoundRobin, Selector};
+use registry::types::Node;
+use registry::SharedRegistry;
+use tokio::sync::{mpsc, oneshot, Mutex as AsyncMutex};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Streaming;
+
+use crate::options::{CallOptions, Options};
+use crate::{Client, Stream};
+
+/// the implement of [`Client`] calling the gRPC services of the nodes
+/// picked by a [`Selector`] over the registry
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// # use broker::memory::MemoryBroker;
+/// # use client::{grpc::GrpcClient, Client};
+/// # async fn run(registry: registry::SharedRegistry) -> errors::Result<()> {
+/// let client = GrpcClient::new(registry, Arc::new(MemoryBroker::new(None)), None);
+/// let rsp = client
+///     .call("io.vine.helloworld", "Greeter.Hello", Default::default(), None)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct GrpcClient {
+    selector: Arc<dyn Selector>,
+    broker: Arc<dyn Broker + Sync>,
+    options: Options,
+    /// address -> channel, shared by the calls to a node
+    channels: Arc<Mutex<HashMap<String, Channel>>>,
+}
+
+impl GrpcClient {
+    /// a client picking the nodes of `registry` in turn
+    pub fn new(
+        registry: SharedRegistry,
+        broker: Arc<dyn Broker + Sync>,
+        opt: Option<Options>,
+    ) -> Self {
+        GrpcClient {
+            selector: Arc::new(RoundRobin::new(registry, None)),
+            broker,
+            options: opt.unwrap_or_default(),
+            channels: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    #[inline]
+    pub fn with_selector(mut self, selector: Arc<dyn Selector>) -> Self {
+        self.selector = selector;
+        self
+    }
+
+    /// the channel to `node`, connected on its first call
+    fn channel(&self, node: &Node) -> Result<Channel> {
+        let addr = if node.port > 0 {
+            format!("{}:{}", node.address, node.port)
+        } else {
+            node.address.clone()
+        };
+
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(channel) = channels.get(&addr) {
+            return Ok(channel.clone());
+        }
+        let channel = Endpoint::from_shared(format!("http://{}", addr))?.connect_lazy()?;
+        channels.insert(addr, channel.clone());
+        Ok(channel)
+    }
+
+    /// calls `f` with a channel to a node of `service`, trying the next node
+    /// while it fails with a retryable status
+    async fn with_node<T, F, Fut>(&self, service: &str, opt: &CallOptions, f: F) -> Result<T>
+    where
+        F: Fn(Channel) -> Fut,
+        Fut: std::future::Future<Output = std::result::Result<T, Status>>,
+    {
+        let timeout = self.options.timeout_or(opt.timeout);
+        let mut attempts = 0;
+        loop {
+            let node = self
+                .selector
+                .select(service, opt.select.clone())
+                .await
+                .map_err(selection)?;
+            let r = match tokio::time::timeout(timeout, f(self.channel(&node)?)).await {
+                Ok(r) => r,
+                Err(e) => Err(Status::from(e)),
+            };
+
+            match r {
+                Ok(out) => {
+                    self.selector.mark(service, &node, None);
+                    return Ok(out);
+                }
+                Err(s) => {
+                    // the node is only to blame when unreachable or overloaded
+                    let failed = s.is_retryable();
+                    self.selector.mark(service, &node, failed.then_some(&s));
+                    if !s.is_retryable() || attempts >= self.options.retries {
+                        return Err(s.into());
+                    }
+                }
+            }
+            attempts += 1;
+        }
+    }
+}
+
+/// the gRPC path of `endpoint` of `service`, `/io.vine.helloworld.Greeter/Hello`
+/// for `Greeter.Hello`. An endpoint starting with `/` is a path already.
+pub fn method(service: &str, endpoint: &str) -> String {
+    if endpoint.starts_with('/') {
+        return endpoint.to_string();
+    }
+    let mut parts = endpoint.splitn(2, '.');
+    match (parts.next(), parts.next()) {
+        (Some(s), Some(m)) if service.is_empty() => format!("/{}/{}", s, m),
+        (Some(s), Some(m)) => format!("/{}.{}/{}", service, s, m),
+        _ => format!("/{}", endpoint),
+    }
+}
+
+fn path(service: &str, endpoint: &str) -> Result<PathAndQuery> {
+    let method = method(service, endpoint);
+    PathAndQuery::try_from(method.as_str()).map_err(|e| {
+        Status::bad_request(
+            "io.vine.client".to_string(),
+            format!("invalid endpoint {}: {}", endpoint, e),
+        )
+        .into()
+    })
+}
+
+/// the status of a failed selection, the registry errors as their status
+fn selection(e: anyhow::Error) -> anyhow::Error {
+    match e.downcast::<registry::Error>() {
+        Ok(e) => Status::from(e).into(),
+        Err(e) => e,
+    }
+}
+
+/// the status of a channel not ready for calls
+fn unavailable(e: tonic::transport::Error) -> Status {
+    Status::service_unavailable("io.vine.client".to_string(), e.to_string())
+}
+
+#[async_trait]
+impl Client for GrpcClient {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.options = opt.unwrap_or_default();
+        Ok(())
+    }
+
+    #[inline]
+    fn options(&self) -> Options {
+        self.options.clone()
+    }
+
+    async fn call(
+        &self,
+        service: &str,
+        endpoint: &str,
+        req: Bytes,
+        opt: Option<CallOptions>,
+    ) -> Result<Bytes> {
+        let opt = opt.unwrap_or_default();
+        let path = path(service, endpoint)?;
+        self.with_node(service, &opt, |channel| {
+            let (req, path) = (req.clone(), path.clone());
+            async move {
+                let mut grpc = tonic::client::Grpc::new(channel);
+                grpc.ready().await.map_err(unavailable)?;
+                let rsp = grpc
+                    .unary(tonic::Request::new(req), path, BytesCodec)
+                    .await?;
+                Ok(rsp.into_inner())
+            }
+        })
+        .await
+    }
+
+    async fn stream(
+        &self,
+        service: &str,
+        endpoint: &str,
+        opt: Option<CallOptions>,
+    ) -> Result<Box<dyn Stream>> {
+        let opt = opt.unwrap_or_default();
+        let path = path(service, endpoint)?;
+        // the timeout is of connecting to the node
+        let mut grpc = self
+            .with_node(service, &opt, |channel| async move {
+                let mut grpc = tonic::client::Grpc::new(channel);
+                grpc.ready().await.map_err(unavailable)?;
+                Ok(grpc)
+            })
+            .await?;
+
+        // a server may answer once it read the first request, the call goes
+        // on while the caller sends it
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (opened, opening) = oneshot::channel();
+        tokio::spawn(async move {
+            let req = tonic::Request::new(UnboundedReceiverStream::new(rx));
+            let rsp = grpc.streaming(req, path, BytesCodec).await;
+            let _ = opened.send(rsp.map(tonic::Response::into_inner));
+        });
+        Ok(Box::new(GrpcStream {
+            requests: Mutex::new(Some(tx)),
+            responses: AsyncMutex::new(Responses::Opening(opening)),
+        }))
+    }
+
+    async fn publish(&self, topic: &str, m: &Message) -> Result<()> {
+        self.broker.publish_message(topic, m).await
+    }
+
+    #[inline]
+    fn string(&self) -> &'static str {
+        "grpc"
+    }
+}
+
+/// the responses of a [`GrpcStream`]
+enum Responses {
+    Opening(oneshot::Receiver<std::result::Result<Streaming<Bytes>, tonic::Status>>),
+    Open(Box<Streaming<Bytes>>),
+    Failed,
+}
+
+/// the implement of [`Stream`] of a [`GrpcClient`]
+struct GrpcStream {
+    /// taken by close_send
+    requests: Mutex<Option<mpsc::UnboundedSender<Bytes>>>,
+    responses: AsyncMutex<Responses>,
+}
+
+#[async_trait]
+impl Stream for GrpcStream {
+    async fn send(&self, body: Bytes) -> Result<()> {
+        let requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        match requests.as_ref().map(|tx| tx.send(body)) {
+            Some(Ok(())) => Ok(()),
+            _ => Err(Status::bad_request(
+                "io.vine.client".to_string(),
+                "the stream is closed for sending".to_string(),
+            )
+            .into()),
+        }
+    }
+
+    async fn recv(&self) -> Result<Option<Bytes>> {
+        let mut responses = self.responses.lock().await;
+        if let Responses::Opening(opening) = &mut *responses {
+            *responses = match opening.await {
+                Ok(Ok(stream)) => Responses::Open(Box::new(stream)),
+                Ok(Err(s)) => {
+                    *responses = Responses::Failed;
+                    return Err(Status::from(s).into());
+                }
+                Err(_) => Responses::Failed,
+            };
+        }
+        match &mut *responses {
+            Responses::Open(stream) => Ok(stream.message().await.map_err(Status::from)?),
+            _ => Err(err!("the stream is closed")),
+        }
+    }
+
+    async fn close_send(&self) {
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+    }
+}
+
+/// the [`Codec`] of the bodies encoded already, passing them as they are
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BytesCodec;
+
+impl Codec for BytesCodec {
+    type Encode = Bytes;
+    type Decode = Bytes;
+    type Encoder = BytesCodec;
+    type Decoder = BytesCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        BytesCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        BytesCodec
+    }
+}
+
+impl Encoder for BytesCodec {
+    type Item = Bytes;
+    type Error = tonic::Status;
+
+    fn encode(
+        &mut self,
+        item: Bytes,
+        dst: &mut EncodeBuf<'_>,
+    ) -> std::result::Result<(), Self::Error> {
+        dst.put(item);
+        Ok(())
+    }
+}
+
+impl Decoder for BytesCodec {
+    type Item = Bytes;
+    type Error = tonic::Status;
+
+    fn decode(
+        &mut self,
+        src: &mut DecodeBuf<'_>,
+    ) -> std::result::Result<Option<Bytes>, Self::Error> {
+        Ok(Some(src.copy_to_bytes(src.remaining())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use broker::{grpc::serve, memory::MemoryBroker, message::Message, proto, Broker};
+    use errors::{Code, Result, Status};
+    use prost::Message as _;
+    use registry::{
+        memory::MemoryRegistry,
+        types::{Node, Service},
+        Registry,
+    };
+    use tokio::sync::Mutex;
+
+    use super::{method, GrpcClient};
+    use crate::{decode, options::CallOptions, Client, ClientExt};
+
+    #[test]
+    fn test_method() {
+        assert_eq!(
+            method("io.vine.helloworld", "Greeter.Hello"),
+            "/io.vine.helloworld.Greeter/Hello"
+        );
+        assert_eq!(method("", "Greeter.Hello"), "/Greeter/Hello");
+        assert_eq!(
+            method("broker", "/broker.Broker/Publish"),
+            "/broker.Broker/Publish"
+        );
+        assert_eq!(method("broker", "Publish"), "/Publish");
+    }
+
+    #[tokio::test]
+    async fn test_grpc_client() -> Result<()> {
+        let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let backend = MemoryBroker::new(None);
+        let server = tokio::spawn(serve(Box::new(backend.clone()), addr));
+
+        // the broker service is the package broker
+        let r = MemoryRegistry::new(None);
+        let s = Service {
+            name: "broker".to_string(),
+            version: "v1".to_string(),
+            nodes: vec![Node {
+                id: "1".to_string(),
+                address: addr.ip().to_string(),
+                port: addr.port() as i64,
+                metadata: Default::default(),
+            }],
+            ..Service::new()
+        };
+        r.register(&s, None).await?;
+        let c = GrpcClient::new(
+            Arc::new(Mutex::new(Box::new(r))),
+            Arc::new(backend.clone()),
+            None,
+        );
+        assert_eq!(c.string(), "grpc");
+
+        let sub = backend.subscribe("events").await?;
+        let req = proto::PublishRequest {
+            topic: "events".to_string(),
+            message: Some((&Message::new(&b"hello"[..])).into()),
+        };
+        // the server task may not be listening yet, a refused call would
+        // blacklist the node
+        for _ in 0..50 {
+            if tokio::net::TcpStream::connect(addr).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let mut opt = CallOptions::new();
+        opt.with_timeout(Duration::from_secs(2));
+        let _: proto::Empty = c
+            .call_proto("broker", "Broker.Publish", &req, Some(opt))
+            .await?;
+        assert_eq!(sub.next().await?, b"hello");
+
+        let e = c
+            .call("broker", "Broker.Missing", Default::default(), None)
+            .await
+            .unwrap_err();
+        assert_eq!(e.downcast::<Status>()?.code(), Code::NotImplementedError);
+        let e = c
+            .call("missing", "Broker.Publish", Default::default(), None)
+            .await;
+        assert_eq!(e.unwrap_err().downcast::<Status>()?.code(), Code::NotFound);
+
+        let stream = c.stream("broker", "Broker.Subscribe", None).await?;
+        let subscribe = proto::SubscribeRequest {
+            request: Some(proto::subscribe_request::Request::Subscribe(
+                proto::Subscription {
+                    topic: "readings".to_string(),
+                    auto_ack: true,
+                    requeue_on_error: false,
+                },
+            )),
+        };
+        stream.send(subscribe.encode_to_vec().into()).await?;
+        // published until the server subscribed
+        let m = Message::new(&b"21.5"[..]);
+        let mut event = None;
+        for _ in 0..50 {
+            c.publish("readings", &m).await?;
+            let r = tokio::time::timeout(Duration::from_millis(50), stream.recv()).await;
+            if let Ok(r) = r {
+                event = r?;
+                break;
+            }
+        }
+        let event: proto::Event = decode(event.expect("no event"))?;
+        assert_eq!(event.topic, "readings");
+        assert_eq!(event.message.unwrap().body, b"21.5");
+
+        stream.close_send().await;
+        assert!(stream.send(Default::default()).await.is_err());
+        server.abort();
+        Ok(())
+    }
+}
@@ -1,3 +1,97 @@
+pub mod grpc;
+
+pub mod options;
+
+use async_trait::async_trait;
+use broker::message::Message;
+use bytes::Bytes;
+use errors::{Result, Status};
+
+use self::options::{CallOptions, Options};
+
+/// Client calls the endpoints of the services found in the registry, with
+/// the bodies encoded already, and publishes onto the broker
+#[async_trait]
+pub trait Client: Send + Sync {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()>;
+    fn options(&self) -> Options;
+    /// calls `endpoint`, as `Greeter.Hello`, of a node of `service` with
+    /// `req` and returns the body of the response
+    async fn call(
+        &self,
+        service: &str,
+        endpoint: &str,
+        req: Bytes,
+        opt: Option<CallOptions>,
+    ) -> Result<Bytes>;
+    /// opens a stream to `endpoint` of a node of `service`
+    async fn stream(
+        &self,
+        service: &str,
+        endpoint: &str,
+        opt: Option<CallOptions>,
+    ) -> Result<Box<dyn Stream>>;
+    async fn publish(&self, topic: &str, m: &Message) -> Result<()>;
+    fn string(&self) -> &'static str;
+}
+
+/// Stream is a bidirectional stream to an endpoint, opened by
+/// [`Client::stream`]
+#[async_trait]
+pub trait Stream: Send + Sync {
+    async fn send(&self, body: Bytes) -> Result<()>;
+    /// the next response, `None` once the server ended the stream
+    async fn recv(&self) -> Result<Option<Bytes>>;
+    /// ends the requests, the responses may go on
+    async fn close_send(&self);
+}
+
+/// the protobuf messages over a [`Client`] and its streams
+#[async_trait]
+pub trait ClientExt: Client {
+    /// calls `endpoint` of `service` with the message `req`
+    ///
+    /// ```rust
+    /// # use client::{Client, ClientExt};
+    /// # async fn run(client: &dyn Client) -> errors::Result<()> {
+    /// # use broker::proto::Empty;
+    /// let rsp: Empty = client
+    ///     .call_proto("io.vine.helloworld", "Greeter.Hello", &Empty {}, None)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn call_proto<Req, Rsp>(
+        &self,
+        service: &str,
+        endpoint: &str,
+        req: &Req,
+        opt: Option<CallOptions>,
+    ) -> Result<Rsp>
+    where
+        Req: prost::Message,
+        Rsp: prost::Message + Default,
+    {
+        let rsp = self
+            .call(service, endpoint, req.encode_to_vec().into(), opt)
+            .await?;
+        decode(rsp)
+    }
+}
+
+impl<C: Client + ?Sized> ClientExt for C {}
+
+/// the message of a body, a bad request if it does not decode
+pub fn decode<T: prost::Message + Default>(body: Bytes) -> Result<T> {
+    T::decode(body).map_err(|e| {
+        Status::bad_request(
+            "io.vine.client".to_string(),
+            format!("decode response: {}", e),
+        )
+        .into()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use registry::selector::SelectOptions;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// how long a call may take when it does not carry its own timeout
+    pub timeout: Duration,
+    /// how many times a call failing with a retryable status is tried again,
+    /// each on the next selected node
+    pub retries: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Options {
+    #[inline]
+    pub fn new() -> Self {
+        Options {
+            timeout: Duration::from_secs(5),
+            retries: 1,
+        }
+    }
+
+    #[inline]
+    pub fn with_timeout(&mut self, t: Duration) -> &mut Self {
+        self.timeout = t;
+        self
+    }
+
+    #[inline]
+    pub fn with_retries(&mut self, n: u32) -> &mut Self {
+        self.retries = n;
+        self
+    }
+
+    /// returns the per-call timeout if given, otherwise `Options.timeout`
+    #[inline]
+    pub fn timeout_or(&self, t: Option<Duration>) -> Duration {
+        t.unwrap_or(self.timeout)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CallOptions {
+    /// overrides `Options.timeout` for this call
+    pub timeout: Option<Duration>,
+    /// the filters of the nodes the call may go to
+    pub select: Option<SelectOptions>,
+}
+
+impl CallOptions {
+    #[inline]
+    pub fn new() -> Self {
+        CallOptions {
+            timeout: None,
+            select: None,
+        }
+    }
+
+    #[inline]
+    pub fn with_timeout(&mut self, t: Duration) -> &mut Self {
+        self.timeout = Some(t);
+        self
+    }
+
+    #[inline]
+    pub fn with_select(&mut self, opts: SelectOptions) -> &mut Self {
+        self.select = Some(opts);
+        self
+    }
+}
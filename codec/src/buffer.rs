@@ -1,10 +1,13 @@
 use bytes::buf::UninitSlice;
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 
 /// A specialized buffer to decode gRPC messages from.
+///
+/// It reads the first `len` bytes of a shared [`Bytes`], so the parts split
+/// off it are views of the same memory rather than copies.
 #[derive(Debug)]
 pub struct DecodeBuf<'a> {
-    buf: &'a mut BytesMut,
+    buf: &'a mut Bytes,
     len: usize,
 }
 
@@ -15,9 +18,33 @@ pub struct EncodeBuf<'a> {
 }
 
 impl<'a> DecodeBuf<'a> {
-    pub(crate) fn new(buf: &'a mut BytesMut, len: usize) -> Self {
+    pub(crate) fn new(buf: &'a mut Bytes, len: usize) -> Self {
+        assert!(len <= buf.len());
         DecodeBuf { buf, len }
     }
+
+    /// splits the next `n` bytes off without copying them
+    #[inline]
+    pub fn split_to(&mut self, n: usize) -> Bytes {
+        assert!(n <= self.len);
+        self.len -= n;
+        self.buf.split_to(n)
+    }
+
+    /// runs `f` on the next `len` bytes, which are consumed whatever `f`
+    /// reads of them
+    pub(crate) fn frame<T>(&mut self, len: usize, f: impl FnOnce(&mut DecodeBuf<'_>) -> T) -> T {
+        assert!(len <= self.len);
+        let mut frame = DecodeBuf {
+            buf: &mut *self.buf,
+            len,
+        };
+        let out = f(&mut frame);
+        let rest = frame.len;
+        self.buf.advance(rest);
+        self.len -= len;
+        out
+    }
 }
 
 impl Buf for DecodeBuf<'_> {
@@ -28,13 +55,7 @@ impl Buf for DecodeBuf<'_> {
 
     #[inline]
     fn chunk(&self) -> &[u8] {
-        let ret = self.buf.chunk();
-
-        if ret.len() > self.len {
-            &ret[..self.len]
-        } else {
-            ret
-        }
+        &self.buf[..self.len]
     }
 
     #[inline]
@@ -43,6 +64,13 @@ impl Buf for DecodeBuf<'_> {
         self.buf.advance(cnt);
         self.len -= cnt;
     }
+
+    /// shares the bytes rather than copying them, for the `bytes` fields of
+    /// prost messages among others
+    #[inline]
+    fn copy_to_bytes(&mut self, len: usize) -> Bytes {
+        self.split_to(len)
+    }
 }
 
 impl<'a> EncodeBuf<'a> {
@@ -61,6 +89,30 @@ impl EncodeBuf<'_> {
     pub fn reserve(&mut self, additional: usize) {
         self.buf.reserve(additional);
     }
+
+    /// the bytes in the buffer, including those written before it was
+    /// wrapped
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// the bytes written so far, to patch a length written ahead of them
+    #[inline]
+    pub(crate) fn written_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[..]
+    }
+
+    /// drops the bytes after the first `len`, undoing a failed write
+    #[inline]
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.buf.truncate(len);
+    }
 }
 
 unsafe impl BufMut for EncodeBuf<'_> {
@@ -86,8 +138,7 @@ mod tests {
 
     #[test]
     fn decode_buf() {
-        let mut payload = BytesMut::with_capacity(100);
-        payload.put(&vec![0u8; 50][..]);
+        let mut payload = Bytes::from(vec![0u8; 50]);
         let mut buf = DecodeBuf::new(&mut payload, 20);
 
         assert_eq!(buf.len, 20);
@@ -104,6 +155,21 @@ mod tests {
 
         assert_eq!(buf.copy_to_bytes(5).len(), 5);
         assert!(!buf.has_remaining());
+        assert_eq!(payload.len(), 30);
+    }
+
+    #[test]
+    fn decode_buf_split_to() {
+        let mut payload = Bytes::from_static(b"hello vine");
+        let ptr = payload.as_ptr();
+        let mut buf = DecodeBuf::new(&mut payload, 8);
+
+        let hello = buf.split_to(5);
+        assert_eq!(&hello[..], b"hello");
+        // a view of the same memory
+        assert_eq!(hello.as_ptr(), ptr);
+        assert_eq!(buf.remaining(), 3);
+        assert_eq!(buf.chunk(), b" vi");
     }
 
     #[test]
@@ -118,4 +184,4 @@ mod tests {
         buf.put_u8(b'a');
         assert_eq!(buf.remaining_mut(), initial - 20 - 1);
     }
-}
\ No newline at end of file
+}
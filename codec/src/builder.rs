@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use errors::Status;
+use serde::Serialize;
+
+use crate::{header, Message, MessageType};
+
+/// MessageBuilder builds a [`Message`], see [`Message::builder`]
+#[derive(Debug, Clone)]
+pub struct MessageBuilder {
+    m: Message,
+    err: Option<Status>,
+}
+
+impl Default for MessageBuilder {
+    fn default() -> Self {
+        MessageBuilder {
+            m: Message {
+                id: String::new(),
+                r#type: MessageType::Request,
+                target: String::new(),
+                method: String::new(),
+                endpoint: String::new(),
+                error: String::new(),
+                header: HashMap::new(),
+                body: Bytes::new(),
+            },
+            err: None,
+        }
+    }
+}
+
+impl MessageBuilder {
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.m.id = id.into();
+        self
+    }
+
+    /// the type of the message, [`MessageType::Request`] unless set
+    pub fn r#type(mut self, t: MessageType) -> Self {
+        self.m.r#type = t;
+        self
+    }
+
+    /// the service the message is for
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.m.target = target.into();
+        self
+    }
+
+    pub fn method(mut self, method: impl Into<String>) -> Self {
+        self.m.method = method.into();
+        self
+    }
+
+    /// the endpoint of the service, such as `Greeter.Hello`
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.m.endpoint = endpoint.into();
+        self
+    }
+
+    /// the error of a [`MessageType::Error`] message
+    pub fn error(mut self, error: impl Into<String>) -> Self {
+        self.m.error = error.into();
+        self
+    }
+
+    /// sets the header `key`, replacing a value set under another case of
+    /// the name
+    pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        self.m.header.retain(|k, _| !k.eq_ignore_ascii_case(&key));
+        self.m.header.insert(key, value.into());
+        self
+    }
+
+    pub fn request_id(self, id: impl Into<String>) -> Self {
+        self.header(header::REQUEST_ID, id)
+    }
+
+    pub fn content_type(self, ct: impl Into<String>) -> Self {
+        self.header(header::CONTENT_TYPE, ct)
+    }
+
+    /// the time the answer is no longer waited for, sent as milliseconds
+    /// since the unix epoch
+    pub fn deadline(self, deadline: SystemTime) -> Self {
+        let millis = deadline
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        self.header(header::DEADLINE, millis.to_string())
+    }
+
+    /// the deadline `timeout` from now
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(SystemTime::now() + timeout)
+    }
+
+    /// the encoded body, see [`Message::write_body`] to encode it with a
+    /// codec
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.m.body = body.into();
+        self
+    }
+
+    /// `v` as the JSON body, with the JSON content type
+    pub fn body_json<T: Serialize + ?Sized>(self, v: &T) -> Self {
+        match serde_json::to_vec(v) {
+            Ok(body) => self.body(body).content_type("application/json"),
+            Err(e) => {
+                let err = Status::bad_request("io.vine.codec", &e.to_string());
+                MessageBuilder {
+                    err: Some(err),
+                    ..self
+                }
+            }
+        }
+    }
+
+    /// the message, or the error of a body which could not be encoded
+    pub fn build(self) -> Result<Message, Status> {
+        match self.err {
+            Some(e) => Err(e),
+            None => Ok(self.m),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::{header, Message, MessageType};
+
+    #[test]
+    fn message_builder() {
+        let deadline = UNIX_EPOCH + Duration::from_millis(1_600_000_000_123);
+        let m = Message::builder()
+            .id("1")
+            .target("io.vine.helloworld")
+            .endpoint("Greeter.Hello")
+            .header("x-request-id", "abc")
+            .request_id("def")
+            .deadline(deadline)
+            .body_json(&HashMap::from([("name", "vine")]))
+            .build()
+            .unwrap();
+
+        assert_eq!(m.r#type, MessageType::Request);
+        assert_eq!(m.endpoint, "Greeter.Hello");
+        assert_eq!(m.request_id(), Some("def"));
+        assert_eq!(m.header.len(), 3);
+        assert_eq!(m.content_type(), Some("application/json"));
+        assert_eq!(m.deadline(), Some(deadline));
+        assert_eq!(&m.body[..], br#"{"name":"vine"}"#);
+
+        let m = Message::builder()
+            .timeout(Duration::from_secs(60))
+            .header(header::DEADLINE, "soon")
+            .build()
+            .unwrap();
+        assert_eq!(m.deadline(), None);
+        assert!(m.header(header::REQUEST_ID).is_none());
+
+        let m = Message::builder()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        assert!(m.deadline().unwrap() > SystemTime::now());
+
+        let bad = HashMap::from([(vec![1], 1)]);
+        assert!(Message::builder().body_json(&bad).build().is_err());
+    }
+}
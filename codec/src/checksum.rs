@@ -0,0 +1,241 @@
+use bytes::{Buf, BufMut};
+use errors::Status;
+
+use crate::buffer::{DecodeBuf, EncodeBuf};
+use crate::{Codec, Message, MessageType, Reader, Writer};
+
+/// the algorithm byte and the big endian u32 checksum after every payload
+pub const TRAILER_SIZE: usize = 5;
+
+/// the algorithms computing the checksum of a trailer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Checksum {
+    /// CRC-32C (Castagnoli), as iSCSI and ext4 use
+    #[default]
+    Crc32c = 1,
+}
+
+impl Checksum {
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Checksum::Crc32c),
+            _ => None,
+        }
+    }
+
+    /// the checksum of `data`
+    pub fn compute(&self, data: &[u8]) -> u32 {
+        match self {
+            Checksum::Crc32c => crc32c(data),
+        }
+    }
+}
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0x82f6_3b78 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+static CRC32C: [u32; 256] = crc32c_table();
+
+fn crc32c(data: &[u8]) -> u32 {
+    let mut c = !0u32;
+    for b in data {
+        c = CRC32C[((c ^ *b as u32) & 0xff) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+/// the [`Codec`] appending a checksum trailer to the payloads of another
+/// codec and verifying it before they are decoded, to detect corruption
+/// over unreliable transports. A read takes the whole of its buffer for one
+/// payload and its trailer, a [`GrpcCodec`](crate::grpc::GrpcCodec) around
+/// it frames several.
+///
+/// ```rust
+/// # use codec::{checksum::ChecksumCodec, proto::ProtoCodec, Codec, Message};
+/// # fn run(mut m: Message) -> Result<(), errors::Status> {
+/// let mut codec = ChecksumCodec::<ProtoCodec<String>>::default();
+/// m.write_body(&mut codec.writer(), "hello".to_string())?;
+/// assert_eq!(m.body.len(), 7 + 5);
+/// assert_eq!(m.read_body(&mut codec.reader())?, Some("hello".to_string()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ChecksumCodec<C> {
+    inner: C,
+    checksum: Checksum,
+}
+
+impl<C: Codec> ChecksumCodec<C> {
+    pub fn new(inner: C) -> Self {
+        ChecksumCodec {
+            inner,
+            checksum: Checksum::default(),
+        }
+    }
+
+    /// the algorithm of the trailers written, [`Checksum::Crc32c`] unless
+    /// set. Trailers are verified with the algorithm they name.
+    #[inline]
+    pub fn with_checksum(&mut self, checksum: Checksum) -> &mut Self {
+        self.checksum = checksum;
+        self
+    }
+}
+
+impl<C: Codec> Codec for ChecksumCodec<C> {
+    type Read = C::Read;
+    type Write = C::Write;
+
+    type Reader = ChecksumReader<C::Reader>;
+    type Writer = ChecksumWriter<C::Writer>;
+
+    fn reader(&mut self) -> Self::Reader {
+        ChecksumReader {
+            inner: self.inner.reader(),
+        }
+    }
+
+    fn writer(&mut self) -> Self::Writer {
+        ChecksumWriter {
+            inner: self.inner.writer(),
+            checksum: self.checksum,
+        }
+    }
+
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        self.inner.close()
+    }
+
+    fn string() -> &'static str {
+        "checksum"
+    }
+}
+
+/// the [`Reader`] of a [`ChecksumCodec`]
+#[derive(Debug)]
+pub struct ChecksumReader<R> {
+    inner: R,
+}
+
+impl<R: Reader<Error = Status>> Reader for ChecksumReader<R> {
+    type Item = R::Item;
+    type Error = Status;
+
+    fn read_header(&self, m: Message, mt: MessageType) -> Result<(), Self::Error> {
+        self.inner.read_header(m, mt)
+    }
+
+    /// verifies the trailer at the end of `src` and decodes the payload
+    /// before it
+    fn read_body(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        if src.remaining() < TRAILER_SIZE {
+            return Err(Status::bad_request(
+                "io.vine.codec",
+                "body without its checksum trailer",
+            ));
+        }
+        let len = src.remaining() - TRAILER_SIZE;
+        let (payload, trailer) = src.chunk().split_at(len);
+        let checksum = Checksum::from_id(trailer[0]).ok_or_else(|| {
+            Status::bad_request(
+                "io.vine.codec",
+                &format!("unknown checksum algorithm {}", trailer[0]),
+            )
+        })?;
+        let expected = u32::from_be_bytes([trailer[1], trailer[2], trailer[3], trailer[4]]);
+        let actual = checksum.compute(payload);
+        if actual != expected {
+            return Err(Status::bad_request(
+                "io.vine.codec",
+                &format!(
+                    "checksum {:08x} of the body doesn't match its trailer {:08x}",
+                    actual, expected
+                ),
+            ));
+        }
+
+        let out = src.frame(len, |payload| self.inner.read_body(payload));
+        src.advance(TRAILER_SIZE);
+        out
+    }
+}
+
+/// the [`Writer`] of a [`ChecksumCodec`]
+#[derive(Debug)]
+pub struct ChecksumWriter<W> {
+    inner: W,
+    checksum: Checksum,
+}
+
+impl<W: Writer<Error = Status>> Writer for ChecksumWriter<W> {
+    type Item = W::Item;
+    type Error = Status;
+
+    fn write(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        let start = dst.len();
+        if let Err(e) = self.inner.write(item, dst) {
+            dst.truncate(start);
+            return Err(e);
+        }
+        let sum = self.checksum.compute(&dst.written_mut()[start..]);
+        dst.put_u8(self.checksum as u8);
+        dst.put_u32(sum);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use errors::Code;
+
+    use super::{crc32c, ChecksumCodec};
+    use crate::grpc::GrpcCodec;
+    use crate::proto::ProtoCodec;
+    use crate::{Codec, Message};
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+        assert_eq!(crc32c(b""), 0);
+
+        let mut codec = ChecksumCodec::<ProtoCodec<String>>::default();
+        let mut m = Message::builder().build().unwrap();
+        m.write_body(&mut codec.writer(), "hello".to_string())
+            .unwrap();
+        assert_eq!(m.read_body(&mut codec.reader()).unwrap().unwrap(), "hello");
+
+        // a flipped bit in the payload
+        let mut body = m.body.to_vec();
+        body[3] ^= 0x04;
+        m.body = body.into();
+        let e = m.read_body(&mut codec.reader()).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+        m.body = m.body.slice(..3);
+        assert!(m.read_body(&mut codec.reader()).is_err());
+
+        // framed with grpc, the checksum covers the payload of each frame
+        let mut codec = GrpcCodec::new(ChecksumCodec::<ProtoCodec<String>>::default());
+        m.write_body(&mut codec.writer(), "hello".to_string())
+            .unwrap();
+        assert_eq!(&m.body[..5], &[0, 0, 0, 0, 7 + 5]);
+        assert_eq!(m.read_body(&mut codec.reader()).unwrap().unwrap(), "hello");
+    }
+}
@@ -0,0 +1,213 @@
+use std::io;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use errors::Status;
+
+use crate::buffer::{DecodeBuf, EncodeBuf};
+use crate::{too_large, DEFAULT_MAX_RECV_SIZE};
+
+/// the flags and the big endian u32 length before every chunk
+pub const HEADER_SIZE: usize = 5;
+
+/// the flag of the last chunk of an item
+const LAST: u8 = 1;
+
+/// Chunk is a part of an encoded item sent as several frames, such as a
+/// big file or one message of a stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub data: Bytes,
+    /// whether the item ends with this chunk
+    pub last: bool,
+}
+
+/// reads the next chunk of `src`, `None` until `src` holds all of it. The
+/// default [`Reader::read_chunk`](crate::Reader::read_chunk).
+pub fn read(src: &mut DecodeBuf<'_>) -> io::Result<Option<Chunk>> {
+    if src.remaining() < HEADER_SIZE {
+        return Ok(None);
+    }
+    let header = &src.chunk()[..HEADER_SIZE];
+    let flags = header[0];
+    let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if flags & !LAST != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("bad chunk flags {}", flags),
+        ));
+    }
+    if len > DEFAULT_MAX_RECV_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("chunk of {} bytes is too large", len),
+        ));
+    }
+    if src.remaining() < HEADER_SIZE + len {
+        return Ok(None);
+    }
+
+    src.advance(HEADER_SIZE);
+    Ok(Some(Chunk {
+        data: src.split_to(len),
+        last: flags & LAST != 0,
+    }))
+}
+
+/// writes `chunk` for [`read`]. The default
+/// [`Writer::write_chunk`](crate::Writer::write_chunk).
+pub fn write(chunk: &Chunk, dst: &mut EncodeBuf<'_>) -> io::Result<()> {
+    if chunk.data.len() > u32::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("chunk of {} bytes is too large", chunk.data.len()),
+        ));
+    }
+    dst.reserve(HEADER_SIZE + chunk.data.len());
+    dst.put_u8(if chunk.last { LAST } else { 0 });
+    dst.put_u32(chunk.data.len() as u32);
+    dst.put_slice(&chunk.data);
+    Ok(())
+}
+
+/// splits an encoded body into chunks of at most `size` bytes, the last one
+/// flagged. An empty body is a single empty chunk.
+pub fn split(mut body: Bytes, size: usize) -> impl Iterator<Item = Chunk> {
+    assert!(size > 0, "chunks can't be empty");
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let data = body.split_to(size.min(body.len()));
+        done = body.is_empty();
+        Some(Chunk { data, last: done })
+    })
+}
+
+/// Assembler puts the chunks of an item back together
+///
+/// ```rust
+/// # use codec::chunk::{self, Assembler};
+/// let mut assembler = Assembler::new();
+/// let mut body = None;
+/// for c in chunk::split("a big body".into(), 4) {
+///     body = assembler.push(c)?;
+/// }
+/// assert_eq!(body.as_deref(), Some(&b"a big body"[..]));
+/// # Ok::<(), errors::Status>(())
+/// ```
+#[derive(Debug)]
+pub struct Assembler {
+    buf: BytesMut,
+    max_size: usize,
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Assembler {
+            buf: BytesMut::new(),
+            max_size: DEFAULT_MAX_RECV_SIZE,
+        }
+    }
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// items longer than `n` fail once their chunks add up to more,
+    /// [`DEFAULT_MAX_RECV_SIZE`] unless set
+    #[inline]
+    pub fn with_max_size(&mut self, n: usize) -> &mut Self {
+        self.max_size = n;
+        self
+    }
+
+    /// the bytes of the item being put together
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// adds `chunk` to the item, the whole item once its last chunk is
+    /// added. An item of a single chunk is not copied. A failed item is
+    /// dropped, the next chunk starts another.
+    pub fn push(&mut self, chunk: Chunk) -> Result<Option<Bytes>, Status> {
+        let len = self.buf.len() + chunk.data.len();
+        if len > self.max_size {
+            self.buf.clear();
+            return Err(too_large("chunked body", len, self.max_size));
+        }
+
+        if chunk.last && self.buf.is_empty() {
+            return Ok(Some(chunk.data));
+        }
+        self.buf.extend_from_slice(&chunk.data);
+        if chunk.last {
+            Ok(Some(self.buf.split().freeze()))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{Bytes, BytesMut};
+    use errors::Code;
+
+    use super::{split, Assembler, Chunk};
+    use crate::buffer::{DecodeBuf, EncodeBuf};
+    use crate::proto::ProtoCodec;
+    use crate::{Codec, Message, Reader, Writer};
+
+    #[test]
+    fn chunks() {
+        let mut codec = ProtoCodec::<String>::default();
+        let mut m = Message::builder().build().unwrap();
+        m.write_body(&mut codec.writer(), "a body sent in parts".to_string())
+            .unwrap();
+
+        let mut w = codec.writer();
+        let mut buf = BytesMut::new();
+        for c in split(m.body.clone(), 8) {
+            w.write_chunk(c, &mut EncodeBuf::new(&mut buf)).unwrap();
+        }
+        assert_eq!(buf.len(), 3 * 5 + m.body.len());
+
+        let mut r = codec.reader();
+        let mut buf = buf.freeze();
+        // a chunk is read only once all its bytes came
+        let mut partial = buf.slice(..7);
+        assert_eq!(r.read_chunk(&mut DecodeBuf::new(&mut partial, 7)), Ok(None));
+
+        let mut assembler = Assembler::new();
+        let mut body = None;
+        let len = buf.len();
+        let mut src = DecodeBuf::new(&mut buf, len);
+        while let Some(c) = r.read_chunk(&mut src).unwrap() {
+            body = assembler.push(c).unwrap();
+        }
+        let mut body = body.unwrap();
+        let len = body.len();
+        let item = r.read_body(&mut DecodeBuf::new(&mut body, len)).unwrap();
+        assert_eq!(item.as_deref(), Some("a body sent in parts"));
+
+        assert_eq!(split(Bytes::new(), 8).collect::<Vec<_>>().len(), 1);
+        assembler.with_max_size(4);
+        let c = Chunk {
+            data: Bytes::from_static(b"12345"),
+            last: false,
+        };
+        assert_eq!(assembler.push(c).unwrap_err().code(), Code::BadRequest);
+        assert!(assembler.is_empty());
+
+        let mut bad = Bytes::from_static(&[2, 0, 0, 0, 0]);
+        let e = r.read_chunk(&mut DecodeBuf::new(&mut bad, 5)).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+    }
+}
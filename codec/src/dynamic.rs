@@ -0,0 +1,146 @@
+use std::marker::PhantomData;
+
+use bytes::Bytes;
+use errors::Status;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{header, Codec, Message};
+
+/// DynCodec is the object safe form of a [`Codec`], its items erased into
+/// JSON values and their encodings into [`Bytes`], so codecs of different
+/// types can be kept together and picked at runtime by content type:
+///
+/// ```rust
+/// # use std::collections::HashMap;
+/// # use codec::{json::JsonCodec, proto::ProtoCodec, DynCodec, TypedCodec};
+/// # use serde_json::Value;
+/// let mut codecs: HashMap<String, Box<dyn DynCodec>> = HashMap::new();
+/// codecs.insert(
+///     "application/json".to_string(),
+///     Box::new(TypedCodec::<JsonCodec<Value>>::new("application/json")),
+/// );
+/// codecs.insert(
+///     "application/protobuf".to_string(),
+///     Box::new(TypedCodec::<ProtoCodec<String>>::new("application/protobuf")),
+/// );
+///
+/// let proto = &codecs["application/protobuf"];
+/// let body = proto.encode_body("hello".into())?;
+/// assert_eq!(proto.decode_body(body)?, Value::from("hello"));
+/// # Ok::<(), errors::Status>(())
+/// ```
+pub trait DynCodec: Send + Sync {
+    /// the name of the codec, the [`Codec::string`] of a typed one
+    fn string(&self) -> &str;
+    /// the content type written into the header of encoded messages
+    fn content_type(&self) -> &str;
+    /// checks the header of `m` and decodes its body, `Null` when empty
+    fn decode(&self, m: &Message) -> Result<Value, Status>;
+    /// encodes `v` into the body of `m` and sets its content type
+    fn encode(&self, v: Value, m: &mut Message) -> Result<(), Status>;
+
+    /// decodes a body without a header to check
+    fn decode_body(&self, body: Bytes) -> Result<Value, Status> {
+        self.decode(&Message::builder().body(body).build()?)
+    }
+
+    /// encodes `v` into a body on its own
+    fn encode_body(&self, v: Value) -> Result<Bytes, Status> {
+        let mut m = Message::builder().build()?;
+        self.encode(v, &mut m)?;
+        Ok(m.body)
+    }
+}
+
+/// the [`DynCodec`] of a typed codec, converting between its items and JSON
+/// values with serde
+pub struct TypedCodec<C> {
+    content_type: String,
+    _pd: PhantomData<fn() -> C>,
+}
+
+impl<C> TypedCodec<C> {
+    /// the codec writing `content_type` into the header of the messages it
+    /// encodes
+    pub fn new(content_type: impl Into<String>) -> Self {
+        TypedCodec {
+            content_type: content_type.into(),
+            _pd: PhantomData,
+        }
+    }
+}
+
+impl<C> DynCodec for TypedCodec<C>
+where
+    C: Codec,
+    C::Read: Serialize,
+    C::Write: DeserializeOwned,
+{
+    fn string(&self) -> &str {
+        C::string()
+    }
+
+    fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    fn decode(&self, m: &Message) -> Result<Value, Status> {
+        let mut codec = C::default();
+        match m.read_body(&mut codec.reader())? {
+            Some(item) => serde_json::to_value(item)
+                .map_err(|e| Status::internal_server_error("io.vine.codec", &e.to_string())),
+            None => Ok(Value::Null),
+        }
+    }
+
+    fn encode(&self, v: Value, m: &mut Message) -> Result<(), Status> {
+        let item: C::Write = serde_json::from_value(v)
+            .map_err(|e| Status::bad_request("io.vine.codec", &e.to_string()))?;
+        let mut codec = C::default();
+        m.write_body(&mut codec.writer(), item)?;
+        m.header
+            .retain(|k, _| !k.eq_ignore_ascii_case(header::CONTENT_TYPE));
+        m.header
+            .insert(header::CONTENT_TYPE.to_string(), self.content_type.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{DynCodec, TypedCodec};
+    use crate::grpc::GrpcCodec;
+    use crate::json::JsonCodec;
+    use crate::proto::ProtoCodec;
+
+    #[test]
+    fn dyn_codec() {
+        let codecs: Vec<Box<dyn DynCodec>> = vec![
+            Box::new(TypedCodec::<JsonCodec<Value>>::new("application/json")),
+            Box::new(TypedCodec::<GrpcCodec<ProtoCodec<String>>>::new(
+                "application/grpc",
+            )),
+        ];
+        assert_eq!(codecs[0].string(), "json");
+        assert_eq!(codecs[1].string(), "grpc");
+
+        for c in &codecs {
+            let body = c.encode_body(json!("hello")).unwrap();
+            assert_eq!(
+                c.decode_body(body).unwrap(),
+                json!("hello"),
+                "{}",
+                c.string()
+            );
+        }
+        assert_eq!(
+            codecs[0].decode_body(Default::default()).unwrap(),
+            Value::Null
+        );
+        // not a string
+        assert!(codecs[1].encode_body(json!({"name": "vine"})).is_err());
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use bytes::{Buf, BufMut};
+use errors::Status;
+use serde_json::{Map, Value};
+
+use crate::buffer::{DecodeBuf, EncodeBuf};
+use crate::registry::essence;
+use crate::{
+    too_large, Codec, Message, MessageType, Reader, Writer, DEFAULT_MAX_RECV_SIZE,
+    DEFAULT_MAX_SEND_SIZE,
+};
+
+/// the content type of the bodies a [`FormWriter`] writes
+pub const URLENCODED: &str = "application/x-www-form-urlencoded";
+
+/// the content type of forms uploading files
+pub const MULTIPART: &str = "multipart/form-data";
+
+/// the fields of a form by name. A field given more than once is an array
+/// of its values, a file of a multipart form an object of its `filename`,
+/// `content_type` and base64 `data`.
+pub type Form = HashMap<String, Value>;
+
+fn bad(detail: &str) -> Status {
+    Status::bad_request("io.vine.codec", detail)
+}
+
+/// the [`Codec`] of HTML forms, reading `application/x-www-form-urlencoded`
+/// and `multipart/form-data` bodies and writing urlencoded ones
+///
+/// ```rust
+/// # use codec::{form::FormCodec, Codec, Message};
+/// # fn run(mut m: Message) -> Result<(), errors::Status> {
+/// m.header.insert("Content-Type".to_string(), codec::form::URLENCODED.to_string());
+/// m.body = "name=vine&tag=a&tag=b".into();
+/// let form = m.read_body(&mut FormCodec::default().reader())?.unwrap();
+/// assert_eq!(form["name"], "vine");
+/// assert_eq!(form["tag"], serde_json::json!(["a", "b"]));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FormCodec {
+    max_recv_size: usize,
+    max_send_size: usize,
+}
+
+impl Default for FormCodec {
+    fn default() -> Self {
+        FormCodec {
+            max_recv_size: DEFAULT_MAX_RECV_SIZE,
+            max_send_size: DEFAULT_MAX_SEND_SIZE,
+        }
+    }
+}
+
+impl FormCodec {
+    /// bodies longer than `n` fail the read before they are parsed,
+    /// [`DEFAULT_MAX_RECV_SIZE`] unless set
+    #[inline]
+    pub fn with_max_recv_size(&mut self, n: usize) -> &mut Self {
+        self.max_recv_size = n;
+        self
+    }
+
+    /// forms encoding to more than `n` bytes fail the write,
+    /// [`DEFAULT_MAX_SEND_SIZE`] unless set
+    #[inline]
+    pub fn with_max_send_size(&mut self, n: usize) -> &mut Self {
+        self.max_send_size = n;
+        self
+    }
+}
+
+impl Codec for FormCodec {
+    type Read = Form;
+    type Write = Form;
+
+    type Reader = FormReader;
+    type Writer = FormWriter;
+
+    fn reader(&mut self) -> Self::Reader {
+        FormReader {
+            max_size: self.max_recv_size,
+            boundary: Mutex::new(None),
+        }
+    }
+
+    fn writer(&mut self) -> Self::Writer {
+        FormWriter {
+            max_size: self.max_send_size,
+        }
+    }
+
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn string() -> &'static str {
+        "form"
+    }
+}
+
+/// the [`Reader`] of a [`FormCodec`]
+#[derive(Debug)]
+pub struct FormReader {
+    max_size: usize,
+    /// the boundary of the multipart body, `None` for an urlencoded one
+    boundary: Mutex<Option<String>>,
+}
+
+impl Reader for FormReader {
+    type Item = Form;
+    type Error = Status;
+
+    /// takes the boundary of a multipart body from its content type, a
+    /// message without one is taken as urlencoded
+    fn read_header(&self, m: Message, _mt: MessageType) -> Result<(), Self::Error> {
+        let boundary = match m.content_type() {
+            None => None,
+            Some(ct) => match essence(ct).as_str() {
+                URLENCODED => None,
+                MULTIPART => Some(
+                    param(ct, "boundary")
+                        .ok_or_else(|| bad("multipart content type without a boundary"))?,
+                ),
+                _ => return Err(bad(&format!("content type {} is not a form", ct))),
+            },
+        };
+        *self.boundary.lock().unwrap_or_else(|e| e.into_inner()) = boundary;
+        Ok(())
+    }
+
+    fn read_body(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        if src.remaining() > self.max_size {
+            return Err(too_large("body", src.remaining(), self.max_size));
+        }
+        let body = src.split_to(src.remaining());
+        let boundary = self.boundary.lock().unwrap_or_else(|e| e.into_inner());
+        let form = match boundary.as_deref() {
+            Some(boundary) => parse_multipart(&body, boundary)?,
+            None => parse_urlencoded(&body)?,
+        };
+        Ok(Some(form))
+    }
+}
+
+/// the [`Writer`] of a [`FormCodec`], writing [`URLENCODED`] bodies
+#[derive(Debug)]
+pub struct FormWriter {
+    max_size: usize,
+}
+
+impl Writer for FormWriter {
+    type Item = Form;
+    type Error = Status;
+
+    /// writes the fields sorted by name, arrays as repeated fields. Files
+    /// and other objects can't be urlencoded.
+    fn write(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        let mut fields: Vec<_> = item.into_iter().collect();
+        fields.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut out = String::new();
+        for (k, v) in &fields {
+            let values = match v {
+                Value::Array(values) => values.iter().collect(),
+                v => vec![v],
+            };
+            for v in values {
+                let v = match v {
+                    Value::Null => String::new(),
+                    Value::String(s) => s.clone(),
+                    Value::Bool(_) | Value::Number(_) => v.to_string(),
+                    _ => return Err(bad(&format!("field {} can't be urlencoded", k))),
+                };
+                if !out.is_empty() {
+                    out.push('&');
+                }
+                percent_encode(&mut out, k);
+                out.push('=');
+                percent_encode(&mut out, &v);
+            }
+        }
+
+        if out.len() > self.max_size {
+            return Err(too_large("body", out.len(), self.max_size));
+        }
+        dst.put_slice(out.as_bytes());
+        Ok(())
+    }
+}
+
+/// adds `v` to the field `k` of `form`, turning a repeated field into an
+/// array of its values
+fn insert(form: &mut Form, k: String, v: Value) {
+    match form.get_mut(&k) {
+        None => {
+            form.insert(k, v);
+        }
+        Some(Value::Array(values)) => values.push(v),
+        Some(old) => {
+            let first = old.take();
+            *old = Value::Array(vec![first, v]);
+        }
+    }
+}
+
+fn parse_urlencoded(body: &[u8]) -> Result<Form, Status> {
+    let mut form = Form::new();
+    for pair in body.split(|b| *b == b'&').filter(|p| !p.is_empty()) {
+        let (k, v) = match pair.iter().position(|b| *b == b'=') {
+            Some(i) => (&pair[..i], &pair[i + 1..]),
+            None => (pair, &[][..]),
+        };
+        insert(
+            &mut form,
+            percent_decode(k)?,
+            Value::String(percent_decode(v)?),
+        );
+    }
+    Ok(form)
+}
+
+fn parse_multipart(body: &[u8], boundary: &str) -> Result<Form, Status> {
+    let delimiter = format!("--{}", boundary);
+    let delimiter = delimiter.as_bytes();
+    let truncated = || bad("multipart body without its closing boundary");
+
+    // the preamble before the first boundary is ignored
+    let mut rest = match find(body, delimiter) {
+        Some(i) => &body[i + delimiter.len()..],
+        None => return Err(truncated()),
+    };
+    let mut form = Form::new();
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(form);
+        }
+        rest = rest
+            .strip_prefix(b"\r\n")
+            .ok_or_else(|| bad("malformed multipart boundary"))?;
+
+        let mut next = b"\r\n".to_vec();
+        next.extend_from_slice(delimiter);
+        let end = find(rest, &next).ok_or_else(truncated)?;
+        let (name, v) = parse_part(&rest[..end])?;
+        insert(&mut form, name, v);
+        rest = &rest[end + next.len()..];
+    }
+}
+
+/// the name and value of a part, the value of a file an object
+fn parse_part(part: &[u8]) -> Result<(String, Value), Status> {
+    let (head, data) = match find(part, b"\r\n\r\n") {
+        Some(i) => (&part[..i], &part[i + 4..]),
+        None if part.starts_with(b"\r\n") => (&[][..], &part[2..]),
+        None => return Err(bad("multipart part without a header")),
+    };
+    let head = std::str::from_utf8(head).map_err(|e| bad(&e.to_string()))?;
+
+    let mut disposition = None;
+    let mut content_type = None;
+    for line in head.split("\r\n") {
+        if let Some((k, v)) = line.split_once(':') {
+            if k.trim().eq_ignore_ascii_case("content-disposition") {
+                disposition = Some(v.trim());
+            } else if k.trim().eq_ignore_ascii_case("content-type") {
+                content_type = Some(v.trim());
+            }
+        }
+    }
+    let disposition =
+        disposition.ok_or_else(|| bad("multipart part without a content disposition"))?;
+    let name = param(disposition, "name").ok_or_else(|| bad("multipart part without a name"))?;
+
+    let v = match param(disposition, "filename") {
+        Some(filename) => {
+            let mut file = Map::new();
+            file.insert("filename".to_string(), Value::String(filename));
+            file.insert(
+                "content_type".to_string(),
+                Value::String(
+                    content_type
+                        .unwrap_or("application/octet-stream")
+                        .to_string(),
+                ),
+            );
+            file.insert("data".to_string(), Value::String(base64::encode(data)));
+            Value::Object(file)
+        }
+        None => {
+            let s = std::str::from_utf8(data).map_err(|e| bad(&e.to_string()))?;
+            Value::String(s.to_string())
+        }
+    };
+    Ok((name, v))
+}
+
+/// the parameter `name` of a header value such as a content type,
+/// unquoted
+fn param(v: &str, name: &str) -> Option<String> {
+    v.split(';').skip(1).find_map(|p| {
+        let (k, v) = p.split_once('=')?;
+        if !k.trim().eq_ignore_ascii_case(name) {
+            return None;
+        }
+        let v = v.trim();
+        let v = v
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(v);
+        Some(v.to_string())
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn percent_decode(s: &[u8]) -> Result<String, Status> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        match s[i] {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let hex = s
+                    .get(i + 1..i + 3)
+                    .and_then(|h| std::str::from_utf8(h).ok())
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    .ok_or_else(|| bad("malformed percent encoding"))?;
+                out.push(hex);
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(out).map_err(|e| bad(&e.to_string()))
+}
+
+fn percent_encode(out: &mut String, s: &str) {
+    for b in s.bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'*' => {
+                out.push(b as char)
+            }
+            b' ' => out.push('+'),
+            b => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use errors::Code;
+    use serde_json::json;
+
+    use super::{Form, FormCodec, MULTIPART, URLENCODED};
+    use crate::{Codec, Message};
+
+    #[test]
+    fn urlencoded() {
+        let mut codec = FormCodec::default();
+        let mut m = Message::builder()
+            .content_type(URLENCODED)
+            .body("name=vine+rs&tag=a&tag=b&tag=c&empty&emoji=%F0%9F%8C%B1")
+            .build()
+            .unwrap();
+        let form = m.read_body(&mut codec.reader()).unwrap().unwrap();
+        assert_eq!(form["name"], "vine rs");
+        assert_eq!(form["tag"], json!(["a", "b", "c"]));
+        assert_eq!(form["empty"], "");
+        assert_eq!(form["emoji"], "🌱");
+
+        let mut form = Form::new();
+        form.insert("q".to_string(), json!("a&b = c"));
+        form.insert("n".to_string(), json!([1, true]));
+        m.write_body(&mut codec.writer(), form.clone()).unwrap();
+        assert_eq!(&m.body[..], b"n=1&n=true&q=a%26b+%3D+c");
+
+        form.insert("o".to_string(), json!({}));
+        assert!(m.write_body(&mut codec.writer(), form).is_err());
+        m.body = "bad=%G1".into();
+        let e = m.read_body(&mut codec.reader()).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+    }
+
+    #[test]
+    fn multipart() {
+        let mut codec = FormCodec::default();
+        let body = "preamble\r\n--XyZ\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+            hello\r\n--XyZ\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            line 1\r\nline 2\r\n--XyZ--\r\n";
+        let mut m = Message::builder()
+            .content_type(format!("{}; boundary=\"XyZ\"", MULTIPART))
+            .body(body)
+            .build()
+            .unwrap();
+        let form = m.read_body(&mut codec.reader()).unwrap().unwrap();
+        assert_eq!(form["title"], "hello");
+        assert_eq!(
+            form["file"],
+            json!({
+                "filename": "a.txt",
+                "content_type": "text/plain",
+                "data": base64::encode("line 1\r\nline 2"),
+            })
+        );
+
+        m.body = body.trim_end_matches("--XyZ--\r\n").to_string().into();
+        assert!(m.read_body(&mut codec.reader()).is_err());
+        m.header
+            .insert("Content-Type".to_string(), MULTIPART.to_string());
+        assert!(m.read_body(&mut codec.reader()).is_err());
+    }
+}
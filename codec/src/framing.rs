@@ -0,0 +1,186 @@
+use bytes::{Bytes, BytesMut};
+use errors::Status;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{too_large, DEFAULT_MAX_RECV_SIZE, DEFAULT_MAX_SEND_SIZE};
+
+/// the length before every frame, a big endian u32
+pub const LENGTH_SIZE: usize = 4;
+
+/// FrameReader reads the frames written by a [`FrameWriter`] from a byte
+/// stream, each a big endian u32 length followed by as many bytes
+///
+/// ```rust
+/// # use codec::framing::{FrameReader, FrameWriter};
+/// # async fn run() -> Result<(), errors::Status> {
+/// let (client, server) = tokio::io::duplex(1024);
+/// FrameWriter::new(client).write_frame(b"hello").await?;
+/// let frame = FrameReader::new(server).read_frame().await?;
+/// assert_eq!(frame.as_deref(), Some(&b"hello"[..]));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FrameReader<R> {
+    inner: R,
+    max_recv_size: usize,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    pub fn new(inner: R) -> Self {
+        FrameReader {
+            inner,
+            max_recv_size: DEFAULT_MAX_RECV_SIZE,
+        }
+    }
+
+    /// frames longer than `n` fail the read before they are buffered,
+    /// [`DEFAULT_MAX_RECV_SIZE`] unless set
+    #[inline]
+    pub fn with_max_recv_size(&mut self, n: usize) -> &mut Self {
+        self.max_recv_size = n;
+        self
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// the next frame, `None` when the stream ends between two frames. A
+    /// stream ending within a frame fails with the `UnexpectedEof` of the
+    /// read.
+    pub async fn read_frame(&mut self) -> Result<Option<Bytes>, Status> {
+        let mut length = [0u8; LENGTH_SIZE];
+        let mut filled = 0;
+        while filled < LENGTH_SIZE {
+            let n = self.inner.read(&mut length[filled..]).await?;
+            if n == 0 {
+                if filled == 0 {
+                    return Ok(None);
+                }
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            filled += n;
+        }
+
+        let len = u32::from_be_bytes(length) as usize;
+        if len > self.max_recv_size {
+            return Err(too_large("frame", len, self.max_recv_size));
+        }
+
+        let mut frame = BytesMut::with_capacity(len);
+        frame.resize(len, 0);
+        self.inner.read_exact(&mut frame).await?;
+        Ok(Some(frame.freeze()))
+    }
+}
+
+/// FrameWriter writes frames to a byte stream for a [`FrameReader`]
+#[derive(Debug)]
+pub struct FrameWriter<W> {
+    inner: W,
+    max_send_size: usize,
+}
+
+impl<W: AsyncWrite + Unpin> FrameWriter<W> {
+    pub fn new(inner: W) -> Self {
+        FrameWriter {
+            inner,
+            max_send_size: DEFAULT_MAX_SEND_SIZE,
+        }
+    }
+
+    /// frames longer than `n` fail the write before anything is written,
+    /// [`DEFAULT_MAX_SEND_SIZE`] unless set
+    #[inline]
+    pub fn with_max_send_size(&mut self, n: usize) -> &mut Self {
+        self.max_send_size = n;
+        self
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// writes and flushes one frame
+    pub async fn write_frame(&mut self, frame: &[u8]) -> Result<(), Status> {
+        let max = self.max_send_size.min(u32::MAX as usize);
+        if frame.len() > max {
+            return Err(too_large("frame", frame.len(), max));
+        }
+
+        self.inner
+            .write_all(&(frame.len() as u32).to_be_bytes())
+            .await?;
+        self.inner.write_all(frame).await?;
+        self.inner.flush().await?;
+        Ok(())
+    }
+
+    /// shuts the stream down, the reader sees the end after the last frame
+    pub async fn shutdown(&mut self) -> Result<(), Status> {
+        Ok(self.inner.shutdown().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use errors::Code;
+    use tokio::io::AsyncWriteExt;
+
+    use super::{FrameReader, FrameWriter};
+
+    #[tokio::test]
+    async fn frames() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut w = FrameWriter::new(client);
+        let mut r = FrameReader::new(server);
+
+        let writes = tokio::spawn(async move {
+            w.write_frame(b"hello").await.unwrap();
+            w.write_frame(b"").await.unwrap();
+            // larger than the pipe, read while written
+            w.write_frame(&[7u8; 1000]).await.unwrap();
+            w.shutdown().await.unwrap();
+        });
+
+        assert_eq!(
+            r.read_frame().await.unwrap().as_deref(),
+            Some(&b"hello"[..])
+        );
+        assert_eq!(r.read_frame().await.unwrap().as_deref(), Some(&b""[..]));
+        assert_eq!(r.read_frame().await.unwrap().unwrap().len(), 1000);
+        assert_eq!(r.read_frame().await.unwrap(), None);
+        writes.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn frame_errors() {
+        let (client, server) = tokio::io::duplex(64);
+        let mut w = FrameWriter::new(client);
+        w.with_max_send_size(4);
+        let e = w.write_frame(b"hello").await.unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+
+        let mut r = FrameReader::new(server);
+        r.with_max_recv_size(4);
+        let mut client = w.into_inner();
+        client.write_all(&[0, 0, 0, 5]).await.unwrap();
+        assert_eq!(r.read_frame().await.unwrap_err().code(), Code::BadRequest);
+
+        // the stream ends within a frame
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut r = FrameReader::new(server);
+        client.write_all(&[0, 0, 0, 3, 1]).await.unwrap();
+        drop(client);
+        assert!(r.read_frame().await.is_err());
+    }
+}
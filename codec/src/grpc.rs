@@ -0,0 +1,297 @@
+use bytes::{Buf, BufMut};
+use errors::Status;
+
+use crate::buffer::{DecodeBuf, EncodeBuf};
+use crate::{
+    too_large, Codec, Message, MessageType, Reader, Writer, DEFAULT_MAX_RECV_SIZE,
+    DEFAULT_MAX_SEND_SIZE,
+};
+
+/// the compressed flag and the big endian u32 length before every payload
+pub const HEADER_SIZE: usize = 5;
+
+/// the [`Codec`] framing the payloads of another codec like gRPC over
+/// HTTP/2 does: a byte flagging compression, the length of the payload as a
+/// big endian u32 and the payload. The frames are written uncompressed and
+/// compressed ones can't be read. The limits of the codec bound the
+/// payloads, those of the inner codec still apply.
+///
+/// ```rust
+/// # use codec::{grpc::GrpcCodec, proto::ProtoCodec, Codec, Message};
+/// # fn run(mut m: Message) -> Result<(), errors::Status> {
+/// let mut codec = GrpcCodec::<ProtoCodec<String>>::default();
+/// m.write_body(&mut codec.writer(), "hello".to_string())?;
+/// assert_eq!(&m.body[..5], &[0, 0, 0, 0, 7]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct GrpcCodec<C> {
+    inner: C,
+    max_recv_size: usize,
+    max_send_size: usize,
+}
+
+impl<C: Default> Default for GrpcCodec<C> {
+    fn default() -> Self {
+        GrpcCodec {
+            inner: C::default(),
+            max_recv_size: DEFAULT_MAX_RECV_SIZE,
+            max_send_size: DEFAULT_MAX_SEND_SIZE,
+        }
+    }
+}
+
+impl<C: Codec> GrpcCodec<C> {
+    pub fn new(inner: C) -> Self {
+        GrpcCodec {
+            inner,
+            ..Default::default()
+        }
+    }
+
+    /// frames announcing a payload longer than `n` fail the read before
+    /// the payload is waited for, [`DEFAULT_MAX_RECV_SIZE`] unless set
+    #[inline]
+    pub fn with_max_recv_size(&mut self, n: usize) -> &mut Self {
+        self.max_recv_size = n;
+        self
+    }
+
+    /// payloads longer than `n` fail the write, [`DEFAULT_MAX_SEND_SIZE`]
+    /// unless set
+    #[inline]
+    pub fn with_max_send_size(&mut self, n: usize) -> &mut Self {
+        self.max_send_size = n;
+        self
+    }
+}
+
+impl<C: Codec> Codec for GrpcCodec<C> {
+    type Read = C::Read;
+    type Write = C::Write;
+
+    type Reader = GrpcReader<C::Reader>;
+    type Writer = GrpcWriter<C::Writer>;
+
+    fn reader(&mut self) -> Self::Reader {
+        GrpcReader {
+            inner: self.inner.reader(),
+            max_size: self.max_recv_size,
+        }
+    }
+
+    fn writer(&mut self) -> Self::Writer {
+        GrpcWriter {
+            inner: self.inner.writer(),
+            max_size: self.max_send_size,
+        }
+    }
+
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        self.inner.close()
+    }
+
+    fn string() -> &'static str {
+        "grpc"
+    }
+}
+
+/// the [`Reader`] of a [`GrpcCodec`], reading one frame per call
+#[derive(Debug)]
+pub struct GrpcReader<R> {
+    inner: R,
+    max_size: usize,
+}
+
+impl<R: Reader<Error = Status>> Reader for GrpcReader<R> {
+    type Item = R::Item;
+    type Error = Status;
+
+    /// passes the header to the inner reader with a gRPC content type such
+    /// as `application/grpc+json` turned into the one of the payload,
+    /// `application/json`, `application/grpc+proto` into
+    /// `application/protobuf` and `application/grpc` left out
+    fn read_header(&self, mut m: Message, mt: MessageType) -> Result<(), Self::Error> {
+        let key = m
+            .header
+            .keys()
+            .find(|k| k.eq_ignore_ascii_case("content-type"))
+            .cloned();
+        if let Some(key) = key {
+            let ct = m.header[&key].clone();
+            if ct == "application/grpc" {
+                m.header.remove(&key);
+            } else if let Some(sub) = ct.strip_prefix("application/grpc+") {
+                let ct = match sub {
+                    "proto" => "application/protobuf".to_string(),
+                    sub => format!("application/{}", sub),
+                };
+                m.header.insert(key, ct);
+            }
+        }
+        self.inner.read_header(m, mt)
+    }
+
+    /// decodes the payload of the next frame, `None` until `src` holds all
+    /// of it
+    fn read_body(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        if src.remaining() < HEADER_SIZE {
+            return Ok(None);
+        }
+        let header = src.chunk();
+        let (compressed, len) = if header.len() >= HEADER_SIZE {
+            let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+            (header[0], len as usize)
+        } else {
+            return Ok(None);
+        };
+        match compressed {
+            0 => {}
+            1 => {
+                return Err(Status::bad_request(
+                    "io.vine.codec",
+                    "compressed grpc frames are not supported",
+                ))
+            }
+            f => {
+                return Err(Status::bad_request(
+                    "io.vine.codec",
+                    &format!("bad grpc compressed flag {}", f),
+                ))
+            }
+        }
+        if len > self.max_size {
+            return Err(too_large("grpc payload", len, self.max_size));
+        }
+        if src.remaining() < HEADER_SIZE + len {
+            return Ok(None);
+        }
+
+        src.advance(HEADER_SIZE);
+        src.frame(len, |payload| self.inner.read_body(payload))
+    }
+}
+
+/// the [`Writer`] of a [`GrpcCodec`], writing one frame per item
+#[derive(Debug)]
+pub struct GrpcWriter<W> {
+    inner: W,
+    max_size: usize,
+}
+
+impl<W: Writer<Error = Status>> Writer for GrpcWriter<W> {
+    type Item = W::Item;
+    type Error = Status;
+
+    /// writes the payload in place after a header patched with its length
+    fn write(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        let start = dst.len();
+        dst.put_slice(&[0; HEADER_SIZE]);
+        if let Err(e) = self.inner.write(item, dst) {
+            dst.truncate(start);
+            return Err(e);
+        }
+
+        let len = dst.len() - start - HEADER_SIZE;
+        let max = self.max_size.min(u32::MAX as usize);
+        if len > max {
+            dst.truncate(start);
+            return Err(too_large("grpc payload", len, max));
+        }
+        dst.written_mut()[start + 1..start + HEADER_SIZE]
+            .copy_from_slice(&(len as u32).to_be_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{Bytes, BytesMut};
+    use errors::Code;
+
+    use super::GrpcCodec;
+    use crate::buffer::{DecodeBuf, EncodeBuf};
+    use crate::proto::ProtoCodec;
+    use crate::{Codec, Message, Reader, Writer};
+
+    #[test]
+    fn grpc_frames() {
+        let mut codec = GrpcCodec::<ProtoCodec<String>>::default();
+        let mut w = codec.writer();
+        let mut r = codec.reader();
+
+        let mut buf = BytesMut::new();
+        w.write("hello".to_string(), &mut EncodeBuf::new(&mut buf))
+            .unwrap();
+        w.write("vine".to_string(), &mut EncodeBuf::new(&mut buf))
+            .unwrap();
+        assert_eq!(&buf[..5], &[0, 0, 0, 0, 7]);
+        assert_eq!(buf.len(), 5 + 7 + 5 + 6);
+
+        // a partial frame is left for the next read
+        let mut buf = buf.freeze();
+        let mut partial = buf.slice(..10);
+        let len = partial.len();
+        assert_eq!(
+            r.read_body(&mut DecodeBuf::new(&mut partial, len)),
+            Ok(None)
+        );
+        assert_eq!(partial.len(), 10);
+
+        let len = buf.len();
+        let mut src = DecodeBuf::new(&mut buf, len);
+        assert_eq!(r.read_body(&mut src), Ok(Some("hello".to_string())));
+        assert_eq!(r.read_body(&mut src), Ok(Some("vine".to_string())));
+        assert_eq!(r.read_body(&mut src), Ok(None));
+
+        let mut compressed = Bytes::from_static(&[1, 0, 0, 0, 0]);
+        let e = r.read_body(&mut DecodeBuf::new(&mut compressed, 5));
+        assert_eq!(e.unwrap_err().code(), Code::BadRequest);
+    }
+
+    #[test]
+    fn grpc_max_size() {
+        let mut codec = GrpcCodec::<ProtoCodec<String>>::default();
+        codec.with_max_recv_size(8).with_max_send_size(4);
+
+        // refused from the header, before the payload arrives
+        let mut header = Bytes::from_static(&[0, 0, 0, 0, 9]);
+        let e = codec
+            .reader()
+            .read_body(&mut DecodeBuf::new(&mut header, 5));
+        assert_eq!(e.unwrap_err().code(), Code::BadRequest);
+
+        let mut buf = BytesMut::from(&b"x"[..]);
+        let e = codec
+            .writer()
+            .write("hello".to_string(), &mut EncodeBuf::new(&mut buf));
+        assert_eq!(e.unwrap_err().code(), Code::BadRequest);
+        assert_eq!(&buf[..], b"x");
+    }
+
+    #[test]
+    fn grpc_content_type() {
+        let mut codec = GrpcCodec::<ProtoCodec<String>>::default();
+        let mut m = Message::builder()
+            .id("1")
+            .target("helloworld")
+            .method("Call")
+            .endpoint("Helloworld.Call")
+            .build()
+            .unwrap();
+        m.write_body(&mut codec.writer(), "hello".to_string())
+            .unwrap();
+
+        for ct in &["application/grpc", "application/grpc+proto"] {
+            m.header.insert("Content-Type".to_string(), ct.to_string());
+            let out = m.read_body(&mut codec.reader()).unwrap();
+            assert_eq!(out, Some("hello".to_string()));
+        }
+        m.header.insert(
+            "Content-Type".to_string(),
+            "application/grpc+json".to_string(),
+        );
+        assert!(m.read_body(&mut codec.reader()).is_err());
+    }
+}
@@ -0,0 +1,402 @@
+//! the HTTP/2 transport of messages: every call is a stream of its own, so
+//! the calls to a server share one connection, and the fields of a
+//! [`Message`] are HTTP headers, so the load balancers in front of a server
+//! can route on them. A request is a `POST` of its body to
+//! `http://{target}/{endpoint}`, its fields in the `vine-` headers and its
+//! own headers as they are, their names lowercased as HTTP/2 has them.
+
+use std::future::Future;
+
+use bytes::{Bytes, BytesMut};
+use errors::{Code, Status};
+use h2::{client::SendRequest, server::SendResponse, RecvStream};
+use http::{
+    header::{HeaderName, HeaderValue},
+    HeaderMap, Request, Response, StatusCode,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+
+use crate::{too_large, Message, MessageType, DEFAULT_MAX_RECV_SIZE};
+
+/// the headers of the fields of a message
+pub const ID: &str = "vine-id";
+pub const TYPE: &str = "vine-type";
+pub const TARGET: &str = "vine-target";
+pub const METHOD: &str = "vine-method";
+pub const ENDPOINT: &str = "vine-endpoint";
+pub const ERROR: &str = "vine-error";
+
+const FIELDS: &[&str] = &[ID, TYPE, TARGET, METHOD, ENDPOINT, ERROR];
+
+fn bad(detail: &str) -> Status {
+    Status::bad_request("io.vine.codec", detail)
+}
+
+/// the status of a failed stream or connection
+fn h2_error(e: h2::Error) -> Status {
+    if e.is_io() {
+        return e.into_io().map(Status::from).unwrap_or_else(|| {
+            Status::service_unavailable("io.vine.codec", "http2 connection failed")
+        });
+    }
+    let code = match e.reason() {
+        Some(h2::Reason::REFUSED_STREAM) => Code::ServiceUnavailable,
+        _ => Code::BadGateway,
+    };
+    Status::new("io.vine.codec".to_string(), format!("http2: {}", e), code)
+}
+
+/// Client calls a server over one HTTP/2 connection, each call on a stream
+/// of its own, so many can be in flight together. Cloned, it calls over the
+/// same connection.
+///
+/// ```rust,no_run
+/// # use codec::{http2::Client, Message};
+/// # async fn run() -> Result<(), errors::Status> {
+/// let client = Client::dial("127.0.0.1:8080").await?;
+/// let req = Message::builder()
+///     .target("greeter")
+///     .endpoint("Greeter.Hello")
+///     .body(&b"hello"[..])
+///     .build()?;
+/// let reply = client.call(&req).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Client {
+    send: SendRequest<Bytes>,
+    max_recv_size: usize,
+}
+
+impl Client {
+    /// the HTTP/2 handshake over `io`, the connection driven in the
+    /// background until every clone of the client is dropped
+    pub async fn connect<S>(io: S) -> Result<Self, Status>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (send, conn) = h2::client::handshake(io).await.map_err(h2_error)?;
+        tokio::spawn(async move {
+            let _ = conn.await;
+        });
+        Ok(Client {
+            send,
+            max_recv_size: DEFAULT_MAX_RECV_SIZE,
+        })
+    }
+
+    /// connects to `addr` over tcp, HTTP/2 without tls
+    pub async fn dial(addr: &str) -> Result<Self, Status> {
+        Client::connect(TcpStream::connect(addr).await?).await
+    }
+
+    /// replies with a body longer than `n` fail the call,
+    /// [`DEFAULT_MAX_RECV_SIZE`] unless set
+    #[inline]
+    pub fn with_max_recv_size(&mut self, n: usize) -> &mut Self {
+        self.max_recv_size = n;
+        self
+    }
+
+    /// sends `m` and waits for the reply on a new stream
+    pub async fn call(&self, m: &Message) -> Result<Message, Status> {
+        let mut req = Request::post(format!("http://{}/{}", m.target, m.endpoint))
+            .body(())
+            .map_err(|e| bad(&format!("http2 request: {}", e)))?;
+        write_fields(m, req.headers_mut())?;
+
+        let mut send = self.send.clone().ready().await.map_err(h2_error)?;
+        let (resp, mut body) = send
+            .send_request(req, m.body.is_empty())
+            .map_err(h2_error)?;
+        if !m.body.is_empty() {
+            body.send_data(m.body.clone(), true).map_err(h2_error)?;
+        }
+
+        let (parts, body) = resp.await.map_err(h2_error)?.into_parts();
+        let body = read_body(body, self.max_recv_size).await?;
+        // refused by the server before the handler, or by a proxy
+        if !parts.status.is_success() && parts.headers.get(TYPE).is_none() {
+            let detail = match parts.headers.get(ERROR).and_then(|v| v.to_str().ok()) {
+                Some(e) => e.to_string(),
+                None => format!("http2 call answered {}", parts.status),
+            };
+            let code = match parts.status {
+                StatusCode::BAD_REQUEST => Code::BadRequest,
+                _ => Code::BadGateway,
+            };
+            return Err(Status::new("io.vine.codec".to_string(), detail, code));
+        }
+        read_fields(&parts.headers, body)
+    }
+}
+
+/// serves the calls of a client over `io` until it goes away, each with
+/// `handler` on a task of its own, and sends back the message it returns.
+/// A request which is not a message is answered with a 400.
+///
+/// ```rust,no_run
+/// # async fn run(listener: tokio::net::TcpListener) -> Result<(), errors::Status> {
+/// let (tcp, _) = listener.accept().await?;
+/// codec::http2::serve(tcp, |mut m: codec::Message| async move {
+///     m.r#type = codec::MessageType::Response;
+///     m
+/// })
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn serve<S, F, Fut>(io: S, handler: F) -> Result<(), Status>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: Fn(Message) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Message> + Send + 'static,
+{
+    let mut conn = h2::server::handshake(io).await.map_err(h2_error)?;
+    while let Some(stream) = conn.accept().await {
+        let (req, respond) = stream.map_err(h2_error)?;
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            // the client sees the stream reset when the reply can't be sent
+            let _ = respond_to(req, respond, handler).await;
+        });
+    }
+    Ok(())
+}
+
+async fn respond_to<F, Fut>(
+    req: Request<RecvStream>,
+    mut respond: SendResponse<Bytes>,
+    handler: F,
+) -> Result<(), Status>
+where
+    F: Fn(Message) -> Fut,
+    Fut: Future<Output = Message>,
+{
+    let (parts, body) = req.into_parts();
+    let m = match read_body(body, DEFAULT_MAX_RECV_SIZE).await {
+        Ok(body) => read_fields(&parts.headers, body),
+        Err(e) => Err(e),
+    };
+    let reply = match m {
+        Ok(m) => handler(m).await,
+        Err(e) => {
+            let mut resp = Response::new(());
+            *resp.status_mut() = StatusCode::BAD_REQUEST;
+            if let Ok(v) = HeaderValue::from_str(e.detail()) {
+                resp.headers_mut().insert(ERROR, v);
+            }
+            respond.send_response(resp, true).map_err(h2_error)?;
+            return Ok(());
+        }
+    };
+
+    let mut resp = Response::new(());
+    write_fields(&reply, resp.headers_mut())?;
+    let mut body = respond
+        .send_response(resp, reply.body.is_empty())
+        .map_err(h2_error)?;
+    if !reply.body.is_empty() {
+        body.send_data(reply.body, true).map_err(h2_error)?;
+    }
+    Ok(())
+}
+
+/// reads the whole of `body`, giving its flow control back as it goes
+async fn read_body(mut body: RecvStream, max_size: usize) -> Result<Bytes, Status> {
+    let mut buf = BytesMut::new();
+    while let Some(data) = body.data().await {
+        let data = data.map_err(h2_error)?;
+        let _ = body.flow_control().release_capacity(data.len());
+        if buf.len() + data.len() > max_size {
+            return Err(too_large("http2 body", buf.len() + data.len(), max_size));
+        }
+        buf.extend_from_slice(&data);
+    }
+    Ok(buf.freeze())
+}
+
+/// the fields and the headers of `m` as the headers `dst`
+fn write_fields(m: &Message, dst: &mut HeaderMap) -> Result<(), Status> {
+    let r#type = (m.r#type.clone() as i32).to_string();
+    let fields = [
+        (ID, m.id.as_str()),
+        (TYPE, r#type.as_str()),
+        (TARGET, m.target.as_str()),
+        (METHOD, m.method.as_str()),
+        (ENDPOINT, m.endpoint.as_str()),
+        (ERROR, m.error.as_str()),
+    ];
+    let headers = m.header.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+    for (k, v) in fields.iter().copied().chain(headers) {
+        if v.is_empty() && FIELDS.contains(&k) {
+            continue;
+        }
+        let name = HeaderName::from_bytes(k.as_bytes())
+            .map_err(|_| bad(&format!("http2 header name {:?}", k)))?;
+        let value =
+            HeaderValue::from_str(v).map_err(|_| bad(&format!("http2 value of header {}", k)))?;
+        dst.insert(name, value);
+    }
+    Ok(())
+}
+
+/// the message of the headers `src` and `body`, a request unless typed
+fn read_fields(src: &HeaderMap, body: Bytes) -> Result<Message, Status> {
+    let field = |name: &str| -> Result<String, Status> {
+        match src.get(name) {
+            Some(v) => v
+                .to_str()
+                .map(str::to_string)
+                .map_err(|_| bad(&format!("http2 header {} is not text", name))),
+            None => Ok(String::new()),
+        }
+    };
+    let r#type = match field(TYPE)?.as_str() {
+        "0" => MessageType::Error,
+        "" | "1" => MessageType::Request,
+        "2" => MessageType::Response,
+        "3" => MessageType::Event,
+        _ => return Err(bad("http2 message of an unknown type")),
+    };
+    let mut header = std::collections::HashMap::new();
+    for (k, v) in src {
+        if FIELDS.contains(&k.as_str()) {
+            continue;
+        }
+        let v = v
+            .to_str()
+            .map_err(|_| bad(&format!("http2 header {} is not text", k)))?;
+        header.insert(k.as_str().to_string(), v.to_string());
+    }
+    Ok(Message {
+        id: field(ID)?,
+        r#type,
+        target: field(TARGET)?,
+        method: field(METHOD)?,
+        endpoint: field(ENDPOINT)?,
+        error: field(ERROR)?,
+        header,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use errors::Code;
+    use tokio::net::TcpListener;
+
+    use super::{serve, Client};
+    use crate::{Message, MessageType};
+
+    /// serves the calls of the first client, replying with the body
+    /// reversed after the delay in milliseconds of its `delay` header
+    async fn server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            serve(tcp, |mut m: Message| async move {
+                if let Some(delay) = m.header.get("delay") {
+                    let delay = delay.parse().unwrap();
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                }
+                m.r#type = MessageType::Response;
+                m.body = m.body.iter().rev().copied().collect::<Vec<_>>().into();
+                m
+            })
+            .await
+            .unwrap();
+        });
+        addr
+    }
+
+    fn request(body: &'static [u8]) -> Message {
+        Message::builder()
+            .id("7")
+            .target("greeter")
+            .method("POST")
+            .endpoint("Greeter.Hello")
+            .request_id("1")
+            .body(body)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_call() {
+        let client = Client::dial(&server().await).await.unwrap();
+        let reply = client.call(&request(b"hello")).await.unwrap();
+        assert_eq!(reply.r#type, MessageType::Response);
+        assert_eq!(
+            (
+                reply.id.as_str(),
+                reply.target.as_str(),
+                reply.endpoint.as_str()
+            ),
+            ("7", "greeter", "Greeter.Hello")
+        );
+        assert_eq!(reply.method, "POST");
+        assert_eq!(&reply.body[..], b"olleh");
+        // the names of the headers are lowercased
+        assert_eq!(
+            reply.header.get("x-request-id").map(String::as_str),
+            Some("1")
+        );
+
+        let reply = client.call(&request(b"")).await.unwrap();
+        assert!(reply.body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_streams() {
+        let client = Client::dial(&server().await).await.unwrap();
+        let mut slow = request(b"slow");
+        slow.header.insert("delay".to_string(), "200".to_string());
+
+        // both on the one connection, the fast one not waiting on the slow
+        let slow = tokio::spawn({
+            let client = client.clone();
+            async move { client.call(&slow).await }
+        });
+        let fast = tokio::time::timeout(Duration::from_millis(150), client.call(&request(b"fast")))
+            .await
+            .expect("a call waited on another")
+            .unwrap();
+        assert_eq!(&fast.body[..], b"tsaf");
+        assert_eq!(&slow.await.unwrap().unwrap().body[..], b"wols");
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let mut client = Client::dial(&server().await).await.unwrap();
+        let mut m = request(b"hello");
+        m.header.insert("bad name".to_string(), "1".to_string());
+        assert_eq!(client.call(&m).await.unwrap_err().code(), Code::BadRequest);
+
+        client.with_max_recv_size(2);
+        assert_eq!(
+            client.call(&request(b"hello")).await.unwrap_err().code(),
+            Code::BadRequest
+        );
+
+        // not a message
+        let mut m = request(b"hello");
+        m.header.insert("vine-type".to_string(), "9".to_string());
+        m.r#type = MessageType::Request;
+        let e = Client::dial(&server().await)
+            .await
+            .unwrap()
+            .call(&m)
+            .await
+            .unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+        assert_eq!(e.detail(), "http2 message of an unknown type");
+    }
+}
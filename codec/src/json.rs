@@ -0,0 +1,226 @@
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut};
+use errors::Status;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::buffer::{DecodeBuf, EncodeBuf};
+use crate::{
+    too_large, Codec, Message, MessageType, Reader, Writer, DEFAULT_MAX_RECV_SIZE,
+    DEFAULT_MAX_SEND_SIZE,
+};
+
+/// whether `ct` names JSON, such as `application/json` or
+/// `application/problem+json`
+pub fn is_json(ct: &str) -> bool {
+    let ct = crate::registry::essence(ct);
+    ct == "application/json" || ct == "text/json" || ct.ends_with("+json")
+}
+
+/// the [`Codec`] of serde values as JSON, writing `T` and reading `U`
+///
+/// ```rust
+/// # use codec::{json::JsonCodec, Codec, Message};
+/// # fn run(mut m: Message) -> Result<(), errors::Status> {
+/// let mut codec = JsonCodec::<Vec<String>>::default();
+/// m.write_body(&mut codec.writer(), vec!["hello".to_string()])?;
+/// assert_eq!(&m.body[..], br#"["hello"]"#);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct JsonCodec<T, U = T> {
+    max_recv_size: usize,
+    max_send_size: usize,
+    _pd: PhantomData<(T, U)>,
+}
+
+impl<T, U> Default for JsonCodec<T, U> {
+    fn default() -> Self {
+        JsonCodec {
+            max_recv_size: DEFAULT_MAX_RECV_SIZE,
+            max_send_size: DEFAULT_MAX_SEND_SIZE,
+            _pd: PhantomData,
+        }
+    }
+}
+
+impl<T, U> JsonCodec<T, U> {
+    /// bodies longer than `n` fail the read before they are decoded,
+    /// [`DEFAULT_MAX_RECV_SIZE`] unless set
+    #[inline]
+    pub fn with_max_recv_size(&mut self, n: usize) -> &mut Self {
+        self.max_recv_size = n;
+        self
+    }
+
+    /// items encoding to more than `n` bytes fail the write,
+    /// [`DEFAULT_MAX_SEND_SIZE`] unless set
+    #[inline]
+    pub fn with_max_send_size(&mut self, n: usize) -> &mut Self {
+        self.max_send_size = n;
+        self
+    }
+}
+
+impl<T, U> Codec for JsonCodec<T, U>
+where
+    T: Serialize + Send + Sync + 'static,
+    U: DeserializeOwned + Send + Sync + 'static,
+{
+    type Read = U;
+    type Write = T;
+
+    type Reader = JsonReader<U>;
+    type Writer = JsonWriter<T>;
+
+    fn reader(&mut self) -> Self::Reader {
+        JsonReader {
+            max_size: self.max_recv_size,
+            _pd: PhantomData,
+        }
+    }
+
+    fn writer(&mut self) -> Self::Writer {
+        JsonWriter {
+            max_size: self.max_send_size,
+            _pd: PhantomData,
+        }
+    }
+
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn string() -> &'static str {
+        "json"
+    }
+}
+
+/// the [`Reader`] of a [`JsonCodec`]
+#[derive(Debug)]
+pub struct JsonReader<U> {
+    max_size: usize,
+    _pd: PhantomData<U>,
+}
+
+impl<U: DeserializeOwned> Reader for JsonReader<U> {
+    type Item = U;
+    type Error = Status;
+
+    fn read_header(&self, m: Message, _mt: MessageType) -> Result<(), Self::Error> {
+        match m.content_type() {
+            Some(ct) if !is_json(ct) => Err(Status::bad_request(
+                "io.vine.codec",
+                &format!("content type {} is not json", ct),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// `None` for an empty body
+    fn read_body(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        if !src.has_remaining() {
+            return Ok(None);
+        }
+        if src.remaining() > self.max_size {
+            return Err(too_large("body", src.remaining(), self.max_size));
+        }
+        let item = serde_json::from_slice(src.chunk())
+            .map_err(|e| Status::bad_request("io.vine.codec", &e.to_string()))?;
+        src.advance(src.remaining());
+        Ok(Some(item))
+    }
+}
+
+/// the [`Writer`] of a [`JsonCodec`]
+#[derive(Debug)]
+pub struct JsonWriter<T> {
+    max_size: usize,
+    _pd: PhantomData<T>,
+}
+
+impl<T: Serialize> Writer for JsonWriter<T> {
+    type Item = T;
+    type Error = Status;
+
+    /// the length is only known once written, an item over the limit is
+    /// undone
+    fn write(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        let start = dst.len();
+        serde_json::to_writer(dst.writer(), &item)
+            .map_err(|e| Status::internal_server_error("io.vine.codec", &e.to_string()))?;
+
+        let len = dst.len() - start;
+        if len > self.max_size {
+            dst.truncate(start);
+            return Err(too_large("body", len, self.max_size));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use errors::Code;
+    use serde::{Deserialize, Serialize};
+
+    use super::{is_json, JsonCodec};
+    use crate::{Codec, Message};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Request {
+        name: String,
+    }
+
+    #[test]
+    fn json_max_size() {
+        let mut codec = JsonCodec::<Request>::default();
+        codec.with_max_recv_size(8).with_max_send_size(8);
+        let mut m = Message::builder().build().unwrap();
+
+        let req = Request {
+            name: "vine".to_string(),
+        };
+        let e = m.write_body(&mut codec.writer(), req).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+        assert!(m.body.is_empty());
+
+        m.body = br#"{"name":"vine"}"#[..].into();
+        let e = m.read_body(&mut codec.reader()).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+    }
+
+    #[test]
+    fn json_codec() {
+        let mut codec = JsonCodec::<Request>::default();
+        let mut m = Message::builder()
+            .id("1")
+            .target("helloworld")
+            .method("Call")
+            .endpoint("Helloworld.Call")
+            .build()
+            .unwrap();
+        assert_eq!(m.read_body(&mut codec.reader()).unwrap(), None);
+
+        let req = Request {
+            name: "vine".to_string(),
+        };
+        m.write_body(&mut codec.writer(), req).unwrap();
+        assert_eq!(&m.body[..], br#"{"name":"vine"}"#);
+        m.header.insert(
+            "Content-Type".to_string(),
+            "application/json; charset=utf-8".to_string(),
+        );
+        let out = m.read_body(&mut codec.reader()).unwrap().unwrap();
+        assert_eq!(out.name, "vine");
+
+        m.header
+            .insert("Content-Type".to_string(), "text/plain".to_string());
+        let e = m.read_body(&mut codec.reader()).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+
+        assert!(is_json("application/problem+json"));
+        assert!(!is_json("application/protobuf"));
+    }
+}
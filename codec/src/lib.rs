@@ -1,10 +1,71 @@
 pub mod buffer;
 
-use buffer::{DecodeBuf, EncodeBuf};
-use errors::{Status};
+mod builder;
+
+pub mod checksum;
+
+pub mod chunk;
+
+mod dynamic;
+
+pub mod form;
+
+pub mod framing;
+
+pub mod grpc;
+
+pub mod http2;
+
+pub mod json;
+
+pub mod ndjson;
+
+pub mod proto;
+
+pub mod raw;
 
+pub mod registry;
+
+pub mod text;
+
+pub mod transcode;
+
+pub mod websocket;
+
+use buffer::{DecodeBuf, EncodeBuf};
+pub use builder::MessageBuilder;
+use bytes::{Bytes, BytesMut};
+pub use chunk::Chunk;
+pub use dynamic::{DynCodec, TypedCodec};
+use errors::Status;
+pub use registry::from_content_type;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{collections::HashMap, io};
 
+/// the largest body or frame read unless configured otherwise, so a peer
+/// can't make the reader buffer without bounds
+pub const DEFAULT_MAX_RECV_SIZE: usize = 4 << 20;
+
+/// the largest body or frame written unless configured otherwise
+pub const DEFAULT_MAX_SEND_SIZE: usize = u32::MAX as usize;
+
+/// the error of a body or frame of `len` bytes over the limit `max`
+pub(crate) fn too_large(what: &str, len: usize, max: usize) -> Status {
+    Status::bad_request(
+        "io.vine.codec",
+        &format!("{} of {} bytes exceeds the max size {}", what, len, max),
+    )
+}
+
+/// the names of the headers with a meaning to vine
+pub mod header {
+    pub const CONTENT_TYPE: &str = "Content-Type";
+    pub const REQUEST_ID: &str = "X-Request-Id";
+    /// milliseconds since the unix epoch
+    pub const DEADLINE: &str = "X-Deadline";
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum MessageType {
     Error = 0,
@@ -25,6 +86,11 @@ pub trait Codec: Default {
 
     type Writer: Writer<Item = Self::Write, Error = Status> + Send + Sync + 'static;
 
+    /// the reader decoding the messages
+    fn reader(&mut self) -> Self::Reader;
+    /// the writer encoding the messages
+    fn writer(&mut self) -> Self::Writer;
+
     fn close(&mut self) -> Result<(), std::io::Error>;
     fn string() -> &'static str;
 }
@@ -40,6 +106,14 @@ pub trait Reader {
 
     fn read_header(&self, m: Message, mt: MessageType) -> Result<(), Self::Error>;
     fn read_body(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error>;
+
+    /// reads the next part of an item sent in chunks by
+    /// [`Writer::write_chunk`], `None` until `src` holds all of it. The
+    /// parts are put back together with a [`chunk::Assembler`] and the
+    /// item read from them with [`Reader::read_body`].
+    fn read_chunk(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Chunk>, Self::Error> {
+        Ok(chunk::read(src)?)
+    }
 }
 
 pub trait Writer {
@@ -50,6 +124,12 @@ pub trait Writer {
     type Error: From<io::Error>;
 
     fn write(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error>;
+
+    /// writes a part of an item written with [`Writer::write`] and split
+    /// with [`chunk::split`], so one item can be sent as many frames
+    fn write_chunk(&mut self, chunk: Chunk, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        Ok(chunk::write(&chunk, dst)?)
+    }
 }
 
 /// Message represents detailed information about
@@ -66,13 +146,81 @@ pub struct Message {
 
     /// The values read from the socket
     pub header: HashMap<String, String>,
-    pub body: Vec<u8>,
+    pub body: Bytes,
+}
+
+impl Message {
+    /// builds a request, only the fields set differ from empty
+    ///
+    /// ```rust
+    /// # use codec::Message;
+    /// let m = Message::builder()
+    ///     .endpoint("Greeter.Hello")
+    ///     .request_id("1")
+    ///     .body_json(&["vine"])
+    ///     .build()?;
+    /// assert_eq!(m.content_type(), Some("application/json"));
+    /// # Ok::<(), errors::Status>(())
+    /// ```
+    pub fn builder() -> MessageBuilder {
+        MessageBuilder::default()
+    }
+
+    /// the header `key`, whatever the case of its name
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.header
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// the [`header::CONTENT_TYPE`] header
+    pub fn content_type(&self) -> Option<&str> {
+        self.header(header::CONTENT_TYPE)
+    }
+
+    /// the [`header::REQUEST_ID`] header
+    pub fn request_id(&self) -> Option<&str> {
+        self.header(header::REQUEST_ID)
+    }
+
+    /// the [`header::DEADLINE`] header, `None` when missing or malformed
+    pub fn deadline(&self) -> Option<SystemTime> {
+        let millis: u64 = self.header(header::DEADLINE)?.trim().parse().ok()?;
+        Some(UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    /// checks the header with `r` and decodes the body into an item
+    pub fn read_body<R: Reader>(&self, r: &mut R) -> Result<Option<R::Item>, R::Error> {
+        let head = Message {
+            id: self.id.clone(),
+            r#type: self.r#type.clone(),
+            target: self.target.clone(),
+            method: self.method.clone(),
+            endpoint: self.endpoint.clone(),
+            error: self.error.clone(),
+            header: self.header.clone(),
+            body: Bytes::new(),
+        };
+        r.read_header(head, self.r#type.clone())?;
+
+        // shares the body, the reader splits its parts off without copies
+        let mut body = self.body.clone();
+        let len = body.len();
+        r.read_body(&mut DecodeBuf::new(&mut body, len))
+    }
+
+    /// encodes `item` with `w`, replacing the body
+    pub fn write_body<W: Writer>(&mut self, w: &mut W, item: W::Item) -> Result<(), W::Error> {
+        let mut buf = BytesMut::new();
+        w.write(item, &mut EncodeBuf::new(&mut buf))?;
+        self.body = buf.freeze();
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::fs::File;
-
     #[test]
     fn it_works() {
         // let mut f = File::open("foo.txt").unwrap();
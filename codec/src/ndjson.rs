@@ -0,0 +1,203 @@
+use std::marker::PhantomData;
+
+use bytes::{Buf, BufMut};
+use errors::Status;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::buffer::{DecodeBuf, EncodeBuf};
+use crate::registry::essence;
+use crate::{
+    too_large, Codec, Message, MessageType, Reader, Writer, DEFAULT_MAX_RECV_SIZE,
+    DEFAULT_MAX_SEND_SIZE,
+};
+
+/// the content types a [`NdjsonReader`] accepts, the first one written
+pub const CONTENT_TYPES: &[&str] = &["application/x-ndjson", "application/jsonl"];
+
+/// the [`Codec`] of newline delimited JSON, one serde value per line,
+/// writing the items of a `Vec<T>` and reading a `Vec<U>`
+///
+/// ```rust
+/// # use codec::{ndjson::NdjsonCodec, Codec, Message};
+/// # fn run(mut m: Message) -> Result<(), errors::Status> {
+/// let mut codec = NdjsonCodec::<u32>::default();
+/// m.write_body(&mut codec.writer(), vec![1, 2])?;
+/// assert_eq!(&m.body[..], b"1\n2\n");
+/// assert_eq!(m.read_body(&mut codec.reader())?, Some(vec![1, 2]));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct NdjsonCodec<T, U = T> {
+    max_recv_size: usize,
+    max_send_size: usize,
+    _pd: PhantomData<(T, U)>,
+}
+
+impl<T, U> Default for NdjsonCodec<T, U> {
+    fn default() -> Self {
+        NdjsonCodec {
+            max_recv_size: DEFAULT_MAX_RECV_SIZE,
+            max_send_size: DEFAULT_MAX_SEND_SIZE,
+            _pd: PhantomData,
+        }
+    }
+}
+
+impl<T, U> NdjsonCodec<T, U> {
+    /// bodies longer than `n` fail the read before they are decoded,
+    /// [`DEFAULT_MAX_RECV_SIZE`] unless set
+    #[inline]
+    pub fn with_max_recv_size(&mut self, n: usize) -> &mut Self {
+        self.max_recv_size = n;
+        self
+    }
+
+    /// items encoding to more than `n` bytes in all fail the write,
+    /// [`DEFAULT_MAX_SEND_SIZE`] unless set
+    #[inline]
+    pub fn with_max_send_size(&mut self, n: usize) -> &mut Self {
+        self.max_send_size = n;
+        self
+    }
+}
+
+impl<T, U> Codec for NdjsonCodec<T, U>
+where
+    T: Serialize + Send + Sync + 'static,
+    U: DeserializeOwned + Send + Sync + 'static,
+{
+    type Read = Vec<U>;
+    type Write = Vec<T>;
+
+    type Reader = NdjsonReader<U>;
+    type Writer = NdjsonWriter<T>;
+
+    fn reader(&mut self) -> Self::Reader {
+        NdjsonReader {
+            max_size: self.max_recv_size,
+            _pd: PhantomData,
+        }
+    }
+
+    fn writer(&mut self) -> Self::Writer {
+        NdjsonWriter {
+            max_size: self.max_send_size,
+            _pd: PhantomData,
+        }
+    }
+
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn string() -> &'static str {
+        "ndjson"
+    }
+}
+
+/// the [`Reader`] of a [`NdjsonCodec`]
+#[derive(Debug)]
+pub struct NdjsonReader<U> {
+    max_size: usize,
+    _pd: PhantomData<U>,
+}
+
+impl<U: DeserializeOwned> Reader for NdjsonReader<U> {
+    type Item = Vec<U>;
+    type Error = Status;
+
+    fn read_header(&self, m: Message, _mt: MessageType) -> Result<(), Self::Error> {
+        match m.content_type() {
+            Some(ct) if !CONTENT_TYPES.contains(&essence(ct).as_str()) => Err(Status::bad_request(
+                "io.vine.codec",
+                &format!("content type {} is not ndjson", ct),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// the value of every line, blank lines skipped. A failed line is
+    /// reported by its number.
+    fn read_body(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        if src.remaining() > self.max_size {
+            return Err(too_large("body", src.remaining(), self.max_size));
+        }
+        let mut items = Vec::new();
+        for (i, line) in src.chunk().split(|b| *b == b'\n').enumerate() {
+            if line.iter().all(|b| b.is_ascii_whitespace()) {
+                continue;
+            }
+            let item = serde_json::from_slice(line).map_err(|e| {
+                Status::bad_request("io.vine.codec", &format!("line {}: {}", i + 1, e))
+            })?;
+            items.push(item);
+        }
+        src.advance(src.remaining());
+        Ok(Some(items))
+    }
+}
+
+/// the [`Writer`] of a [`NdjsonCodec`]
+#[derive(Debug)]
+pub struct NdjsonWriter<T> {
+    max_size: usize,
+    _pd: PhantomData<T>,
+}
+
+impl<T: Serialize> Writer for NdjsonWriter<T> {
+    type Item = Vec<T>;
+    type Error = Status;
+
+    /// every item on a line of its own, ended by a newline
+    fn write(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        let start = dst.len();
+        for v in &item {
+            if let Err(e) = serde_json::to_writer(dst.writer(), v) {
+                dst.truncate(start);
+                return Err(Status::internal_server_error(
+                    "io.vine.codec",
+                    &e.to_string(),
+                ));
+            }
+            dst.put_u8(b'\n');
+        }
+
+        let len = dst.len() - start;
+        if len > self.max_size {
+            dst.truncate(start);
+            return Err(too_large("body", len, self.max_size));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use errors::Code;
+    use serde_json::{json, Value};
+
+    use super::NdjsonCodec;
+    use crate::{Codec, Message};
+
+    #[test]
+    fn ndjson_codec() {
+        let mut codec = NdjsonCodec::<Value>::default();
+        let mut m = Message::builder()
+            .content_type("application/x-ndjson")
+            .build()
+            .unwrap();
+        assert_eq!(m.read_body(&mut codec.reader()).unwrap(), Some(vec![]));
+
+        m.write_body(&mut codec.writer(), vec![json!({"a": 1}), json!("b\nc")])
+            .unwrap();
+        assert_eq!(&m.body[..], b"{\"a\":1}\n\"b\\nc\"\n");
+        let items = m.read_body(&mut codec.reader()).unwrap().unwrap();
+        assert_eq!(items, vec![json!({"a": 1}), json!("b\nc")]);
+
+        m.body = "1\r\n\n  \n{\"bad\"\n".into();
+        let e = m.read_body(&mut codec.reader()).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+        assert!(e.detail().starts_with("line 4"), "{}", e.detail());
+    }
+}
@@ -0,0 +1,208 @@
+use std::marker::PhantomData;
+
+use bytes::Buf;
+use errors::Status;
+
+use crate::buffer::{DecodeBuf, EncodeBuf};
+use crate::{
+    too_large, Codec, Message, MessageType, Reader, Writer, DEFAULT_MAX_RECV_SIZE,
+    DEFAULT_MAX_SEND_SIZE,
+};
+
+/// the content types a [`ProtoReader`] accepts, a message without one is
+/// taken as protobuf as well
+pub const CONTENT_TYPES: &[&str] = &[
+    "application/protobuf",
+    "application/x-protobuf",
+    "application/octet-stream",
+];
+
+/// the [`Codec`] of prost messages, writing `T` and reading `U`
+///
+/// ```rust
+/// # use codec::{proto::ProtoCodec, Codec, Message, MessageType};
+/// # fn run(mut m: Message) -> Result<(), errors::Status> {
+/// let mut codec = ProtoCodec::<String>::default();
+/// m.write_body(&mut codec.writer(), "hello".to_string())?;
+/// assert_eq!(m.read_body(&mut codec.reader())?, Some("hello".to_string()));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProtoCodec<T, U = T> {
+    max_recv_size: usize,
+    max_send_size: usize,
+    _pd: PhantomData<(T, U)>,
+}
+
+impl<T, U> Default for ProtoCodec<T, U> {
+    fn default() -> Self {
+        ProtoCodec {
+            max_recv_size: DEFAULT_MAX_RECV_SIZE,
+            max_send_size: DEFAULT_MAX_SEND_SIZE,
+            _pd: PhantomData,
+        }
+    }
+}
+
+impl<T, U> ProtoCodec<T, U> {
+    /// bodies longer than `n` fail the read before they are decoded,
+    /// [`DEFAULT_MAX_RECV_SIZE`] unless set
+    #[inline]
+    pub fn with_max_recv_size(&mut self, n: usize) -> &mut Self {
+        self.max_recv_size = n;
+        self
+    }
+
+    /// items encoding to more than `n` bytes fail the write,
+    /// [`DEFAULT_MAX_SEND_SIZE`] unless set
+    #[inline]
+    pub fn with_max_send_size(&mut self, n: usize) -> &mut Self {
+        self.max_send_size = n;
+        self
+    }
+}
+
+impl<T, U> Codec for ProtoCodec<T, U>
+where
+    T: prost::Message + Send + 'static,
+    U: prost::Message + Default + Send + 'static,
+{
+    type Read = U;
+    type Write = T;
+
+    type Reader = ProtoReader<U>;
+    type Writer = ProtoWriter<T>;
+
+    fn reader(&mut self) -> Self::Reader {
+        ProtoReader {
+            max_size: self.max_recv_size,
+            _pd: PhantomData,
+        }
+    }
+
+    fn writer(&mut self) -> Self::Writer {
+        ProtoWriter {
+            max_size: self.max_send_size,
+            _pd: PhantomData,
+        }
+    }
+
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn string() -> &'static str {
+        "proto"
+    }
+}
+
+/// the [`Reader`] of a [`ProtoCodec`]
+#[derive(Debug)]
+pub struct ProtoReader<U> {
+    max_size: usize,
+    _pd: PhantomData<U>,
+}
+
+impl<U: prost::Message + Default> Reader for ProtoReader<U> {
+    type Item = U;
+    type Error = Status;
+
+    fn read_header(&self, m: Message, _mt: MessageType) -> Result<(), Self::Error> {
+        match m.content_type() {
+            Some(ct) if !CONTENT_TYPES.contains(&ct) => Err(Status::bad_request(
+                "io.vine.codec",
+                &format!("content type {} is not protobuf", ct),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn read_body(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        if src.remaining() > self.max_size {
+            return Err(too_large("body", src.remaining(), self.max_size));
+        }
+        U::decode(src)
+            .map(Some)
+            .map_err(|e| Status::bad_request("io.vine.codec", &e.to_string()))
+    }
+}
+
+/// the [`Writer`] of a [`ProtoCodec`]
+#[derive(Debug)]
+pub struct ProtoWriter<T> {
+    max_size: usize,
+    _pd: PhantomData<T>,
+}
+
+impl<T: prost::Message> Writer for ProtoWriter<T> {
+    type Item = T;
+    type Error = Status;
+
+    fn write(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        let len = item.encoded_len();
+        if len > self.max_size {
+            return Err(too_large("body", len, self.max_size));
+        }
+        dst.reserve(len);
+        item.encode(dst)
+            .map_err(|e| Status::internal_server_error("io.vine.codec", &e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use errors::Code;
+
+    use super::ProtoCodec;
+    use crate::{Codec, Message};
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Request {
+        #[prost(string, tag = "1")]
+        name: String,
+        #[prost(int64, tag = "2")]
+        count: i64,
+    }
+
+    fn message() -> Message {
+        Message::builder()
+            .id("1")
+            .target("helloworld")
+            .method("Call")
+            .endpoint("Helloworld.Call")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn proto_codec() {
+        let mut codec = ProtoCodec::<Request>::default();
+        let req = Request {
+            name: "vine".to_string(),
+            count: 3,
+        };
+
+        let mut m = message();
+        m.write_body(&mut codec.writer(), req.clone()).unwrap();
+        assert_eq!(m.body.to_vec(), prost::Message::encode_to_vec(&req));
+        assert_eq!(m.read_body(&mut codec.reader()).unwrap(), Some(req.clone()));
+
+        m.header
+            .insert("content-type".to_string(), "application/json".to_string());
+        let e = m.read_body(&mut codec.reader()).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+
+        let mut m = message();
+        m.body = Bytes::from_static(&[0xff, 0xff]);
+        assert!(m.read_body(&mut codec.reader()).is_err());
+
+        codec.with_max_recv_size(1);
+        let e = m.read_body(&mut codec.reader()).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+        codec.with_max_send_size(1);
+        let e = m.write_body(&mut codec.writer(), req).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+    }
+}
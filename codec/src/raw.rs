@@ -0,0 +1,168 @@
+use bytes::{Buf, BufMut, Bytes};
+use errors::Status;
+
+use crate::buffer::{DecodeBuf, EncodeBuf};
+use crate::{
+    too_large, Codec, Message, MessageType, Reader, Writer, DEFAULT_MAX_RECV_SIZE,
+    DEFAULT_MAX_SEND_SIZE,
+};
+
+/// the [`Codec`] passing bodies through as they are, whatever their content
+/// type, for proxies and gateways forwarding messages they don't decode.
+/// The bodies read share the memory of the message.
+///
+/// ```rust
+/// # use bytes::Bytes;
+/// # use codec::{raw::BytesCodec, Codec, Message};
+/// # fn run(mut m: Message) -> Result<(), errors::Status> {
+/// let mut codec = BytesCodec::default();
+/// m.write_body(&mut codec.writer(), Bytes::from_static(b"\x08\x01"))?;
+/// assert_eq!(m.read_body(&mut codec.reader())?.as_deref(), Some(&b"\x08\x01"[..]));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BytesCodec {
+    max_recv_size: usize,
+    max_send_size: usize,
+}
+
+impl Default for BytesCodec {
+    fn default() -> Self {
+        BytesCodec {
+            max_recv_size: DEFAULT_MAX_RECV_SIZE,
+            max_send_size: DEFAULT_MAX_SEND_SIZE,
+        }
+    }
+}
+
+impl BytesCodec {
+    /// bodies longer than `n` fail the read, [`DEFAULT_MAX_RECV_SIZE`]
+    /// unless set
+    #[inline]
+    pub fn with_max_recv_size(&mut self, n: usize) -> &mut Self {
+        self.max_recv_size = n;
+        self
+    }
+
+    /// bodies longer than `n` fail the write, [`DEFAULT_MAX_SEND_SIZE`]
+    /// unless set
+    #[inline]
+    pub fn with_max_send_size(&mut self, n: usize) -> &mut Self {
+        self.max_send_size = n;
+        self
+    }
+}
+
+impl Codec for BytesCodec {
+    type Read = Bytes;
+    type Write = Bytes;
+
+    type Reader = BytesReader;
+    type Writer = BytesWriter;
+
+    fn reader(&mut self) -> Self::Reader {
+        BytesReader {
+            max_size: self.max_recv_size,
+        }
+    }
+
+    fn writer(&mut self) -> Self::Writer {
+        BytesWriter {
+            max_size: self.max_send_size,
+        }
+    }
+
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn string() -> &'static str {
+        "bytes"
+    }
+}
+
+/// the [`Reader`] of a [`BytesCodec`]
+#[derive(Debug)]
+pub struct BytesReader {
+    max_size: usize,
+}
+
+impl Reader for BytesReader {
+    type Item = Bytes;
+    type Error = Status;
+
+    /// any content type is read, it is the forwarded header's to tell
+    fn read_header(&self, _m: Message, _mt: MessageType) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// the whole body, empty ones included
+    fn read_body(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        if src.remaining() > self.max_size {
+            return Err(too_large("body", src.remaining(), self.max_size));
+        }
+        Ok(Some(src.split_to(src.remaining())))
+    }
+}
+
+/// the [`Writer`] of a [`BytesCodec`]
+#[derive(Debug)]
+pub struct BytesWriter {
+    max_size: usize,
+}
+
+impl Writer for BytesWriter {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn write(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        if item.len() > self.max_size {
+            return Err(too_large("body", item.len(), self.max_size));
+        }
+        dst.put(item);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use errors::Code;
+
+    use super::BytesCodec;
+    use crate::grpc::GrpcCodec;
+    use crate::{Codec, Message};
+
+    #[test]
+    fn bytes_codec() {
+        let mut codec = BytesCodec::default();
+        let mut m = Message::builder()
+            .content_type("application/x-unknown")
+            .build()
+            .unwrap();
+        assert_eq!(m.read_body(&mut codec.reader()), Ok(Some(Bytes::new())));
+
+        m.write_body(&mut codec.writer(), Bytes::from_static(b"payload"))
+            .unwrap();
+        let body = m.read_body(&mut codec.reader()).unwrap().unwrap();
+        assert_eq!(&body[..], b"payload");
+        // a view of the body, not a copy
+        assert_eq!(body.as_ptr(), m.body.as_ptr());
+
+        // forwards gRPC frames without knowing the payload
+        let mut grpc = GrpcCodec::<BytesCodec>::default();
+        m.write_body(&mut grpc.writer(), body).unwrap();
+        assert_eq!(&m.body[..5], &[0, 0, 0, 0, 7]);
+        let out = m.read_body(&mut grpc.reader()).unwrap();
+        assert_eq!(out.as_deref(), Some(&b"payload"[..]));
+
+        codec.with_max_recv_size(4).with_max_send_size(4);
+        let e = m.read_body(&mut codec.reader()).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+        let e = m
+            .write_body(&mut codec.writer(), Bytes::from_static(b"payload"))
+            .unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+    }
+}
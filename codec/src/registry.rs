@@ -0,0 +1,280 @@
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+use std::sync::{Arc, RwLock};
+
+use bytes::Bytes;
+use errors::Status;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::dynamic::{DynCodec, TypedCodec};
+use crate::form::{FormCodec, MULTIPART, URLENCODED};
+use crate::grpc::GrpcCodec;
+use crate::json::{is_json, JsonCodec};
+use crate::ndjson::{self, NdjsonCodec};
+use crate::raw::BytesCodec;
+use crate::text::{self, TextCodec};
+use crate::{header, Codec, Message};
+
+/// builds the codec of a content type
+pub type Factory = dyn Fn() -> Box<dyn DynCodec> + Send + Sync;
+
+/// content type -> factory
+static CODECS: RwLock<BTreeMap<String, Arc<Factory>>> = RwLock::new(BTreeMap::new());
+
+/// the media type of `ct` without its parameters, lowercased:
+/// `application/json` of `Application/JSON; charset=utf-8`
+pub fn essence(ct: &str) -> String {
+    ct.split(';')
+        .next()
+        .unwrap_or(ct)
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// makes `factory` the codec of `content_type`, replacing an earlier one
+/// and the built in one
+pub fn register<F>(content_type: &str, factory: F)
+where
+    F: Fn() -> Box<dyn DynCodec> + Send + Sync + 'static,
+{
+    let mut codecs = CODECS.write().unwrap_or_else(|e| e.into_inner());
+    codecs.insert(essence(content_type), Arc::new(factory));
+}
+
+/// makes the typed codec `C` the codec of `content_type`, converting
+/// between its items and JSON values with serde. A protobuf codec can be
+/// registered this way for a message deriving serde:
+///
+/// ```rust
+/// # use codec::{proto::ProtoCodec, registry};
+/// registry::register_codec::<ProtoCodec<String>>("application/protobuf");
+/// assert!(codec::from_content_type("application/protobuf").is_some());
+/// ```
+pub fn register_codec<C>(content_type: &str)
+where
+    C: Codec + 'static,
+    C::Read: Serialize,
+    C::Write: DeserializeOwned,
+{
+    let ct = content_type.to_string();
+    register(content_type, move || {
+        Box::new(TypedCodec::<C>::new(ct.clone()))
+    });
+}
+
+/// removes the codec registered for `content_type`, false when there was
+/// none. The built in ones can't be removed.
+pub fn deregister(content_type: &str) -> bool {
+    let mut codecs = CODECS.write().unwrap_or_else(|e| e.into_inner());
+    codecs.remove(&essence(content_type)).is_some()
+}
+
+/// the content types a codec is registered for, built in or not, sorted
+pub fn content_types() -> Vec<String> {
+    let codecs = CODECS.read().unwrap_or_else(|e| e.into_inner());
+    let mut out: Vec<String> = codecs.keys().cloned().collect();
+    for ct in BUILT_IN {
+        if !codecs.contains_key(*ct) {
+            out.push(ct.to_string());
+        }
+    }
+    out.sort();
+    out
+}
+
+/// the content types served by [`JsonCodec`], along with the `+json`
+/// suffixed ones, [`FormCodec`], [`NdjsonCodec`], [`TextCodec`] and
+/// [`ProtoBytes`] unless registered otherwise
+const BUILT_IN: &[&str] = &[
+    "application/json",
+    "text/json",
+    URLENCODED,
+    MULTIPART,
+    "application/x-ndjson",
+    "application/jsonl",
+    text::CONTENT_TYPE,
+    "application/protobuf",
+    "application/x-protobuf",
+    GRPC_PROTO,
+];
+
+const GRPC_PROTO: &str = "application/grpc+proto";
+
+/// the built in codec of protobuf bodies. Without the type of the message
+/// to decode them into, they are carried as the base64 of their wire bytes,
+/// the JSON of a protobuf `bytes`, so they still pass through a server
+/// serving every content type. A typed codec registered with
+/// [`register_codec`] replaces it.
+struct ProtoBytes<C> {
+    content_type: String,
+    _pd: PhantomData<fn() -> C>,
+}
+
+impl<C> ProtoBytes<C> {
+    fn new(content_type: impl Into<String>) -> Self {
+        ProtoBytes {
+            content_type: content_type.into(),
+            _pd: PhantomData,
+        }
+    }
+}
+
+impl<C> DynCodec for ProtoBytes<C>
+where
+    C: Codec<Read = Bytes, Write = Bytes>,
+{
+    fn string(&self) -> &str {
+        C::string()
+    }
+
+    fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    fn decode(&self, m: &Message) -> Result<Value, Status> {
+        match m.read_body(&mut C::default().reader())? {
+            Some(body) if !body.is_empty() => Ok(Value::String(base64::encode(body))),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    fn encode(&self, v: Value, m: &mut Message) -> Result<(), Status> {
+        let body = match v {
+            Value::Null => Bytes::new(),
+            Value::String(s) => base64::decode(s)
+                .map_err(|e| Status::bad_request("io.vine.codec", &e.to_string()))?
+                .into(),
+            v => {
+                return Err(Status::bad_request(
+                    "io.vine.codec",
+                    &format!("a protobuf body is a base64 string, not {}", v),
+                ))
+            }
+        };
+        m.write_body(&mut C::default().writer(), body)?;
+        m.header
+            .retain(|k, _| !k.eq_ignore_ascii_case(header::CONTENT_TYPE));
+        m.header
+            .insert(header::CONTENT_TYPE.to_string(), self.content_type.clone());
+        Ok(())
+    }
+}
+
+/// the codec of the content type `ct`, parameters such as the charset
+/// ignored, `None` when no codec is registered for it. The JSON, form,
+/// ndjson, plain text and protobuf types are built in, protobuf as the
+/// base64 of the bodies; typed protobuf messages and other formats are
+/// registered with [`register`] or [`register_codec`].
+pub fn from_content_type(ct: &str) -> Option<Box<dyn DynCodec>> {
+    let ct = essence(ct);
+    let factory = {
+        let codecs = CODECS.read().unwrap_or_else(|e| e.into_inner());
+        codecs.get(&ct).cloned()
+    };
+    match factory {
+        Some(factory) => Some(factory()),
+        None if is_json(&ct) => Some(Box::new(TypedCodec::<JsonCodec<Value>>::new(ct))),
+        // forms are written urlencoded whatever they were read as
+        None if ct == URLENCODED || ct == MULTIPART => {
+            Some(Box::new(TypedCodec::<FormCodec>::new(URLENCODED)))
+        }
+        None if ndjson::CONTENT_TYPES.contains(&ct.as_str()) => {
+            Some(Box::new(TypedCodec::<NdjsonCodec<Value>>::new(ct)))
+        }
+        None if ct == text::CONTENT_TYPE => Some(Box::new(TypedCodec::<TextCodec>::new(ct))),
+        None if ct == GRPC_PROTO => Some(Box::new(ProtoBytes::<GrpcCodec<BytesCodec>>::new(ct))),
+        None if ct == "application/protobuf" || ct == "application/x-protobuf" => {
+            Some(Box::new(ProtoBytes::<BytesCodec>::new(ct)))
+        }
+        None => None,
+    }
+}
+
+/// a codec of every content type in [`content_types`], for a server
+/// serving them all
+pub fn codecs() -> HashMap<String, Box<dyn DynCodec>> {
+    content_types()
+        .into_iter()
+        .filter_map(|ct| from_content_type(&ct).map(|c| (ct, c)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{codecs, content_types, deregister, essence, from_content_type, register_codec};
+    use crate::proto::ProtoCodec;
+    use crate::Message;
+
+    fn message() -> Message {
+        Message::builder()
+            .id("1")
+            .target("helloworld")
+            .method("Call")
+            .endpoint("Helloworld.Call")
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn content_type_registry() {
+        assert_eq!(
+            essence(" Application/JSON; charset=utf-8"),
+            "application/json"
+        );
+
+        let json = from_content_type("application/json; charset=utf-8").unwrap();
+        let mut m = message();
+        json.encode(json!({"name": "vine"}), &mut m).unwrap();
+        assert_eq!(m.content_type(), Some("application/json"));
+        assert_eq!(json.decode(&m).unwrap(), json!({"name": "vine"}));
+        assert!(from_content_type("application/vnd.vine+json").is_some());
+
+        assert!(from_content_type("application/x-test-proto").is_none());
+        register_codec::<ProtoCodec<String>>("application/x-test-proto");
+        assert!(content_types().contains(&"application/x-test-proto".to_string()));
+        assert_eq!(codecs()["application/x-test-proto"].string(), "proto");
+
+        let proto = from_content_type("application/x-test-proto").unwrap();
+        let mut m = message();
+        proto.encode(json!("hello"), &mut m).unwrap();
+        assert_eq!(m.content_type(), Some("application/x-test-proto"));
+        assert_eq!(
+            m.body.to_vec(),
+            prost::Message::encode_to_vec(&"hello".to_string())
+        );
+        // the proto reader only knows its own content types
+        m.header.clear();
+        assert_eq!(proto.decode(&m).unwrap(), json!("hello"));
+        assert!(proto.encode(json!(1), &mut m).is_err());
+
+        assert!(deregister("application/x-test-proto"));
+        assert!(!deregister("application/json"));
+    }
+
+    #[test]
+    fn built_in_protobuf() {
+        let body = prost::Message::encode_to_vec(&"hello".to_string());
+        for ct in &["application/protobuf", "application/grpc+proto"] {
+            assert!(content_types().contains(&ct.to_string()));
+            let proto = from_content_type(ct).unwrap();
+            let mut m = message();
+            proto.encode(json!(base64::encode(&body)), &mut m).unwrap();
+            assert_eq!(m.content_type(), Some(*ct));
+            assert_eq!(proto.decode(&m).unwrap(), json!(base64::encode(&body)));
+            assert!(proto.encode(json!({"name": "vine"}), &mut m).is_err());
+        }
+
+        // the wire bytes, framed for gRPC
+        let proto = from_content_type("application/protobuf").unwrap();
+        let mut m = message();
+        proto.encode(json!(base64::encode(&body)), &mut m).unwrap();
+        assert_eq!(m.body.to_vec(), body);
+        let grpc = from_content_type("application/grpc+proto").unwrap();
+        grpc.encode(json!(base64::encode(&body)), &mut m).unwrap();
+        assert_eq!(&m.body[..5], &[0, 0, 0, 0, body.len() as u8]);
+        assert_eq!(&m.body[5..], &body[..]);
+    }
+}
@@ -0,0 +1,186 @@
+use bytes::{Buf, BufMut};
+use errors::Status;
+
+use crate::buffer::{DecodeBuf, EncodeBuf};
+use crate::registry::essence;
+use crate::{
+    too_large, Codec, Message, MessageType, Reader, Writer, DEFAULT_MAX_RECV_SIZE,
+    DEFAULT_MAX_SEND_SIZE,
+};
+
+/// the content type the [`TextCodec`] is registered for
+pub const CONTENT_TYPE: &str = "text/plain";
+
+/// the [`Codec`] of UTF-8 text bodies, for debug endpoints and the command
+/// line to show the bodies as they are
+///
+/// ```rust
+/// # use codec::{text::TextCodec, Codec, Message};
+/// # fn run(mut m: Message) -> Result<(), errors::Status> {
+/// let mut codec = TextCodec::default();
+/// m.write_body(&mut codec.writer(), "hello\n".to_string())?;
+/// assert_eq!(m.read_body(&mut codec.reader())?.as_deref(), Some("hello\n"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TextCodec {
+    max_recv_size: usize,
+    max_send_size: usize,
+}
+
+impl Default for TextCodec {
+    fn default() -> Self {
+        TextCodec {
+            max_recv_size: DEFAULT_MAX_RECV_SIZE,
+            max_send_size: DEFAULT_MAX_SEND_SIZE,
+        }
+    }
+}
+
+impl TextCodec {
+    /// bodies longer than `n` fail the read, [`DEFAULT_MAX_RECV_SIZE`]
+    /// unless set
+    #[inline]
+    pub fn with_max_recv_size(&mut self, n: usize) -> &mut Self {
+        self.max_recv_size = n;
+        self
+    }
+
+    /// text longer than `n` bytes fails the write, [`DEFAULT_MAX_SEND_SIZE`]
+    /// unless set
+    #[inline]
+    pub fn with_max_send_size(&mut self, n: usize) -> &mut Self {
+        self.max_send_size = n;
+        self
+    }
+}
+
+impl Codec for TextCodec {
+    type Read = String;
+    type Write = String;
+
+    type Reader = TextReader;
+    type Writer = TextWriter;
+
+    fn reader(&mut self) -> Self::Reader {
+        TextReader {
+            max_size: self.max_recv_size,
+        }
+    }
+
+    fn writer(&mut self) -> Self::Writer {
+        TextWriter {
+            max_size: self.max_send_size,
+        }
+    }
+
+    fn close(&mut self) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn string() -> &'static str {
+        "text"
+    }
+}
+
+/// the [`Reader`] of a [`TextCodec`]
+#[derive(Debug)]
+pub struct TextReader {
+    max_size: usize,
+}
+
+impl Reader for TextReader {
+    type Item = String;
+    type Error = Status;
+
+    /// accepts any `text/*` content type, the charset must be UTF-8
+    fn read_header(&self, m: Message, _mt: MessageType) -> Result<(), Self::Error> {
+        let ct = match m.content_type() {
+            Some(ct) => ct,
+            None => return Ok(()),
+        };
+        if !essence(ct).starts_with("text/") {
+            return Err(Status::bad_request(
+                "io.vine.codec",
+                &format!("content type {} is not text", ct),
+            ));
+        }
+        let charset = ct
+            .split(';')
+            .skip(1)
+            .filter_map(|p| p.split_once('='))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case("charset"))
+            .map(|(_, v)| v.trim().trim_matches('"'));
+        match charset {
+            Some(cs)
+                if !cs.eq_ignore_ascii_case("utf-8") && !cs.eq_ignore_ascii_case("us-ascii") =>
+            {
+                Err(Status::bad_request(
+                    "io.vine.codec",
+                    &format!("charset {} is not supported", cs),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// the whole body, an empty one included
+    fn read_body(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        if src.remaining() > self.max_size {
+            return Err(too_large("body", src.remaining(), self.max_size));
+        }
+        let text = std::str::from_utf8(src.chunk())
+            .map_err(|e| Status::bad_request("io.vine.codec", &e.to_string()))?
+            .to_string();
+        src.advance(src.remaining());
+        Ok(Some(text))
+    }
+}
+
+/// the [`Writer`] of a [`TextCodec`]
+#[derive(Debug)]
+pub struct TextWriter {
+    max_size: usize,
+}
+
+impl Writer for TextWriter {
+    type Item = String;
+    type Error = Status;
+
+    fn write(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        if item.len() > self.max_size {
+            return Err(too_large("body", item.len(), self.max_size));
+        }
+        dst.put_slice(item.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use errors::Code;
+
+    use super::TextCodec;
+    use crate::{Codec, Message};
+
+    #[test]
+    fn text_codec() {
+        let mut codec = TextCodec::default();
+        let mut m = Message::builder()
+            .content_type("text/plain; charset=UTF-8")
+            .body("héllo")
+            .build()
+            .unwrap();
+        assert_eq!(m.read_body(&mut codec.reader()).unwrap().unwrap(), "héllo");
+
+        m.body = vec![0xff, 0xfe].into();
+        let e = m.read_body(&mut codec.reader()).unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+
+        for ct in &["text/plain; charset=latin1", "application/json"] {
+            m.header.insert("Content-Type".to_string(), ct.to_string());
+            assert!(m.read_body(&mut codec.reader()).is_err(), "{}", ct);
+        }
+    }
+}
@@ -0,0 +1,840 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use errors::Status;
+use prost::Message as _;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorSet};
+use serde_json::{Map, Number, Value};
+
+use crate::proto::CONTENT_TYPES;
+use crate::DynCodec;
+use crate::{header, Message};
+
+/// the deepest nesting of messages transcoded, so a peer can't exhaust the
+/// stack
+const MAX_DEPTH: usize = 100;
+
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const DELIMITED: u64 = 2;
+const FIXED32: u64 = 5;
+
+fn bad(detail: &str) -> Status {
+    Status::bad_request("io.vine.codec", detail)
+}
+
+/// Descriptors transcodes protobuf messages to JSON and back with the
+/// descriptors of their types, following the proto3 JSON mapping: fields
+/// by their JSON name, 64 bit integers as strings, bytes as base64 and
+/// enums by name. The well known types are transcoded as plain messages.
+///
+/// ```rust
+/// # use std::sync::Arc;
+/// # use codec::{DynCodec, transcode::Descriptors, Message};
+/// # fn run(set: &[u8], mut http: Message, mut grpc: Message) -> Result<(), errors::Status> {
+/// // the output of protoc --descriptor_set_out
+/// let descriptors = Arc::new(Descriptors::decode(set)?);
+/// let request = descriptors.codec("helloworld.Request")?;
+/// let json = codec::from_content_type("application/json").unwrap();
+/// request.encode(json.decode(&http)?, &mut grpc)?;
+///
+/// let response = descriptors.codec("helloworld.Response")?;
+/// json.encode(response.decode(&grpc)?, &mut http)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Descriptors {
+    /// by full name, without the leading dot
+    messages: HashMap<String, DescriptorProto>,
+    enums: HashMap<String, EnumDescriptorProto>,
+}
+
+impl Descriptors {
+    pub fn new(set: FileDescriptorSet) -> Self {
+        let mut d = Descriptors::default();
+        for file in set.file {
+            let prefix = match file.package() {
+                "" => String::new(),
+                pkg => format!("{}.", pkg),
+            };
+            for e in file.enum_type {
+                d.enums.insert(format!("{}{}", prefix, e.name()), e);
+            }
+            for m in file.message_type {
+                d.index(&prefix, m);
+            }
+        }
+        d
+    }
+
+    /// the descriptors of an encoded `FileDescriptorSet`
+    pub fn decode(buf: &[u8]) -> Result<Self, Status> {
+        let set = FileDescriptorSet::decode(buf).map_err(|e| bad(&e.to_string()))?;
+        Ok(Descriptors::new(set))
+    }
+
+    fn index(&mut self, prefix: &str, mut m: DescriptorProto) {
+        let name = format!("{}{}", prefix, m.name());
+        let nested = format!("{}.", name);
+        for e in std::mem::take(&mut m.enum_type) {
+            self.enums.insert(format!("{}{}", nested, e.name()), e);
+        }
+        for n in std::mem::take(&mut m.nested_type) {
+            self.index(&nested, n);
+        }
+        self.messages.insert(name, m);
+    }
+
+    /// the full names of the messages, such as `helloworld.Request`, sorted
+    pub fn messages(&self) -> Vec<&str> {
+        let mut out: Vec<&str> = self.messages.keys().map(|k| k.as_str()).collect();
+        out.sort_unstable();
+        out
+    }
+
+    fn message(&self, name: &str) -> Result<&DescriptorProto, Status> {
+        let name = name.trim_start_matches('.');
+        self.messages
+            .get(name)
+            .ok_or_else(|| bad(&format!("unknown message type {}", name)))
+    }
+
+    /// the [`DynCodec`] of the message type `message`, reading and
+    /// writing protobuf bodies as JSON values
+    pub fn codec(self: &Arc<Self>, message: &str) -> Result<ProtoJson, Status> {
+        self.message(message)?;
+        Ok(ProtoJson {
+            descriptors: self.clone(),
+            message: message.trim_start_matches('.').to_string(),
+        })
+    }
+
+    /// the encoded message `message` in `buf` as JSON
+    pub fn to_json(&self, message: &str, buf: &[u8]) -> Result<Value, Status> {
+        self.message_to_json(self.message(message)?, buf, 0)
+    }
+
+    /// the JSON `v` encoded as the message `message`
+    pub fn from_json(&self, message: &str, v: &Value) -> Result<Vec<u8>, Status> {
+        let mut out = Vec::new();
+        self.message_from_json(self.message(message)?, v, &mut out, 0)?;
+        Ok(out)
+    }
+
+    fn entry(&self, f: &FieldDescriptorProto) -> Option<&DescriptorProto> {
+        if f.label() != Label::Repeated || f.r#type() != Type::Message {
+            return None;
+        }
+        let m = self.message(f.type_name()).ok()?;
+        match m.options.as_ref().and_then(|o| o.map_entry) {
+            Some(true) => Some(m),
+            _ => None,
+        }
+    }
+
+    fn message_to_json(
+        &self,
+        desc: &DescriptorProto,
+        mut buf: &[u8],
+        depth: usize,
+    ) -> Result<Value, Status> {
+        if depth > MAX_DEPTH {
+            return Err(bad("protobuf message nested too deep"));
+        }
+
+        let mut out = Map::new();
+        while !buf.is_empty() {
+            let key = read_varint(&mut buf)?;
+            let (number, wire) = (key >> 3, key & 7);
+            let f = match desc.field.iter().find(|f| f.number() as u64 == number) {
+                Some(f) => f,
+                None => {
+                    skip(&mut buf, wire)?;
+                    continue;
+                }
+            };
+            let name = json_name(f);
+
+            if let Some(entry) = self.entry(f) {
+                let bytes = read_delimited(&mut buf, wire)?;
+                let (k, v) = self.entry_to_json(entry, bytes, depth + 1)?;
+                let map = out.entry(name).or_insert_with(|| Value::Object(Map::new()));
+                if let Value::Object(map) = map {
+                    map.insert(k, v);
+                }
+            } else if f.label() == Label::Repeated {
+                let values = out.entry(name).or_insert_with(|| Value::Array(vec![]));
+                let values = match values {
+                    Value::Array(values) => values,
+                    _ => unreachable!(),
+                };
+                if wire == DELIMITED && packable(f.r#type()) {
+                    let mut packed = read_delimited(&mut buf, wire)?;
+                    while !packed.is_empty() {
+                        let wire = wire_type(f.r#type());
+                        values.push(self.value_to_json(f, wire, &mut packed, depth)?);
+                    }
+                } else {
+                    values.push(self.value_to_json(f, wire, &mut buf, depth)?);
+                }
+            } else {
+                let v = self.value_to_json(f, wire, &mut buf, depth)?;
+                out.insert(name, v);
+            }
+        }
+        Ok(Value::Object(out))
+    }
+
+    fn entry_to_json(
+        &self,
+        entry: &DescriptorProto,
+        buf: &[u8],
+        depth: usize,
+    ) -> Result<(String, Value), Status> {
+        let field = |n| entry.field.iter().find(|f| f.number() == n);
+        let (kf, vf) = match (field(1), field(2)) {
+            (Some(k), Some(v)) => (k, v),
+            _ => return Err(bad("bad map entry descriptor")),
+        };
+        let mut obj = match self.message_to_json(entry, buf, depth)? {
+            Value::Object(obj) => obj,
+            _ => unreachable!(),
+        };
+        let k = match obj.remove(&json_name(kf)) {
+            Some(Value::String(s)) => s,
+            Some(v) => v.to_string(),
+            None => self
+                .default_json(kf)
+                .to_string()
+                .trim_matches('"')
+                .to_string(),
+        };
+        let v = match obj.remove(&json_name(vf)) {
+            Some(v) => v,
+            None => self.default_json(vf),
+        };
+        Ok((k, v))
+    }
+
+    /// the JSON of a field missing from the wire, for the values of maps
+    fn default_json(&self, f: &FieldDescriptorProto) -> Value {
+        match f.r#type() {
+            Type::Message | Type::Group => Value::Object(Map::new()),
+            Type::String | Type::Bytes => Value::String(String::new()),
+            Type::Bool => Value::Bool(false),
+            Type::Int64 | Type::Uint64 | Type::Fixed64 | Type::Sfixed64 | Type::Sint64 => {
+                Value::String("0".to_string())
+            }
+            Type::Enum => self
+                .enums
+                .get(f.type_name().trim_start_matches('.'))
+                .and_then(|e| e.value.iter().find(|v| v.number() == 0))
+                .map_or(Value::from(0), |v| Value::String(v.name().to_string())),
+            _ => Value::from(0),
+        }
+    }
+
+    fn value_to_json(
+        &self,
+        f: &FieldDescriptorProto,
+        wire: u64,
+        buf: &mut &[u8],
+        depth: usize,
+    ) -> Result<Value, Status> {
+        let t = f.r#type();
+        if wire != wire_type(t) {
+            return Err(bad(&format!(
+                "field {} has wire type {}, expected {}",
+                f.name(),
+                wire,
+                wire_type(t)
+            )));
+        }
+
+        let v = match t {
+            Type::Double => float_to_json(f64::from_le_bytes(read_fixed(buf)?)),
+            Type::Float => float_to_json(f32::from_le_bytes(read_fixed(buf)?) as f64),
+            Type::Fixed64 => Value::String(u64::from_le_bytes(read_fixed(buf)?).to_string()),
+            Type::Sfixed64 => Value::String(i64::from_le_bytes(read_fixed(buf)?).to_string()),
+            Type::Fixed32 => Value::from(u32::from_le_bytes(read_fixed(buf)?)),
+            Type::Sfixed32 => Value::from(i32::from_le_bytes(read_fixed(buf)?)),
+            Type::Int64 => Value::String((read_varint(buf)? as i64).to_string()),
+            Type::Uint64 => Value::String(read_varint(buf)?.to_string()),
+            Type::Sint64 => Value::String(unzigzag(read_varint(buf)?).to_string()),
+            Type::Int32 => Value::from(read_varint(buf)? as i32),
+            Type::Uint32 => Value::from(read_varint(buf)? as u32),
+            Type::Sint32 => Value::from(unzigzag(read_varint(buf)?) as i32),
+            Type::Bool => Value::Bool(read_varint(buf)? != 0),
+            Type::Enum => {
+                let n = read_varint(buf)? as i32;
+                self.enums
+                    .get(f.type_name().trim_start_matches('.'))
+                    .and_then(|e| e.value.iter().find(|v| v.number() == n))
+                    .map_or(Value::from(n), |v| Value::String(v.name().to_string()))
+            }
+            Type::String => {
+                let bytes = read_delimited(buf, wire)?;
+                let s = std::str::from_utf8(bytes).map_err(|e| bad(&e.to_string()))?;
+                Value::String(s.to_string())
+            }
+            Type::Bytes => Value::String(base64::encode(read_delimited(buf, wire)?)),
+            Type::Message => {
+                let bytes = read_delimited(buf, wire)?;
+                self.message_to_json(self.message(f.type_name())?, bytes, depth + 1)?
+            }
+            Type::Group => return Err(bad("protobuf groups are not supported")),
+        };
+        Ok(v)
+    }
+
+    fn message_from_json(
+        &self,
+        desc: &DescriptorProto,
+        v: &Value,
+        out: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<(), Status> {
+        if depth > MAX_DEPTH {
+            return Err(bad("json message nested too deep"));
+        }
+        let obj = match v {
+            Value::Object(obj) => obj,
+            Value::Null => return Ok(()),
+            v => return Err(bad(&format!("expected an object, got {}", v))),
+        };
+
+        for (k, v) in obj {
+            let f = desc
+                .field
+                .iter()
+                .find(|f| json_name(f) == *k || f.name() == k)
+                .ok_or_else(|| bad(&format!("unknown field {} of {}", k, desc.name())))?;
+            if v.is_null() {
+                continue;
+            }
+
+            if let Some(entry) = self.entry(f) {
+                let map = v
+                    .as_object()
+                    .ok_or_else(|| bad(&format!("field {} is not an object", k)))?;
+                let field = |n| entry.field.iter().find(|f| f.number() == n);
+                let (kf, vf) = match (field(1), field(2)) {
+                    (Some(k), Some(v)) => (k, v),
+                    _ => return Err(bad("bad map entry descriptor")),
+                };
+                for (mk, mv) in map {
+                    let mut e = Vec::new();
+                    self.field_from_json(kf, &Value::String(mk.clone()), &mut e, depth + 1)?;
+                    self.field_from_json(vf, mv, &mut e, depth + 1)?;
+                    write_key(out, f.number(), DELIMITED);
+                    write_delimited(out, &e);
+                }
+            } else if f.label() == Label::Repeated {
+                let values = v
+                    .as_array()
+                    .ok_or_else(|| bad(&format!("field {} is not an array", k)))?;
+                if packable(f.r#type()) {
+                    let mut packed = Vec::new();
+                    for v in values {
+                        self.scalar_from_json(f, v, &mut packed)?;
+                    }
+                    write_key(out, f.number(), DELIMITED);
+                    write_delimited(out, &packed);
+                } else {
+                    for v in values {
+                        self.field_from_json(f, v, out, depth)?;
+                    }
+                }
+            } else {
+                self.field_from_json(f, v, out, depth)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn field_from_json(
+        &self,
+        f: &FieldDescriptorProto,
+        v: &Value,
+        out: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<(), Status> {
+        let t = f.r#type();
+        write_key(out, f.number(), wire_type(t));
+        match t {
+            Type::Message => {
+                let mut m = Vec::new();
+                self.message_from_json(self.message(f.type_name())?, v, &mut m, depth + 1)?;
+                write_delimited(out, &m);
+            }
+            Type::String => {
+                let s = v
+                    .as_str()
+                    .ok_or_else(|| bad(&format!("field {} is not a string", f.name())))?;
+                write_delimited(out, s.as_bytes());
+            }
+            Type::Bytes => {
+                let s = v
+                    .as_str()
+                    .ok_or_else(|| bad(&format!("field {} is not a string", f.name())))?;
+                let bytes = base64::decode(s)
+                    .or_else(|_| base64::decode_config(s, base64::URL_SAFE))
+                    .map_err(|e| bad(&e.to_string()))?;
+                write_delimited(out, &bytes);
+            }
+            Type::Group => return Err(bad("protobuf groups are not supported")),
+            _ => self.scalar_from_json(f, v, out)?,
+        }
+        Ok(())
+    }
+
+    /// writes the value of a numeric, bool or enum field without its key
+    fn scalar_from_json(
+        &self,
+        f: &FieldDescriptorProto,
+        v: &Value,
+        out: &mut Vec<u8>,
+    ) -> Result<(), Status> {
+        let invalid = || bad(&format!("invalid value {} of field {}", v, f.name()));
+        match f.r#type() {
+            Type::Double => {
+                out.extend_from_slice(&json_to_float(v).ok_or_else(invalid)?.to_le_bytes())
+            }
+            Type::Float => {
+                let n = json_to_float(v).ok_or_else(invalid)? as f32;
+                out.extend_from_slice(&n.to_le_bytes())
+            }
+            Type::Bool => {
+                let b = match v {
+                    Value::Bool(b) => *b,
+                    Value::String(s) if s == "true" => true,
+                    Value::String(s) if s == "false" => false,
+                    _ => return Err(invalid()),
+                };
+                write_varint(out, b as u64)
+            }
+            Type::Enum => {
+                let n = match v {
+                    Value::String(s) => self
+                        .enums
+                        .get(f.type_name().trim_start_matches('.'))
+                        .and_then(|e| e.value.iter().find(|ev| ev.name() == s))
+                        .map(|ev| ev.number())
+                        .ok_or_else(invalid)?,
+                    v => {
+                        json_to_int(v, i32::MIN.into(), i32::MAX.into()).ok_or_else(invalid)? as i32
+                    }
+                };
+                write_varint(out, n as i64 as u64)
+            }
+            t => {
+                let (min, max) = match t {
+                    Type::Int32 | Type::Sint32 | Type::Sfixed32 => {
+                        (i32::MIN.into(), i32::MAX.into())
+                    }
+                    Type::Uint32 | Type::Fixed32 => (0, u32::MAX.into()),
+                    Type::Uint64 | Type::Fixed64 => (0, u64::MAX.into()),
+                    _ => (i64::MIN.into(), i64::MAX.into()),
+                };
+                let n = json_to_int(v, min, max).ok_or_else(invalid)?;
+                match t {
+                    Type::Fixed32 => out.extend_from_slice(&(n as u32).to_le_bytes()),
+                    Type::Sfixed32 => out.extend_from_slice(&(n as i32).to_le_bytes()),
+                    Type::Fixed64 => out.extend_from_slice(&(n as u64).to_le_bytes()),
+                    Type::Sfixed64 => out.extend_from_slice(&(n as i64).to_le_bytes()),
+                    Type::Sint32 | Type::Sint64 => write_varint(out, zigzag(n as i64)),
+                    // negative int32 are sign extended to ten bytes, as
+                    // protobuf does
+                    _ => write_varint(out, n as i64 as u64),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the [`DynCodec`] of one protobuf message type, standing JSON values
+/// for its messages, see [`Descriptors::codec`]
+#[derive(Debug, Clone)]
+pub struct ProtoJson {
+    descriptors: Arc<Descriptors>,
+    message: String,
+}
+
+impl ProtoJson {
+    /// the full name of the message type
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl DynCodec for ProtoJson {
+    fn string(&self) -> &str {
+        "transcode"
+    }
+
+    fn content_type(&self) -> &str {
+        CONTENT_TYPES[0]
+    }
+
+    fn decode(&self, m: &Message) -> Result<Value, Status> {
+        match m.content_type() {
+            Some(ct) if !CONTENT_TYPES.contains(&ct) => {
+                Err(bad(&format!("content type {} is not protobuf", ct)))
+            }
+            _ => self.descriptors.to_json(&self.message, &m.body),
+        }
+    }
+
+    fn encode(&self, v: Value, m: &mut Message) -> Result<(), Status> {
+        m.body = self.descriptors.from_json(&self.message, &v)?.into();
+        m.header
+            .retain(|k, _| !k.eq_ignore_ascii_case(header::CONTENT_TYPE));
+        m.header.insert(
+            header::CONTENT_TYPE.to_string(),
+            CONTENT_TYPES[0].to_string(),
+        );
+        Ok(())
+    }
+}
+
+/// the JSON name protoc fills in, or the lower camel case of the name
+fn json_name(f: &FieldDescriptorProto) -> String {
+    if let Some(name) = &f.json_name {
+        return name.clone();
+    }
+    let mut out = String::new();
+    let mut upper = false;
+    for c in f.name().chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn wire_type(t: Type) -> u64 {
+    match t {
+        Type::Double | Type::Fixed64 | Type::Sfixed64 => FIXED64,
+        Type::Float | Type::Fixed32 | Type::Sfixed32 => FIXED32,
+        Type::String | Type::Bytes | Type::Message | Type::Group => DELIMITED,
+        _ => VARINT,
+    }
+}
+
+/// whether repeated fields of the type are packed, as proto3 does
+fn packable(t: Type) -> bool {
+    wire_type(t) != DELIMITED
+}
+
+fn float_to_json(n: f64) -> Value {
+    match Number::from_f64(n) {
+        Some(n) => Value::Number(n),
+        None if n.is_nan() => Value::String("NaN".to_string()),
+        None if n > 0.0 => Value::String("Infinity".to_string()),
+        None => Value::String("-Infinity".to_string()),
+    }
+}
+
+fn json_to_float(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => match s.as_str() {
+            "NaN" => Some(f64::NAN),
+            "Infinity" => Some(f64::INFINITY),
+            "-Infinity" => Some(f64::NEG_INFINITY),
+            s => s.parse().ok(),
+        },
+        _ => None,
+    }
+}
+
+/// integers are numbers or strings of them, exponents and zero fractions
+/// allowed
+fn json_to_int(v: &Value, min: i128, max: i128) -> Option<i128> {
+    let n = match v {
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i as i128,
+            (_, Some(u)) => u as i128,
+            _ => float_to_int(n.as_f64()?)?,
+        },
+        Value::String(s) => match s.parse::<i128>() {
+            Ok(n) => n,
+            Err(_) => float_to_int(s.parse().ok()?)?,
+        },
+        _ => return None,
+    };
+    if n < min || n > max {
+        return None;
+    }
+    Some(n)
+}
+
+fn float_to_int(f: f64) -> Option<i128> {
+    if f.fract() != 0.0 || !f.is_finite() || f.abs() > 1.9e19 {
+        return None;
+    }
+    Some(f as i128)
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn unzigzag(n: u64) -> i64 {
+    ((n >> 1) as i64) ^ -((n & 1) as i64)
+}
+
+fn read_varint(buf: &mut &[u8]) -> Result<u64, Status> {
+    let mut out = 0u64;
+    for i in 0..10 {
+        let (&b, rest) = buf
+            .split_first()
+            .ok_or_else(|| bad("truncated protobuf varint"))?;
+        *buf = rest;
+        out |= ((b & 0x7f) as u64) << (7 * i);
+        if b < 0x80 {
+            return Ok(out);
+        }
+    }
+    Err(bad("protobuf varint too long"))
+}
+
+fn read_fixed<const N: usize>(buf: &mut &[u8]) -> Result<[u8; N], Status> {
+    if buf.len() < N {
+        return Err(bad("truncated protobuf fixed value"));
+    }
+    let (head, rest) = buf.split_at(N);
+    *buf = rest;
+    let mut out = [0u8; N];
+    out.copy_from_slice(head);
+    Ok(out)
+}
+
+fn read_delimited<'a>(buf: &mut &'a [u8], wire: u64) -> Result<&'a [u8], Status> {
+    if wire != DELIMITED {
+        return Err(bad(&format!("expected wire type 2, got {}", wire)));
+    }
+    let len = read_varint(buf)? as usize;
+    if buf.len() < len {
+        return Err(bad("truncated protobuf field"));
+    }
+    let (head, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(head)
+}
+
+/// skips the value of an unknown field
+fn skip(buf: &mut &[u8], wire: u64) -> Result<(), Status> {
+    match wire {
+        VARINT => read_varint(buf).map(drop),
+        FIXED64 => read_fixed::<8>(buf).map(drop),
+        DELIMITED => read_delimited(buf, wire).map(drop),
+        FIXED32 => read_fixed::<4>(buf).map(drop),
+        w => Err(bad(&format!("unsupported protobuf wire type {}", w))),
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn write_key(out: &mut Vec<u8>, number: i32, wire: u64) {
+    write_varint(out, ((number as u64) << 3) | wire);
+}
+
+fn write_delimited(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use errors::Code;
+    use prost::Message as _;
+    use prost_types::field_descriptor_proto::{Label, Type};
+    use prost_types::{
+        DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto,
+        FileDescriptorProto, FileDescriptorSet, MessageOptions,
+    };
+    use serde_json::json;
+
+    use super::Descriptors;
+    use crate::DynCodec;
+    use crate::Message;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Request {
+        #[prost(string, tag = "1")]
+        user_name: String,
+        #[prost(int64, tag = "2")]
+        count: i64,
+        #[prost(sint32, repeated, tag = "3")]
+        ids: Vec<i32>,
+        #[prost(map = "string, int64", tag = "4")]
+        tags: HashMap<String, i64>,
+        #[prost(enumeration = "Kind", tag = "5")]
+        kind: i32,
+        #[prost(bytes = "vec", tag = "6")]
+        data: Vec<u8>,
+        #[prost(message, repeated, tag = "7")]
+        scores: Vec<Score>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Score {
+        #[prost(double, tag = "1")]
+        value: f64,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+    enum Kind {
+        Unknown = 0,
+        Hello = 1,
+    }
+
+    fn field(
+        name: &str,
+        number: i32,
+        label: Label,
+        t: Type,
+        type_name: &str,
+    ) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(t as i32),
+            type_name: Some(type_name.to_string()).filter(|n| !n.is_empty()),
+            ..Default::default()
+        }
+    }
+
+    fn descriptors() -> Descriptors {
+        let entry = DescriptorProto {
+            name: Some("TagsEntry".to_string()),
+            field: vec![
+                field("key", 1, Label::Optional, Type::String, ""),
+                field("value", 2, Label::Optional, Type::Int64, ""),
+            ],
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let request = DescriptorProto {
+            name: Some("Request".to_string()),
+            field: vec![
+                field("user_name", 1, Label::Optional, Type::String, ""),
+                field("count", 2, Label::Optional, Type::Int64, ""),
+                field("ids", 3, Label::Repeated, Type::Sint32, ""),
+                field(
+                    "tags",
+                    4,
+                    Label::Repeated,
+                    Type::Message,
+                    ".test.Request.TagsEntry",
+                ),
+                field("kind", 5, Label::Optional, Type::Enum, ".test.Kind"),
+                field("data", 6, Label::Optional, Type::Bytes, ""),
+                field("scores", 7, Label::Repeated, Type::Message, ".test.Score"),
+            ],
+            nested_type: vec![entry],
+            ..Default::default()
+        };
+        let score = DescriptorProto {
+            name: Some("Score".to_string()),
+            field: vec![field("value", 1, Label::Optional, Type::Double, "")],
+            ..Default::default()
+        };
+        let value = |name: &str, number| EnumValueDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            ..Default::default()
+        };
+        let kind = EnumDescriptorProto {
+            name: Some("Kind".to_string()),
+            value: vec![value("UNKNOWN", 0), value("HELLO", 1)],
+            ..Default::default()
+        };
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("test.proto".to_string()),
+                package: Some("test".to_string()),
+                message_type: vec![request, score],
+                enum_type: vec![kind],
+                ..Default::default()
+            }],
+        };
+        Descriptors::decode(&set.encode_to_vec()).unwrap()
+    }
+
+    #[test]
+    fn transcode() {
+        let d = Arc::new(descriptors());
+        assert_eq!(
+            d.messages(),
+            vec!["test.Request", "test.Request.TagsEntry", "test.Score"]
+        );
+
+        let req = Request {
+            user_name: "vine".to_string(),
+            count: -3,
+            ids: vec![1, -2, 300],
+            tags: HashMap::from([("a".to_string(), 1), ("b".to_string(), 0)]),
+            kind: Kind::Hello as i32,
+            data: vec![0, 1, 2],
+            scores: vec![Score { value: 0.5 }, Score { value: 0.0 }],
+        };
+        let v = json!({
+            "userName": "vine",
+            "count": "-3",
+            "ids": [1, -2, 300],
+            "tags": {"a": "1", "b": "0"},
+            "kind": "HELLO",
+            "data": "AAEC",
+            "scores": [{"value": 0.5}, {}],
+        });
+        assert_eq!(d.to_json("test.Request", &req.encode_to_vec()).unwrap(), v);
+
+        let out = d.from_json("test.Request", &v).unwrap();
+        assert_eq!(Request::decode(&out[..]).unwrap(), req);
+        // the proto names, numbers in place of strings and enum numbers
+        let v = json!({"user_name": "vine", "count": -3, "kind": 1, "tags": {"a": 1}});
+        let out = Request::decode(&d.from_json(".test.Request", &v).unwrap()[..]).unwrap();
+        assert_eq!((out.count, out.kind, out.tags["a"]), (-3, 1, 1));
+
+        for v in &[
+            json!({"unknown": 1}),
+            json!({"count": 1.5}),
+            json!({"kind": "BYE"}),
+            json!({"ids": 1}),
+        ] {
+            let e = d.from_json("test.Request", v).unwrap_err();
+            assert_eq!(e.code(), Code::BadRequest, "{}", v);
+        }
+        assert!(d.to_json("test.Request", &[0x0a, 5, b'v']).is_err());
+        assert!(d.codec("test.Missing").is_err());
+
+        let codec = d.codec("test.Request").unwrap();
+        let mut m = Message::builder().build().unwrap();
+        codec.encode(json!({"userName": "vine"}), &mut m).unwrap();
+        assert_eq!(m.content_type(), Some("application/protobuf"));
+        assert_eq!(codec.decode(&m).unwrap(), json!({"userName": "vine"}));
+    }
+}
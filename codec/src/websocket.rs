@@ -0,0 +1,1763 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use errors::{Code, Status};
+use std::{
+    collections::HashMap,
+    io,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use ring::{digest, rand::SecureRandom};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{
+        AllowAnyAuthenticatedClient, Certificate, ClientConfig, PrivateKey, RootCertStore,
+        ServerConfig,
+    },
+    webpki::DNSNameRef,
+    TlsAcceptor, TlsConnector, TlsStream,
+};
+
+use crate::{too_large, Message, MessageType, DEFAULT_MAX_RECV_SIZE, DEFAULT_MAX_SEND_SIZE};
+
+/// the value the key of the client is hashed with into the accept of the
+/// server, RFC 6455 1.3
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// the largest handshake read, so a peer can't make it buffer without bounds
+const MAX_HANDSHAKE_SIZE: usize = 8 << 10;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// the normal closure, RFC 6455 7.4.1
+const CLOSE_NORMAL: u16 = 1000;
+
+fn bad(detail: &str) -> Status {
+    Status::bad_request("io.vine.codec", detail)
+}
+
+/// the `Sec-WebSocket-Accept` of the server for the `Sec-WebSocket-Key` of
+/// the client
+pub fn accept_key(key: &str) -> String {
+    let sum = digest::digest(
+        &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        format!("{}{}", key.trim(), GUID).as_bytes(),
+    );
+    base64::encode(sum.as_ref())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Client,
+    Server,
+}
+
+/// WebSocket sends messages as the binary messages of a websocket, RFC 6455,
+/// over a byte stream, so the clients in a browser or behind a proxy
+/// speaking only http can be served. The stream is a tcp or, for `wss://`,
+/// a tls stream. Made by the handshake of [`WebSocket::accept`] on the
+/// server and [`WebSocket::connect`] on the client, or over tcp and tls by
+/// an [`Acceptor`] and a [`Dialer`].
+///
+/// The pings of the peer are answered while a message is read.
+///
+/// ```rust
+/// # use codec::websocket::WebSocket;
+/// # async fn run() -> Result<(), errors::Status> {
+/// let (client, server) = tokio::io::duplex(1024);
+/// let server = tokio::spawn(async move {
+///     let (mut ws, path) = WebSocket::accept(server).await?;
+///     let m = ws.read_message().await?;
+///     Ok::<_, errors::Status>((path, m))
+/// });
+/// let mut ws = WebSocket::connect(client, "localhost", "/rpc").await?;
+/// ws.write_message(b"hello").await?;
+/// let (path, m) = server.await.unwrap()?;
+/// assert_eq!(path, "/rpc");
+/// assert_eq!(m.as_deref(), Some(&b"hello"[..]));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct WebSocket<S> {
+    inner: S,
+    role: Role,
+    max_recv_size: usize,
+    max_send_size: usize,
+    /// the close frame was sent or the stream ended, nothing else may be
+    closed: bool,
+    idle_timeout: Option<Duration>,
+    /// the bytes read of the frame being read
+    buf: BytesMut,
+}
+
+/// a frame read whole, its payload unmasked
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: BytesMut,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> WebSocket<S> {
+    fn new(inner: S, role: Role) -> Self {
+        WebSocket {
+            inner,
+            role,
+            max_recv_size: DEFAULT_MAX_RECV_SIZE,
+            max_send_size: DEFAULT_MAX_SEND_SIZE,
+            closed: false,
+            idle_timeout: None,
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// reads the upgrade request of a client from `inner` and answers it,
+    /// returning the websocket and the path requested. A request not
+    /// upgrading to a websocket is answered with a 400 and fails.
+    pub async fn accept(mut inner: S) -> Result<(Self, String), Status> {
+        let head = read_head(&mut inner).await?;
+        let mut lines = head.split("\r\n");
+        let path = match lines.next().map(|l| l.split(' ').collect::<Vec<_>>()) {
+            Some(parts) if parts.len() == 3 && parts[0] == "GET" => parts[1].to_string(),
+            _ => return Err(refuse(&mut inner, "not a websocket upgrade request").await),
+        };
+        let headers: Vec<(&str, &str)> = lines
+            .filter_map(|l| l.split_once(':'))
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .collect();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| *v)
+        };
+
+        let upgrade = header("Upgrade").is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+        let connection = header("Connection").is_some_and(|v| {
+            v.split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case("upgrade"))
+        });
+        if !upgrade || !connection {
+            return Err(refuse(&mut inner, "not a websocket upgrade request").await);
+        }
+        if header("Sec-WebSocket-Version") != Some("13") {
+            return Err(refuse(&mut inner, "unsupported websocket version").await);
+        }
+        let key = match header("Sec-WebSocket-Key") {
+            Some(key) if !key.is_empty() => key,
+            _ => return Err(refuse(&mut inner, "missing Sec-WebSocket-Key").await),
+        };
+
+        let resp = format!(
+            "HTTP/1.1 101 Switching Protocols\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(key)
+        );
+        inner.write_all(resp.as_bytes()).await?;
+        inner.flush().await?;
+        Ok((WebSocket::new(inner, Role::Server), path))
+    }
+
+    /// sends the upgrade request for `path` on `host` over `inner` and
+    /// checks the answer of the server
+    pub async fn connect(mut inner: S, host: &str, path: &str) -> Result<Self, Status> {
+        let mut nonce = [0u8; 16];
+        fill(&mut nonce)?;
+        let key = base64::encode(nonce);
+        let req = format!(
+            "GET {} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\n\
+             Sec-WebSocket-Version: 13\r\n\r\n",
+            path, host, key
+        );
+        inner.write_all(req.as_bytes()).await?;
+        inner.flush().await?;
+
+        let head = read_head(&mut inner).await?;
+        let mut lines = head.split("\r\n");
+        let status = lines.next().unwrap_or_default();
+        if status.split(' ').nth(1) != Some("101") {
+            return Err(Status::new(
+                "io.vine.codec".to_string(),
+                format!("websocket upgrade refused: {}", status),
+                Code::BadGateway,
+            ));
+        }
+        let accept = lines
+            .filter_map(|l| l.split_once(':'))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case("Sec-WebSocket-Accept"))
+            .map(|(_, v)| v.trim());
+        if accept != Some(accept_key(&key).as_str()) {
+            return Err(Status::new(
+                "io.vine.codec",
+                "websocket upgrade with a wrong Sec-WebSocket-Accept",
+                Code::BadGateway,
+            ));
+        }
+        Ok(WebSocket::new(inner, Role::Client))
+    }
+
+    /// messages longer than `n` fail the read before they are buffered,
+    /// [`DEFAULT_MAX_RECV_SIZE`] unless set
+    #[inline]
+    pub fn with_max_recv_size(&mut self, n: usize) -> &mut Self {
+        self.max_recv_size = n;
+        self
+    }
+
+    /// messages longer than `n` fail the write before anything is written,
+    /// [`DEFAULT_MAX_SEND_SIZE`] unless set
+    #[inline]
+    pub fn with_max_send_size(&mut self, n: usize) -> &mut Self {
+        self.max_send_size = n;
+        self
+    }
+
+    /// pings the peer when no whole frame came for `d` while a message is
+    /// read, and closes the stream when none comes within `d` more, so a
+    /// dead peer or a half open connection fails the read with
+    /// [`Code::ServiceUnavailable`] instead of hanging. Unset, a read waits
+    /// for as long as it takes.
+    #[inline]
+    pub fn with_idle_timeout(&mut self, d: Option<Duration>) -> &mut Self {
+        self.idle_timeout = d;
+        self
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// the stream, the bytes read of a frame not read whole are dropped
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// the next message, its fragments put together, `None` when the peer
+    /// closed the websocket or the stream ends between two messages. Text
+    /// messages are read as their bytes.
+    pub async fn read_message(&mut self) -> Result<Option<Bytes>, Status> {
+        let mut message = BytesMut::new();
+        let mut fragmented = false;
+        loop {
+            let frame = match self.read_frame(message.len()).await? {
+                Some(frame) => frame,
+                None if fragmented => return Err(eof()),
+                None => {
+                    self.closed = true;
+                    return Ok(None);
+                }
+            };
+
+            match frame.opcode {
+                OP_CONTINUATION if fragmented => message.extend_from_slice(&frame.payload),
+                OP_TEXT | OP_BINARY if !fragmented => message.extend_from_slice(&frame.payload),
+                OP_CONTINUATION | OP_TEXT | OP_BINARY => {
+                    return Err(bad("websocket fragments out of order"))
+                }
+                OP_CLOSE => {
+                    if !self.closed {
+                        let code = &frame.payload[..frame.payload.len().min(2)];
+                        self.write_frame(OP_CLOSE, code).await?;
+                        self.closed = true;
+                    }
+                    return Ok(None);
+                }
+                OP_PING => {
+                    if !self.closed {
+                        self.write_frame(OP_PONG, &frame.payload).await?;
+                    }
+                    continue;
+                }
+                OP_PONG => continue,
+                _ => return Err(bad("websocket frame of an unknown opcode")),
+            }
+            if frame.fin {
+                return Ok(Some(message.freeze()));
+            }
+            fragmented = true;
+        }
+    }
+
+    /// the next [`Message`], written by [`WebSocket::write`] as one binary
+    /// message, `None` when the websocket closed
+    pub async fn read(&mut self) -> Result<Option<Message>, Status> {
+        match self.read_message().await? {
+            Some(message) => decode(message).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// writes and flushes `m` as one binary message: the big endian u32
+    /// length of the JSON of its header, the header, then its body
+    pub async fn write(&mut self, m: &Message) -> Result<(), Status> {
+        self.write_message(&encode(m)?).await
+    }
+
+    /// writes and flushes one binary message
+    pub async fn write_message(&mut self, message: &[u8]) -> Result<(), Status> {
+        if self.closed {
+            return Err(Status::new(
+                "io.vine.codec",
+                "websocket closed",
+                Code::ServiceUnavailable,
+            ));
+        }
+        if message.len() > self.max_send_size {
+            return Err(too_large(
+                "websocket message",
+                message.len(),
+                self.max_send_size,
+            ));
+        }
+        self.write_frame(OP_BINARY, message).await
+    }
+
+    /// sends the close frame, the peer answers it with its own before it
+    /// closes the stream. Nothing may be written after.
+    pub async fn close(&mut self) -> Result<(), Status> {
+        if !self.closed {
+            self.write_frame(OP_CLOSE, &CLOSE_NORMAL.to_be_bytes())
+                .await?;
+            self.closed = true;
+        }
+        Ok(())
+    }
+
+    /// the next frame, `None` when the stream ends before it starts. With an
+    /// idle timeout the whole frame has to come within it, the peer is
+    /// pinged when it does not and the stream closed when the frame is not
+    /// there within the timeout again.
+    async fn read_frame(&mut self, buffered: usize) -> Result<Option<Frame>, Status> {
+        let idle = match self.idle_timeout {
+            Some(idle) => idle,
+            None => return self.try_read_frame(buffered).await,
+        };
+        let mut pinged = false;
+        loop {
+            // the frame is read into `buf`, nothing is lost on a timeout
+            match tokio::time::timeout(idle, self.try_read_frame(buffered)).await {
+                Ok(frame) => return frame,
+                Err(_) if !pinged && !self.closed => {
+                    self.write_frame(OP_PING, b"").await?;
+                    pinged = true;
+                }
+                Err(_) => {
+                    self.closed = true;
+                    let _ = self.inner.shutdown().await;
+                    return Err(Status::service_unavailable(
+                        "io.vine.codec",
+                        "websocket peer stopped responding",
+                    ));
+                }
+            }
+        }
+    }
+
+    /// reads the frame into `buf` then splits it off, so it can be called
+    /// again after being cancelled. `buffered` is the length of the message
+    /// the frame continues.
+    async fn try_read_frame(&mut self, buffered: usize) -> Result<Option<Frame>, Status> {
+        if !self.fill(2).await? {
+            return match self.buf.is_empty() {
+                true => Ok(None),
+                false => Err(eof()),
+            };
+        }
+        let (b0, b1) = (self.buf[0], self.buf[1]);
+        let fin = b0 & 0x80 != 0;
+        let opcode = b0 & 0x0f;
+        if b0 & 0x70 != 0 {
+            return Err(bad("websocket frame with reserved bits set"));
+        }
+        // the frames of a client are masked, those of a server not
+        let masked = b1 & 0x80 != 0;
+        if masked != (self.role == Role::Server) {
+            return Err(bad("websocket frame masked wrongly"));
+        }
+
+        let (len, mut at) = match b1 & 0x7f {
+            126 => {
+                self.fill_frame(4).await?;
+                (u16::from_be_bytes([self.buf[2], self.buf[3]]) as u64, 4)
+            }
+            127 => {
+                self.fill_frame(10).await?;
+                let mut len = [0u8; 8];
+                len.copy_from_slice(&self.buf[2..10]);
+                (u64::from_be_bytes(len), 10)
+            }
+            n => (n as u64, 2),
+        };
+        let control = opcode & 0x8 != 0;
+        if control && (!fin || len > 125) {
+            return Err(bad("websocket control frame fragmented or too long"));
+        }
+        let total = match control {
+            true => len,
+            false => buffered as u64 + len,
+        };
+        if total > self.max_recv_size as u64 {
+            return Err(too_large(
+                "websocket message",
+                total as usize,
+                self.max_recv_size,
+            ));
+        }
+
+        let mut mask = None;
+        if masked {
+            self.fill_frame(at + 4).await?;
+            mask = Some([
+                self.buf[at],
+                self.buf[at + 1],
+                self.buf[at + 2],
+                self.buf[at + 3],
+            ]);
+            at += 4;
+        }
+        self.fill_frame(at + len as usize).await?;
+        let mut payload = self.buf.split_to(at + len as usize).split_off(at);
+        if let Some(mask) = mask {
+            apply_mask(&mut payload, mask);
+        }
+        Ok(Some(Frame {
+            fin,
+            opcode,
+            payload,
+        }))
+    }
+
+    /// reads until `n` bytes are in `buf`, false when the stream ends first
+    async fn fill(&mut self, n: usize) -> Result<bool, Status> {
+        while self.buf.len() < n {
+            self.buf.reserve(n - self.buf.len());
+            if self.inner.read_buf(&mut self.buf).await? == 0 {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// reads until `n` bytes of a frame started are in `buf`
+    async fn fill_frame(&mut self, n: usize) -> Result<(), Status> {
+        match self.fill(n).await? {
+            true => Ok(()),
+            false => Err(eof()),
+        }
+    }
+
+    async fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> Result<(), Status> {
+        let mask_bit = match self.role {
+            Role::Client => 0x80,
+            Role::Server => 0,
+        };
+        let mut frame = BytesMut::with_capacity(payload.len() + 14);
+        frame.extend_from_slice(&[0x80 | opcode]);
+        match payload.len() {
+            n if n < 126 => frame.extend_from_slice(&[mask_bit | n as u8]),
+            n if n <= u16::MAX as usize => {
+                frame.extend_from_slice(&[mask_bit | 126]);
+                frame.extend_from_slice(&(n as u16).to_be_bytes());
+            }
+            n => {
+                frame.extend_from_slice(&[mask_bit | 127]);
+                frame.extend_from_slice(&(n as u64).to_be_bytes());
+            }
+        }
+        let start = frame.len();
+        match self.role {
+            Role::Client => {
+                let mut mask = [0u8; 4];
+                fill(&mut mask)?;
+                frame.extend_from_slice(&mask);
+                frame.extend_from_slice(payload);
+                apply_mask(&mut frame[start + 4..], mask);
+            }
+            Role::Server => frame.extend_from_slice(payload),
+        }
+
+        self.inner.write_all(&frame).await?;
+        self.inner.flush().await?;
+        Ok(())
+    }
+}
+
+/// the fields of a [`Message`] but its body, sent as JSON before it
+#[derive(Serialize, Deserialize)]
+struct Head {
+    id: String,
+    r#type: i32,
+    target: String,
+    method: String,
+    endpoint: String,
+    error: String,
+    header: HashMap<String, String>,
+}
+
+fn encode(m: &Message) -> Result<BytesMut, Status> {
+    let head = Head {
+        id: m.id.clone(),
+        r#type: m.r#type.clone() as i32,
+        target: m.target.clone(),
+        method: m.method.clone(),
+        endpoint: m.endpoint.clone(),
+        error: m.error.clone(),
+        header: m.header.clone(),
+    };
+    let head = serde_json::to_vec(&head)
+        .map_err(|e| Status::internal_server_error("io.vine.codec", &e.to_string()))?;
+    let mut buf = BytesMut::with_capacity(4 + head.len() + m.body.len());
+    buf.put_u32(head.len() as u32);
+    buf.extend_from_slice(&head);
+    buf.extend_from_slice(&m.body);
+    Ok(buf)
+}
+
+/// the message of `buf`, its body sharing the bytes of `buf`
+fn decode(mut buf: Bytes) -> Result<Message, Status> {
+    if buf.len() < 4 {
+        return Err(bad("websocket message without a header"));
+    }
+    let len = buf.get_u32() as usize;
+    if len > buf.len() {
+        return Err(bad("websocket message header longer than the message"));
+    }
+    let head = buf.split_to(len);
+    let head: Head = serde_json::from_slice(&head)
+        .map_err(|e| bad(&format!("websocket message header: {}", e)))?;
+    let r#type = match head.r#type {
+        0 => MessageType::Error,
+        1 => MessageType::Request,
+        2 => MessageType::Response,
+        3 => MessageType::Event,
+        _ => return Err(bad("websocket message of an unknown type")),
+    };
+    Ok(Message {
+        id: head.id,
+        r#type,
+        target: head.target,
+        method: head.method,
+        endpoint: head.endpoint,
+        error: head.error,
+        header: head.header,
+        body: buf,
+    })
+}
+
+/// Stream is the stream a [`Dialer`] dials, tcp for `ws://` and tls for
+/// `wss://`, and an [`Acceptor`] accepts
+#[derive(Debug)]
+pub enum Stream {
+    Tcp(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl Stream {
+    /// the certificates the peer presented over tls, the one of the client
+    /// on a server asking for it, see [`Acceptor::with_client_auth`]
+    pub fn peer_certificates(&self) -> Option<Vec<Certificate>> {
+        match self {
+            Stream::Tcp(_) => None,
+            Stream::Tls(s) => s.get_ref().1.get_peer_certificates(),
+        }
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            Stream::Tls(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            Stream::Tls(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_flush(cx),
+            Stream::Tls(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            Stream::Tls(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+/// the parts of a `ws://` or `wss://` url dialed
+#[derive(Debug, PartialEq, Eq)]
+struct Url<'a> {
+    tls: bool,
+    /// the host and port as written, the `Host` of the upgrade
+    authority: &'a str,
+    host: &'a str,
+    port: u16,
+    /// the path and query, `/` when the url has none
+    path: &'a str,
+}
+
+fn parse_url(url: &str) -> Result<Url<'_>, Status> {
+    let (tls, rest) = if let Some(rest) = url.strip_prefix("ws://") {
+        (false, rest)
+    } else if let Some(rest) = url.strip_prefix("wss://") {
+        (true, rest)
+    } else {
+        return Err(bad("websocket url is not ws:// or wss://"));
+    };
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    if path.starts_with('?') {
+        return Err(bad("websocket url with a query but no path"));
+    }
+
+    // a v6 address is in brackets, its colons aren't the port's
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(v6) => match v6.split_once(']') {
+            Some((host, "")) => (host, None),
+            Some((host, port)) => match port.strip_prefix(':') {
+                Some(port) => (host, Some(port)),
+                None => return Err(bad("websocket url with a malformed host")),
+            },
+            None => return Err(bad("websocket url with a malformed host")),
+        },
+        None => match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    if host.is_empty() {
+        return Err(bad("websocket url without a host"));
+    }
+    let port = match port {
+        Some(port) => port
+            .parse()
+            .map_err(|_| bad("websocket url with a malformed port"))?,
+        None if tls => 443,
+        None => 80,
+    };
+    Ok(Url {
+        tls,
+        authority,
+        host,
+        port,
+        path,
+    })
+}
+
+/// Dialer dials websockets from their urls, `ws://host[:port]/path` over
+/// tcp or `wss://` over tls, its server name the host of the url. The
+/// server certificate is checked against the webpki roots unless another
+/// tls is set. Its [`DialOptions`] bound the time and the tries of a dial.
+///
+/// ```rust,no_run
+/// # use std::sync::Arc;
+/// # use codec::websocket::Dialer;
+/// # use tokio_rustls::rustls::{Certificate, ClientConfig, PrivateKey};
+/// # async fn run(ca: Certificate, cert: Certificate, key: PrivateKey) -> Result<(), errors::Status> {
+/// let mut tls = ClientConfig::new();
+/// tls.root_store.add(&ca).unwrap();
+/// let mut dialer = Dialer::new();
+/// dialer.with_tls(Arc::new(tls)).with_client_cert(vec![cert], key)?;
+/// let mut ws = dialer.dial("wss://greeter.internal/rpc").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Dialer {
+    tls: Arc<ClientConfig>,
+    options: DialOptions,
+}
+
+/// DialOptions bound the dials of a [`Dialer`]: each attempt within the
+/// timeout, the failed ones tried again `retries` times, waiting `backoff`
+/// before the first retry and twice as long before each one after
+#[derive(Debug, Clone)]
+pub struct DialOptions {
+    /// the longest an attempt may take, the tcp connect and the tls and
+    /// websocket handshakes, as long as they take unless set
+    pub timeout: Option<Duration>,
+    pub retries: u32,
+    pub backoff: Duration,
+}
+
+impl Default for DialOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DialOptions {
+    #[inline]
+    pub fn new() -> Self {
+        DialOptions {
+            timeout: None,
+            retries: 0,
+            backoff: Duration::from_millis(100),
+        }
+    }
+
+    #[inline]
+    pub fn with_timeout(&mut self, d: Option<Duration>) -> &mut Self {
+        self.timeout = d;
+        self
+    }
+
+    #[inline]
+    pub fn with_retries(&mut self, n: u32) -> &mut Self {
+        self.retries = n;
+        self
+    }
+
+    #[inline]
+    pub fn with_backoff(&mut self, d: Duration) -> &mut Self {
+        self.backoff = d;
+        self
+    }
+}
+
+impl Default for Dialer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dialer {
+    pub fn new() -> Self {
+        let mut config = ClientConfig::new();
+        config
+            .root_store
+            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+        Dialer {
+            tls: Arc::new(config),
+            options: DialOptions::new(),
+        }
+    }
+
+    /// the timeout and the retries of the dials
+    #[inline]
+    pub fn with_options(&mut self, options: DialOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
+
+    /// the tls of the `wss://` urls dialed
+    #[inline]
+    pub fn with_tls(&mut self, config: Arc<ClientConfig>) -> &mut Self {
+        self.tls = config;
+        self
+    }
+
+    /// presents the certificate chain `certs` and its `key` to the servers
+    /// asking for a client certificate, mutual tls. Fails on a key which
+    /// can't sign.
+    pub fn with_client_cert(
+        &mut self,
+        certs: Vec<Certificate>,
+        key: PrivateKey,
+    ) -> Result<&mut Self, Status> {
+        Arc::make_mut(&mut self.tls)
+            .set_single_client_cert(certs, key)
+            .map_err(|e| bad(&format!("websocket client certificate: {}", e)))?;
+        Ok(self)
+    }
+
+    /// dials the websocket at `url`. A dial out of time fails with
+    /// [`Code::RequestTimeout`], one refused or reset by the server with
+    /// [`Code::ServiceUnavailable`], both tried again; a malformed url or a
+    /// refused upgrade fails the dial right away.
+    pub async fn dial(&self, url: &str) -> Result<WebSocket<Stream>, Status> {
+        let parsed = parse_url(url)?;
+        let mut backoff = self.options.backoff;
+        let mut retries = self.options.retries;
+        loop {
+            let attempt = self.dial_once(&parsed);
+            let result = match self.options.timeout {
+                Some(timeout) => match tokio::time::timeout(timeout, attempt).await {
+                    Ok(result) => result,
+                    Err(_) => Err(Status::timeout(
+                        "io.vine.codec",
+                        &format!("dial of {} timed out after {:?}", url, timeout),
+                    )),
+                },
+                None => attempt.await,
+            };
+            match result {
+                Err(e) if retries > 0 && retryable(&e) => {
+                    retries -= 1;
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn dial_once(&self, url: &Url<'_>) -> Result<WebSocket<Stream>, Status> {
+        let tcp = TcpStream::connect((url.host, url.port))
+            .await
+            .map_err(|e| dial_error(url, e))?;
+        let stream = match url.tls {
+            false => Stream::Tcp(tcp),
+            true => {
+                let name = DNSNameRef::try_from_ascii_str(url.host)
+                    .map_err(|_| bad("websocket url host is not a dns name"))?;
+                let tls = TlsConnector::from(self.tls.clone())
+                    .connect(name, tcp)
+                    .await?;
+                Stream::Tls(Box::new(tls.into()))
+            }
+        };
+        WebSocket::connect(stream, url.authority, url.path).await
+    }
+}
+
+/// Acceptor accepts websockets on the tcp connections of a listener, over
+/// tls once it is set
+///
+/// ```rust,no_run
+/// # use std::sync::Arc;
+/// # use codec::websocket::Acceptor;
+/// # use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+/// # async fn run(tls: ServerConfig, clients: RootCertStore) -> Result<(), errors::Status> {
+/// let mut acceptor = Acceptor::new();
+/// acceptor.with_tls(Arc::new(tls)).with_client_auth(clients)?;
+/// let listener = tokio::net::TcpListener::bind("0.0.0.0:443").await?;
+/// let (tcp, _) = listener.accept().await?;
+/// let (ws, path) = acceptor.accept(tcp).await?;
+/// let client = ws.get_ref().peer_certificates();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct Acceptor {
+    tls: Option<Arc<ServerConfig>>,
+}
+
+impl Acceptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the tls the connections are accepted over
+    #[inline]
+    pub fn with_tls(&mut self, config: Arc<ServerConfig>) -> &mut Self {
+        self.tls = Some(config);
+        self
+    }
+
+    /// asks the clients for a certificate signed by one of `roots`, mutual
+    /// tls, and refuses those without one. The certificate of a client is
+    /// read from [`Stream::peer_certificates`] to tell who it is. Fails
+    /// without a tls set first.
+    pub fn with_client_auth(&mut self, roots: RootCertStore) -> Result<&mut Self, Status> {
+        let tls = self
+            .tls
+            .as_mut()
+            .ok_or_else(|| bad("websocket client auth without tls"))?;
+        Arc::make_mut(tls).set_client_certificate_verifier(AllowAnyAuthenticatedClient::new(roots));
+        Ok(self)
+    }
+
+    /// the tls handshake if any then the one of the websocket on `tcp`,
+    /// returning the websocket and the path requested
+    pub async fn accept(&self, tcp: TcpStream) -> Result<(WebSocket<Stream>, String), Status> {
+        let stream = match &self.tls {
+            None => Stream::Tcp(tcp),
+            Some(tls) => {
+                let tls = TlsAcceptor::from(tls.clone()).accept(tcp).await?;
+                Stream::Tls(Box::new(tls.into()))
+            }
+        };
+        WebSocket::accept(stream).await
+    }
+}
+
+/// Pool keeps the websockets its [`Dialer`] dialed once they are released,
+/// by url, so the calls to a server reuse them rather than dial it again.
+/// The url is the whole of it, path included, the handshake asked for it.
+/// The websockets idle for longer than the idle timeout or older than the
+/// max lifetime are closed, those over the max idle of a url as well.
+///
+/// ```rust,no_run
+/// # use codec::websocket::{Dialer, Pool};
+/// # async fn run(m: codec::Message) -> Result<(), errors::Status> {
+/// let pool = Pool::new(Dialer::new());
+/// let mut ws = pool.get("wss://greeter.internal/rpc").await?;
+/// ws.write(&m).await?;
+/// match ws.read().await {
+///     Ok(reply) => drop(ws), // back into the pool
+///     Err(e) => ws.discard(),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Pool {
+    dialer: Dialer,
+    max_idle: usize,
+    idle_timeout: Duration,
+    max_lifetime: Option<Duration>,
+    shared: Arc<Shared>,
+}
+
+/// the idle websockets of a [`Pool`] and its clones
+#[derive(Default)]
+struct Shared {
+    idle: Mutex<HashMap<String, Vec<Idle>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evicted: AtomicU64,
+}
+
+struct Idle {
+    ws: WebSocket<Stream>,
+    dialed: Instant,
+    released: Instant,
+}
+
+/// PoolStats counts the gets of a [`Pool`] and the websockets it closed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// the gets served by an idle websocket
+    pub hits: u64,
+    /// the gets which dialed
+    pub misses: u64,
+    /// the idle websockets closed for their age or over the max idle
+    pub evicted: u64,
+}
+
+impl Pool {
+    /// the pool of the websockets of `dialer`, keeping 8 idle ones a url
+    /// for 90 seconds each unless set
+    pub fn new(dialer: Dialer) -> Self {
+        Pool {
+            dialer,
+            max_idle: 8,
+            idle_timeout: Duration::from_secs(90),
+            max_lifetime: None,
+            shared: Arc::default(),
+        }
+    }
+
+    /// the idle websockets kept a url, the ones released over it are closed
+    #[inline]
+    pub fn with_max_idle(&mut self, n: usize) -> &mut Self {
+        self.max_idle = n;
+        self
+    }
+
+    /// closes the websockets idle for longer than `d`
+    #[inline]
+    pub fn with_idle_timeout(&mut self, d: Duration) -> &mut Self {
+        self.idle_timeout = d;
+        self
+    }
+
+    /// closes the websockets dialed longer than `d` ago once released,
+    /// none unless set
+    #[inline]
+    pub fn with_max_lifetime(&mut self, d: Option<Duration>) -> &mut Self {
+        self.max_lifetime = d;
+        self
+    }
+
+    /// the gets and evictions so far of the pool and its clones
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            hits: self.shared.hits.load(Ordering::Relaxed),
+            misses: self.shared.misses.load(Ordering::Relaxed),
+            evicted: self.shared.evicted.load(Ordering::Relaxed),
+        }
+    }
+
+    /// the websocket last released to `url` and still alive, or a new one
+    /// dialed
+    pub async fn get(&self, url: &str) -> Result<Pooled, Status> {
+        let idle = {
+            let mut idle = self.lock();
+            self.evict(&mut idle, Instant::now());
+            idle.get_mut(url).and_then(Vec::pop)
+        };
+        let (ws, dialed) = match idle {
+            Some(idle) => {
+                self.shared.hits.fetch_add(1, Ordering::Relaxed);
+                (idle.ws, idle.dialed)
+            }
+            None => {
+                self.shared.misses.fetch_add(1, Ordering::Relaxed);
+                (self.dialer.dial(url).await?, Instant::now())
+            }
+        };
+        Ok(Pooled {
+            ws: Some(ws),
+            url: url.to_string(),
+            dialed,
+            pool: self.clone(),
+        })
+    }
+
+    /// the idle websockets of `url`
+    pub fn idle(&self, url: &str) -> usize {
+        self.lock().get(url).map_or(0, Vec::len)
+    }
+
+    fn release(&self, url: String, ws: WebSocket<Stream>, dialed: Instant) {
+        let now = Instant::now();
+        let mut idle = self.lock();
+        let conns = idle.entry(url).or_default();
+        conns.push(Idle {
+            ws,
+            dialed,
+            released: now,
+        });
+        if conns.len() > self.max_idle {
+            // the one idle the longest
+            conns.remove(0);
+            self.shared.evicted.fetch_add(1, Ordering::Relaxed);
+        }
+        self.evict(&mut idle, now);
+    }
+
+    /// drops the websockets idle too long or too old of every url
+    fn evict(&self, idle: &mut HashMap<String, Vec<Idle>>, now: Instant) {
+        let mut evicted = 0;
+        idle.retain(|_, conns| {
+            let before = conns.len();
+            conns.retain(|c| {
+                now.duration_since(c.released) < self.idle_timeout
+                    && self
+                        .max_lifetime
+                        .is_none_or(|max| now.duration_since(c.dialed) < max)
+            });
+            evicted += before - conns.len();
+            !conns.is_empty()
+        });
+        self.shared
+            .evicted
+            .fetch_add(evicted as u64, Ordering::Relaxed);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Vec<Idle>>> {
+        self.shared.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Pooled is a websocket of a [`Pool`], released to it when dropped unless
+/// it was closed. One which failed should be given up with
+/// [`Pooled::discard`] so it is not handed out again.
+pub struct Pooled {
+    ws: Option<WebSocket<Stream>>,
+    url: String,
+    dialed: Instant,
+    pool: Pool,
+}
+
+impl Pooled {
+    /// closes the websocket rather than release it to the pool
+    pub fn discard(mut self) {
+        self.ws = None;
+    }
+}
+
+impl Deref for Pooled {
+    type Target = WebSocket<Stream>;
+
+    fn deref(&self) -> &Self::Target {
+        self.ws.as_ref().expect("pooled websocket taken")
+    }
+}
+
+impl DerefMut for Pooled {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.ws.as_mut().expect("pooled websocket taken")
+    }
+}
+
+impl Drop for Pooled {
+    fn drop(&mut self) {
+        if let Some(ws) = self.ws.take() {
+            if !ws.closed {
+                self.pool
+                    .release(std::mem::take(&mut self.url), ws, self.dialed);
+            }
+        }
+    }
+}
+
+/// dials the websocket at `url` with a [`Dialer`] checking the server
+/// certificates against the webpki roots
+///
+/// ```rust,no_run
+/// # async fn run() -> Result<(), errors::Status> {
+/// let mut ws = codec::websocket::connect("wss://example.com/rpc").await?;
+/// ws.write(&codec::Message::builder().endpoint("Greeter.Hello").build()?)
+///     .await?;
+/// let reply = ws.read().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn connect(url: &str) -> Result<WebSocket<Stream>, Status> {
+    Dialer::new().dial(url).await
+}
+
+/// dials the websocket at `url` as [`connect`] does, a `wss://` url with
+/// the tls of `config`
+pub async fn connect_with(
+    url: &str,
+    config: Arc<ClientConfig>,
+) -> Result<WebSocket<Stream>, Status> {
+    Dialer::new().with_tls(config).dial(url).await
+}
+
+/// the status of the tcp connect to `url` failed with `e`, the server
+/// refusing it unavailable
+fn dial_error(url: &Url<'_>, e: io::Error) -> Status {
+    let detail = format!("dial of {}:{}: {}", url.host, url.port, e);
+    let code = match e.kind() {
+        io::ErrorKind::TimedOut => Code::RequestTimeout,
+        io::ErrorKind::ConnectionRefused
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::NotConnected => Code::ServiceUnavailable,
+        _ => Status::from(e).code(),
+    };
+    Status::new("io.vine.codec".to_string(), detail, code)
+}
+
+/// whether a dial failed with `e` may succeed when tried again
+fn retryable(e: &Status) -> bool {
+    matches!(e.code(), Code::RequestTimeout | Code::ServiceUnavailable)
+}
+
+fn eof() -> Status {
+    std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()
+}
+
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, b) in payload.iter_mut().enumerate() {
+        *b ^= mask[i % 4];
+    }
+}
+
+fn fill(dst: &mut [u8]) -> Result<(), Status> {
+    ring::rand::SystemRandom::new()
+        .fill(dst)
+        .map_err(|_| Status::internal_server_error("io.vine.codec", "no random bytes"))
+}
+
+/// the request or status line and headers of the handshake, read a byte
+/// at a time so none of the frames after it are taken from the stream
+async fn read_head<S: AsyncRead + Unpin>(inner: &mut S) -> Result<String, Status> {
+    let mut head = Vec::with_capacity(256);
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_HANDSHAKE_SIZE {
+            return Err(too_large(
+                "websocket handshake",
+                head.len(),
+                MAX_HANDSHAKE_SIZE,
+            ));
+        }
+        head.push(inner.read_u8().await?);
+    }
+    head.truncate(head.len() - 4);
+    String::from_utf8(head).map_err(|_| bad("websocket handshake is not utf-8"))
+}
+
+/// answers a request not upgrading with a 400, the error of the accept
+async fn refuse<S: AsyncWrite + Unpin>(inner: &mut S, detail: &str) -> Status {
+    let _ = inner
+        .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")
+        .await;
+    let _ = inner.flush().await;
+    bad(detail)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use errors::Code;
+    use tokio::{
+        io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream},
+        net::TcpListener,
+    };
+    use tokio_rustls::{
+        rustls::{
+            Certificate, ClientConfig, NoClientAuth, PrivateKey, RootCertStore, ServerConfig,
+        },
+        TlsAcceptor,
+    };
+
+    use super::{
+        accept_key, connect, connect_with, decode, parse_url, Acceptor, DialOptions, Dialer, Pool,
+        PoolStats, Url, WebSocket,
+    };
+    use crate::{Message, MessageType};
+
+    async fn pair() -> (WebSocket<DuplexStream>, WebSocket<DuplexStream>) {
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let server = tokio::spawn(WebSocket::accept(server));
+        let client = WebSocket::connect(client, "localhost", "/rpc")
+            .await
+            .unwrap();
+        let (server, path) = server.await.unwrap().unwrap();
+        assert_eq!(path, "/rpc");
+        (client, server)
+    }
+
+    /// sends back the messages read until the websocket closes, returning
+    /// the path requested
+    async fn echo<S: AsyncRead + AsyncWrite + Unpin>(stream: S) -> String {
+        let (mut ws, path) = WebSocket::accept(stream).await.unwrap();
+        while let Some(m) = ws.read().await.unwrap() {
+            ws.write(&m).await.unwrap();
+        }
+        path
+    }
+
+    fn message() -> Message {
+        Message::builder()
+            .id("7")
+            .r#type(MessageType::Response)
+            .target("greeter")
+            .method("POST")
+            .endpoint("Greeter.Hello")
+            .request_id("1")
+            .body(&b"\x00hello"[..])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // RFC 6455 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[tokio::test]
+    async fn test_messages() {
+        let (mut client, mut server) = pair().await;
+
+        client.write_message(b"hello").await.unwrap();
+        client.write_message(&[7u8; 70000]).await.unwrap();
+        assert_eq!(
+            server.read_message().await.unwrap().as_deref(),
+            Some(&b"hello"[..])
+        );
+        assert_eq!(server.read_message().await.unwrap().unwrap().len(), 70000);
+
+        server.write_message(b"").await.unwrap();
+        server.write_message(&[1u8; 300]).await.unwrap();
+        assert_eq!(
+            client.read_message().await.unwrap().as_deref(),
+            Some(&b""[..])
+        );
+        assert_eq!(client.read_message().await.unwrap().unwrap().len(), 300);
+
+        // the close is answered, the reader of each side sees the end
+        client.close().await.unwrap();
+        assert_eq!(server.read_message().await.unwrap(), None);
+        assert_eq!(client.read_message().await.unwrap(), None);
+        assert_eq!(
+            client.write_message(b"late").await.unwrap_err().code(),
+            Code::ServiceUnavailable
+        );
+    }
+
+    #[tokio::test]
+    async fn test_codec_messages() {
+        let (mut client, mut server) = pair().await;
+        let m = message();
+        client.write(&m).await.unwrap();
+        client
+            .write(&Message::builder().build().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(server.read().await.unwrap(), Some(m));
+        let empty = server.read().await.unwrap().unwrap();
+        assert_eq!(empty.r#type, MessageType::Request);
+        assert!(empty.body.is_empty());
+
+        // one binary message of the header and the body
+        client.write_message(b"\x00\x00").await.unwrap();
+        client.write_message(b"\x00\x00\x00\x09{}").await.unwrap();
+        client
+            .write_message(b"\x00\x00\x00\x02{}body")
+            .await
+            .unwrap();
+        for _ in 0..3 {
+            assert_eq!(server.read().await.unwrap_err().code(), Code::BadRequest);
+        }
+        let head =
+            br#"{"id":"","type":9,"target":"","method":"","endpoint":"","error":"","header":{}}"#;
+        let mut unknown = (head.len() as u32).to_be_bytes().to_vec();
+        unknown.extend_from_slice(head);
+        assert_eq!(
+            decode(unknown.into()).unwrap_err().detail(),
+            "websocket message of an unknown type"
+        );
+
+        client.close().await.unwrap();
+        assert_eq!(server.read().await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            parse_url("ws://localhost:8080/rpc?v=1#top").unwrap(),
+            Url {
+                tls: false,
+                authority: "localhost:8080",
+                host: "localhost",
+                port: 8080,
+                path: "/rpc?v=1",
+            }
+        );
+        let url = parse_url("wss://example.com").unwrap();
+        assert!(url.tls);
+        assert_eq!((url.host, url.port, url.path), ("example.com", 443, "/"));
+        let url = parse_url("ws://[::1]:9000/a").unwrap();
+        assert_eq!(
+            (url.host, url.port, url.authority),
+            ("::1", 9000, "[::1]:9000")
+        );
+        assert_eq!(parse_url("ws://[::1]").unwrap().port, 80);
+
+        for url in [
+            "http://localhost/",
+            "ws:///rpc",
+            "ws://localhost:http/",
+            "ws://[::1/",
+            "ws://localhost?v=1",
+        ] {
+            assert_eq!(
+                parse_url(url).unwrap_err().code(),
+                Code::BadRequest,
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move { echo(listener.accept().await.unwrap().0).await });
+
+        let mut ws = connect(&format!("ws://{}/rpc?v=1", addr)).await.unwrap();
+        let m = message();
+        ws.write(&m).await.unwrap();
+        assert_eq!(ws.read().await.unwrap(), Some(m));
+        ws.close().await.unwrap();
+        assert_eq!(ws.read().await.unwrap(), None);
+        assert_eq!(server.await.unwrap(), "/rpc?v=1");
+
+        // nothing listens
+        drop(TcpListener::bind(addr).await.unwrap());
+        assert!(connect(&format!("ws://{}/", addr)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connect_tls() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let der = Certificate(cert.serialize_der().unwrap());
+        let mut config = ServerConfig::new(NoClientAuth::new());
+        config
+            .set_single_cert(
+                vec![der.clone()],
+                PrivateKey(cert.serialize_private_key_der()),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            echo(acceptor.accept(tcp).await.unwrap()).await
+        });
+
+        let mut client = ClientConfig::new();
+        client.root_store.add(&der).unwrap();
+        let url = format!("wss://localhost:{}/rpc", port);
+        let mut ws = connect_with(&url, Arc::new(client)).await.unwrap();
+        let m = message();
+        ws.write(&m).await.unwrap();
+        assert_eq!(ws.read().await.unwrap(), Some(m));
+        ws.close().await.unwrap();
+        assert_eq!(ws.read().await.unwrap(), None);
+        assert_eq!(server.await.unwrap(), "/rpc");
+
+        // the certificate is not one of the webpki roots
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let _ = listener.accept().await;
+        });
+        assert!(connect(&format!("wss://localhost:{}/rpc", port))
+            .await
+            .is_err());
+    }
+
+    /// a self signed certificate of `name` and its key
+    fn self_signed(name: &str) -> (Certificate, PrivateKey) {
+        let cert = rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap();
+        (
+            Certificate(cert.serialize_der().unwrap()),
+            PrivateKey(cert.serialize_private_key_der()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls() {
+        let (server_cert, server_key) = self_signed("localhost");
+        let (client_cert, client_key) = self_signed("client");
+        let mut tls = ServerConfig::new(NoClientAuth::new());
+        tls.set_single_cert(vec![server_cert.clone()], server_key)
+            .unwrap();
+        let mut clients = RootCertStore::empty();
+        clients.add(&client_cert).unwrap();
+        let mut acceptor = Acceptor::new();
+        acceptor
+            .with_tls(Arc::new(tls))
+            .with_client_auth(clients)
+            .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let (mut ws, path) = acceptor.accept(tcp).await.unwrap();
+            let peer = ws.get_ref().peer_certificates();
+            while let Some(m) = ws.read().await.unwrap() {
+                ws.write(&m).await.unwrap();
+            }
+            // refused without a client certificate
+            let (tcp, _) = listener.accept().await.unwrap();
+            assert!(acceptor.accept(tcp).await.is_err());
+            (path, peer)
+        });
+
+        let mut tls = ClientConfig::new();
+        tls.root_store.add(&server_cert).unwrap();
+        let mut dialer = Dialer::new();
+        dialer
+            .with_tls(Arc::new(tls))
+            .with_client_cert(vec![client_cert.clone()], client_key)
+            .unwrap();
+        let url = format!("wss://localhost:{}/rpc", port);
+        let mut ws = dialer.dial(&url).await.unwrap();
+        assert!(ws.get_ref().peer_certificates().is_some());
+        let m = message();
+        ws.write(&m).await.unwrap();
+        assert_eq!(ws.read().await.unwrap(), Some(m));
+        ws.close().await.unwrap();
+        assert_eq!(ws.read().await.unwrap(), None);
+
+        let mut tls = ClientConfig::new();
+        tls.root_store.add(&server_cert).unwrap();
+        assert!(connect_with(&url, Arc::new(tls)).await.is_err());
+        let (path, peer) = server.await.unwrap();
+        assert_eq!(path, "/rpc");
+        assert_eq!(peer, Some(vec![client_cert]));
+
+        // the key of a client certificate must sign
+        let e = Dialer::new()
+            .with_client_cert(vec![], PrivateKey(vec![1, 2, 3]))
+            .err()
+            .unwrap();
+        assert_eq!(e.code(), Code::BadRequest);
+        assert!(Acceptor::new()
+            .with_client_auth(RootCertStore::empty())
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dial_options() {
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let url = format!("ws://{}/rpc", addr);
+        let mut opts = DialOptions::new();
+        opts.with_retries(2).with_backoff(Duration::from_millis(20));
+        let mut dialer = Dialer::new();
+        dialer.with_options(opts.clone());
+
+        // refused each time, after the backoff of both retries
+        let start = std::time::Instant::now();
+        let e = dialer.dial(&url).await.unwrap_err();
+        assert_eq!(e.code(), Code::ServiceUnavailable);
+        assert!(start.elapsed() >= Duration::from_millis(60));
+
+        // listening by the second retry
+        let server = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            let listener = TcpListener::bind(addr).await.unwrap();
+            echo(listener.accept().await.unwrap().0).await
+        });
+        let mut ws = dialer.dial(&url).await.unwrap();
+        ws.close().await.unwrap();
+        assert_eq!(ws.read().await.unwrap(), None);
+        assert_eq!(server.await.unwrap(), "/rpc");
+
+        // the server never answers the upgrade
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/rpc", listener.local_addr().unwrap());
+        let (accepted, mut accepts) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut held = vec![];
+            while let Ok((tcp, _)) = listener.accept().await {
+                held.push(tcp);
+                let _ = accepted.send(());
+            }
+        });
+        opts.with_retries(1)
+            .with_timeout(Some(Duration::from_millis(50)));
+        dialer.with_options(opts);
+        let e = dialer.dial(&url).await.unwrap_err();
+        assert_eq!(e.code(), Code::RequestTimeout);
+        for _ in 0..2 {
+            accepts.recv().await.unwrap();
+        }
+
+        // not worth another try
+        let e = dialer.dial("http://localhost/").await.unwrap_err();
+        assert_eq!(e.code(), Code::BadRequest);
+    }
+
+    #[tokio::test]
+    async fn test_pool() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/rpc", listener.local_addr().unwrap());
+        let (accepted, mut accepts) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((tcp, _)) = listener.accept().await {
+                let _ = accepted.send(());
+                tokio::spawn(echo(tcp));
+            }
+        });
+
+        let mut pool = Pool::new(Dialer::new());
+        pool.with_max_idle(1)
+            .with_idle_timeout(Duration::from_millis(100));
+        let m = message();
+        for _ in 0..2 {
+            let mut ws = pool.get(&url).await.unwrap();
+            ws.write(&m).await.unwrap();
+            assert_eq!(ws.read().await.unwrap(), Some(m.clone()));
+        }
+        // reused, dialed once
+        accepts.recv().await.unwrap();
+        assert!(accepts.try_recv().is_err());
+        assert_eq!(pool.idle(&url), 1);
+
+        // two at once, one over the max idle
+        let (a, b) = (pool.get(&url).await.unwrap(), pool.get(&url).await.unwrap());
+        drop((a, b));
+        assert_eq!(pool.idle(&url), 1);
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                hits: 2,
+                misses: 2,
+                evicted: 1
+            }
+        );
+
+        // neither a discarded nor a closed one is released
+        pool.get(&url).await.unwrap().discard();
+        let mut ws = pool.get(&url).await.unwrap();
+        ws.close().await.unwrap();
+        drop(ws);
+        assert_eq!(pool.idle(&url), 0);
+
+        // idle past the timeout
+        drop(pool.get(&url).await.unwrap());
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        drop(pool.get(&url).await.unwrap());
+        assert_eq!(pool.stats().evicted, 2);
+        assert_eq!(pool.stats().hits, 3);
+
+        // too old once released
+        pool.with_max_lifetime(Some(Duration::ZERO));
+        drop(pool.get(&url).await.unwrap());
+        assert_eq!(pool.idle(&url), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fragments_and_pings() {
+        let (client, mut server) = pair().await;
+        let mut raw = client.into_inner();
+        // unmasked frames are refused by a server, these have a zero mask
+        raw.write_all(&[0x01, 0x83, 0, 0, 0, 0, b'a', b'b', b'c'])
+            .await
+            .unwrap();
+        raw.write_all(&[0x89, 0x82, 0, 0, 0, 0, b'h', b'i'])
+            .await
+            .unwrap();
+        raw.write_all(&[0x80, 0x82, 0, 0, 0, 0, b'd', b'e'])
+            .await
+            .unwrap();
+        assert_eq!(
+            server.read_message().await.unwrap().as_deref(),
+            Some(&b"abcde"[..])
+        );
+
+        // the ping in between was answered
+        let mut pong = [0u8; 4];
+        raw.read_exact(&mut pong).await.unwrap();
+        assert_eq!(pong, [0x8A, 0x02, b'h', b'i']);
+
+        raw.write_all(&[0x82, 0x01, b'x']).await.unwrap();
+        assert_eq!(
+            server.read_message().await.unwrap_err().code(),
+            Code::BadRequest
+        );
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        let (mut client, mut server) = pair().await;
+        server.with_idle_timeout(Some(Duration::from_millis(50)));
+
+        // a message after the ping keeps the peer alive
+        let write = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(70)).await;
+            client.write_message(b"late").await.unwrap();
+            client
+        });
+        assert_eq!(
+            server.read_message().await.unwrap().as_deref(),
+            Some(&b"late"[..])
+        );
+        let mut client = write.await.unwrap();
+
+        // the ping is never answered
+        let start = std::time::Instant::now();
+        let e = server.read_message().await.unwrap_err();
+        assert_eq!(e.code(), Code::ServiceUnavailable);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        // the client sees the pings then the end of the stream
+        assert_eq!(client.read_message().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_mid_frame() {
+        let (client, mut server) = pair().await;
+        server.with_idle_timeout(Some(Duration::from_millis(50)));
+        let mut raw = client.into_inner();
+
+        // the rest of the frame after the ping, nothing read before is lost
+        raw.write_all(&[0x82, 0x85, 0, 0, 0, 0, b'h', b'e'])
+            .await
+            .unwrap();
+        let write = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(70)).await;
+            raw.write_all(b"llo").await.unwrap();
+            raw
+        });
+        assert_eq!(
+            server.read_message().await.unwrap().as_deref(),
+            Some(&b"hello"[..])
+        );
+        let mut raw = write.await.unwrap();
+        let mut ping = [0u8; 2];
+        raw.read_exact(&mut ping).await.unwrap();
+        assert_eq!(ping, [0x89, 0x00]);
+
+        // half a frame then nothing
+        raw.write_all(&[0x82, 0xFE, 0x01]).await.unwrap();
+        let start = std::time::Instant::now();
+        let e = server.read_message().await.unwrap_err();
+        assert_eq!(e.code(), Code::ServiceUnavailable);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let (mut client, mut server) = pair().await;
+        server.with_max_recv_size(4);
+        client.write_message(b"hello").await.unwrap();
+        assert_eq!(
+            server.read_message().await.unwrap_err().code(),
+            Code::BadRequest
+        );
+        client.with_max_send_size(2);
+        assert!(client.write_message(b"abc").await.is_err());
+
+        // not an upgrade
+        let (mut client, server) = tokio::io::duplex(1024);
+        let accept = tokio::spawn(WebSocket::accept(server));
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        assert!(accept.await.unwrap().is_err());
+        let mut resp = vec![0u8; 12];
+        client.read_exact(&mut resp).await.unwrap();
+        assert_eq!(resp, b"HTTP/1.1 400");
+
+        // refused by the server
+        let (client, mut server) = tokio::io::duplex(1024);
+        tokio::spawn(async move {
+            let mut req = [0u8; 16];
+            let _ = server.read(&mut req).await;
+            let _ = server.write_all(b"HTTP/1.1 404 Not Found\r\n\r\n").await;
+        });
+        let e = WebSocket::connect(client, "localhost", "/")
+            .await
+            .unwrap_err();
+        assert_eq!(e.code(), Code::BadGateway);
+    }
+}
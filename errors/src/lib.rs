@@ -1,8 +1,26 @@
 pub use anyhow;
-use serde::{Deserialize, Serialize};
-use vine_util::caller::caller;
-
-use std::fmt;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use std::{
+    backtrace::Backtrace,
+    collections::HashMap,
+    convert::TryFrom,
+    error::Error,
+    fmt,
+    iter::FromIterator,
+    panic::Location,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// the header of an http response carrying the [`Code`] of its [`Status`]
+pub const STATUS_HEADER: &str = "vine-status";
+
+/// the key of the metadata [`Status::retry_after`] is read from
+pub const RETRY_AFTER_KEY: &str = "retry-after";
 
 pub type Result<T> = anyhow::Result<T>;
 
@@ -18,6 +36,39 @@ pub enum Code {
     /// RFC 7231, 6.3.1
     Ok = 200,
 
+    /// RFC 7231, 6.3.2
+    Created = 201,
+
+    /// RFC 7231, 6.3.3
+    Accepted = 202,
+
+    /// RFC 7231, 6.3.5
+    NoContent = 204,
+
+    /// RFC 7233, 4.1
+    PartialContent = 206,
+
+    /// RFC 7231, 6.4.1
+    MultipleChoices = 300,
+
+    /// RFC 7231, 6.4.2
+    MovedPermanently = 301,
+
+    /// RFC 7231, 6.4.3
+    Found = 302,
+
+    /// RFC 7231, 6.4.4
+    SeeOther = 303,
+
+    /// RFC 7232, 4.1
+    NotModified = 304,
+
+    /// RFC 7231, 6.4.7
+    TemporaryRedirect = 307,
+
+    /// RFC 7538, 3
+    PermanentRedirect = 308,
+
     /// RFC 7231, 6.5.1
     BadRequest = 400,
 
@@ -42,9 +93,21 @@ pub enum Code {
     /// RFC 7232, 4.2
     PreconditionFailed = 412,
 
+    /// RFC 7231, 6.5.11
+    PayloadTooLarge = 413,
+
+    /// RFC 7231, 6.5.13
+    UnsupportedMediaType = 415,
+
+    /// RFC 4918, 11.2
+    UnprocessableEntity = 422,
+
     ///  RFC 6585, 4
     TooManyRequests = 429,
 
+    /// the client closed the connection before the response, as nginx
+    ClientClosedRequest = 499,
+
     /// RFC 7231, 6.6.1
     InternalServerError = 500,
 
@@ -69,6 +132,21 @@ impl Code {
         Code::from(i)
     }
 
+    /// returns true for the 2xx codes
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&(*self as i32))
+    }
+
+    /// returns true for the 4xx codes, the errors of the client
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&(*self as i32))
+    }
+
+    /// returns true for the 5xx codes, the errors of the server
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&(*self as i32))
+    }
+
     /// Convert the string representation of a `Code` (as stored, for example, in the `vine-status`
     /// header in a response) into a `Code`. Returns `Code::Unknown` if the code string is not a
     /// valid vine status code.
@@ -76,6 +154,17 @@ impl Code {
         match self {
             Code::Continue => "Continue",
             Code::Ok => "OK",
+            Code::Created => "Created",
+            Code::Accepted => "Accepted",
+            Code::NoContent => "No Content",
+            Code::PartialContent => "Partial Content",
+            Code::MultipleChoices => "Multiple Choices",
+            Code::MovedPermanently => "Moved Permanently",
+            Code::Found => "Found",
+            Code::SeeOther => "See Other",
+            Code::NotModified => "Not Modified",
+            Code::TemporaryRedirect => "Temporary Redirect",
+            Code::PermanentRedirect => "Permanent Redirect",
             Code::BadRequest => "Bad Request",
             Code::Unauthorized => "Unauthorized",
             Code::Forbidden => "forbidden",
@@ -84,7 +173,11 @@ impl Code {
             Code::RequestTimeout => "Request Timeout",
             Code::Conflict => "Conflict",
             Code::PreconditionFailed => "Precondition Failed",
+            Code::PayloadTooLarge => "Payload Too Large",
+            Code::UnsupportedMediaType => "Unsupported Media Type",
+            Code::UnprocessableEntity => "Unprocessable Entity",
             Code::TooManyRequests => "Too Many Requests",
+            Code::ClientClosedRequest => "Client Closed Request",
             Code::InternalServerError => "Internal Server Error",
             Code::NotImplementedError => "Not Implemented",
             Code::BadGateway => "Bad Gateway",
@@ -104,8 +197,19 @@ impl std::fmt::Display for Code {
 impl From<i32> for Code {
     fn from(i: i32) -> Self {
         match i {
+            100 => Code::Continue,
             200 => Code::Ok,
-            201 => Code::Continue,
+            201 => Code::Created,
+            202 => Code::Accepted,
+            204 => Code::NoContent,
+            206 => Code::PartialContent,
+            300 => Code::MultipleChoices,
+            301 => Code::MovedPermanently,
+            302 => Code::Found,
+            303 => Code::SeeOther,
+            304 => Code::NotModified,
+            307 => Code::TemporaryRedirect,
+            308 => Code::PermanentRedirect,
             400 => Code::BadRequest,
             401 => Code::Unauthorized,
             403 => Code::Forbidden,
@@ -114,7 +218,11 @@ impl From<i32> for Code {
             408 => Code::RequestTimeout,
             409 => Code::Conflict,
             412 => Code::PreconditionFailed,
+            413 => Code::PayloadTooLarge,
+            415 => Code::UnsupportedMediaType,
+            422 => Code::UnprocessableEntity,
             429 => Code::TooManyRequests,
+            499 => Code::ClientClosedRequest,
             500 => Code::InternalServerError,
             501 => Code::NotImplementedError,
             502 => Code::BadGateway,
@@ -133,6 +241,20 @@ impl From<Code> for i32 {
     }
 }
 
+impl TryFrom<http::StatusCode> for Code {
+    type Error = Status;
+
+    fn try_from(code: http::StatusCode) -> std::result::Result<Self, Self::Error> {
+        match Code::from(code.as_u16() as i32) {
+            Code::Unknown => Err(Status::bad_request(
+                "io.vine".to_string(),
+                format!("no vine code for http status {}", code),
+            )),
+            c => Ok(c),
+        }
+    }
+}
+
 /// A Vine status describing the result of an RPC call.
 ///
 /// Values can be created using the `new` function or one of the specialized
@@ -152,21 +274,135 @@ pub struct Status {
     detail: String,
     status: String,
     position: String,
+    /// boxed to keep the results failing with a status small
+    #[serde(flatten)]
+    details: Box<Details>,
+    #[serde(skip)]
+    local: Local,
+}
+
+/// the structured context of a [`Status`], written with its other fields
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Details {
+    /// the context of the error, as the id of the request, when to retry or
+    /// the invalid fields, carried as the metadata of a tonic status
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    metadata: HashMap<String, String>,
+    /// the invalid fields of a bad request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    violations: Vec<Violation>,
+    /// the typed detail of the error, as the quota exceeded or the diff of a
+    /// conflict, carried as the details of a tonic status
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    payload: Option<serde_json::Value>,
+}
+
+/// a field of a request failing the validation and why
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Violation {
+    pub field: String,
+    pub description: String,
+}
+
+/// the parts of a [`Status`] kept local, neither serialized nor compared:
+/// the error it was made from and the backtrace of where it was made. One
+/// pointer, allocated only with either.
+#[derive(Clone, Default)]
+struct Local(Option<Arc<LocalInner>>);
+
+#[derive(Default)]
+struct LocalInner {
+    source: Option<Arc<dyn Error + Send + Sync>>,
+    backtrace: Option<Arc<Backtrace>>,
+}
+
+impl Local {
+    /// the backtrace of the caller when enabled, see [`set_backtrace`]
+    fn capture() -> Self {
+        match backtrace_enabled() {
+            true => Local(Some(Arc::new(LocalInner {
+                source: None,
+                backtrace: Some(Arc::new(Backtrace::force_capture())),
+            }))),
+            false => Local(None),
+        }
+    }
+
+    fn source(&self) -> Option<&Arc<dyn Error + Send + Sync>> {
+        self.0.as_ref().and_then(|l| l.source.as_ref())
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.0.as_ref().and_then(|l| l.backtrace.as_deref())
+    }
+}
+
+impl fmt::Debug for Local {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.source() {
+            Some(s) => write!(f, "Some({:?})", s),
+            None => f.write_str("None"),
+        }
+    }
+}
+
+impl PartialEq for Local {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Local {}
+
+/// 0 until read from `VINE_BACKTRACE`, then 1 when disabled and 2 when not
+static BACKTRACE: AtomicU8 = AtomicU8::new(0);
+
+fn backtrace_enabled() -> bool {
+    match BACKTRACE.load(Ordering::Relaxed) {
+        0 => {
+            let enabled = matches!(
+                std::env::var("VINE_BACKTRACE").as_deref(),
+                Ok("1") | Ok("true") | Ok("full")
+            );
+            set_backtrace(enabled);
+            enabled
+        }
+        v => v == 2,
+    }
+}
+
+/// capture the backtrace of every [`Status`] made from now on, as
+/// `VINE_BACKTRACE=1` does, or stop when disabled. Off by default, a
+/// backtrace costing far more than the status.
+pub fn set_backtrace(enabled: bool) {
+    BACKTRACE.store(if enabled { 2 } else { 1 }, Ordering::Relaxed);
+}
+
+/// the `file:line` of the caller of the function tracking its caller
+#[track_caller]
+fn location() -> String {
+    let loc = Location::caller();
+    format!("{}:{}", loc.file(), loc.line())
 }
 
 impl Status {
     #[inline]
+    #[track_caller]
     pub fn new<T: Into<String>>(id: T, detail: T, code: Code) -> Self {
         Status {
             id: id.into(),
             code,
             detail: detail.into(),
             status: code.description().to_string(),
-            position: String::new(),
+            position: location(),
+            details: Box::default(),
+            local: Local::capture(),
         }
     }
 
     #[inline]
+    #[allow(clippy::should_implement_trait)]
+    #[track_caller]
     pub fn from_str(e: impl Into<String>) -> Result<Self> {
         let s = e.into();
         if let Ok(out) = serde_json::from_str(s.as_str()) {
@@ -176,6 +412,29 @@ impl Status {
         Ok(Status::internal_server_error("", s.as_str()))
     }
 
+    /// returns true if the call may succeed when made again, as after a
+    /// timeout, a rate limit or an unavailable upstream
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.code,
+            Code::RequestTimeout
+                | Code::TooManyRequests
+                | Code::BadGateway
+                | Code::ServiceUnavailable
+                | Code::GatewayTimeout
+        )
+    }
+
+    /// how long to wait before calling again, from the seconds of the
+    /// `retry-after` metadata a rate limited response carries
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.details
+            .metadata
+            .get(RETRY_AFTER_KEY)
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .map(Duration::from_secs)
+    }
+
     #[inline]
     pub fn equal(&self, another: &Self) -> bool {
         self.code == another.code
@@ -201,6 +460,20 @@ impl Status {
         self.position.as_str()
     }
 
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.details.metadata
+    }
+
+    pub fn violations(&self) -> &[Violation] {
+        &self.details.violations
+    }
+
+    /// the backtrace of where the status was made, only captured when
+    /// enabled by `VINE_BACKTRACE=1` or [`set_backtrace`]
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.local.backtrace()
+    }
+
     #[inline]
     pub fn with_id(mut self, id: impl Into<String>) -> Self {
         self.id = id.into();
@@ -220,105 +493,248 @@ impl Status {
         self
     }
 
+    /// adds the `key` entry to the metadata, a tonic status carries only the
+    /// lowercase ascii ones
+    #[inline]
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.details.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// embed `t` as the typed detail of the status, replacing any. Left
+    /// unset when `t` cannot be written as JSON.
+    #[inline]
+    pub fn with_detail_message<T: Serialize>(mut self, t: T) -> Self {
+        self.details.payload = serde_json::to_value(t).ok();
+        self
+    }
+
+    /// the typed detail of the status, `None` when there is none of `T`
+    pub fn detail_as<T: DeserializeOwned>(&self) -> Option<T> {
+        let payload = self.details.payload.as_ref()?;
+        T::deserialize(payload).ok()
+    }
+
+    /// embed a protobuf message as the typed detail of the status, its
+    /// value in base64 as `{"@type": .., "value": ..}`
+    #[cfg(feature = "prost")]
+    pub fn with_detail_any(mut self, any: prost_types::Any) -> Self {
+        self.details.payload = Some(serde_json::json!({
+            "@type": any.type_url,
+            "value": base64::encode(&any.value),
+        }));
+        self
+    }
+
+    /// the protobuf message embedded by [`Status::with_detail_any`]
+    #[cfg(feature = "prost")]
+    pub fn detail_any(&self) -> Option<prost_types::Any> {
+        let payload = self.details.payload.as_ref()?;
+        Some(prost_types::Any {
+            type_url: payload["@type"].as_str()?.to_string(),
+            value: base64::decode(payload["value"].as_str()?).ok()?,
+        })
+    }
+
+    /// add an invalid field of the request
+    #[inline]
+    pub fn with_violation(
+        mut self,
+        field: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        self.details.violations.push(Violation {
+            field: field.into(),
+            description: description.into(),
+        });
+        self
+    }
+
+    /// keep the error the status was made from, as the failure of etcd or
+    /// of an IO, as its [`Error::source`]
+    #[inline]
+    pub fn with_source(mut self, err: impl Error + Send + Sync + 'static) -> Self {
+        let backtrace = self.local.0.as_ref().and_then(|l| l.backtrace.clone());
+        self.local = Local(Some(Arc::new(LocalInner {
+            source: Some(Arc::new(err)),
+            backtrace,
+        })));
+        self
+    }
+
+    /// set the `retry-after` metadata, in whole seconds rounded up
+    #[inline]
+    pub fn with_retry_after(self, after: Duration) -> Self {
+        let mut secs = after.as_secs();
+        if after.subsec_nanos() > 0 {
+            secs += 1;
+        }
+        self.with_metadata(RETRY_AFTER_KEY, secs.to_string())
+    }
+
     #[inline]
+    #[track_caller]
     pub fn with_pos(mut self) -> Self {
-        self.position = caller(5);
+        self.position = location();
+        self
+    }
+
+    /// set the position the status was made at, as `file:line`
+    #[inline]
+    pub fn with_position(mut self, position: impl Into<String>) -> Self {
+        self.position = position.into();
         self
     }
 
     // unknown generates a unknown error.
+    #[track_caller]
     pub fn unknown<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::Unknown)
     }
 
     // ok generates a success status
+    #[track_caller]
     pub fn ok(id: impl Into<String>) -> Self {
         Status {
             id: id.into(),
             code: Code::Unknown,
             detail: String::new(),
             status: Code::Unknown.to_string(),
-            position: String::new(),
+            position: location(),
+            details: Box::default(),
+            local: Local::capture(),
         }
     }
 
     // continue generates a contine status
+    #[track_caller]
     pub fn do_continue(id: impl Into<String>) -> Self {
         Status {
             id: id.into(),
             code: Code::Ok,
             detail: String::new(),
             status: Code::Ok.to_string(),
-            position: String::new(),
+            position: location(),
+            details: Box::default(),
+            local: Local::capture(),
         }
     }
 
     /// bad_request generates a 400 error.
+    #[track_caller]
     pub fn bad_request<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::BadRequest)
     }
 
+    /// bad_request_with_fields generates a 400 error with the invalid fields
+    /// of the request and why, listed in the detail too.
+    ///
+    /// ```rust
+    /// # use errors::Status;
+    /// let s = Status::bad_request_with_fields(
+    ///     "io.vine.api",
+    ///     vec![("email", "invalid format"), ("name", "required")],
+    /// );
+    /// assert_eq!(s.detail(), "email: invalid format; name: required");
+    /// assert_eq!(s.violations()[1].field, "name");
+    /// ```
+    #[track_caller]
+    pub fn bad_request_with_fields<F, D>(
+        id: impl Into<String>,
+        fields: impl IntoIterator<Item = (F, D)>,
+    ) -> Self
+    where
+        F: Into<String>,
+        D: Into<String>,
+    {
+        let mut s = Status::bad_request(id.into(), String::new());
+        for (field, description) in fields {
+            s = s.with_violation(field, description);
+        }
+        s.detail = s
+            .details
+            .violations
+            .iter()
+            .map(|v| format!("{}: {}", v.field, v.description))
+            .collect::<Vec<_>>()
+            .join("; ");
+        s
+    }
+
     /// unauthorized generats a 401 error.
+    #[track_caller]
     pub fn unauthorized<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::Unauthorized)
     }
 
     /// forbidden generates a 403 error.
+    #[track_caller]
     pub fn forbidden<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::Forbidden)
     }
 
     /// not_found generates a 404 error.
+    #[track_caller]
     pub fn not_found<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::NotFound)
     }
 
     /// method_not_allowed generates a 405 error.
+    #[track_caller]
     pub fn method_not_allowed<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::MethodNotAllowed)
     }
 
     /// timeout generates a 408 error.
+    #[track_caller]
     pub fn timeout<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::RequestTimeout)
     }
 
     /// conflict generates a 409 error.
+    #[track_caller]
     pub fn conflict<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::Conflict)
     }
 
     /// precondition_failed generates a 412 error.
+    #[track_caller]
     pub fn precondition_failed<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::PreconditionFailed)
     }
 
     // too_many_requests generates a 429 error.
+    #[track_caller]
     pub fn too_many_requests<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::TooManyRequests)
     }
 
     /// internal_server_error generates a 500 error.
+    #[track_caller]
     pub fn internal_server_error<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::InternalServerError)
     }
 
     /// not_implemented generates a 501 error.
+    #[track_caller]
     pub fn not_implemented<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::NotImplementedError)
     }
 
     /// bad_gateway generates a 502 error.
+    #[track_caller]
     pub fn bad_gateway<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::BadGateway)
     }
 
     /// service_unavailable generates a 503 error.
+    #[track_caller]
     pub fn service_unavailable<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::ServiceUnavailable)
     }
 
     /// gateway_timeout generates a 504 error.
+    #[track_caller]
     pub fn gateway_timeout<T: Into<String>>(id: T, detail: T) -> Self {
         Status::new(id, detail, Code::GatewayTimeout)
     }
@@ -339,9 +755,125 @@ impl fmt::Display for Status {
     }
 }
 
-impl std::error::Error for Status {}
+impl Error for Status {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.local.source().map(|s| &**s as &(dyn Error + 'static))
+    }
+}
+
+/// MultiStatus collects the [`Status`] of every failed item of an
+/// operation touching several items, so it does not stop at the first error.
+///
+/// ```rust
+/// # use errors::{MultiStatus, Status};
+/// let mut ms = MultiStatus::new();
+/// ms.push(Status::bad_request("io.vine", "name is empty"));
+/// assert_eq!(ms.len(), 1);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiStatus {
+    statuses: Vec<Status>,
+}
+
+impl MultiStatus {
+    #[inline]
+    pub fn new() -> Self {
+        MultiStatus { statuses: vec![] }
+    }
+
+    #[inline]
+    pub fn push(&mut self, s: Status) {
+        self.statuses.push(s);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statuses.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.statuses.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Status> {
+        self.statuses.iter()
+    }
+
+    /// the code of the failures as a whole: their code when they agree,
+    /// else [`Code::BadRequest`] when all are errors of the client and
+    /// [`Code::InternalServerError`] when not. [`Code::Ok`] without any.
+    pub fn code(&self) -> Code {
+        let first = match self.statuses.first() {
+            Some(s) => s.code(),
+            None => return Code::Ok,
+        };
+        if self.statuses.iter().all(|s| s.code() == first) {
+            return first;
+        }
+        match self.statuses.iter().all(|s| s.code().is_client_error()) {
+            true => Code::BadRequest,
+            false => Code::InternalServerError,
+        }
+    }
+
+    /// `Ok` when no item failed, else the failures
+    pub fn into_result(self) -> std::result::Result<(), MultiStatus> {
+        match self.is_empty() {
+            true => Ok(()),
+            false => Err(self),
+        }
+    }
+}
+
+impl FromIterator<Status> for MultiStatus {
+    fn from_iter<I: IntoIterator<Item = Status>>(iter: I) -> Self {
+        MultiStatus {
+            statuses: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<Status> for MultiStatus {
+    fn extend<I: IntoIterator<Item = Status>>(&mut self, iter: I) {
+        self.statuses.extend(iter)
+    }
+}
+
+impl IntoIterator for MultiStatus {
+    type Item = Status;
+    type IntoIter = std::vec::IntoIter<Status>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.statuses.into_iter()
+    }
+}
+
+impl fmt::Display for MultiStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match serde_json::to_string(self) {
+            Ok(s) => write!(f, "{}", s),
+            Err(_) => write!(f, "{} errors", self.len()),
+        }
+    }
+}
+
+impl Error for MultiStatus {}
+
+/// a single status for the failures, of their [`MultiStatus::code`] and
+/// with their JSON as the detail, the id of the first failure kept
+impl From<MultiStatus> for Status {
+    #[track_caller]
+    fn from(ms: MultiStatus) -> Self {
+        let id = ms
+            .iter()
+            .next()
+            .map(|s| s.id().to_string())
+            .unwrap_or_default();
+        Status::new(id, ms.to_string(), ms.code())
+    }
+}
 
 impl From<std::io::Error> for Status {
+    #[track_caller]
     fn from(err: std::io::Error) -> Self {
         use std::io::ErrorKind;
         let code = match err.kind() {
@@ -362,11 +894,72 @@ impl From<std::io::Error> for Status {
             ErrorKind::UnexpectedEof => Code::ServiceUnavailable,
             _ => Code::Unknown,
         };
-        Status::new("", err.to_string().as_str(), code)
+        Status::new("", err.to_string().as_str(), code).with_source(err)
+    }
+}
+
+impl From<serde_json::Error> for Status {
+    #[track_caller]
+    fn from(err: serde_json::Error) -> Self {
+        use serde_json::error::Category;
+        let code = match err.classify() {
+            Category::Io => Code::InternalServerError,
+            Category::Syntax | Category::Data | Category::Eof => Code::BadRequest,
+        };
+        Status::new("", err.to_string().as_str(), code).with_source(err)
+    }
+}
+
+/// a timeout of tokio, as the deadline of a call to another service
+impl From<tokio::time::error::Elapsed> for Status {
+    #[track_caller]
+    fn from(err: tokio::time::error::Elapsed) -> Self {
+        Status::new("", err.to_string().as_str(), Code::GatewayTimeout).with_source(err)
+    }
+}
+
+#[cfg(feature = "etcd")]
+impl From<etcd_client::Error> for Status {
+    #[track_caller]
+    fn from(err: etcd_client::Error) -> Self {
+        use etcd_client::Error;
+        let err = match err {
+            Error::IoError(e) => return Status::from(e),
+            Error::GRpcStatus(s) => return Status::from(s),
+            err => err,
+        };
+        let code = match err {
+            Error::InvalidArgs(_)
+            | Error::InvalidUri(_)
+            | Error::Utf8Error(_)
+            | Error::InvalidHeaderValue(_) => Code::BadRequest,
+            _ => Code::ServiceUnavailable,
+        };
+        Status::new("", err.to_string().as_str(), code).with_source(err)
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl From<hyper::Error> for Status {
+    #[track_caller]
+    fn from(err: hyper::Error) -> Self {
+        let code = if err.is_parse() {
+            Code::BadRequest
+        } else if err.is_timeout() {
+            Code::GatewayTimeout
+        } else if err.is_connect() || err.is_closed() || err.is_incomplete_message() {
+            Code::BadGateway
+        } else if err.is_canceled() {
+            Code::ServiceUnavailable
+        } else {
+            Code::InternalServerError
+        };
+        Status::new("", err.to_string().as_str(), code).with_source(err)
     }
 }
 
 impl From<tonic::Status> for Status {
+    #[track_caller]
     fn from(s: tonic::Status) -> Self {
         let code = match s.code() {
             tonic::Code::Ok => Code::Ok,
@@ -388,15 +981,36 @@ impl From<tonic::Status> for Status {
             tonic::Code::Unauthenticated => Code::Unauthorized,
         };
 
-        Status::new("", s.message(), code)
+        let mut status = Status::new("", s.message(), code);
+        status.details.payload = serde_json::from_slice(s.details()).ok();
+        for entry in s.metadata().iter() {
+            if let tonic::metadata::KeyAndValueRef::Ascii(key, value) = entry {
+                if let Ok(value) = value.to_str() {
+                    status = status.with_metadata(key.as_str(), value);
+                }
+            }
+        }
+        status
     }
 }
 
-impl Into<tonic::Status> for Status {
-    fn into(self) -> tonic::Status {
-        let code = match self.code() {
+impl From<Status> for tonic::Status {
+    fn from(s: Status) -> Self {
+        let code = match s.code() {
             Code::Unknown => tonic::Code::Unknown,
-            Code::Continue | Code::Ok => tonic::Code::Ok,
+            Code::Continue
+            | Code::Ok
+            | Code::Created
+            | Code::Accepted
+            | Code::NoContent
+            | Code::PartialContent
+            | Code::NotModified => tonic::Code::Ok,
+            Code::MultipleChoices
+            | Code::MovedPermanently
+            | Code::Found
+            | Code::SeeOther
+            | Code::TemporaryRedirect
+            | Code::PermanentRedirect => tonic::Code::Unknown,
             Code::BadRequest => tonic::Code::InvalidArgument,
             Code::Unauthorized => tonic::Code::Unauthenticated,
             Code::Forbidden => tonic::Code::PermissionDenied,
@@ -405,14 +1019,57 @@ impl Into<tonic::Status> for Status {
             Code::RequestTimeout => tonic::Code::Cancelled,
             Code::Conflict => tonic::Code::DataLoss,
             Code::PreconditionFailed => tonic::Code::FailedPrecondition,
-            Code::TooManyRequests => tonic::Code::ResourceExhausted,
+            Code::PayloadTooLarge | Code::TooManyRequests => tonic::Code::ResourceExhausted,
+            Code::UnsupportedMediaType | Code::UnprocessableEntity => tonic::Code::InvalidArgument,
+            Code::ClientClosedRequest => tonic::Code::Cancelled,
             Code::InternalServerError => tonic::Code::Internal,
             Code::NotImplementedError => tonic::Code::Unimplemented,
             Code::BadGateway => tonic::Code::Internal,
             Code::ServiceUnavailable => tonic::Code::Unavailable,
             Code::GatewayTimeout => tonic::Code::DeadlineExceeded,
         };
-        tonic::Status::new(code, self.detail())
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        for (key, value) in s.metadata() {
+            let key = tonic::metadata::AsciiMetadataKey::from_bytes(key.as_bytes());
+            if let (Ok(key), Ok(value)) = (key, value.parse()) {
+                metadata.insert(key, value);
+            }
+        }
+        let details = match &s.details.payload {
+            Some(payload) => serde_json::to_vec(payload).unwrap_or_default(),
+            None => vec![],
+        };
+        tonic::Status::with_details_and_metadata(code, s.detail(), details.into(), metadata)
+    }
+}
+
+impl From<&Status> for http::StatusCode {
+    fn from(s: &Status) -> Self {
+        http::StatusCode::from_u16(s.code() as u16)
+            .unwrap_or(http::StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+/// the response of an http front-end failing with the status: the JSON of
+/// the status as its body and its code in the `vine-status` header, kept
+/// when the http status is a fallback as for [`Code::Unknown`]
+impl From<Status> for http::Response<String> {
+    fn from(s: Status) -> Self {
+        let mut resp = http::Response::new(s.to_string());
+        *resp.status_mut() = http::StatusCode::from(&s);
+        let headers = resp.headers_mut();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static("application/json"),
+        );
+        headers.insert(STATUS_HEADER, http::HeaderValue::from(s.code() as i32));
+        for (key, value) in s.metadata() {
+            let key = http::header::HeaderName::from_bytes(key.as_bytes());
+            if let (Ok(key), Ok(value)) = (key, http::HeaderValue::from_str(value)) {
+                headers.insert(key, value);
+            }
+        }
+        resp
     }
 }
 
@@ -454,11 +1111,63 @@ macro_rules! bail {
     };
 }
 
+/// makes a [`Status`] of the [`Code`] named first with the detail formatted
+/// from the rest, its position set to where the macro is.
+///
+/// ```rust
+/// # use errors::{status, Code};
+/// let id = 7;
+/// let s = status!(NotFound, "user {} not found", id);
+/// assert_eq!(s.code(), Code::NotFound);
+/// assert_eq!(s.detail(), "user 7 not found");
+/// ```
+#[macro_export]
+macro_rules! status {
+    ($code:ident, $($arg:tt)+) => {
+        $crate::Status::new(
+            ::std::string::String::new(),
+            ::std::format!($($arg)+),
+            $crate::Code::$code,
+        )
+        .with_position(::std::concat!(::std::file!(), ":", ::std::line!()))
+    };
+}
+
+/// returns the status early with its position set to where the macro is
+/// unless the condition holds. The status is a constructor of [`Status`]
+/// called by its name, as `bad_request(..)`, or any expression of a status.
+/// It is converted into the error of the function, a [`Status`] or an
+/// [`anyhow::Error`].
+///
+/// ```rust
+/// # use errors::{ensure, Status};
+/// fn register(name: &str) -> Result<(), Status> {
+///     ensure!(!name.is_empty(), bad_request("io.vine.api", "name required"));
+///     Ok(())
+/// }
+/// assert_eq!(register("").unwrap_err().detail(), "name required");
+/// ```
+#[macro_export]
+macro_rules! ensure {
+    ($cond:expr, $ctor:ident($($arg:tt)*) $(,)?) => {
+        $crate::ensure!($cond, $crate::Status::$ctor($($arg)*))
+    };
+    ($cond:expr, $status:expr $(,)?) => {
+        if !$cond {
+            let status: $crate::Status = $status;
+            return ::std::result::Result::Err(::std::convert::From::from(
+                status.with_position(::std::concat!(::std::file!(), ":", ::std::line!())),
+            ));
+        }
+    };
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::bail;
+    use std::{convert::TryFrom, time::Duration};
+
     use crate::Result;
-    use crate::{Code, Status};
+    use crate::{Code, MultiStatus, Status};
 
     #[test]
     fn test_new() {
@@ -489,7 +1198,7 @@ mod tests {
             return;
         }
 
-        assert!(false)
+        panic!("from_str failed")
     }
 
     #[test]
@@ -509,6 +1218,309 @@ mod tests {
         assert_eq!(ts.code(), tonic::Code::Internal);
     }
 
+    #[test]
+    fn test_metadata() -> Result<()> {
+        let s = Status::too_many_requests("io.vine", "slow down")
+            .with_metadata("retry-after", "30")
+            .with_metadata("Invalid Key", "dropped");
+        assert_eq!(s.metadata()["retry-after"], "30");
+
+        let json = s.to_string();
+        let out = Status::from_str(json)?;
+        assert_eq!(out, s);
+        // the statuses written before the metadata still parse
+        let out = Status::from_str(
+            r#"{"id":"io.vine","code":500,"detail":"","status":"","position":""}"#,
+        )?;
+        assert!(out.metadata().is_empty());
+
+        let ts: tonic::Status = s.into();
+        assert_eq!(ts.metadata().get("retry-after").unwrap(), "30");
+        assert_eq!(ts.metadata().len(), 1);
+        let s = Status::from(ts);
+        assert_eq!(s.metadata().len(), 1);
+        assert_eq!(s.metadata()["retry-after"], "30");
+        Ok(())
+    }
+
+    #[test]
+    fn test_detail_message() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Quota {
+            limit: u32,
+            used: u32,
+        }
+
+        let s =
+            Status::too_many_requests("io.vine.api", "quota exceeded").with_detail_message(Quota {
+                limit: 10,
+                used: 10,
+            });
+        assert_eq!(
+            s.detail_as::<Quota>(),
+            Some(Quota {
+                limit: 10,
+                used: 10
+            })
+        );
+        assert_eq!(s.detail_as::<Vec<String>>(), None);
+        assert_eq!(Status::bad_request("", "").detail_as::<Quota>(), None);
+
+        // in the JSON and through tonic
+        let back = Status::from_str(s.to_string()).unwrap();
+        assert_eq!(back.detail_as::<Quota>(), s.detail_as::<Quota>());
+        let back = Status::from(tonic::Status::from(s));
+        assert_eq!(
+            back.detail_as::<Quota>(),
+            Some(Quota {
+                limit: 10,
+                used: 10
+            })
+        );
+
+        #[cfg(feature = "prost")]
+        {
+            let any = prost_types::Any {
+                type_url: "type.googleapis.com/vine.Quota".to_string(),
+                value: vec![8, 10, 16, 10],
+            };
+            let s = Status::conflict("", "").with_detail_any(any.clone());
+            assert_eq!(s.detail_any(), Some(any));
+        }
+    }
+
+    #[test]
+    fn test_code() {
+        assert_eq!(Code::from(100), Code::Continue);
+        assert_eq!(Code::from(201), Code::Created);
+        assert_eq!(Code::from(308), Code::PermanentRedirect);
+        assert_eq!(Code::from(422), Code::UnprocessableEntity);
+        assert_eq!(Code::from(499), Code::ClientClosedRequest);
+        assert_eq!(Code::from(418), Code::Unknown);
+        assert_eq!(i32::from(Code::NoContent), 204);
+
+        assert!(Code::Accepted.is_success());
+        assert!(!Code::NotModified.is_success());
+        assert!(Code::PayloadTooLarge.is_client_error());
+        assert!(!Code::BadGateway.is_client_error());
+        assert!(Code::GatewayTimeout.is_server_error());
+        assert!(!Code::Unknown.is_server_error());
+        assert_eq!(
+            Code::UnsupportedMediaType.to_string(),
+            "Unsupported Media Type"
+        );
+    }
+
+    #[test]
+    fn test_position() {
+        let line = line!() + 1;
+        let s = Status::not_found("io.vine", "no node");
+        assert_eq!(s.position(), format!("{}:{}", file!(), line));
+
+        fn read() -> std::result::Result<(), Status> {
+            std::fs::read("/no/such/file")?;
+            Ok(())
+        }
+        // the position of the `?`
+        let s = read().unwrap_err();
+        assert_eq!(s.code(), Code::NotFound);
+        assert!(s.position().starts_with(file!()), "{}", s.position());
+
+        let line = line!() + 1;
+        let s = s.with_pos();
+        assert_eq!(s.position(), format!("{}:{}", file!(), line));
+    }
+
+    #[test]
+    fn test_macros() -> Result<()> {
+        let s = status!(NotFound, "user {} not found", 7);
+        assert_eq!(s.code(), Code::NotFound);
+        assert_eq!(s.detail(), "user 7 not found");
+        assert!(s.position().starts_with(file!()));
+
+        fn check(name: &str) -> std::result::Result<(), Status> {
+            ensure!(
+                !name.is_empty(),
+                bad_request("io.vine.api", "name required")
+            );
+            ensure!(name != "root", status!(Forbidden, "{} is reserved", name));
+            Ok(())
+        }
+        assert!(check("ann").is_ok());
+        let s = check("").unwrap_err();
+        assert_eq!(s.code(), Code::BadRequest);
+        assert!(s.position().starts_with(file!()));
+        assert_eq!(check("root").unwrap_err().detail(), "root is reserved");
+
+        // into an anyhow error too
+        fn any(ok: bool) -> Result<()> {
+            ensure!(ok, timeout("io.vine.api", "too slow"));
+            Ok(())
+        }
+        let e = any(false).unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<Status>().unwrap().code(),
+            Code::RequestTimeout
+        );
+        any(true)
+    }
+
+    #[test]
+    fn test_violations() {
+        let s = Status::bad_request_with_fields("io.vine.api", vec![("email", "invalid format")])
+            .with_violation("name", "required");
+        assert_eq!(s.code(), Code::BadRequest);
+        assert_eq!(s.detail(), "email: invalid format");
+        assert_eq!(s.violations().len(), 2);
+
+        let json = s.to_string();
+        assert!(json.contains(r#""violations":[{"field":"email","description":"invalid format"}"#));
+        assert_eq!(Status::from_str(json).unwrap(), s);
+        assert!(!Status::bad_request("", "")
+            .to_string()
+            .contains("violations"));
+    }
+
+    #[test]
+    fn test_multi_status() {
+        let mut ms = MultiStatus::new();
+        assert_eq!(ms.code(), Code::Ok);
+        assert!(ms.clone().into_result().is_ok());
+
+        ms.push(Status::not_found("io.vine.registry", "a not found"));
+        ms.push(Status::not_found("io.vine.registry", "b not found"));
+        assert_eq!(ms.code(), Code::NotFound);
+
+        ms.extend(vec![Status::conflict("io.vine.registry", "c exists")]);
+        assert_eq!(ms.code(), Code::BadRequest);
+
+        let ms: MultiStatus = ms
+            .into_iter()
+            .chain(std::iter::once(Status::service_unavailable(
+                "",
+                "etcd down",
+            )))
+            .collect();
+        assert_eq!(ms.len(), 4);
+        assert_eq!(ms.code(), Code::InternalServerError);
+
+        let s = Status::from(ms.clone());
+        assert_eq!(s.code(), Code::InternalServerError);
+        assert_eq!(s.id(), "io.vine.registry");
+        let back: MultiStatus = serde_json::from_str(s.detail()).unwrap();
+        assert_eq!(back, ms);
+        assert_eq!(ms.into_result().unwrap_err().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_from_errors() {
+        let s = Status::from(serde_json::from_str::<Status>("{").unwrap_err());
+        assert_eq!(s.code(), Code::BadRequest);
+        assert!(std::error::Error::source(&s).is_some());
+
+        let elapsed = tokio::time::timeout(Duration::from_millis(1), std::future::pending::<()>())
+            .await
+            .unwrap_err();
+        assert_eq!(Status::from(elapsed).code(), Code::GatewayTimeout);
+
+        #[cfg(feature = "etcd")]
+        {
+            let s = Status::from(etcd_client::Error::InvalidArgs("no key".to_string()));
+            assert_eq!(s.code(), Code::BadRequest);
+            let s = Status::from(etcd_client::Error::GRpcStatus(tonic::Status::unavailable(
+                "etcd down",
+            )));
+            assert_eq!(s.code(), Code::ServiceUnavailable);
+            assert_eq!(s.detail(), "etcd down");
+        }
+    }
+
+    #[test]
+    fn test_backtrace() {
+        crate::set_backtrace(true);
+        let s = Status::internal_server_error("io.vine", "db down")
+            .with_source(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+        crate::set_backtrace(false);
+        assert!(s.backtrace().is_some());
+        assert!(std::error::Error::source(&s).is_some());
+        assert!(s.clone().backtrace().is_some());
+
+        assert!(Status::internal_server_error("io.vine", "db down")
+            .backtrace()
+            .is_none());
+    }
+
+    #[test]
+    fn test_source() {
+        use std::error::Error;
+
+        let s = Status::from(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "etcd lease keepalive",
+        ));
+        let source = s.source().expect("the io error is kept");
+        let io = source.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(io.kind(), std::io::ErrorKind::TimedOut);
+
+        // the clones keep it, the comparison and the JSON ignore it
+        assert!(s.clone().source().is_some());
+        assert_eq!(
+            s,
+            Status::timeout("", "etcd lease keepalive").with_position(s.position())
+        );
+        let back = Status::from_str(s.to_string()).unwrap();
+        assert!(back.source().is_none());
+        assert!(Status::bad_request("", "").source().is_none());
+    }
+
+    #[test]
+    fn test_http() {
+        let s = Status::not_found("io.vine", "no such service")
+            .with_retry_after(Duration::from_secs(3));
+        assert_eq!(http::StatusCode::from(&s), http::StatusCode::NOT_FOUND);
+        assert_eq!(
+            http::StatusCode::from(&Status::unknown("", "")),
+            http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        assert_eq!(
+            Code::try_from(http::StatusCode::TOO_MANY_REQUESTS).unwrap(),
+            Code::TooManyRequests
+        );
+        assert_eq!(
+            Code::try_from(http::StatusCode::CONTINUE).unwrap(),
+            Code::Continue
+        );
+        assert!(Code::try_from(http::StatusCode::IM_A_TEAPOT).is_err());
+
+        let resp = http::Response::<String>::from(s.clone());
+        assert_eq!(resp.status(), http::StatusCode::NOT_FOUND);
+        assert_eq!(resp.headers()[crate::STATUS_HEADER], "404");
+        assert_eq!(resp.headers()["retry-after"], "3");
+        assert_eq!(Status::from_str(resp.body().as_str()).unwrap(), s);
+    }
+
+    #[test]
+    fn test_retry() {
+        assert!(Status::service_unavailable("io.vine", "").is_retryable());
+        assert!(Status::timeout("io.vine", "").is_retryable());
+        assert!(!Status::bad_request("io.vine", "").is_retryable());
+        assert!(!Status::internal_server_error("io.vine", "").is_retryable());
+
+        let s = Status::too_many_requests("io.vine", "slow down")
+            .with_retry_after(Duration::from_millis(1500));
+        assert_eq!(s.retry_after(), Some(Duration::from_secs(2)));
+        assert_eq!(Status::too_many_requests("", "").retry_after(), None);
+
+        // from the metadata of a rate limited tonic response
+        let mut ts = tonic::Status::new(tonic::Code::ResourceExhausted, "slow down");
+        ts.metadata_mut()
+            .insert("retry-after", "30".parse().unwrap());
+        let s = Status::from(ts);
+        assert!(s.is_retryable());
+        assert_eq!(s.retry_after(), Some(Duration::from_secs(30)));
+    }
+
     fn bail() -> Result<()> {
         bail!("bail error");
     }
@@ -529,10 +1541,11 @@ mod tests {
         let ee = e.err().unwrap();
         assert_eq!(ee.to_string(), "true".to_string());
 
-        let se: Result<()> = { Err(err!(Status::new("io.vine", "custom", Code::BadRequest))) };
+        let s = Status::new("io.vine", "custom", Code::BadRequest);
+        let se: Result<()> = { Err(err!(s.clone())) };
         assert!(se.is_err());
         let see = se.err().unwrap();
-        assert_eq!(see.to_string(), Status::new("io.vine", "custom", Code::BadRequest).to_string());
+        assert_eq!(see.to_string(), s.to_string());
 
         let b = bail();
         assert!(b.is_err());
@@ -540,6 +1553,8 @@ mod tests {
 
         let bb = bail_status();
         assert!(bb.is_err());
-        assert_eq!(bb.err().unwrap().to_string(), Status::new("io.vine", "custom", Code::InternalServerError).to_string());
+        let bs = bb.err().unwrap().downcast::<Status>().unwrap();
+        assert_eq!(bs.code(), Code::InternalServerError);
+        assert_eq!(bs.detail(), "custom");
     }
 }
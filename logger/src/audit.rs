@@ -0,0 +1,272 @@
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, Write},
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use chrono::prelude::*;
+use errors::Result;
+use once_cell::sync::OnceCell;
+use ring::{digest, hmac};
+
+/// the outcome of an audited action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure,
+    /// refused for the lack of a permission
+    Denied,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+            Outcome::Denied => "denied",
+        })
+    }
+}
+
+/// the sequence number and the hash of the last entry written
+#[derive(Debug, Default)]
+struct Chain {
+    seq: u64,
+    hash: String,
+}
+
+/// AuditLogger writes the security relevant events, as the failures to
+/// authenticate or the operations of the admins, apart from the entries of
+/// the application. An entry is a JSON object per line with its `seq`, one
+/// more than the entry before it, the hash of that entry as `prev` and its
+/// own `hash`, so an entry removed, reordered or changed breaks the chain
+/// [`verify`] checks. With a key the hashes are HMAC-SHA256, which cannot
+/// be made again without the key, else SHA-256.
+///
+/// ```rust,no_run
+/// # use logger::audit::{AuditLogger, Outcome};
+/// let audit = AuditLogger::file("logs/audit.log")?.with_key(b"secret");
+/// audit.audit("admin", "deregister", "io.vine.greeter", Outcome::Success);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct AuditLogger {
+    out: Arc<Mutex<dyn Write + Send>>,
+    key: Option<hmac::Key>,
+    chain: Mutex<Chain>,
+}
+
+impl AuditLogger {
+    /// writes the entries to `out`, the chain starting over
+    pub fn new(out: Arc<Mutex<dyn Write + Send>>) -> Self {
+        AuditLogger {
+            out,
+            key: None,
+            chain: Mutex::new(Chain::default()),
+        }
+    }
+
+    /// appends the entries to the file at `path`, creating it and its
+    /// directory when missing. The chain goes on from the last entry of the
+    /// file.
+    pub fn file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+
+        let mut chain = Chain::default();
+        if let Ok(file) = fs::File::open(path) {
+            let last = io::BufReader::new(file)
+                .lines()
+                .map_while(|l| l.ok())
+                .filter(|l| !l.trim().is_empty())
+                .last();
+            if let Some(last) = last {
+                let entry: serde_json::Value = serde_json::from_str(&last)?;
+                chain.seq = entry["seq"].as_u64().unwrap_or_default();
+                chain.hash = entry["hash"].as_str().unwrap_or_default().to_string();
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(AuditLogger {
+            out: Arc::new(Mutex::new(file)),
+            key: None,
+            chain: Mutex::new(chain),
+        })
+    }
+
+    /// set the key the hashes are HMAC-SHA256 with
+    #[inline]
+    pub fn with_key(mut self, key: &[u8]) -> Self {
+        self.key = Some(hmac::Key::new(hmac::HMAC_SHA256, key));
+        self
+    }
+
+    /// writes that `actor` did `action` on `resource` with `outcome`. The
+    /// entry is flushed before it returns.
+    pub fn audit(&self, actor: &str, action: &str, resource: &str, outcome: Outcome) {
+        let mut chain = match self.chain.lock() {
+            Ok(chain) => chain,
+            Err(e) => e.into_inner(),
+        };
+
+        let mut entry = serde_json::Map::new();
+        entry.insert("seq".to_string(), (chain.seq + 1).into());
+        entry.insert(
+            "timestamp".to_string(),
+            Utc::now()
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+                .into(),
+        );
+        entry.insert("actor".to_string(), actor.into());
+        entry.insert("action".to_string(), action.into());
+        entry.insert("resource".to_string(), resource.into());
+        entry.insert("outcome".to_string(), outcome.to_string().into());
+        entry.insert("prev".to_string(), chain.hash.clone().into());
+        let hash = hash(self.key.as_ref(), &entry);
+        entry.insert("hash".to_string(), hash.clone().into());
+
+        let mut line = serde_json::to_vec(&entry).unwrap_or_default();
+        line.push(b'\n');
+        if let Ok(ref mut writer) = self.out.lock() {
+            if writer.write_all(&line).and_then(|_| writer.flush()).is_ok() {
+                chain.seq += 1;
+                chain.hash = hash;
+            }
+        };
+    }
+}
+
+/// the hex of the hash of the entry without its `hash`
+fn hash(key: Option<&hmac::Key>, entry: &serde_json::Map<String, serde_json::Value>) -> String {
+    let data = serde_json::to_vec(entry).unwrap_or_default();
+    let sum: Vec<u8> = match key {
+        Some(key) => hmac::sign(key, &data).as_ref().to_vec(),
+        None => digest::digest(&digest::SHA256, &data).as_ref().to_vec(),
+    };
+    sum.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// checks the chain of the entries read from `r`, written with `key` if
+/// any, and returns the number of entries. Fails at the first entry not
+/// following the one before it or whose hash is not its own.
+pub fn verify(r: impl BufRead, key: Option<&[u8]>) -> Result<u64> {
+    let key = key.map(|k| hmac::Key::new(hmac::HMAC_SHA256, k));
+    let mut last: Option<(u64, String)> = None;
+    let mut count = 0;
+    for line in r.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut entry: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&line)?;
+        let seq = entry
+            .get("seq")
+            .and_then(|v| v.as_u64())
+            .unwrap_or_default();
+        let claimed = match entry.remove("hash") {
+            Some(serde_json::Value::String(h)) => h,
+            _ => errors::bail!("audit entry {} has no hash", seq),
+        };
+        if let Some((prev_seq, prev_hash)) = &last {
+            let prev = entry.get("prev").and_then(|v| v.as_str());
+            if seq != prev_seq + 1 || prev != Some(prev_hash.as_str()) {
+                errors::bail!("audit entry {} does not follow entry {}", seq, prev_seq);
+            }
+        }
+        if hash(key.as_ref(), &entry) != claimed {
+            errors::bail!("audit entry {} was changed", seq);
+        }
+        last = Some((seq, claimed));
+        count += 1;
+    }
+    Ok(count)
+}
+
+static AUDIT_LOGGER: OnceCell<RwLock<Arc<AuditLogger>>> = OnceCell::new();
+
+fn global() -> &'static RwLock<Arc<AuditLogger>> {
+    AUDIT_LOGGER.get_or_init(|| {
+        RwLock::new(Arc::new(AuditLogger::new(Arc::new(Mutex::new(
+            io::stderr(),
+        )))))
+    })
+}
+
+/// the global audit logger, writing to stderr unless set
+pub fn audit_logger() -> Arc<AuditLogger> {
+    match global().read() {
+        Ok(l) => l.clone(),
+        Err(e) => e.into_inner().clone(),
+    }
+}
+
+/// replaces the global audit logger
+pub fn set_audit_logger(val: AuditLogger) -> Result<()> {
+    let mut l = match global().write() {
+        Ok(l) => l,
+        Err(e) => e.into_inner(),
+    };
+    *l = Arc::new(val);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::Cursor,
+        sync::{Arc, Mutex},
+    };
+
+    use errors::Result;
+
+    use super::{verify, AuditLogger, Outcome};
+
+    #[test]
+    fn test_chain() -> Result<()> {
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let audit = AuditLogger::new(out.clone()).with_key(b"secret");
+        audit.audit("ann", "login", "io.vine.auth", Outcome::Failure);
+        audit.audit("ann", "login", "io.vine.auth", Outcome::Success);
+        audit.audit("ann", "deregister", "io.vine.greeter", Outcome::Denied);
+
+        let out = String::from_utf8(out.lock().unwrap().clone())?;
+        let entry: serde_json::Value = serde_json::from_str(out.lines().next().unwrap())?;
+        assert_eq!(entry["seq"], 1);
+        assert_eq!(entry["outcome"], "failure");
+        assert_eq!(entry["prev"], "");
+        assert_eq!(verify(Cursor::new(&out), Some(b"secret"))?, 3);
+
+        // the key is needed to make the hashes again
+        assert!(verify(Cursor::new(&out), None).is_err());
+        // an entry removed
+        let lines: Vec<&str> = out.lines().collect();
+        let removed = format!("{}\n{}\n", lines[0], lines[2]);
+        assert!(verify(Cursor::new(removed), Some(b"secret")).is_err());
+        // an entry changed
+        let changed = out.replacen("\"denied\"", "\"success\"", 1);
+        assert!(verify(Cursor::new(changed), Some(b"secret")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vine-audit-{}", std::process::id()));
+        let path = dir.join("audit.log");
+        AuditLogger::file(&path)?.audit("ann", "login", "io.vine.auth", Outcome::Success);
+        // the chain goes on after a restart
+        AuditLogger::file(&path)?.audit("ann", "logout", "io.vine.auth", Outcome::Success);
+
+        let file = std::io::BufReader::new(std::fs::File::open(&path)?);
+        assert_eq!(verify(file, None)?, 2);
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use errors::Result;
+
+use crate::{global_logger, helper::Helper, level::Level, value::Value, Logger};
+
+/// whether the bridge is the logger of the `log` crate
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// the implement of [`log::Log`] forwarding the records to the global logger
+struct Bridge;
+
+impl Bridge {
+    fn level(level: log::Level) -> Level {
+        match level {
+            log::Level::Error => Level::ErrorLevel,
+            log::Level::Warn => Level::WarnLevel,
+            log::Level::Info => Level::InfoLevel,
+            log::Level::Debug => Level::DebugLevel,
+            log::Level::Trace => Level::TraceLevel,
+        }
+    }
+}
+
+impl log::Log for Bridge {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        global_logger()
+            .options()
+            .enabled(Some(metadata.target()), &Bridge::level(metadata.level()))
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        forward(&global_logger(), record);
+    }
+
+    fn flush(&self) {
+        global_logger().flush();
+    }
+}
+
+/// logs `record` with its target and the file it was logged in as fields,
+/// along with the fields of `l`
+fn forward(l: &Helper, record: &log::Record<'_>) {
+    let mut fields = HashMap::new();
+    if let Some(file) = record.file() {
+        let file = match record.line() {
+            Some(line) => format!("{}:{}", file, line),
+            None => file.to_string(),
+        };
+        fields.insert("file".to_string(), Value::from(file));
+    }
+    l.log_fields(
+        Bridge::level(record.level()),
+        record.target(),
+        fields,
+        record.args().to_string().as_bytes(),
+    );
+}
+
+/// makes the global logger the logger of the `log` crate, so the records
+/// of dependencies logging with `log::info!` and the like, such as
+/// etcd-client or hyper, are written along with the entries of vine. Fails
+/// when another `log` logger is installed already.
+///
+/// ```rust
+/// logger::init_log_bridge()?;
+/// log::info!(target: "hyper", "connected");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn init_log_bridge() -> Result<()> {
+    log::set_boxed_logger(Box::new(Bridge))
+        .map_err(|e| errors::err!("init log bridge failed: {}", e))?;
+    INSTALLED.store(true, Ordering::Relaxed);
+    set_max_level(&global_logger().options().max_level());
+    Ok(())
+}
+
+/// makes `level` the most verbose one the `log` crate hands the bridge,
+/// when it is installed
+pub(crate) fn set_max_level(level: &Level) {
+    if !INSTALLED.load(Ordering::Relaxed) {
+        return;
+    }
+    log::set_max_level(match level {
+        Level::TraceLevel => log::LevelFilter::Trace,
+        Level::DebugLevel => log::LevelFilter::Debug,
+        Level::InfoLevel => log::LevelFilter::Info,
+        Level::WarnLevel => log::LevelFilter::Warn,
+        Level::ErrorLevel | Level::FatalLevel => log::LevelFilter::Error,
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use errors::Result;
+
+    use super::{forward, init_log_bridge};
+    use crate::{helper::Helper, new_logger, options::Options, Logger};
+
+    #[test]
+    fn test_forward() -> Result<()> {
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let mut fields = HashMap::new();
+        fields.insert("a".to_string(), "b".into());
+        let opts = Options::new().with_fields(fields).with_out(out.clone());
+        let l = Helper::new(new_logger(Some(opts))?);
+
+        forward(
+            &l,
+            &log::Record::builder()
+                .args(format_args!("connected to {}", "etcd"))
+                .level(log::Level::Warn)
+                .target("etcd_client")
+                .file(Some("src/client.rs"))
+                .line(Some(7))
+                .build(),
+        );
+        forward(
+            &l,
+            &log::Record::builder()
+                .args(format_args!("hidden"))
+                .level(log::Level::Debug)
+                .build(),
+        );
+
+        let out = String::from_utf8(out.lock().unwrap().clone())?;
+        assert!(out.ends_with(
+            " a=b file=src/client.rs:7 level=warn target=etcd_client connected to etcd\n"
+        ));
+        assert_eq!(out.lines().count(), 1);
+        // the fields of the record are for the entry alone
+        assert_eq!(l.options().fields().len(), 1);
+
+        init_log_bridge()?;
+        assert!(init_log_bridge().is_err());
+        log::info!(target: "hyper", "bridged");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use std::cell::RefCell;
+
+use bytes::BytesMut;
+
+/// the capacity the buffer of a thread starts with, enough for most entries
+const CAPACITY: usize = 512;
+
+/// the capacity over which the buffer is let go after an entry, so a large
+/// entry does not keep its memory for the life of the thread
+const MAX_CAPACITY: usize = 64 * 1024;
+
+thread_local! {
+    /// the buffer the entries of the thread are formatted in, kept between
+    /// the entries rather than allocated for each
+    static BUFFER: RefCell<BytesMut> = RefCell::new(BytesMut::with_capacity(CAPACITY));
+}
+
+/// calls `f` with the empty buffer of the thread. A fresh buffer is used
+/// when the one of the thread is in use, as by an entry logged while the
+/// output is written, or already dropped with the thread.
+pub(crate) fn with_buffer<R>(f: impl FnOnce(&mut BytesMut) -> R) -> R {
+    let mut f = Some(f);
+    let pooled = BUFFER.try_with(|b| {
+        let mut buf = b.try_borrow_mut().ok()?;
+        buf.clear();
+        let r = (f.take()?)(&mut buf);
+        if buf.capacity() > MAX_CAPACITY {
+            *buf = BytesMut::with_capacity(CAPACITY);
+        }
+        Some(r)
+    });
+    match (pooled, f) {
+        (Ok(Some(r)), _) => r,
+        (_, Some(f)) => f(&mut BytesMut::new()),
+        // f is taken only when it returned
+        (_, None) => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BufMut;
+
+    use super::{with_buffer, MAX_CAPACITY};
+
+    #[test]
+    fn test_with_buffer() {
+        let first = with_buffer(|buf| {
+            buf.put_slice(b"entry");
+            buf.as_ptr() as usize
+        });
+        // the buffer is reused, empty
+        let second = with_buffer(|buf| {
+            assert!(buf.is_empty());
+            // nested, as by a writer logging
+            with_buffer(|inner| assert_ne!(inner.as_ptr(), buf.as_ptr()));
+            buf.as_ptr() as usize
+        });
+        assert_eq!(first, second);
+
+        with_buffer(|buf| buf.put_slice(&vec![0u8; MAX_CAPACITY + 1]));
+        with_buffer(|buf| assert!(buf.capacity() <= MAX_CAPACITY));
+    }
+}
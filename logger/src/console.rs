@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use chrono::prelude::*;
+use errors::Result;
+
+use crate::{
+    buffer::with_buffer, call_hooks, level::Level, options::Options, text_entry, text_time,
+    value::Value, Logger,
+};
+
+// the functions the host of the module provides under `vine`, as a page
+// does with
+//
+// ```js
+// const vine = {
+//   console: (method, ptr, len) => {
+//     const text = new TextDecoder().decode(new Uint8Array(memory.buffer, ptr, len));
+//     [console.log, console.warn, console.error][method](text);
+//   },
+//   now_millis: () => Date.now(),
+// };
+// WebAssembly.instantiate(module, { vine });
+// ```
+#[link(wasm_import_module = "vine")]
+extern "C" {
+    /// writes the utf-8 text at `ptr` with `console.log` for 0,
+    /// `console.warn` for 1 and `console.error` for 2
+    fn console(method: u32, ptr: *const u8, len: usize);
+
+    /// the milliseconds since the epoch, as `Date.now()`
+    fn now_millis() -> f64;
+}
+
+/// the time of the host in UTC, the clock of std panics on wasm
+pub(crate) fn now() -> DateTime<Local> {
+    let millis = unsafe { now_millis() } as i64;
+    let utc = NaiveDateTime::from_timestamp(
+        millis.div_euclid(1000),
+        (millis.rem_euclid(1000) * 1_000_000) as u32,
+    );
+    DateTime::from_utc(utc, FixedOffset::east(0))
+}
+
+/// the implement of [`Logger`] for vine compiled to wasm, writing the text
+/// entries to the console of the browser: the warn entries with
+/// `console.warn`, the error and fatal ones with `console.error` and the
+/// others with `console.log`. The output of the options is not used.
+#[derive(Clone)]
+pub struct ConsoleLogger {
+    opts: Options,
+}
+
+impl ConsoleLogger {
+    pub fn new(opts: Option<Options>) -> Self {
+        ConsoleLogger {
+            opts: opts.unwrap_or_default(),
+        }
+    }
+}
+
+impl Logger for ConsoleLogger {
+    fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.opts = opt.unwrap_or_default();
+        Ok(())
+    }
+
+    fn options(&self) -> Options {
+        self.opts.clone()
+    }
+
+    fn fields(&mut self, fields: HashMap<String, Value>) {
+        self.opts = self.opts.clone().with_fields(fields);
+    }
+
+    fn log(&self, level: Level, arg: &[u8]) {
+        self.log_with(level, &HashMap::new(), arg)
+    }
+
+    fn log_with(&self, level: Level, with: &HashMap<String, Value>, arg: &[u8]) {
+        let mut fields = match self.opts.entry_fields(&level, with) {
+            Some(fields) => fields,
+            None => return,
+        };
+        fields.insert("level".to_string(), level.to_string().into());
+
+        let local = now();
+        call_hooks(&self.opts, &level, local, &fields, arg);
+
+        let method = match level {
+            Level::WarnLevel => 1,
+            Level::ErrorLevel | Level::FatalLevel => 2,
+            _ => 0,
+        };
+        with_buffer(|entry| {
+            text_time(entry, &self.opts, local);
+            text_entry(entry, &fields, arg, false);
+            let text = entry.strip_suffix(b"\n").unwrap_or(&entry[..]);
+            unsafe { console(method, text.as_ptr(), text.len()) };
+        })
+    }
+
+    fn flush(&self) {}
+
+    fn string(&self) -> &'static str {
+        "console"
+    }
+}
@@ -0,0 +1,128 @@
+use std::env;
+
+use errors::{err, Result};
+
+use crate::level::Level;
+
+/// the variable [`Filter::from_env`] reads the directives from
+pub const ENV_KEY: &str = "VINE_LOG";
+
+/// Filter picks the level of an entry by the `target` it is logged from,
+/// the module path for the macros. It is parsed from comma separated
+/// directives, each a level for the entries of every target or a
+/// `target=level` for the entries of the target and its submodules, the
+/// longest target matching winning.
+///
+/// ```rust
+/// # use logger::{filter::Filter, level::Level};
+/// let filter = Filter::parse("info,registry=debug,etcd_client=warn")?;
+/// assert_eq!(filter.level(Some("registry::etcd")), Some(Level::DebugLevel));
+/// assert_eq!(filter.level(Some("broker")), Some(Level::InfoLevel));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    default: Option<Level>,
+    /// the directives of targets, the longest target first
+    directives: Vec<(String, Level)>,
+}
+
+impl Filter {
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut filter = Filter::default();
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    if target.is_empty() {
+                        return Err(err!("no target in log directive '{}'", directive));
+                    }
+                    filter.directives.retain(|(t, _)| t.as_str() != target);
+                    filter
+                        .directives
+                        .push((target.to_string(), Level::from(level.trim())?));
+                }
+                None => filter.default = Some(Level::from(directive)?),
+            }
+        }
+        filter
+            .directives
+            .sort_by_key(|(t, _)| std::cmp::Reverse(t.len()));
+        Ok(filter)
+    }
+
+    /// set the level of the entries of the targets no directive is for
+    #[inline]
+    pub fn with_default(mut self, level: Level) -> Self {
+        self.default = Some(level);
+        self
+    }
+
+    /// the filter of the [`ENV_KEY`] variable, `None` when it is unset or
+    /// does not parse
+    pub fn from_env() -> Option<Self> {
+        env::var(ENV_KEY).ok().and_then(|s| Filter::parse(&s).ok())
+    }
+
+    /// the level of the entries of `target`, `None` when no directive is
+    /// for it
+    pub fn level(&self, target: Option<&str>) -> Option<Level> {
+        if let Some(target) = target {
+            for (t, level) in &self.directives {
+                let matched = target
+                    .strip_prefix(t.as_str())
+                    .map(|rest| rest.is_empty() || rest.starts_with("::"))
+                    .unwrap_or(false);
+                if matched {
+                    return Some(level.clone());
+                }
+            }
+        }
+        self.default.clone()
+    }
+
+    /// the most verbose level of the directives
+    pub fn max_level(&self) -> Option<Level> {
+        self.directives
+            .iter()
+            .map(|(_, level)| level)
+            .chain(self.default.iter())
+            .fold(None, |max: Option<&Level>, level| match max {
+                Some(max) if max <= level => Some(max),
+                _ => Some(level),
+            })
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Filter;
+    use crate::level::Level;
+
+    #[test]
+    fn test_parse() {
+        let filter =
+            Filter::parse("warn, registry=debug,registry::etcd=error,etcd_client=trace").unwrap();
+        assert_eq!(filter.level(None), Some(Level::WarnLevel));
+        assert_eq!(filter.level(Some("broker")), Some(Level::WarnLevel));
+        assert_eq!(filter.level(Some("registry")), Some(Level::DebugLevel));
+        assert_eq!(
+            filter.level(Some("registry::mdns")),
+            Some(Level::DebugLevel)
+        );
+        assert_eq!(
+            filter.level(Some("registry::etcd")),
+            Some(Level::ErrorLevel)
+        );
+        assert_eq!(filter.level(Some("registry_x")), Some(Level::WarnLevel));
+        assert_eq!(filter.max_level(), Some(Level::TraceLevel));
+
+        let filter = Filter::parse("codec=debug").unwrap();
+        assert_eq!(filter.level(Some("broker")), None);
+        assert_eq!(filter.max_level(), Some(Level::DebugLevel));
+
+        assert!(Filter::parse("info,codec=loud").is_err());
+        assert!(Filter::parse("=debug").is_err());
+    }
+}
@@ -1,43 +1,53 @@
 use std::{
     collections::HashMap,
     ops::Deref,
+    panic::Location,
     process::exit,
-    sync::{Arc, Mutex},
+    sync::atomic::{AtomicI8, Ordering},
 };
 
-use errors::Result;
+use errors::{Result, Status};
 
-use crate::{level::Level, options::Options, Logger};
+use crate::{
+    filter::Filter,
+    level::Level,
+    options::{Fatal, Options},
+    value::Value,
+    Logger,
+};
 
 /// the implemention of [`Logger`] trait
 ///
 /// ```rust
-/// let l = NewLogger::<String>(Some(Options::new()))?;
-/// let mut helper = Helper::new(l);
-/// helper.debug(format!("debug test").as_bytes());
-/// helper.info(format!("info test").as_bytes());
-/// helper.warn(format!("warn test").as_bytes());
-/// helper.fatal(format!("fatal test").as_bytes());
+/// # use logger::{helper::Helper, new_logger, options::Options};
+/// let l = new_logger(Some(Options::new()))?;
+/// let helper = Helper::new(l);
+/// helper.debug(b"debug test");
+/// helper.info(b"info test");
+/// helper.warn(b"warn test");
+/// helper.error(b"error test");
+/// # Ok::<(), anyhow::Error>(())
 /// ```
-pub struct Helper<T: Into<String> + Clone> {
-    level: Level,
-    log: Box<dyn Logger<T> + Send>,
-    fields: Arc<Mutex<HashMap<String, T>>>,
+pub struct Helper {
+    /// the most verbose level of the options, for the entries above it to
+    /// be dropped before their fields are made
+    level: AtomicI8,
+    log: Box<dyn Logger + Send + Sync>,
+    fields: HashMap<String, Value>,
 }
 
-impl<T> Logger<T> for Helper<T>
-where
-    T: Into<String> + Clone + Send,
-{
-    fn init(&mut self, opt: Option<Options<T>>) -> Result<()> {
-        self.log.init(opt)
+impl Logger for Helper {
+    fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.log.init(opt)?;
+        self.refresh_level();
+        Ok(())
     }
 
-    fn options(&self) -> Options<T> {
+    fn options(&self) -> Options {
         self.log.options()
     }
 
-    fn fields(&mut self, fields: HashMap<String, T>) {
+    fn fields(&mut self, fields: HashMap<String, Value>) {
         self.log.fields(fields)
     }
 
@@ -45,125 +55,418 @@ where
         self.log.log(level, arg)
     }
 
+    fn log_with(&self, level: Level, fields: &HashMap<String, Value>, arg: &[u8]) {
+        self.log.log_with(level, fields, arg)
+    }
+
+    fn flush(&self) {
+        self.log.flush()
+    }
+
     fn string(&self) -> &'static str {
         self.log.string()
     }
 }
 
-impl<T> Deref for Helper<T>
-where
-    T: Into<String> + Clone + Sync + ?Sized,
-{
-    type Target = Box<dyn Logger<T> + Send>;
+impl Deref for Helper {
+    type Target = Box<dyn Logger + Send + Sync>;
 
     fn deref(&self) -> &Self::Target {
         &self.log
     }
 }
 
-impl<T> Helper<T>
-where
-    T: Into<String> + Clone + Send,
-{
+impl Helper {
     #[inline]
-    pub fn new(log: impl Logger<T> + Send + 'static) -> Self {
+    pub fn new(log: impl Logger + Send + Sync + 'static) -> Self {
         Helper {
-            level: log.options().level(),
+            level: AtomicI8::new(log.options().max_level().into()),
             log: Box::new(log),
-            fields: Arc::new(Mutex::new(HashMap::new())),
+            fields: HashMap::new(),
         }
     }
 
-    fn get_fields(&self) -> HashMap<String, T> {
-        if let Ok(m) = self.fields.clone().lock() {
-            return m.clone();
-        };
-        HashMap::new()
+    #[inline]
+    #[track_caller]
+    pub fn trace(&self, arg: &[u8]) {
+        if !self.max_level().enabled(&Level::TraceLevel) {
+            return;
+        }
+        self.log.log_with(
+            Level::TraceLevel,
+            &self.entry_fields(None, HashMap::new()),
+            arg,
+        );
     }
 
     #[inline]
-    pub fn trace(&mut self, arg: &[u8]) {
-        if !self.level.enabled(&Level::TraceLevel) {
+    #[track_caller]
+    pub fn debug(&self, arg: &[u8]) {
+        if !self.max_level().enabled(&Level::DebugLevel) {
             return;
         }
-        self.log.fields(self.get_fields());
-        self.log(Level::TraceLevel, arg);
+        self.log.log_with(
+            Level::DebugLevel,
+            &self.entry_fields(None, HashMap::new()),
+            arg,
+        );
     }
 
     #[inline]
-    pub fn debug(&mut self, arg: &[u8]) {
-        if !self.level.enabled(&Level::DebugLevel) {
+    #[track_caller]
+    pub fn info(&self, arg: &[u8]) {
+        if !self.max_level().enabled(&Level::InfoLevel) {
             return;
         }
-        self.log.fields(self.get_fields());
-        self.log(Level::DebugLevel, arg);
+        self.log.log_with(
+            Level::InfoLevel,
+            &self.entry_fields(None, HashMap::new()),
+            arg,
+        );
     }
 
     #[inline]
-    pub fn info(&mut self, arg: &[u8]) {
-        if !self.level.enabled(&Level::InfoLevel) {
+    #[track_caller]
+    pub fn warn(&self, arg: &[u8]) {
+        if !self.max_level().enabled(&Level::WarnLevel) {
             return;
         }
-        self.log.fields(self.get_fields());
-        self.log(Level::InfoLevel, arg);
+        self.log.log_with(
+            Level::WarnLevel,
+            &self.entry_fields(None, HashMap::new()),
+            arg,
+        );
     }
 
     #[inline]
-    pub fn warn(&mut self, arg: &[u8]) {
-        if !self.level.enabled(&Level::WarnLevel) {
+    #[track_caller]
+    pub fn error(&self, arg: &[u8]) {
+        if !self.max_level().enabled(&Level::ErrorLevel) {
             return;
         }
-        self.log.fields(self.get_fields());
-        self.log(Level::WarnLevel, arg);
+        self.log.log_with(
+            Level::ErrorLevel,
+            &self.entry_fields(None, HashMap::new()),
+            arg,
+        );
     }
 
     #[inline]
-    pub fn error(&mut self, arg: &[u8]) {
-        if !self.level.enabled(&Level::ErrorLevel) {
+    #[track_caller]
+    pub fn fatal(&self, arg: &[u8]) {
+        if !self.max_level().enabled(&Level::FatalLevel) {
             return;
         }
-        self.log.fields(self.get_fields());
-        self.log(Level::ErrorLevel, arg);
+        self.log.log_with(
+            Level::FatalLevel,
+            &self.entry_fields(None, HashMap::new()),
+            arg,
+        );
+        self.on_fatal();
     }
 
+    /// logs `arg` with `target` as the `target` field, for the filter of
+    /// the logger to pick the level by
     #[inline]
-    pub fn fatal(&mut self, arg: &[u8]) {
-        if !self.level.enabled(&Level::FatalLevel) {
+    #[track_caller]
+    pub fn log_target(&self, level: Level, target: &str, arg: &[u8]) {
+        if !self.max_level().enabled(&level) {
             return;
         }
-        self.log.fields(self.get_fields());
-        self.log(Level::FatalLevel, arg);
-        exit(1);
+        self.log.log_with(
+            level.clone(),
+            &self.entry_fields(Some(target), HashMap::new()),
+            arg,
+        );
+        if level == Level::FatalLevel {
+            self.on_fatal();
+        }
     }
 
+    /// logs `arg` as [`log_target`](Helper::log_target) does, with `fields`
+    /// for the entry alone on top of the fields of the helper
     #[inline]
-    pub fn with_error(self, e: T) -> Self {
-        if let Ok(ref mut m) = self.fields.clone().lock() {
-            m.insert("error".to_string(), e);
-        };
+    #[track_caller]
+    pub fn log_fields(
+        &self,
+        level: Level,
+        target: &str,
+        fields: HashMap<String, Value>,
+        arg: &[u8],
+    ) {
+        if !self.max_level().enabled(&level) {
+            return;
+        }
+        self.log
+            .log_with(level.clone(), &self.entry_fields(Some(target), fields), arg);
+        if level == Level::FatalLevel {
+            self.on_fatal();
+        }
+    }
+
+    /// logs the failure of `s` with its id, code, detail and position as
+    /// fields, at the level of its code: warn for a 4xx, error for a 5xx or
+    /// an unknown one and info for the others. The message is the status of
+    /// the code.
+    #[inline]
+    #[track_caller]
+    pub fn status(&self, s: &Status) {
+        self.log_status(None, s)
+    }
+
+    /// logs `s` as [`status`](Helper::status) does, with `target` as the
+    /// `target` field
+    #[inline]
+    #[track_caller]
+    pub fn status_target(&self, target: &str, s: &Status) {
+        self.log_status(Some(target), s)
+    }
+
+    #[track_caller]
+    fn log_status(&self, target: Option<&str>, s: &Status) {
+        let level = status_level(s);
+        if !self.max_level().enabled(&level) {
+            return;
+        }
+        let mut fields = HashMap::new();
+        fields.insert("id".to_string(), Value::from(s.id()));
+        fields.insert("code".to_string(), Value::from(i32::from(s.code())));
+        fields.insert("detail".to_string(), Value::from(s.detail()));
+        if !s.position().is_empty() {
+            fields.insert("position".to_string(), Value::from(s.position()));
+        }
+        self.log.log_with(
+            level,
+            &self.entry_fields(target, fields),
+            s.status().as_bytes(),
+        );
+    }
+
+    /// changes the level of the logger while it is in use, as from an admin
+    /// endpoint, see [`Options::set_level`]. Change the level through the
+    /// helper rather than through its options, the helper would keep
+    /// dropping the entries above the level before.
+    pub fn set_level(&self, level: Level) {
+        self.log.options().set_level(level);
+        self.refresh_level();
+    }
+
+    /// changes the filter of the logger while it is in use, see
+    /// [`set_level`](Helper::set_level)
+    pub fn set_filter(&self, filter: Option<Filter>) {
+        self.log.options().set_filter(filter);
+        self.refresh_level();
+    }
+
+    fn max_level(&self) -> Level {
+        self.level.load(Ordering::Relaxed).into()
+    }
+
+    fn refresh_level(&self) {
+        let level = self.log.options().max_level();
+        self.level.store(level.into(), Ordering::Relaxed);
+    }
+
+    /// does as the [`Fatal`] of the options says after a fatal entry
+    fn on_fatal(&self) {
+        match self.log.options().fatal() {
+            Fatal::Exit(code) => {
+                // exit skips the destructors flushing the output
+                self.log.flush();
+                exit(code)
+            }
+            Fatal::Panic => panic!("fatal entry logged"),
+            Fatal::Callback(f) => f(),
+        }
+    }
+
+    /// the fields of the helper and `fields`, with the target and the file
+    /// and line the helper was called from unless in `fields`
+    #[track_caller]
+    fn entry_fields(
+        &self,
+        target: Option<&str>,
+        fields: HashMap<String, Value>,
+    ) -> HashMap<String, Value> {
+        let mut with = self.fields.clone();
+        with.extend(fields);
+        if let Some(target) = target {
+            with.insert("target".to_string(), target.into());
+        }
+        if !with.contains_key("file") {
+            let location = Location::caller();
+            let file = format!("{}:{}", location.file(), location.line());
+            with.insert("file".to_string(), file.into());
+        }
+        with
+    }
+
+    #[inline]
+    pub fn with_error(mut self, e: impl Into<Value>) -> Self {
+        self.fields.insert("error".to_string(), e.into());
         self
     }
 
     #[inline]
-    pub fn with_fields(mut self, fields: HashMap<String, T>) -> Self {
-        self.fields = Arc::new(Mutex::new(fields));
+    pub fn with_fields(mut self, fields: HashMap<String, Value>) -> Self {
+        self.fields = fields;
         self
     }
 }
 
+/// the level a status is logged at
+fn status_level(s: &Status) -> Level {
+    match i32::from(s.code()) {
+        400..=499 => Level::WarnLevel,
+        100..=399 => Level::InfoLevel,
+        _ => Level::ErrorLevel,
+    }
+}
+
+/// makes the panics be logged as errors through the global logger, with
+/// the file and line they were raised at and a backtrace, then aborts the
+/// process. It replaces the panic hook set before.
+pub fn set_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => match info.payload().downcast_ref::<String>() {
+                Some(s) => s.clone(),
+                None => "Box<dyn Any>".to_string(),
+            },
+        };
+
+        let mut fields = HashMap::new();
+        if let Some(location) = info.location() {
+            let file = format!("{}:{}", location.file(), location.line());
+            fields.insert("file".to_string(), Value::from(file));
+        }
+        // there is no backtrace on wasm
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+            fields.insert("backtrace".to_string(), backtrace.into());
+        }
+        if let Some(name) = std::thread::current().name() {
+            fields.insert("thread".to_string(), name.into());
+        }
+
+        let l = crate::global_logger();
+        l.log_fields(
+            Level::ErrorLevel,
+            "panic",
+            fields,
+            format!("panicked: {}", message).as_bytes(),
+        );
+        l.flush();
+        std::process::abort();
+    }));
+}
+
 #[cfg(test)]
 mod test {
-    use crate::{helper::Helper, options::Options, new_logger};
-    use errors::Result;
+    use std::{
+        panic,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use crate::{
+        filter::Filter,
+        helper::Helper,
+        level::Level,
+        new_logger,
+        options::{Fatal, Options},
+    };
+    use errors::{Result, Status};
 
     #[test]
     fn test_new_helper() -> Result<()> {
-        let l = new_logger::<String>(Some(Options::new()))?;
-        let mut helper = Helper::new(l);
-        helper.debug(format!("debug test").as_bytes());
-        helper.info(format!("info test").as_bytes());
-        helper.warn(format!("warn test").as_bytes());
-        helper.fatal(format!("fatal test").as_bytes());
+        let fatals = Arc::new(AtomicUsize::new(0));
+        let counted = fatals.clone();
+        let opts = Options::new().with_fatal(Fatal::Callback(Arc::new(move || {
+            counted.fetch_add(1, Ordering::SeqCst);
+        })));
+        let l = new_logger(Some(opts))?;
+        let helper = Helper::new(l);
+        helper.debug(b"debug test");
+        helper.info(b"info test");
+        helper.warn(b"warn test");
+        helper.fatal(b"fatal test");
+        assert_eq!(fatals.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_level() -> Result<()> {
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let opts = Options::new()
+            .with_filter(Filter::parse("warn,registry=error")?)
+            .with_out(out.clone());
+        let helper = Helper::new(new_logger(Some(opts))?);
+        helper.info(b"hidden");
+        helper.set_level(Level::DebugLevel);
+        helper.debug(b"raised");
+        helper.log_target(Level::WarnLevel, "registry", b"directive");
+        helper.set_filter(None);
+        helper.log_target(Level::WarnLevel, "registry", b"unfiltered");
+        helper.set_level(Level::ErrorLevel);
+        helper.warn(b"lowered");
+
+        let out = String::from_utf8(out.lock().unwrap().clone())?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", out);
+        assert!(lines[0].ends_with(" raised"), "{}", lines[0]);
+        assert!(lines[1].ends_with(" unfiltered"), "{}", lines[1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_status() -> Result<()> {
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let opts = Options::new()
+            .with_level(Level::WarnLevel)
+            .with_out(out.clone());
+        let helper = Helper::new(new_logger(Some(opts))?);
+        helper.status(&Status::internal_server_error("io.vine", "db down"));
+        helper.status_target(
+            "client",
+            &Status::not_found("io.vine", "no node").with_pos(),
+        );
+        helper.status(&Status::do_continue("io.vine"));
+
+        let out = String::from_utf8(out.lock().unwrap().clone())?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", out);
+        assert!(
+            lines[0].contains(" code=500 detail=db down file=")
+                && lines[0].contains(" id=io.vine "),
+            "{}",
+            lines[0]
+        );
+        assert!(
+            lines[0].contains(" level=error position=logger/src/helper.rs:")
+                && lines[0].ends_with(" Internal Server Error"),
+            "{}",
+            lines[0]
+        );
+        assert!(lines[1].contains(" level=warn position="), "{}", lines[1]);
+        assert!(
+            lines[1].ends_with(" target=client Not Found"),
+            "{}",
+            lines[1]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fatal_panic() -> Result<()> {
+        let l = new_logger(Some(Options::new().with_fatal(Fatal::Panic)))?;
+        let helper = Helper::new(l);
+        let fatal = panic::AssertUnwindSafe(|| helper.fatal(b"fatal test"));
+        assert!(panic::catch_unwind(fatal).is_err());
         Ok(())
     }
 }
@@ -0,0 +1,203 @@
+use std::{collections::HashMap, io, os::unix::net::UnixDatagram, path::PathBuf};
+
+use errors::Result;
+use itertools::Itertools;
+
+use crate::{level::Level, options::Options, value::Value, Logger};
+
+/// the socket of the native protocol of journald
+pub const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// the implement of [`Logger`] sending the entries to journald over its
+/// native protocol, the message as `MESSAGE`, the level as `PRIORITY`, the
+/// file as `CODE_FILE` and `CODE_LINE` and the other fields by their names
+/// in upper case. An entry larger than a datagram is dropped.
+///
+/// ```rust,no_run
+/// # use logger::journald::JournaldLogger;
+/// let l = JournaldLogger::new(None)?.with_identifier("vine");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct JournaldLogger {
+    opts: Options,
+    socket: UnixDatagram,
+    path: PathBuf,
+    identifier: Option<String>,
+}
+
+impl JournaldLogger {
+    /// sends the entries to [`JOURNAL_SOCKET`]
+    pub fn new(opts: Option<Options>) -> Result<Self> {
+        Self::with_socket(opts, JOURNAL_SOCKET)
+    }
+
+    /// sends the entries to the socket at `path`
+    pub fn with_socket(opts: Option<Options>, path: impl Into<PathBuf>) -> Result<Self> {
+        Ok(JournaldLogger {
+            opts: opts.unwrap_or_default(),
+            socket: UnixDatagram::unbound()?,
+            path: path.into(),
+            identifier: None,
+        })
+    }
+
+    /// set the `SYSLOG_IDENTIFIER` of the entries, the name journald shows
+    /// them under. default is the name of the executable
+    #[inline]
+    pub fn with_identifier(mut self, identifier: impl Into<String>) -> Self {
+        self.identifier = Some(identifier.into());
+        self
+    }
+
+    fn send(&self, entry: &[u8]) -> io::Result<()> {
+        self.socket.send_to(entry, &self.path).map(|_| ())
+    }
+}
+
+impl Logger for JournaldLogger {
+    fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.opts = opt.unwrap_or_default();
+        Ok(())
+    }
+
+    fn options(&self) -> Options {
+        self.opts.clone()
+    }
+
+    fn fields(&mut self, fields: HashMap<String, Value>) {
+        self.opts = self.opts.clone().with_fields(fields);
+    }
+
+    fn log(&self, level: Level, arg: &[u8]) {
+        self.log_with(level, &HashMap::new(), arg)
+    }
+
+    fn log_with(&self, level: Level, with: &HashMap<String, Value>, arg: &[u8]) {
+        let fields = match self.opts.entry_fields(&level, with) {
+            Some(fields) => fields,
+            None => return,
+        };
+        let entry = entry(&level, self.identifier.as_deref(), fields, arg);
+        let _ = self.send(&entry);
+    }
+
+    fn string(&self) -> &'static str {
+        "journald"
+    }
+}
+
+/// the entry in the native protocol, a `KEY=value` line per field
+fn entry(
+    level: &Level,
+    identifier: Option<&str>,
+    mut fields: HashMap<String, Value>,
+    arg: &[u8],
+) -> Vec<u8> {
+    let arg = arg.strip_suffix(b"\n").unwrap_or(arg);
+    let mut entry = Vec::new();
+    put(&mut entry, "MESSAGE", arg);
+    put(
+        &mut entry,
+        "PRIORITY",
+        level.syslog_severity().to_string().as_bytes(),
+    );
+    if let Some(identifier) = identifier {
+        put(&mut entry, "SYSLOG_IDENTIFIER", identifier.as_bytes());
+    }
+    if let Some(file) = fields.remove("file") {
+        let file = file.to_string();
+        match file.rsplit_once(':') {
+            Some((file, line)) if line.parse::<u32>().is_ok() => {
+                put(&mut entry, "CODE_FILE", file.as_bytes());
+                put(&mut entry, "CODE_LINE", line.as_bytes());
+            }
+            _ => put(&mut entry, "CODE_FILE", file.as_bytes()),
+        }
+    }
+    for (k, v) in fields.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+        if let Some(key) = key(k) {
+            put(&mut entry, &key, v.to_string().as_bytes());
+        }
+    }
+    entry
+}
+
+/// writes a field, as `KEY=value` or, when the value has a newline, as the
+/// key, a newline, the length as a little endian u64 and the value
+fn put(entry: &mut Vec<u8>, key: &str, value: &[u8]) {
+    entry.extend_from_slice(key.as_bytes());
+    if value.contains(&b'\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value);
+    entry.push(b'\n');
+}
+
+/// the journald name of a field, its ascii letters and digits in upper case
+/// with `_` for the others, `None` when nothing is left or it starts with a
+/// digit. A leading `_` is dropped, those fields are set by journald alone.
+fn key(name: &str) -> Option<String> {
+    let key: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' => c.to_ascii_uppercase(),
+            'A'..='Z' | '0'..='9' => c,
+            _ => '_',
+        })
+        .take(64)
+        .collect();
+    let key = key.trim_start_matches('_');
+    match key.chars().next() {
+        Some(c) if !c.is_ascii_digit() => Some(key.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, os::unix::net::UnixDatagram};
+
+    use errors::Result;
+
+    use super::{entry, key, JournaldLogger};
+    use crate::{level::Level, options::Options, Logger};
+
+    #[test]
+    fn test_entry() {
+        let mut fields = HashMap::new();
+        fields.insert("file".to_string(), "src/lib.rs:7".into());
+        fields.insert("request-id".to_string(), 42.into());
+        fields.insert("trace".to_string(), "a\nb".into());
+        let e = entry(&Level::ErrorLevel, Some("vine"), fields, b"failed\n");
+
+        let mut expected = b"MESSAGE=failed\nPRIORITY=3\nSYSLOG_IDENTIFIER=vine\n\
+            CODE_FILE=src/lib.rs\nCODE_LINE=7\nREQUEST_ID=42\nTRACE\n"
+            .to_vec();
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(b"a\nb\n");
+        assert_eq!(e, expected);
+
+        assert_eq!(key("_pid"), Some("PID".to_string()));
+        assert_eq!(key("1st"), None);
+        assert_eq!(key("__"), None);
+    }
+
+    #[test]
+    fn test_socket() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("vine-journal-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = UnixDatagram::bind(&path)?;
+
+        let l = JournaldLogger::with_socket(Some(Options::new()), &path)?;
+        l.log(Level::InfoLevel, b"journaled");
+        let mut buf = [0u8; 1024];
+        let n = server.recv(&mut buf)?;
+        assert_eq!(&buf[..n], b"MESSAGE=journaled\nPRIORITY=6\n");
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -1,34 +1,73 @@
+use std::str::FromStr;
+
 use errors::err;
 use errors::Result;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+/// the level of an entry, serialized as its name as `info`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum Level {
     /// TraceLevel level. Designates finer-grained informational events than the Debug.
+    #[serde(rename = "trace")]
     TraceLevel = -2,
 
     /// DebugLevel level. Usually only enabled when debugging. Very verbose logging.
+    #[serde(rename = "debug")]
     DebugLevel = -1,
 
     /// InfoLevel is the default logging priority.
     /// General operational entries about what's going on inside the application.
+    #[serde(rename = "info")]
     InfoLevel = 0,
 
     /// WarnLevel level. Non-critical entries that deserve eyes.
+    #[serde(rename = "warn")]
     WarnLevel = 1,
 
     /// ErrorLevel level. Logs. Used for errors that should definitely by noted.
+    #[serde(rename = "error")]
     ErrorLevel = 2,
 
     // FatalLevel level. Logs and then calls [`logger.Exit(1)`]. Highest level of severity.
+    #[serde(rename = "fatal")]
     FatalLevel = 3,
 }
 
+/// the escape ending a color
+pub(crate) const RESET: &str = "\x1b[0m";
+/// the escape dimming text
+pub(crate) const DIM: &str = "\x1b[2m";
+
 impl Level {
+    /// the escape coloring the level in a terminal
+    pub(crate) fn color(&self) -> &'static str {
+        match self {
+            Level::TraceLevel => "\x1b[35m",
+            Level::DebugLevel => "\x1b[34m",
+            Level::InfoLevel => "\x1b[32m",
+            Level::WarnLevel => "\x1b[33m",
+            Level::ErrorLevel => "\x1b[31m",
+            Level::FatalLevel => "\x1b[1;31m",
+        }
+    }
+
     /// retruns true if the given level is at or above this level.
     pub fn enabled(&self, lvl: &Level) -> bool {
         lvl >= self
     }
 
+    /// the severity of the level in syslog and journald, from 2 for critical
+    /// to 7 for debug
+    pub fn syslog_severity(&self) -> u8 {
+        match self {
+            Level::TraceLevel | Level::DebugLevel => 7,
+            Level::InfoLevel => 6,
+            Level::WarnLevel => 4,
+            Level::ErrorLevel => 3,
+            Level::FatalLevel => 2,
+        }
+    }
+
     /// converts a level string into a logger Level value.
     /// returns an error if the input string does not match known values.
     pub fn from(ls: impl Into<String>) -> Result<Level> {
@@ -48,9 +87,39 @@ impl Level {
     }
 }
 
-impl Into<String> for Level {
-    fn into(self) -> String {
-        self.to_string()
+impl FromStr for Level {
+    type Err = errors::anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Level::from(s)
+    }
+}
+
+/// the level of the number, as `-1` for `DebugLevel`, the numbers below
+/// trace being trace and those above fatal being fatal
+impl From<i8> for Level {
+    fn from(i: i8) -> Self {
+        match i {
+            i8::MIN..=-2 => Level::TraceLevel,
+            -1 => Level::DebugLevel,
+            0 => Level::InfoLevel,
+            1 => Level::WarnLevel,
+            2 => Level::ErrorLevel,
+            _ => Level::FatalLevel,
+        }
+    }
+}
+
+impl From<Level> for i8 {
+    #[inline]
+    fn from(l: Level) -> i8 {
+        l as i8
+    }
+}
+
+impl From<Level> for String {
+    fn from(l: Level) -> Self {
+        l.to_string()
     }
 }
 
@@ -79,7 +148,31 @@ mod tests {
         let l1 = Level::DebugLevel;
         let l2 = Level::InfoLevel;
         assert!(l1 < l2);
-        println!("{}", l1.to_string());
+        println!("{}", l1);
+    }
+
+    #[test]
+    fn test_conversions() -> Result<()> {
+        let l: Level = "warn".parse()?;
+        assert_eq!(l, Level::WarnLevel);
+        assert!("loud".parse::<Level>().is_err());
+
+        let l: Level = (-1i8).into();
+        assert_eq!(l, Level::DebugLevel);
+        let l: Level = 100i8.into();
+        assert_eq!(l, Level::FatalLevel);
+        assert_eq!(i8::from(Level::TraceLevel), -2);
+
+        assert_eq!(serde_json::to_string(&Level::ErrorLevel)?, "\"error\"");
+        let l: Level = serde_json::from_str("\"trace\"")?;
+        assert_eq!(l, Level::TraceLevel);
+
+        let levels: std::collections::HashSet<Level> = vec![Level::InfoLevel, Level::InfoLevel]
+            .into_iter()
+            .collect();
+        assert_eq!(levels.len(), 1);
+        assert_eq!(Level::WarnLevel.max(Level::ErrorLevel), Level::ErrorLevel);
+        Ok(())
     }
 
     #[test]
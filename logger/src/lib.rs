@@ -1,142 +1,383 @@
+pub mod audit;
+mod bridge;
+mod buffer;
+#[cfg(target_arch = "wasm32")]
+pub mod console;
+pub mod filter;
 pub mod helper;
+#[cfg(all(unix, feature = "logger-journald"))]
+pub mod journald;
 pub mod level;
 pub mod macro_rule;
 pub mod options;
+pub mod record;
+pub mod redact;
+pub mod rolling;
+#[cfg(feature = "logger-tracing")]
+pub mod subscriber;
+#[cfg(feature = "logger-syslog")]
+pub mod syslog;
+pub mod test;
+pub mod value;
 
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    env,
+    fmt::Write as _,
+    sync::{Arc, RwLock},
 };
 
-use bytes::BufMut;
+use buffer::with_buffer;
+use bytes::{BufMut, BytesMut};
 use chrono::prelude::*;
+use errors::Result;
+use filter::Filter;
 use helper::Helper;
 use itertools::Itertools;
+use level::{Level, DIM, RESET};
 use once_cell::sync::OnceCell;
-use errors::Result;
-use level::Level;
-use options::Options;
+use options::{Format, Options, Precision};
+use record::Record;
+use rolling::RotationPolicy;
+use value::Value;
+#[cfg(not(target_arch = "wasm32"))]
 use vine_util::caller::caller;
 
-static DEFAULT_LOGGER: OnceCell<Arc<Mutex<Helper<String>>>> = OnceCell::new();
-pub fn global_logger() -> &'static Arc<Mutex<Helper<String>>> {
+#[cfg(target_arch = "wasm32")]
+use console::{now, ConsoleLogger};
+
+pub use bridge::init_log_bridge;
+pub use helper::set_panic_hook;
+
+static DEFAULT_LOGGER: OnceCell<RwLock<Arc<Helper>>> = OnceCell::new();
+
+fn global() -> &'static RwLock<Arc<Helper>> {
     DEFAULT_LOGGER.get_or_init(|| {
-        let l = new_logger::<String>(Some(Options::new())).unwrap();
-        let helper = Helper::new(l);
-        Arc::new(Mutex::new(helper))
+        let mut opts = Options::new();
+        if let Some(filter) = Filter::from_env() {
+            opts = opts.with_filter(filter);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let l = new_logger(Some(opts)).unwrap();
+        #[cfg(target_arch = "wasm32")]
+        let l = ConsoleLogger::new(Some(opts));
+        RwLock::new(Arc::new(Helper::new(l)))
     })
 }
 
-pub fn set_global_logger(val: Helper<String>) -> Result<()> {
-    match DEFAULT_LOGGER.set(Arc::new(Mutex::new(val))) {
-        Ok(()) => Ok(()),
-        Err(_) => Err(errors::err!("set global logger failed")),
+/// the global logger. The lock is held only to clone it, the entries are
+/// logged through `&self` without locking anything else than the output.
+pub fn global_logger() -> Arc<Helper> {
+    match global().read() {
+        Ok(l) => l.clone(),
+        Err(e) => e.into_inner().clone(),
     }
 }
 
-pub trait Logger<T>
-where
-    T: Into<String> + Clone + Send,
-{
+/// replaces the global logger, the entries logged after are written by
+/// `val` while those being logged finish with the one replaced
+pub fn set_global_logger(val: Helper) -> Result<()> {
+    let mut l = match global().write() {
+        Ok(l) => l,
+        Err(e) => e.into_inner(),
+    };
+    *l = Arc::new(val);
+    Ok(())
+}
+
+/// changes the level of the global logger while it is in use, as from an
+/// admin endpoint raising the verbosity without a restart. Its filter takes
+/// the level for the targets it has no directive for.
+pub fn set_global_level(level: Level) {
+    let l = global_logger();
+    l.set_level(level);
+    bridge::set_max_level(&l.options().max_level());
+}
+
+/// changes the filter of the global logger while it is in use
+pub fn set_global_filter(filter: Filter) {
+    let l = global_logger();
+    l.set_filter(Some(filter));
+    bridge::set_max_level(&l.options().max_level());
+}
+
+/// the variable of the level of [`init_from_env`], `info` unless set
+pub const LEVEL_KEY: &str = "VINE_LOG_LEVEL";
+/// the variable of the format of [`init_from_env`], `text` or `json`
+pub const FORMAT_KEY: &str = "VINE_LOG_FORMAT";
+/// the variable of the file of [`init_from_env`], stdout unless set
+pub const FILE_KEY: &str = "VINE_LOG_FILE";
+
+/// sets the global logger as the environment says: the level of
+/// `VINE_LOG_LEVEL`, the format of `VINE_LOG_FORMAT`, the file of
+/// `VINE_LOG_FILE`, rotated daily with a week of segments kept, and the
+/// filter of `VINE_LOG`. Fails when a variable does not parse or the file
+/// does not open.
+///
+/// ```rust,no_run
+/// let _guard = logger::init_from_env()?;
+/// logger::info!("started");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn init_from_env() -> Result<FlushGuard> {
+    let l = new_logger(Some(options_from_env()?))?;
+    set_global_logger(Helper::new(l))?;
+    Ok(FlushGuard { _priv: () })
+}
+
+fn options_from_env() -> Result<Options> {
+    let mut opts = Options::new();
+    if let Ok(level) = env::var(LEVEL_KEY) {
+        opts = opts.with_level(Level::from(level)?);
+    }
+    if let Ok(format) = env::var(FORMAT_KEY) {
+        opts = opts.with_format(Format::from(format)?);
+    }
+    if let Ok(file) = env::var(FILE_KEY) {
+        opts = opts.with_file(file, RotationPolicy::Daily, 7)?;
+    }
+    if let Some(filter) = Filter::from_env() {
+        opts = opts.with_filter(filter);
+    }
+    Ok(opts)
+}
+
+pub trait Logger {
     /// initialises options
-    fn init(&mut self, opt: Option<Options<T>>) -> Result<()>;
+    fn init(&mut self, opt: Option<Options>) -> Result<()>;
 
     /// the Logger options
-    fn options(&self) -> Options<T>;
+    fn options(&self) -> Options;
 
     /// set fields to always be logged
-    fn fields(&mut self, fields: HashMap<String, T>);
+    fn fields(&mut self, fields: HashMap<String, Value>);
 
     /// writes a log entry
     fn log(&self, level: Level, arg: &[u8]);
 
+    /// writes a log entry with `fields` on top of the fields of the options,
+    /// for the entry alone. Loggers without fields of entries log without.
+    fn log_with(&self, level: Level, fields: &HashMap<String, Value>, arg: &[u8]) {
+        let _ = fields;
+        self.log(level, arg)
+    }
+
+    /// writes the entries buffered by the output
+    fn flush(&self) {
+        let rc = self.options().out();
+        if let Ok(ref mut writer) = rc.lock() {
+            let _ = writer.flush();
+        };
+    }
+
     /// returns the name of logger
     fn string(&self) -> &'static str;
 }
 
+/// FlushGuard flushes the global logger when dropped, so the entries
+/// buffered when `main` returns are written. Keep it until the end of
+/// `main`, as `let _guard = logger::init_from_env()?;`.
+#[must_use = "the logger is flushed when the guard is dropped"]
+#[derive(Debug)]
+pub struct FlushGuard {
+    _priv: (),
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        global_logger().flush();
+    }
+}
+
 #[derive(Clone)]
 /// The default implemention of [`Logger`] trait
 /// ```rust
-/// let mut l = NewLogger::<String>(Some(Options::new()))?;
+/// # use std::collections::HashMap;
+/// # use logger::{level::Level, new_logger, options::Options, Logger};
+/// let mut l = new_logger(Some(Options::new()))?;
 /// let mut m = HashMap::new();
-/// m.insert("a".to_string(), "b".to_string());
+/// m.insert("a".to_string(), "b".into());
 /// l.fields(m);
 /// l.log(Level::InfoLevel, format!("helloworld").as_bytes());
+/// # Ok::<(), anyhow::Error>(())
 /// ```
-struct DefaultLogger<T: Into<String> + Clone + Send> {
-    opts: Options<T>,
-}
-
-impl<T> Logger<T> for DefaultLogger<T>
-where
-    T: Into<String> + Clone + Send,
-{
-    fn init(&mut self, opt: Option<Options<T>>) -> Result<()> {
-        let opts = match opt {
-            Some(o) => o,
-            None => Options::new(),
-        };
-        self.opts = opts;
+struct DefaultLogger {
+    opts: Options,
+}
+
+impl Logger for DefaultLogger {
+    fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.opts = opt.unwrap_or_default();
         Ok(())
     }
 
-    fn options(&self) -> Options<T> {
+    fn options(&self) -> Options {
         self.opts.clone()
     }
 
-    fn fields(&mut self, fields: HashMap<String, T>) {
+    fn fields(&mut self, fields: HashMap<String, Value>) {
         self.opts = self.opts.clone().with_fields(fields);
     }
 
     fn log(&self, level: Level, arg: &[u8]) {
-        if !self.opts.level().enabled(&level) {
-            return;
-        }
+        self.write(level, &HashMap::new(), arg)
+    }
 
-        let mut fields = HashMap::new();
-        for (k, v) in self.opts.fields().clone() {
-            fields.insert(k, v.into());
-        }
-        fields.insert("level".to_string(), level.to_string());
-        if !fields.contains_key("file") {
-            fields.insert("file".to_string(), caller(6 + self.opts.skip() as usize));
+    fn log_with(&self, level: Level, fields: &HashMap<String, Value>, arg: &[u8]) {
+        self.write(level, fields, arg)
+    }
+
+    fn string(&self) -> &'static str {
+        "default"
+    }
+}
+
+impl DefaultLogger {
+    fn write(&self, level: Level, with: &HashMap<String, Value>, arg: &[u8]) {
+        let mut fields = match self.opts.entry_fields(&level, with) {
+            Some(fields) => fields,
+            None => return,
+        };
+        fields.insert("level".to_string(), level.to_string().into());
+        // there is no backtrace on wasm
+        #[cfg(not(target_arch = "wasm32"))]
+        if !fields.contains_key("file") && self.opts.backtrace() {
+            fields.insert(
+                "file".to_string(),
+                caller(7 + self.opts.skip() as usize).into(),
+            );
         }
 
-        let mut metadata = bytes::BytesMut::new();
-        for key in fields.keys().sorted() {
-            metadata.put_slice(format!(" {}={}", key, fields[key]).as_bytes())
+        let local = now();
+        call_hooks(&self.opts, &level, local, &fields, arg);
+
+        with_buffer(|entry| {
+            match self.opts.format() {
+                Format::Text => {
+                    text_time(entry, &self.opts, local);
+                    text_entry(entry, &fields, arg, self.opts.color())
+                }
+                Format::Json => json_entry(entry, json_time(&self.opts, local), fields, arg),
+            }
+
+            let rc = self.opts.out().clone();
+            if let Ok(ref mut writer) = rc.lock() {
+                let _ = writer.write_all(&entry[..]);
+            };
+        })
+    }
+}
+
+/// the time of an entry
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> DateTime<Local> {
+    Local::now()
+}
+
+/// calls the hooks of `opts` at or below `level` with the entry
+fn call_hooks(
+    opts: &Options,
+    level: &Level,
+    time: DateTime<Local>,
+    fields: &HashMap<String, Value>,
+    arg: &[u8],
+) {
+    let hooks = opts.hooks();
+    if !hooks.is_empty() {
+        let record = Record::new(level.clone(), time, fields, arg);
+        for (min, hook) in hooks {
+            if min.enabled(level) {
+                hook(&record);
+            }
         }
+    }
+}
 
-        let rc = self.opts.out().clone();
-        if let Ok(ref mut writer) = rc.lock() {
-            let local: DateTime<Local> = Local::now();
+/// writes the time of a text entry in the time format of `opts`, the
+/// default one when it is not set or does not format
+fn text_time(entry: &mut BytesMut, opts: &Options, local: DateTime<Local>) {
+    let default = opts.precision().unwrap_or(Precision::Seconds).text_format();
+    let start = entry.len();
+    for format in opts.time_format().iter().chain(Some(&default)) {
+        entry.truncate(start);
+        let formatted = if opts.utc() {
+            write!(entry, "{}", local.with_timezone(&Utc).format(format))
+        } else {
+            write!(entry, "{}", local.format(format))
+        };
+        if formatted.is_ok() {
+            break;
+        }
+    }
+}
 
-            let _ = writer.write(local.format("%Y-%m-%d %H:%M:%S").to_string().as_bytes());
-            let _ = writer.write(&metadata[..]);
-            let _ = writer.write(b" ");
-            let _ = writer.write(arg);
+/// the time of a JSON entry in RFC 3339
+fn json_time(opts: &Options, local: DateTime<Local>) -> String {
+    let format = opts
+        .precision()
+        .unwrap_or(Precision::Millis)
+        .seconds_format();
+    if opts.utc() {
+        local.with_timezone(&Utc).to_rfc3339_opts(format, true)
+    } else {
+        local.to_rfc3339_opts(format, false)
+    }
+}
 
-            let last = arg.last();
-            if last.is_some() && last.unwrap() != &10 {
-                let _ = writer.write(b"\n");
+/// writes the sorted fields and the message after the time, ended by a
+/// newline. Colored the level is in the color of the level and the file is
+/// dimmed.
+fn text_entry(entry: &mut BytesMut, fields: &HashMap<String, Value>, arg: &[u8], color: bool) {
+    for key in fields.keys().sorted() {
+        let _ = match key.as_str() {
+            "level" if color => {
+                let code = fields[key]
+                    .as_str()
+                    .and_then(|l| Level::from(l).ok())
+                    .map(|l| l.color())
+                    .unwrap_or(RESET);
+                write!(entry, " {}={}{}{}", key, code, fields[key], RESET)
             }
+            "file" if color => write!(entry, " {}{}={}{}", DIM, key, fields[key], RESET),
+            _ => write!(entry, " {}={}", key, fields[key]),
         };
     }
+    entry.put_slice(b" ");
+    entry.put_slice(arg);
 
-    fn string(&self) -> &'static str {
-        "default"
+    let last = arg.last();
+    if last.is_some() && last.unwrap() != &10 {
+        entry.put_slice(b"\n");
     }
 }
 
-pub fn new_logger<T: Into<String> + Clone + Send>(
-    opts: Option<Options<T>>,
-) -> Result<impl Logger<T>> {
-    let opt = match opts {
-        Some(o) => o,
-        None => Options::new(),
+/// writes the entry as a JSON object on a line of its own, the fields with
+/// the JSON type of their value
+fn json_entry(out: &mut BytesMut, time: String, mut fields: HashMap<String, Value>, arg: &[u8]) {
+    let message = String::from_utf8_lossy(arg);
+    let message = message.strip_suffix('\n').unwrap_or(&message);
+
+    let mut entry = serde_json::Map::new();
+    entry.insert("timestamp".to_string(), time.into());
+    for key in &["level", "file"] {
+        if let Some(v) = fields.remove(*key) {
+            entry.insert(key.to_string(), v.to_json());
+        }
+    }
+    entry.insert("message".to_string(), message.into());
+    let fields: serde_json::Map<String, serde_json::Value> =
+        fields.into_iter().map(|(k, v)| (k, v.to_json())).collect();
+    entry.insert("fields".to_string(), fields.into());
+
+    let _ = serde_json::to_writer((&mut *out).writer(), &entry);
+    out.put_u8(b'\n');
+}
+
+pub fn new_logger(opts: Option<Options>) -> Result<impl Logger> {
+    let mut logger = DefaultLogger {
+        opts: Options::new(),
     };
-    let mut logger = DefaultLogger { opts: opt };
-    logger.init(None)?;
+    logger.init(opts)?;
 
     Ok(logger)
 }
@@ -145,16 +386,31 @@ pub fn new_logger<T: Into<String> + Clone + Send>(
 mod tests {
     use std::{
         collections::HashMap,
+        io::{BufWriter, Write},
         sync::{Arc, Mutex},
         thread,
     };
 
     use crate::{
-        global_logger, level::Level, new_logger, options::Options, set_global_logger, Helper,
-        Logger,
+        filter::Filter,
+        global_logger,
+        level::Level,
+        new_logger,
+        options::{Format, Options, Precision},
+        options_from_env,
+        redact::Redact,
+        set_global_filter, set_global_level, set_global_logger,
+        test::CaptureWriter,
+        value::Value,
+        Helper, Logger, FILE_KEY, FORMAT_KEY, LEVEL_KEY,
     };
+    use chrono::DateTime;
     use errors::Result;
 
+    /// held by the tests replacing the global logger, which would otherwise
+    /// swap it under each other
+    pub(crate) static GLOBAL: Mutex<()> = Mutex::new(());
+
     #[test]
     fn do_work() {
         println!("{:?}", b"\n");
@@ -162,26 +418,237 @@ mod tests {
 
     #[test]
     fn test_new_logger() -> Result<()> {
-        let mut l = new_logger::<String>(Some(Options::new()))?;
+        let mut l = new_logger(Some(Options::new()))?;
         let mut m = HashMap::new();
-        m.insert("a".to_string(), "b".to_string());
+        m.insert("a".to_string(), "b".into());
         l.fields(m);
-        l.log(Level::InfoLevel, format!("helloworld").as_bytes());
+        l.log(Level::InfoLevel, b"helloworld");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_format() -> Result<()> {
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let opts = Options::new()
+            .with_format(Format::Json)
+            .insert_field("a".to_string(), "b")
+            .insert_field("port".to_string(), 8080)
+            .insert_field("tls".to_string(), false)
+            .with_backtrace(true)
+            .with_out(out.clone());
+        let l = new_logger(Some(opts))?;
+        l.log(Level::WarnLevel, b"hello \"vine\"\n");
+
+        let out = out.lock().unwrap();
+        assert_eq!(out.iter().filter(|b| **b == b'\n').count(), 1);
+        let entry: serde_json::Value = serde_json::from_slice(&out[..])?;
+        assert_eq!(entry["level"], "warn");
+        assert_eq!(entry["message"], "hello \"vine\"");
+        assert_eq!(
+            entry["fields"],
+            serde_json::json!({"a": "b", "port": 8080, "tls": false})
+        );
+        assert!(entry["file"].is_string());
+        assert!(entry["timestamp"].is_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter() -> Result<()> {
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let opts = Options::new()
+            .with_filter(Filter::parse("warn,registry=debug")?)
+            .with_out(out.clone());
+        let helper = Helper::new(new_logger(Some(opts))?);
+        helper.log_target(Level::DebugLevel, "registry::etcd", b"watching");
+        helper.log_target(Level::InfoLevel, "broker", b"hidden");
+        helper.info(b"hidden");
+        helper.log_target(Level::WarnLevel, "broker", b"shown");
+
+        let out = String::from_utf8(out.lock().unwrap().clone())?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", out);
+        assert!(lines[0].ends_with(" target=registry::etcd watching"));
+        assert!(lines[1].ends_with(" target=broker shown"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_options_from_env() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("vine-env-{}", std::process::id()));
+        std::env::set_var(LEVEL_KEY, "debug");
+        std::env::set_var(FORMAT_KEY, "json");
+        std::env::set_var(FILE_KEY, dir.join("vine.log"));
+        let opts = options_from_env()?;
+        assert_eq!(opts.level(), Level::DebugLevel);
+        assert_eq!(opts.format(), Format::Json);
+        assert!(dir.join("vine.log").exists());
+
+        std::env::set_var(FORMAT_KEY, "xml");
+        assert!(options_from_env().is_err());
+
+        for key in &[LEVEL_KEY, FORMAT_KEY, FILE_KEY] {
+            std::env::remove_var(key);
+        }
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_color() -> Result<()> {
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let opts = Options::new().with_out(out.clone());
+        assert!(!opts.color());
+        let l = new_logger(Some(opts.with_color(true).with_backtrace(true)))?;
+        l.log(Level::WarnLevel, b"colored");
+
+        let out = String::from_utf8(out.lock().unwrap().clone())?;
+        assert!(out.contains(" \x1b[2mfile="), "{:?}", out);
+        assert!(out.contains(" level=\x1b[33mwarn\x1b[0m "), "{:?}", out);
+        assert!(out.ends_with(" colored\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hook() -> Result<()> {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let hooked = seen.clone();
+        let opts = Options::new()
+            .with_out(Arc::new(Mutex::new(std::io::sink())))
+            .insert_field("a".to_string(), "b".to_string())
+            .with_hook(
+                Level::ErrorLevel,
+                Box::new(move |r| {
+                    let entry = format!("{} {} {}", r.level(), r.field("a").unwrap(), r.message());
+                    hooked.lock().unwrap().push(entry);
+                }),
+            );
+        let l = new_logger(Some(opts))?;
+        l.log(Level::WarnLevel, b"not hooked");
+        l.log(Level::ErrorLevel, b"hooked\n");
+
+        assert_eq!(*seen.lock().unwrap(), vec!["error b hooked".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_caller() -> Result<()> {
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let l = new_logger(Some(Options::new().with_out(out.clone())))?;
+        let helper = Helper::new(l);
+        let line = line!() + 1;
+        helper.info(b"located");
+        helper.log(Level::InfoLevel, b"unlocated");
+
+        let out = String::from_utf8(out.lock().unwrap().clone())?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(
+            lines[0].contains(&format!(" file={}:{} ", file!(), line)),
+            "{}",
+            lines[0]
+        );
+        assert!(!lines[1].contains("file="), "{}", lines[1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_flush() -> Result<()> {
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let buffered = BufWriter::new(Shared(out.clone()));
+        let opts = Options::new().with_out(Arc::new(Mutex::new(buffered)));
+        let helper = Helper::new(new_logger(Some(opts))?);
+        helper.info(b"buffered");
+        assert!(out.lock().unwrap().is_empty());
+
+        helper.flush();
+        assert!(String::from_utf8(out.lock().unwrap().clone())?.ends_with(" buffered\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_format() -> Result<()> {
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let opts = Options::new()
+            .with_time_format("%+")
+            .with_utc(true)
+            .with_out(out.clone());
+        new_logger(Some(opts))?.log(Level::InfoLevel, b"rfc3339");
+        let opts = Options::new()
+            .with_precision(Precision::Nanos)
+            .with_out(out.clone());
+        new_logger(Some(opts))?.log(Level::InfoLevel, b"nanos");
+        let opts = Options::new()
+            .with_format(Format::Json)
+            .with_utc(true)
+            .with_precision(Precision::Micros)
+            .with_out(out.clone());
+        new_logger(Some(opts))?.log(Level::InfoLevel, b"json");
+
+        let out = String::from_utf8(out.lock().unwrap().clone())?;
+        let lines: Vec<&str> = out.lines().collect();
+        let time = lines[0].split(' ').next().unwrap();
+        assert!(DateTime::parse_from_rfc3339(time).is_ok(), "{}", time);
+        assert!(time.ends_with("+00:00"), "{}", time);
+        // 2021-09-30 10:10:10.123456789
+        let time = lines[1].split(' ').nth(1).unwrap();
+        assert_eq!(time.len(), "10:10:10.123456789".len(), "{}", time);
+        let entry: serde_json::Value = serde_json::from_str(lines[2])?;
+        let time = entry["timestamp"].as_str().unwrap();
+        assert_eq!(time.len(), "2021-09-30T10:10:10.123456Z".len(), "{}", time);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_redact() -> Result<()> {
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let opts = Options::new()
+            .with_redact(Redact::new(["password", "*_secret"]))
+            .insert_field("client_secret".to_string(), "s3cr3t".to_string())
+            .with_out(out.clone());
+        let helper = Helper::new(new_logger(Some(opts))?);
+        let mut fields = HashMap::new();
+        fields.insert("password".to_string(), Value::from("hunter2"));
+        fields.insert("user".to_string(), Value::from("vine"));
+        helper.log_fields(Level::InfoLevel, "auth", fields, b"login");
+
+        let out = String::from_utf8(out.lock().unwrap().clone())?;
+        assert!(
+            !out.contains("hunter2") && !out.contains("s3cr3t"),
+            "{}",
+            out
+        );
+        assert!(out.contains(" client_secret=[REDACTED] "), "{}", out);
+        assert!(out.contains(" password=[REDACTED] "), "{}", out);
+        assert!(out.contains(" user=vine "), "{}", out);
 
         Ok(())
     }
 
     #[test]
     fn test_sync_logger() -> Result<()> {
-        let l = new_logger::<String>(Some(Options::new()))?;
+        let l = new_logger(Some(Options::new()))?;
         let helper = Helper::new(l);
-        let sync_logger = Arc::new(Mutex::new(helper));
+        let sync_logger = Arc::new(helper);
 
         let l1 = sync_logger.clone();
         let _ = thread::spawn(move || {
-            let a = l1.lock().unwrap();
-
-            a.log(Level::InfoLevel, "thread info".as_bytes());
+            l1.log(Level::InfoLevel, "thread info".as_bytes());
         })
         .join();
 
@@ -190,23 +657,68 @@ mod tests {
 
     #[test]
     fn test_global_logger() {
-        let a = global_logger().clone();
-        if let Ok(ref mut m) = a.clone().lock() {
-            m.info(b"hello");
-        }
+        global_logger().info(b"hello");
     }
 
     #[test]
     fn test_set_global_logger() -> Result<()> {
-        let l = new_logger::<String>(Some(Options::new()))?;
-        let helper = Helper::new(l).with_error("aa".to_string());
+        let _global = GLOBAL.lock().unwrap_or_else(|e| e.into_inner());
+        let l = new_logger(Some(Options::new()))?;
+        let helper = Helper::new(l).with_error("aa");
         set_global_logger(helper)?;
+        global_logger().info(b"hello");
+
+        // replaced while in use
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let l = new_logger(Some(Options::new().with_out(out.clone())))?;
+        let used = global_logger();
+        set_global_logger(Helper::new(l))?;
+        used.info(b"replaced");
+        global_logger().log_target(Level::InfoLevel, "swap", b"swapped");
+
+        // the level raised while in use
+        global_logger().log_target(Level::DebugLevel, "swap", b"quiet");
+        set_global_level(Level::DebugLevel);
+        global_logger().log_target(Level::DebugLevel, "swap", b"verbose");
+        set_global_filter(Filter::parse("swap=error")?);
+        global_logger().log_target(Level::WarnLevel, "swap", b"filtered");
+
+        let out = String::from_utf8(out.lock().unwrap().clone())?;
+        assert!(out.contains(" target=swap swapped\n"), "{}", out);
+        assert!(!out.contains("replaced"));
+        assert!(
+            !out.contains("quiet") && !out.contains("filtered"),
+            "{}",
+            out
+        );
+        assert!(out.contains(" target=swap verbose\n"), "{}", out);
 
-        let a = global_logger().clone();
-        if let Ok(ref mut m) = a.clone().lock() {
-            m.info(b"hello");
-        }
+        Ok(())
+    }
 
+    #[test]
+    fn test_swap_global_logger() -> Result<()> {
+        let _global = GLOBAL.lock().unwrap_or_else(|e| e.into_inner());
+        let first = CaptureWriter::new();
+        set_global_logger(Helper::new(new_logger(Some(first.options()))?))?;
+        let old = global_logger();
+
+        let second = CaptureWriter::new();
+        set_global_logger(Helper::new(new_logger(Some(second.options()))?))?;
+        let new = global_logger();
+        assert!(!Arc::ptr_eq(&old, &new));
+
+        // the one taken before the swap keeps writing to its own output
+        old.log_target(Level::InfoLevel, "swap", b"from the old logger");
+        new.log_target(Level::InfoLevel, "swap", b"from the new logger");
+        global_logger().log_target(Level::InfoLevel, "swap", b"from the global logger");
+
+        assert!(first.contains("from the old logger"));
+        assert!(!first.contains("from the new logger"));
+        assert!(!first.contains("from the global logger"));
+        assert!(second.contains("from the new logger"));
+        assert!(second.contains("from the global logger"));
+        assert!(!second.contains("from the old logger"));
         Ok(())
     }
 }
@@ -1,103 +1,183 @@
 #[macro_export]
 macro_rules! trace {
+    (target: $target:expr, $($arg:tt)+) => ({
+        $crate::global_logger().log_target($crate::level::Level::TraceLevel, $target, std::format!($($arg)+).as_bytes());
+    });
+    ($key:ident = $($rest:tt)+) => (
+        $crate::__log_fields!(@split $crate::level::Level::TraceLevel, [] $key = $($rest)+)
+    );
     () => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.trace(b"\n");
-        }
+        $crate::global_logger().log_target($crate::level::Level::TraceLevel, std::module_path!(), b"\n");
     });
     ($($arg:tt)*) => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.trace(std::format!($($arg)*).as_bytes());
-        }
+        $crate::global_logger().log_target($crate::level::Level::TraceLevel, std::module_path!(), std::format!($($arg)*).as_bytes());
     })
 }
 
 #[macro_export]
 macro_rules! debug {
+    (target: $target:expr, $($arg:tt)+) => ({
+        $crate::global_logger().log_target($crate::level::Level::DebugLevel, $target, std::format!($($arg)+).as_bytes());
+    });
+    ($key:ident = $($rest:tt)+) => (
+        $crate::__log_fields!(@split $crate::level::Level::DebugLevel, [] $key = $($rest)+)
+    );
     () => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.debug(b"\n");
-        }
+        $crate::global_logger().log_target($crate::level::Level::DebugLevel, std::module_path!(), b"\n");
     });
     ($($arg:tt)*) => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.debug(std::format!($($arg)*).as_bytes());
-        }
+        $crate::global_logger().log_target($crate::level::Level::DebugLevel, std::module_path!(), std::format!($($arg)*).as_bytes());
     })
 }
 
 #[macro_export]
 macro_rules! info {
+    (target: $target:expr, $($arg:tt)+) => ({
+        $crate::global_logger().log_target($crate::level::Level::InfoLevel, $target, std::format!($($arg)+).as_bytes());
+    });
+    ($key:ident = $($rest:tt)+) => (
+        $crate::__log_fields!(@split $crate::level::Level::InfoLevel, [] $key = $($rest)+)
+    );
     () => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.info(b"\n");
-        }
+        $crate::global_logger().log_target($crate::level::Level::InfoLevel, std::module_path!(), b"\n");
     });
     ($($arg:tt)*) => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.info(std::format!($($arg)*).as_bytes());
-        }
+        $crate::global_logger().log_target($crate::level::Level::InfoLevel, std::module_path!(), std::format!($($arg)*).as_bytes());
     })
 }
 
 #[macro_export]
 macro_rules! warn {
+    (target: $target:expr, $($arg:tt)+) => ({
+        $crate::global_logger().log_target($crate::level::Level::WarnLevel, $target, std::format!($($arg)+).as_bytes());
+    });
+    ($key:ident = $($rest:tt)+) => (
+        $crate::__log_fields!(@split $crate::level::Level::WarnLevel, [] $key = $($rest)+)
+    );
     () => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.warn(b"\n");
-        }
+        $crate::global_logger().log_target($crate::level::Level::WarnLevel, std::module_path!(), b"\n");
     });
     ($($arg:tt)*) => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.warn(std::format!($($arg)*).as_bytes());
-        }
+        $crate::global_logger().log_target($crate::level::Level::WarnLevel, std::module_path!(), std::format!($($arg)*).as_bytes());
     })
 }
 
 #[macro_export]
 macro_rules! error {
+    (target: $target:expr, $($arg:tt)+) => ({
+        $crate::global_logger().log_target($crate::level::Level::ErrorLevel, $target, std::format!($($arg)+).as_bytes());
+    });
+    ($key:ident = $($rest:tt)+) => (
+        $crate::__log_fields!(@split $crate::level::Level::ErrorLevel, [] $key = $($rest)+)
+    );
     () => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.error(b"\n");
-        }
+        $crate::global_logger().log_target($crate::level::Level::ErrorLevel, std::module_path!(), b"\n");
     });
     ($($arg:tt)*) => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.error(std::format!($($arg)*).as_bytes());
-        }
+        $crate::global_logger().log_target($crate::level::Level::ErrorLevel, std::module_path!(), std::format!($($arg)*).as_bytes());
     })
 }
 
 #[macro_export]
 macro_rules! fatal {
+    (target: $target:expr, $($arg:tt)+) => ({
+        $crate::global_logger().log_target($crate::level::Level::FatalLevel, $target, std::format!($($arg)+).as_bytes());
+    });
+    ($key:ident = $($rest:tt)+) => (
+        $crate::__log_fields!(@split $crate::level::Level::FatalLevel, [] $key = $($rest)+)
+    );
     () => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.fatal(b"\n");
-        }
+        $crate::global_logger().log_target($crate::level::Level::FatalLevel, std::module_path!(), b"\n");
     });
     ($($arg:tt)*) => ({
-        let g = $crate::global_logger().clone();
-        if let Ok(ref mut m) = g.clone().lock() {
-            m.fatal(std::format!($($arg)*).as_bytes());
-        }
+        $crate::global_logger().log_target($crate::level::Level::FatalLevel, std::module_path!(), std::format!($($arg)*).as_bytes());
     })
 }
 
+/// logs a [`Status`] with its id, code, detail and position as fields, at a
+/// level of its code, see [`Helper::status`]
+///
+/// ```rust
+/// # use errors::Status;
+/// logger::log_status!(Status::service_unavailable("io.vine.broker", "no subscriber"));
+/// ```
+///
+/// [`Status`]: errors::Status
+/// [`Helper::status`]: crate::helper::Helper::status
+#[macro_export]
+macro_rules! log_status {
+    (target: $target:expr, $status:expr) => ({
+        $crate::global_logger().status_target($target, &$status);
+    });
+    ($status:expr) => ({
+        $crate::global_logger().status_target(std::module_path!(), &$status);
+    })
+}
+
+/// writes that the actor did the action on the resource with the outcome
+/// through the global audit logger, see [`AuditLogger`]
+///
+/// ```rust
+/// # use logger::audit::Outcome;
+/// logger::audit!("admin", "deregister", "io.vine.greeter", Outcome::Success);
+/// ```
+///
+/// [`AuditLogger`]: crate::audit::AuditLogger
+#[macro_export]
+macro_rules! audit {
+    ($actor:expr, $action:expr, $resource:expr, $outcome:expr $(,)?) => ({
+        $crate::audit::audit_logger().audit(&$actor, &$action, &$resource, $outcome);
+    });
+}
+
+/// splits the fields from the message at the `;`, then logs the entry
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_fields {
+    (@split $level:expr, [$($fields:tt)*] ; $($arg:tt)+) => ({
+        let mut fields = std::collections::HashMap::new();
+        $crate::__fields!(fields; $($fields)*);
+        $crate::global_logger().log_fields($level, std::module_path!(), fields, std::format!($($arg)+).as_bytes());
+    });
+    (@split $level:expr, [$($fields:tt)*] $next:tt $($rest:tt)*) => (
+        $crate::__log_fields!(@split $level, [$($fields)* $next] $($rest)*)
+    );
+}
+
+/// inserts the `key = value` fields to the map, as the [`Value`] of the
+/// value, or its `Display` text for `key = %value` and its `Debug` text for
+/// `key = ?value`
+///
+/// [`Value`]: crate::value::Value
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __fields {
+    ($map:ident;) => ();
+    ($map:ident; $key:ident = %$value:expr $(, $($rest:tt)*)?) => ({
+        $map.insert(std::stringify!($key).to_string(), $crate::value::Value::display(&$value));
+        $crate::__fields!($map; $($($rest)*)?);
+    });
+    ($map:ident; $key:ident = ?$value:expr $(, $($rest:tt)*)?) => ({
+        $map.insert(std::stringify!($key).to_string(), $crate::value::Value::from(std::format!("{:?}", $value)));
+        $crate::__fields!($map; $($($rest)*)?);
+    });
+    ($map:ident; $key:ident = $value:expr $(, $($rest:tt)*)?) => ({
+        $map.insert(std::stringify!($key).to_string(), $crate::value::Value::from($value));
+        $crate::__fields!($map; $($($rest)*)?);
+    });
+}
+
 #[cfg(test)]
 mod test {
+    use crate::{helper::Helper, new_logger, set_global_logger, test::CaptureWriter, tests::GLOBAL};
+
     #[test]
     fn test_macro_rule() {
+        let _global = GLOBAL.lock().unwrap_or_else(|e| e.into_inner());
+        let capture = CaptureWriter::new();
+        let l = new_logger(Some(capture.options())).unwrap();
+        set_global_logger(Helper::new(l)).unwrap();
+
         trace!();
         trace!("trace");
         debug!();
@@ -108,5 +188,43 @@ mod test {
         warn!("warn");
         error!();
         error!("error");
+        info!(target: "registry::etcd", "info {}", "etcd");
+        let node = ("node", 1);
+        info!(service = %"greeter", node = ?node, port = 8080; "registered {}", "greeter");
+        warn!(attempt = 2,; "retry");
+        info!("after the fields");
+
+        // the fields of an entry are written with it alone
+        let entry = |msg: &str| {
+            let entries = capture.entries();
+            entries.iter().find(|e| e.ends_with(msg)).cloned().unwrap_or_default()
+        };
+        let registered = entry(" registered greeter");
+        assert!(registered.contains(" level=info "), "{}", registered);
+        // `%` writes the Display text, `?` the Debug text
+        assert!(registered.contains(" service=greeter "), "{}", registered);
+        assert!(registered.contains(r#" node=("node", 1) "#), "{}", registered);
+        assert!(registered.contains(" port=8080 "), "{}", registered);
+        assert!(!registered.contains("attempt="), "{}", registered);
+
+        let retry = entry(" retry");
+        assert!(retry.contains(" level=warn "), "{}", retry);
+        assert!(retry.contains(" attempt=2 "), "{}", retry);
+        for key in ["service=", "node=", "port="] {
+            assert!(!retry.contains(key), "{}", retry);
+        }
+        let after = entry(" after the fields");
+        assert!(!after.is_empty());
+        assert!(!after.contains("attempt=") && !after.contains("service="), "{}", after);
+
+        info!(name = %"ann", quoted = ?"ann"; "display and debug");
+        let names = entry(" display and debug");
+        assert!(names.contains(" name=ann "), "{}", names);
+        assert!(names.contains(r#" quoted="ann" "#), "{}", names);
+        let status = errors::Status::bad_request("io.vine", "name is empty");
+        log_status!(status);
+        log_status!(target: "registry", &status);
+        let user = "ann".to_string();
+        audit!(user, "login", "io.vine.auth", crate::audit::Outcome::Denied);
     }
 }
\ No newline at end of file
@@ -1,48 +1,275 @@
 use std::{
     collections::HashMap,
-    io::{self, Write},
-    sync::{Arc, Mutex},
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicI8, Ordering},
+        Arc, Mutex, RwLock,
+    },
 };
 
-use crate::level::Level;
+use std::path::Path;
+
+use errors::Result;
+
+use crate::{
+    filter::Filter,
+    level::Level,
+    record::{Hook, Record},
+    redact::Redact,
+    rolling::{RollingFile, RotationPolicy},
+    value::Value,
+};
+
+/// the format the entries are written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// the time, then the sorted `key=value` fields and the message
+    #[default]
+    Text,
+    /// a JSON object per line with the `timestamp`, `level`, `file`,
+    /// `message` and the other `fields`, for log collectors to ingest as is
+    Json,
+}
+
+impl Format {
+    /// converts a format string into a Format value.
+    /// returns an error if the input string does not match known values.
+    pub fn from(fs: impl Into<String>) -> Result<Format> {
+        let s = fs.into();
+        match s.as_str() {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(errors::err!("Unknown Format String: '{}'", s.as_str())),
+        }
+    }
+}
+
+/// the digits of the seconds in the time of the entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    Seconds,
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl Precision {
+    /// the default format of the time of the text entries
+    pub(crate) fn text_format(&self) -> &'static str {
+        match self {
+            Precision::Seconds => "%Y-%m-%d %H:%M:%S",
+            Precision::Millis => "%Y-%m-%d %H:%M:%S%.3f",
+            Precision::Micros => "%Y-%m-%d %H:%M:%S%.6f",
+            Precision::Nanos => "%Y-%m-%d %H:%M:%S%.9f",
+        }
+    }
+
+    pub(crate) fn seconds_format(&self) -> chrono::SecondsFormat {
+        match self {
+            Precision::Seconds => chrono::SecondsFormat::Secs,
+            Precision::Millis => chrono::SecondsFormat::Millis,
+            Precision::Micros => chrono::SecondsFormat::Micros,
+            Precision::Nanos => chrono::SecondsFormat::Nanos,
+        }
+    }
+}
+
+/// what the helper does after logging a fatal entry
+#[derive(Clone)]
+pub enum Fatal {
+    /// exits the process with the code, skipping the destructors
+    Exit(i32),
+    /// panics, so the destructors run and a test can catch it
+    Panic,
+    /// calls the function and goes on
+    Callback(Arc<dyn Fn() + Send + Sync>),
+}
+
+impl Default for Fatal {
+    fn default() -> Self {
+        Fatal::Exit(1)
+    }
+}
 
 #[derive(Clone)]
-pub struct Options<T: Into<String> + Clone + Send> {
-    /// the logging level the logger should log at. default is `InfoLevel`
-    level: Level,
+pub struct Options {
+    /// the logging level the logger should log at. default is `InfoLevel`.
+    /// The clones share it, [`set_level`](Options::set_level) changes the
+    /// level of the logger they are in.
+    level: Arc<AtomicI8>,
+
+    /// the format of the entries. default is `Text`
+    format: Format,
+
+    /// the `strftime` format of the time of the text entries. default is
+    /// `%Y-%m-%d %H:%M:%S` with the fraction of the precision
+    time_format: Option<String>,
+
+    /// whether the time is in UTC rather than the local time. default is false
+    utc: bool,
+
+    /// the digits of the seconds when the time format is not set. default
+    /// is seconds in text and millis in JSON
+    precision: Option<Precision>,
+
+    /// whether the text entries are colored. default is whether stdout is
+    /// a terminal
+    color: bool,
 
     skip: i32,
 
+    /// whether the file of an entry logged without one, as by
+    /// [`Logger::log`](crate::Logger::log), is found walking the stack
+    /// `skip` frames up. default is false, the helper and the macros pass
+    /// the file they were called from.
+    backtrace: bool,
+
+    /// the levels of the targets, over `level` for the targets it is for.
+    /// The clones share it as the level.
+    filter: Arc<RwLock<Option<Filter>>>,
+
+    /// what is done after a fatal entry. default is `Exit(1)`
+    fatal: Fatal,
+
+    /// the fields masked before the entries are written
+    redact: Option<Redact>,
+
+    /// the hooks called with the entries at or above their level
+    hooks: Vec<(Level, Hook)>,
+
     /// fields to always be logged
-    fields: Arc<Mutex<HashMap<String, T>>>,
+    fields: Arc<Mutex<HashMap<String, Value>>>,
 
     /// It's common to set this to a file, or leave it default which is `io::Stdout`
     out: Arc<Mutex<dyn Write + Send>>,
 }
 
-impl<T> Options<T>
-where
-    T: Into<String> + Clone + Send,
-{
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Options {
     pub fn new() -> Self {
         let out = io::stdout();
         Options {
-            level: Level::InfoLevel,
+            level: Arc::new(AtomicI8::new(Level::InfoLevel.into())),
+            format: Format::Text,
+            time_format: None,
+            utc: false,
+            precision: None,
+            color: out.is_terminal(),
             skip: 2,
+            backtrace: false,
+            filter: Arc::new(RwLock::new(None)),
+            fatal: Fatal::default(),
+            redact: None,
+            hooks: Vec::new(),
             fields: Arc::new(Mutex::new(HashMap::new())),
             out: Arc::new(Mutex::new(out)),
         }
     }
 
     pub fn level(&self) -> Level {
-        self.level.clone()
+        self.level.load(Ordering::Relaxed).into()
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn time_format(&self) -> Option<&str> {
+        self.time_format.as_deref()
+    }
+
+    pub fn utc(&self) -> bool {
+        self.utc
+    }
+
+    pub fn precision(&self) -> Option<Precision> {
+        self.precision
+    }
+
+    pub fn color(&self) -> bool {
+        self.color
     }
 
     pub fn skip(&self) -> i32 {
         self.skip
     }
 
-    pub fn fields(&self) -> HashMap<String, T> {
+    pub fn backtrace(&self) -> bool {
+        self.backtrace
+    }
+
+    pub fn filter(&self) -> Option<Filter> {
+        self.read_filter().clone()
+    }
+
+    fn read_filter(&self) -> std::sync::RwLockReadGuard<'_, Option<Filter>> {
+        self.filter.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// returns true if the entries of `level` logged from `target` are
+    /// written, by the filter when it is for `target` or else the level
+    pub fn enabled(&self, target: Option<&str>, level: &Level) -> bool {
+        self.read_filter()
+            .as_ref()
+            .and_then(|f| f.level(target))
+            .unwrap_or_else(|| self.level())
+            .enabled(level)
+    }
+
+    /// the most verbose level an entry of any target is written at
+    pub fn max_level(&self) -> Level {
+        let mut max = self.level();
+        if let Some(filter) = self.read_filter().as_ref() {
+            if filter.level(None).is_some() {
+                max = Level::FatalLevel;
+            }
+            if let Some(level) = filter.max_level() {
+                if level < max {
+                    max = level;
+                }
+            }
+        }
+        max
+    }
+
+    pub fn fatal(&self) -> Fatal {
+        self.fatal.clone()
+    }
+
+    pub fn redact(&self) -> Option<&Redact> {
+        self.redact.as_ref()
+    }
+
+    /// the hooks with the level they are called at
+    pub fn hooks(&self) -> &[(Level, Hook)] {
+        &self.hooks
+    }
+
+    /// the fields of an entry of `level` logged with `with`, redacted, or
+    /// `None` when the entry is not written
+    pub(crate) fn entry_fields(
+        &self,
+        level: &Level,
+        with: &HashMap<String, Value>,
+    ) -> Option<HashMap<String, Value>> {
+        let mut fields = self.fields();
+        fields.extend(with.iter().map(|(k, v)| (k.clone(), v.clone())));
+        let target = fields.get("target").and_then(Value::as_str);
+        if !self.enabled(target, level) {
+            return None;
+        }
+        if let Some(redact) = &self.redact {
+            redact.apply(&mut fields);
+        }
+        Some(fields)
+    }
+
+    pub fn fields(&self) -> HashMap<String, Value> {
         let rc = self.fields.clone();
         if let Ok(ref mut out) = rc.lock() {
             return out.clone();
@@ -57,7 +284,101 @@ where
     /// set default level for the logger
     #[inline]
     pub fn with_level(mut self, level: Level) -> Self {
-        self.level = level;
+        self.level = Arc::new(AtomicI8::new(level.into()));
+        self
+    }
+
+    /// changes the level of these options and of their clones, as of the
+    /// logger they were given to. The filter takes it as the level of the
+    /// targets it has no directive for, the entries see both or none.
+    pub fn set_level(&self, level: Level) {
+        let mut filter = self.filter.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(f) = filter.take() {
+            *filter = Some(f.with_default(level.clone()));
+        }
+        self.level.store(level.into(), Ordering::Relaxed);
+    }
+
+    /// changes the filter of these options and of their clones
+    pub fn set_filter(&self, filter: Option<Filter>) {
+        let mut f = self.filter.write().unwrap_or_else(|e| e.into_inner());
+        *f = filter;
+    }
+
+    /// set the format of the entries
+    #[inline]
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// set the filter picking the level by the target of the entries
+    #[inline]
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = Arc::new(RwLock::new(Some(filter)));
+        self
+    }
+
+    /// set the `strftime` format of the time of the text entries, as `%+`
+    /// for RFC 3339. The JSON entries are always in RFC 3339.
+    #[inline]
+    pub fn with_time_format(mut self, format: impl Into<String>) -> Self {
+        self.time_format = Some(format.into());
+        self
+    }
+
+    /// set whether the time of the entries is in UTC
+    #[inline]
+    pub fn with_utc(mut self, utc: bool) -> Self {
+        self.utc = utc;
+        self
+    }
+
+    /// set the digits of the seconds in the time of the entries, unless a
+    /// time format is set for the text entries
+    #[inline]
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    /// set whether the level is colored and the file dimmed in the text
+    /// entries, for a terminal to show
+    #[inline]
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// set what is done after a fatal entry
+    #[inline]
+    pub fn with_fatal(mut self, fatal: Fatal) -> Self {
+        self.fatal = fatal;
+        self
+    }
+
+    /// set the fields masked before the entries are written and given to
+    /// the hooks, as `Redact::new(["password", "token", "*_secret"])`
+    #[inline]
+    pub fn with_redact(mut self, redact: Redact) -> Self {
+        self.redact = Some(redact);
+        self
+    }
+
+    /// add a hook called with every entry at or above `level` before it is
+    /// written, to report errors or count them. The hook is called with the
+    /// logger locked, it must not log through the same logger.
+    #[inline]
+    pub fn with_hook(mut self, level: Level, hook: Box<dyn Fn(&Record<'_>) + Send + Sync>) -> Self {
+        self.hooks.push((level, Arc::from(hook)));
+        self
+    }
+
+    /// set whether the file of the entries logged without one is found by a
+    /// backtrace, which costs far more than the entry
+    #[inline]
+    pub fn with_backtrace(mut self, backtrace: bool) -> Self {
+        self.backtrace = backtrace;
         self
     }
 
@@ -70,27 +391,41 @@ where
 
     /// set default fields for the logger
     #[inline]
-    pub fn with_fields(mut self, fields: HashMap<String, T>) -> Self {
+    pub fn with_fields(mut self, fields: HashMap<String, Value>) -> Self {
         self.fields = Arc::new(Mutex::new(fields));
         self
     }
 
     /// insert key and value to the Options
     #[inline]
-    pub fn insert_field(self, k: String, v: T) -> Self {
+    pub fn insert_field(self, k: String, v: impl Into<Value>) -> Self {
         let rc = &self.fields.clone();
         if let Ok(ref mut m) = rc.lock() {
-            m.insert(k, v);
+            m.insert(k, v.into());
         };
         self
     }
 
-    /// set default output for the logger
+    /// set default output for the logger, uncolored unless set after
     #[inline]
     pub fn with_out(mut self, out: Arc<Mutex<dyn Write + Send>>) -> Self {
         self.out = out;
+        self.color = false;
         self
     }
+
+    /// set the output to the file at `path`, moved aside and compressed as
+    /// `policy` says with the newest `keep` segments kept, see
+    /// [`RollingFile`]
+    pub fn with_file<P: AsRef<Path>>(
+        self,
+        path: P,
+        policy: RotationPolicy,
+        keep: usize,
+    ) -> Result<Self> {
+        let file = RollingFile::new(path, policy, keep)?;
+        Ok(self.with_out(Arc::new(Mutex::new(file))))
+    }
 }
 
 #[cfg(test)]
@@ -101,22 +436,30 @@ mod test {
         sync::{Arc, Mutex},
     };
 
-    use crate::level::Level;
+    use crate::{filter::Filter, level::Level};
 
-    use super::Options;
+    use super::{Format, Options};
 
     #[test]
     fn test_new() {
-        let opt: Options<String> = Options::new();
+        let opt: Options = Options::new();
         assert_eq!(opt.level(), Level::InfoLevel);
+        assert_eq!(opt.format(), Format::Text);
+    }
+
+    #[test]
+    fn test_format_from() {
+        assert_eq!(Format::from("json").unwrap(), Format::Json);
+        assert_eq!(Format::from("text").unwrap(), Format::Text);
+        assert!(Format::from("xml").is_err());
     }
 
     #[test]
     fn test_build() {
         let mut m = HashMap::new();
-        m.insert("k".to_string(), "v".to_string());
+        m.insert("k".to_string(), "v".into());
         let mc = m.clone();
-        let mut opt: Options<String> = Options::new()
+        let mut opt: Options = Options::new()
             .with_level(Level::ErrorLevel)
             .with_out(Arc::new(Mutex::new(io::stdout())));
 
@@ -127,9 +470,24 @@ mod test {
         assert_ne!(opt.fields(), mc);
     }
 
+    #[test]
+    fn test_filter() {
+        let opt: Options = Options::new()
+            .with_level(Level::WarnLevel)
+            .with_filter(Filter::parse("registry=debug").unwrap());
+        assert!(opt.enabled(Some("registry::etcd"), &Level::DebugLevel));
+        assert!(!opt.enabled(Some("broker"), &Level::InfoLevel));
+        assert!(!opt.enabled(None, &Level::InfoLevel));
+        assert_eq!(opt.max_level(), Level::DebugLevel);
+
+        let opt = opt.with_filter(Filter::parse("error").unwrap());
+        assert!(!opt.enabled(Some("broker"), &Level::WarnLevel));
+        assert_eq!(opt.max_level(), Level::ErrorLevel);
+    }
+
     #[test]
     fn test_out() {
-        let opt: Options<String> = Options::new();
+        let opt: Options = Options::new();
         let rc = opt.out().clone();
         if let Ok(ref mut writer) = rc.lock() {
             let result = writer.write(b"buf\n");
@@ -0,0 +1,56 @@
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+use chrono::prelude::*;
+
+use crate::{level::Level, value::Value};
+
+/// the hook of [`Options::with_hook`](crate::options::Options::with_hook)
+pub type Hook = Arc<dyn Fn(&Record<'_>) + Send + Sync>;
+
+/// Record is an entry as the hooks see it, before it is written
+#[derive(Debug, Clone)]
+pub struct Record<'a> {
+    level: Level,
+    time: DateTime<Local>,
+    fields: &'a HashMap<String, Value>,
+    message: &'a [u8],
+}
+
+impl<'a> Record<'a> {
+    pub(crate) fn new(
+        level: Level,
+        time: DateTime<Local>,
+        fields: &'a HashMap<String, Value>,
+        message: &'a [u8],
+    ) -> Self {
+        Record {
+            level,
+            time,
+            fields,
+            message,
+        }
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn time(&self) -> DateTime<Local> {
+        self.time
+    }
+
+    /// the fields of the entry, with its `level` and `file`
+    pub fn fields(&self) -> &HashMap<String, Value> {
+        self.fields
+    }
+
+    pub fn field(&self, key: &str) -> Option<&Value> {
+        self.fields.get(key)
+    }
+
+    /// the message without the newline ending it
+    pub fn message(&self) -> Cow<'a, str> {
+        let message = self.message.strip_suffix(b"\n").unwrap_or(self.message);
+        String::from_utf8_lossy(message)
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use crate::value::Value;
+
+/// the value the redacted fields are written with
+pub const MASK: &str = "[REDACTED]";
+
+/// Redact masks the values of the fields whose key matches one of its
+/// patterns before the entry is written. A pattern matches the key without
+/// regard to case, a `*` in it matching any run of characters.
+///
+/// ```rust
+/// # use logger::redact::Redact;
+/// let redact = Redact::new(["password", "token", "*_secret"]);
+/// assert!(redact.matches("Password"));
+/// assert!(redact.matches("client_secret"));
+/// assert!(!redact.matches("user"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Redact {
+    patterns: Vec<String>,
+}
+
+impl Redact {
+    pub fn new<I, S>(patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Redact {
+            patterns: patterns
+                .into_iter()
+                .map(|p| p.into().to_lowercase())
+                .collect(),
+        }
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    /// returns true if the field of `key` is masked
+    pub fn matches(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        self.patterns
+            .iter()
+            .any(|p| glob(p.as_bytes(), key.as_bytes()))
+    }
+
+    /// masks the values of the matching fields
+    pub fn apply(&self, fields: &mut HashMap<String, Value>) {
+        for (k, v) in fields.iter_mut() {
+            if self.matches(k) {
+                *v = MASK.into();
+            }
+        }
+    }
+}
+
+/// returns true if `s` matches `pattern`, a `*` matching any run
+fn glob(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // the position of the last star and of `s` when it was met
+    let mut star = None;
+    while i < s.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, i));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == s[i] {
+            p += 1;
+            i += 1;
+        } else if let Some((sp, si)) = star {
+            p = sp + 1;
+            i = si + 1;
+            star = Some((sp, si + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{glob, Redact, MASK};
+
+    #[test]
+    fn test_glob() {
+        assert!(glob(b"token", b"token"));
+        assert!(!glob(b"token", b"tokens"));
+        assert!(glob(b"*_secret", b"client_secret"));
+        assert!(!glob(b"*_secret", b"secret"));
+        assert!(glob(b"*key*", b"api_key_id"));
+        assert!(glob(b"a*b*c", b"aXbYbc"));
+        assert!(glob(b"*", b""));
+    }
+
+    #[test]
+    fn test_apply() {
+        let redact = Redact::new(["password", "*_TOKEN"]);
+        let mut fields = HashMap::new();
+        fields.insert("Password".to_string(), "hunter2".into());
+        fields.insert("access_token".to_string(), 42.into());
+        fields.insert("user".to_string(), "vine".into());
+        redact.apply(&mut fields);
+
+        assert_eq!(fields["Password"], MASK);
+        assert_eq!(fields["access_token"], MASK);
+        assert_eq!(fields["user"], "vine");
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::prelude::*;
+use flate2::{write::GzEncoder, Compression};
+
+/// when a [`RollingFile`] starts a new segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationPolicy {
+    /// the first write of every day starts a segment
+    Daily,
+    /// a write taking the segment past the bytes starts a segment, an entry
+    /// is never split over two
+    MaxSize(u64),
+}
+
+/// RollingFile writes to the file at a path, moving it aside when the
+/// [`RotationPolicy`] says so. The segments moved aside are named after the
+/// file with the day, or the time for `MaxSize`, appended and compressed
+/// with gzip, as `vine.log.2021-09-30.gz`; only the newest `keep` are kept.
+///
+/// ```rust,no_run
+/// # use logger::rolling::{RollingFile, RotationPolicy};
+/// # use std::io::Write;
+/// let mut file = RollingFile::new("logs/vine.log", RotationPolicy::MaxSize(100 << 20), 7)?;
+/// file.write_all(b"hello\n")?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct RollingFile {
+    path: PathBuf,
+    policy: RotationPolicy,
+    keep: usize,
+    file: File,
+    /// the bytes in the segment
+    size: u64,
+    /// the day the segment was started
+    day: NaiveDate,
+}
+
+impl RollingFile {
+    /// opens the file at `path` for appending, creating it and its
+    /// directory when missing
+    pub fn new<P: AsRef<Path>>(path: P, policy: RotationPolicy, keep: usize) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+
+        let file = open(&path)?;
+        let metadata = file.metadata()?;
+        let day = match metadata.modified() {
+            Ok(t) if metadata.len() > 0 => DateTime::<Local>::from(t).naive_local().date(),
+            _ => Local::now().naive_local().date(),
+        };
+        Ok(RollingFile {
+            path,
+            policy,
+            keep,
+            file,
+            size: metadata.len(),
+            day,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn should_rotate(&self, now: &DateTime<Local>, len: usize) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        match self.policy {
+            RotationPolicy::Daily => now.naive_local().date() != self.day,
+            RotationPolicy::MaxSize(max) => self.size + len as u64 > max,
+        }
+    }
+
+    /// moves the segment aside compressed, then opens an empty one
+    fn rotate(&mut self, now: &DateTime<Local>) -> io::Result<()> {
+        self.file.flush()?;
+        let stamp = match self.policy {
+            RotationPolicy::Daily => self.day.format("%Y-%m-%d").to_string(),
+            RotationPolicy::MaxSize(_) => now.format("%Y-%m-%dT%H-%M-%S-%3f").to_string(),
+        };
+        let segment = self.segment(&stamp);
+        fs::rename(&self.path, &segment)?;
+
+        self.file = open(&self.path)?;
+        self.size = 0;
+        self.day = now.naive_local().date();
+
+        compress(&segment)?;
+        self.prune()
+    }
+
+    /// the path free for the segment of `stamp`, numbered when a segment of
+    /// it exists
+    fn segment(&self, stamp: &str) -> PathBuf {
+        let mut n = 0;
+        loop {
+            let name = match n {
+                0 => format!("{}.{}", self.file_name(), stamp),
+                n => format!("{}.{}.{}", self.file_name(), stamp, n),
+            };
+            let segment = self.path.with_file_name(name);
+            if !segment.exists() && !gz(&segment).exists() {
+                return segment;
+            }
+            n += 1;
+        }
+    }
+
+    fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// removes the compressed segments past the newest `keep`
+    fn prune(&self) -> io::Result<()> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let prefix = format!("{}.", self.file_name());
+
+        // oldest first, by the stamp then the number of the name
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let stamp = match name
+                .strip_prefix(&prefix)
+                .and_then(|n| n.strip_suffix(".gz"))
+            {
+                Some(stamp) => stamp.to_string(),
+                None => continue,
+            };
+            let (stamp, n) = match stamp.rsplit_once('.') {
+                Some((stamp, n)) => (stamp.to_string(), n.parse::<u32>().unwrap_or(0)),
+                None => (stamp, 0),
+            };
+            segments.push((stamp, n, entry.path()));
+        }
+        segments.sort();
+
+        let remove = segments.len().saturating_sub(self.keep);
+        for (_, _, path) in segments.into_iter().take(remove) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = Local::now();
+        if self.should_rotate(&now, buf.len()) {
+            self.rotate(&now)?;
+        }
+
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn gz(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+/// replaces the file at `path` with its gzip next to it
+fn compress(path: &Path) -> io::Result<()> {
+    let mut from = File::open(path)?;
+    let mut to = GzEncoder::new(File::create(gz(path))?, Compression::default());
+    io::copy(&mut from, &mut to)?;
+    to.finish()?.sync_all()?;
+    fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        io::{Read, Write},
+        path::PathBuf,
+    };
+
+    use chrono::{Duration, Local};
+    use flate2::read::GzDecoder;
+
+    use super::{RollingFile, RotationPolicy};
+
+    fn dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("vine-rolling-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn segments(dir: &PathBuf) -> Vec<PathBuf> {
+        let mut segments: Vec<PathBuf> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.extension().map(|e| e == "gz").unwrap_or(false))
+            .collect();
+        segments.sort();
+        segments
+    }
+
+    fn gunzip(path: &PathBuf) -> String {
+        let mut out = String::new();
+        GzDecoder::new(fs::File::open(path).unwrap())
+            .read_to_string(&mut out)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_max_size() {
+        let dir = dir("size");
+        let path = dir.join("vine.log");
+        let mut file = RollingFile::new(&path, RotationPolicy::MaxSize(10), 2).unwrap();
+        for entry in &["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write_all(entry.as_bytes()).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        let mut kept: Vec<String> = segments(&dir).iter().map(gunzip).collect();
+        kept.sort();
+        assert_eq!(kept, vec!["second\n", "third\n"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_daily() {
+        let dir = dir("daily");
+        let path = dir.join("vine.log");
+        let mut file = RollingFile::new(&path, RotationPolicy::Daily, 7).unwrap();
+        file.write_all(b"yesterday\n").unwrap();
+        file.write_all(b"still yesterday\n").unwrap();
+        let yesterday = Local::now().naive_local().date() - Duration::days(1);
+        file.day = yesterday;
+        file.write_all(b"today\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "today\n");
+        let segments = segments(&dir);
+        assert_eq!(
+            segments,
+            vec![dir.join(format!("vine.log.{}.gz", yesterday.format("%Y-%m-%d")))]
+        );
+        assert_eq!(gunzip(&segments[0]), "yesterday\nstill yesterday\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,367 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use errors::Result;
+use itertools::Itertools;
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+
+use crate::{global_logger, helper::Helper, level::Level, options::Options, value::Value, Logger};
+
+fn from_tracing(level: &tracing::Level) -> Level {
+    match *level {
+        tracing::Level::ERROR => Level::ErrorLevel,
+        tracing::Level::WARN => Level::WarnLevel,
+        tracing::Level::INFO => Level::InfoLevel,
+        tracing::Level::DEBUG => Level::DebugLevel,
+        tracing::Level::TRACE => Level::TraceLevel,
+    }
+}
+
+/// collects the fields of a span or an event, with the type they were
+/// recorded with
+struct Fields<'a>(&'a mut Vec<(String, Value)>);
+
+impl Visit for Fields<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.push((field.name().to_string(), value.into()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.push((field.name().to_string(), value.into()));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.push((field.name().to_string(), value.into()));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.push((field.name().to_string(), value.into()));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name().to_string(), value.into()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .push((field.name().to_string(), format!("{:?}", value).into()));
+    }
+}
+
+struct Span {
+    parent: Option<u64>,
+    fields: Vec<(String, Value)>,
+    /// the handles of the span, it is dropped with the last
+    refs: usize,
+}
+
+thread_local! {
+    /// the spans entered on the thread, innermost last
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// the [`Subscriber`] writing the events of `tracing` to a vine logger, the
+/// global one at the time of the event unless set. An event is logged with the fields of the spans
+/// it is in, inner spans winning, then its own fields and its target; its
+/// `message` field is the message of the entry.
+///
+/// ```rust
+/// # use logger::subscriber::LoggerSubscriber;
+/// tracing::subscriber::set_global_default(LoggerSubscriber::new())?;
+/// let span = tracing::info_span!("request", id = 7);
+/// let _enter = span.enter();
+/// tracing::info!(user = "vine", "served");
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct LoggerSubscriber {
+    logger: Option<Arc<Helper>>,
+    spans: Mutex<HashMap<u64, Span>>,
+    next: AtomicU64,
+}
+
+impl Default for LoggerSubscriber {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoggerSubscriber {
+    pub fn new() -> Self {
+        LoggerSubscriber {
+            logger: None,
+            spans: Mutex::new(HashMap::new()),
+            next: AtomicU64::new(1),
+        }
+    }
+
+    /// set the logger the events are written to
+    #[inline]
+    pub fn with_logger(mut self, logger: Arc<Helper>) -> Self {
+        self.logger = Some(logger);
+        self
+    }
+
+    fn logger(&self) -> Arc<Helper> {
+        match &self.logger {
+            Some(l) => l.clone(),
+            None => global_logger(),
+        }
+    }
+
+    fn spans(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Span>> {
+        self.spans.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn current() -> Option<u64> {
+        ENTERED.with(|e| e.borrow().last().copied())
+    }
+}
+
+impl Subscriber for LoggerSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.logger()
+            .options()
+            .enabled(Some(metadata.target()), &from_tracing(metadata.level()))
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let mut fields = Vec::new();
+        attrs.record(&mut Fields(&mut fields));
+        let parent = if attrs.is_contextual() {
+            Self::current()
+        } else {
+            attrs.parent().map(|id| id.into_u64())
+        };
+
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        self.spans().insert(
+            id,
+            Span {
+                parent,
+                fields,
+                refs: 1,
+            },
+        );
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        if let Some(span) = self.spans().get_mut(&span.into_u64()) {
+            values.record(&mut Fields(&mut span.fields));
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let mut fields = HashMap::new();
+
+        let mut parent = if event.is_contextual() {
+            Self::current()
+        } else {
+            event.parent().map(|id| id.into_u64())
+        };
+        {
+            let spans = self.spans();
+            let mut chain = Vec::new();
+            while let Some(span) = parent.and_then(|id| spans.get(&id)) {
+                chain.push(span);
+                parent = span.parent;
+            }
+            for span in chain.iter().rev() {
+                fields.extend(span.fields.iter().cloned());
+            }
+        }
+
+        let mut own = Vec::new();
+        event.record(&mut Fields(&mut own));
+        let mut message = String::new();
+        for (k, v) in own {
+            if k == "message" {
+                message = v.to_string();
+            } else {
+                fields.insert(k, v);
+            }
+        }
+        if let Some(file) = metadata.file() {
+            let file = match metadata.line() {
+                Some(line) => format!("{}:{}", file, line),
+                None => file.to_string(),
+            };
+            fields.insert("file".to_string(), file.into());
+        }
+
+        self.logger().log_fields(
+            from_tracing(metadata.level()),
+            metadata.target(),
+            fields,
+            message.as_bytes(),
+        );
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|e| e.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|e| {
+            let mut e = e.borrow_mut();
+            if let Some(i) = e.iter().rposition(|id| *id == span.into_u64()) {
+                e.remove(i);
+            }
+        });
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        if let Some(span) = self.spans().get_mut(&id.into_u64()) {
+            span.refs += 1;
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        let mut spans = self.spans();
+        let closed = match spans.get_mut(&id.into_u64()) {
+            Some(span) => {
+                span.refs -= 1;
+                span.refs == 0
+            }
+            None => false,
+        };
+        if closed {
+            spans.remove(&id.into_u64());
+        }
+        closed
+    }
+}
+
+/// makes a [`LoggerSubscriber`] of the global logger the global default of
+/// `tracing`. Fails when another subscriber is the default already.
+pub fn init_tracing() -> Result<()> {
+    tracing::subscriber::set_global_default(LoggerSubscriber::new())
+        .map_err(|e| errors::err!("init tracing failed: {}", e))
+}
+
+/// the implement of [`Logger`] emitting the entries as `tracing` events, so
+/// the logs of vine join the pipeline of an application using `tracing`.
+/// The fields are in the `fields` field of the event.
+#[derive(Clone)]
+pub struct TracingLogger {
+    opts: Options,
+}
+
+impl TracingLogger {
+    pub fn new(opts: Option<Options>) -> Self {
+        TracingLogger {
+            opts: opts.unwrap_or_default(),
+        }
+    }
+}
+
+impl Logger for TracingLogger {
+    fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.opts = opt.unwrap_or_default();
+        Ok(())
+    }
+
+    fn options(&self) -> Options {
+        self.opts.clone()
+    }
+
+    fn fields(&mut self, fields: HashMap<String, Value>) {
+        self.opts = self.opts.clone().with_fields(fields);
+    }
+
+    fn log(&self, level: Level, arg: &[u8]) {
+        self.log_with(level, &HashMap::new(), arg)
+    }
+
+    fn log_with(&self, level: Level, with: &HashMap<String, Value>, arg: &[u8]) {
+        let fields = match self.opts.entry_fields(&level, with) {
+            Some(fields) => fields,
+            None => return,
+        };
+
+        let message = String::from_utf8_lossy(arg);
+        let message = message.strip_suffix('\n').unwrap_or(&message);
+        let fields = fields
+            .iter()
+            .sorted_by(|a, b| a.0.cmp(b.0))
+            .map(|(k, v)| format!("{}={}", k, v))
+            .join(" ");
+
+        match level {
+            Level::TraceLevel => tracing::trace!(fields = %fields, "{}", message),
+            Level::DebugLevel => tracing::debug!(fields = %fields, "{}", message),
+            Level::InfoLevel => tracing::info!(fields = %fields, "{}", message),
+            Level::WarnLevel => tracing::warn!(fields = %fields, "{}", message),
+            Level::ErrorLevel | Level::FatalLevel => {
+                tracing::error!(fields = %fields, "{}", message)
+            }
+        }
+    }
+
+    fn string(&self) -> &'static str {
+        "tracing"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use errors::Result;
+
+    use super::{LoggerSubscriber, TracingLogger};
+    use crate::{helper::Helper, level::Level, new_logger, options::Options, Logger};
+
+    #[test]
+    fn test_subscriber() -> Result<()> {
+        let out = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let opts = Options::new().with_out(out.clone());
+        let helper = Helper::new(new_logger(Some(opts))?);
+        let subscriber = LoggerSubscriber::new().with_logger(Arc::new(helper));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = tracing::info_span!("request", id = 7, user = "ann");
+            let _outer = outer.enter();
+            let inner = tracing::info_span!("call", user = "vine");
+            inner.in_scope(|| tracing::warn!(attempt = 2, "retry {}", "now"));
+            tracing::debug!("hidden");
+
+            let mut fields = HashMap::new();
+            fields.insert("k".to_string(), "v".into());
+            let mut l = TracingLogger::new(Some(Options::new()));
+            l.fields(fields);
+            l.log(Level::ErrorLevel, b"from vine\n");
+        });
+
+        let out = String::from_utf8(out.lock().unwrap().clone())?;
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", out);
+        assert!(lines[0].contains(" attempt=2 "), "{}", lines[0]);
+        assert!(lines[0].contains(" id=7 "), "{}", lines[0]);
+        assert!(lines[0].contains(" level=warn "), "{}", lines[0]);
+        assert!(lines[0].contains(" user=vine "), "{}", lines[0]);
+        assert!(lines[0].ends_with(" retry now"), "{}", lines[0]);
+        assert!(lines[1].contains(" fields=k=v "), "{}", lines[1]);
+        assert!(lines[1].ends_with(" from vine"), "{}", lines[1]);
+
+        Ok(())
+    }
+}
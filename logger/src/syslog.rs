@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+#[cfg(unix)]
+use std::{os::unix::net::UnixDatagram, path::PathBuf};
+
+use chrono::prelude::*;
+use errors::Result;
+use itertools::Itertools;
+
+use crate::{level::Level, options::Options, value::Value, Logger};
+
+/// the socket of the local syslog daemon
+#[cfg(unix)]
+pub const DEV_LOG: &str = "/dev/log";
+
+/// the id of the structured data of the fields, under the enterprise number
+/// reserved for documentation
+const SD_ID: &str = "vine@32473";
+
+/// the facility of the messages, the kind of program sending them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Facility {
+    User = 1,
+    Daemon = 3,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// the socket the messages are sent over
+#[derive(Debug)]
+enum Transport {
+    Udp(UdpSocket, SocketAddr),
+    #[cfg(unix)]
+    Unix(UnixDatagram, PathBuf),
+}
+
+/// the implement of [`Logger`] sending the entries as RFC 5424 messages to
+/// a syslog daemon, the fields as the structured data of the message and
+/// the priority of the facility and the level.
+///
+/// ```rust,no_run
+/// # use logger::syslog::{Facility, SyslogLogger};
+/// let l = SyslogLogger::unix(None, "/dev/log")?.with_facility(Facility::Daemon);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct SyslogLogger {
+    opts: Options,
+    transport: Transport,
+    facility: Facility,
+    hostname: String,
+    app_name: String,
+}
+
+impl SyslogLogger {
+    /// sends the messages over UDP to `addr`, port 514 by convention
+    pub fn udp(opts: Option<Options>, addr: impl ToSocketAddrs) -> Result<Self> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| errors::err!("no address to send syslog messages to"))?;
+        let bind: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(bind)?;
+        Ok(Self::with_transport(opts, Transport::Udp(socket, addr)))
+    }
+
+    /// sends the messages to the unix datagram socket at `path`, as
+    /// [`DEV_LOG`]
+    #[cfg(unix)]
+    pub fn unix(opts: Option<Options>, path: impl Into<PathBuf>) -> Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        Ok(Self::with_transport(
+            opts,
+            Transport::Unix(socket, path.into()),
+        ))
+    }
+
+    fn with_transport(opts: Option<Options>, transport: Transport) -> Self {
+        SyslogLogger {
+            opts: opts.unwrap_or_default(),
+            transport,
+            facility: Facility::User,
+            hostname: hostname(),
+            app_name: app_name(),
+        }
+    }
+
+    /// set the facility of the messages. default is `User`
+    #[inline]
+    pub fn with_facility(mut self, facility: Facility) -> Self {
+        self.facility = facility;
+        self
+    }
+
+    /// set the name of the program sending the messages. default is the
+    /// name of the executable
+    #[inline]
+    pub fn with_app_name(mut self, name: impl Into<String>) -> Self {
+        self.app_name = name.into();
+        self
+    }
+
+    fn send(&self, message: &[u8]) -> io::Result<()> {
+        match &self.transport {
+            Transport::Udp(socket, addr) => socket.send_to(message, addr).map(|_| ()),
+            #[cfg(unix)]
+            Transport::Unix(socket, path) => socket.send_to(message, path).map(|_| ()),
+        }
+    }
+}
+
+impl Logger for SyslogLogger {
+    fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.opts = opt.unwrap_or_default();
+        Ok(())
+    }
+
+    fn options(&self) -> Options {
+        self.opts.clone()
+    }
+
+    fn fields(&mut self, fields: HashMap<String, Value>) {
+        self.opts = self.opts.clone().with_fields(fields);
+    }
+
+    fn log(&self, level: Level, arg: &[u8]) {
+        self.log_with(level, &HashMap::new(), arg)
+    }
+
+    fn log_with(&self, level: Level, with: &HashMap<String, Value>, arg: &[u8]) {
+        let fields = match self.opts.entry_fields(&level, with) {
+            Some(fields) => fields,
+            None => return,
+        };
+        let message = message(
+            self.facility as u8 * 8 + level.syslog_severity(),
+            Utc::now(),
+            &self.hostname,
+            &self.app_name,
+            &fields,
+            arg,
+        );
+        let _ = self.send(message.as_bytes());
+    }
+
+    fn string(&self) -> &'static str {
+        "syslog"
+    }
+}
+
+/// the RFC 5424 message of an entry
+fn message(
+    priority: u8,
+    time: DateTime<Utc>,
+    hostname: &str,
+    app_name: &str,
+    fields: &HashMap<String, Value>,
+    arg: &[u8],
+) -> String {
+    let data = if fields.is_empty() {
+        "-".to_string()
+    } else {
+        let params = fields
+            .iter()
+            .sorted_by(|a, b| a.0.cmp(b.0))
+            .map(|(k, v)| format!(" {}=\"{}\"", param_name(k), escape(&v.to_string())))
+            .join("");
+        format!("[{}{}]", SD_ID, params)
+    };
+
+    let msg = String::from_utf8_lossy(arg);
+    let msg = msg.strip_suffix('\n').unwrap_or(&msg);
+    format!(
+        "<{}>1 {} {} {} {} - {} {}",
+        priority,
+        time.to_rfc3339_opts(SecondsFormat::Micros, true),
+        header_field(hostname, 255),
+        header_field(app_name, 48),
+        std::process::id(),
+        data,
+        msg,
+    )
+}
+
+/// a header field of printable ascii without spaces, `-` when empty
+fn header_field(s: &str, max: usize) -> String {
+    let field: String = s
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max)
+        .collect();
+    if field.is_empty() {
+        "-".to_string()
+    } else {
+        field
+    }
+}
+
+/// the name of a parameter, at most 32 printable characters but `=`, ` `,
+/// `]` and `"`
+fn param_name(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '=' | ']' | '"' => '_',
+            c if c.is_ascii_graphic() => c,
+            _ => '_',
+        })
+        .take(32)
+        .collect()
+}
+
+/// escapes the `"`, `\` and `]` of a parameter value
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn hostname() -> String {
+    if let Ok(name) = std::env::var("HOSTNAME") {
+        return name;
+    }
+    std::fs::read_to_string("/etc/hostname")
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn app_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, net::UdpSocket};
+
+    use chrono::prelude::*;
+    use errors::Result;
+
+    use super::{message, Facility, SyslogLogger};
+    use crate::{level::Level, options::Options, Logger};
+
+    #[test]
+    fn test_message() {
+        let time = Utc.ymd(2021, 9, 30).and_hms_micro(10, 10, 10, 123456);
+        let mut fields = HashMap::new();
+        fields.insert("target".to_string(), "registry".into());
+        fields.insert("quote".to_string(), "a \"b\" [c]".into());
+        let m = message(27, time, "host", "vine app", &fields, b"hello\n");
+        assert_eq!(
+            m,
+            format!(
+                "<27>1 2021-09-30T10:10:10.123456Z host vineapp {} - \
+                 [vine@32473 quote=\"a \\\"b\\\" [c\\]\" target=\"registry\"] hello",
+                std::process::id()
+            )
+        );
+
+        let m = message(14, time, "", "", &HashMap::new(), b"empty");
+        assert!(
+            m.starts_with("<14>1 2021-09-30T10:10:10.123456Z - - "),
+            "{}",
+            m
+        );
+        assert!(m.ends_with(" - - empty"), "{}", m);
+    }
+
+    #[test]
+    fn test_udp() -> Result<()> {
+        let server = UdpSocket::bind("127.0.0.1:0")?;
+        let l = SyslogLogger::udp(Some(Options::new()), server.local_addr()?)?
+            .with_facility(Facility::Daemon)
+            .with_app_name("vine");
+        l.log(Level::DebugLevel, b"hidden");
+        l.log(Level::WarnLevel, b"over udp");
+
+        let mut buf = [0u8; 1024];
+        let n = server.recv(&mut buf)?;
+        let m = String::from_utf8_lossy(&buf[..n]);
+        // daemon is 3, warning is 4
+        assert!(m.starts_with("<28>1 "), "{}", m);
+        assert!(m.contains(" vine "), "{}", m);
+        assert!(m.ends_with(" - - over udp"), "{}", m);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    helper::Helper,
+    level::Level,
+    new_logger,
+    options::{Format, Options},
+    set_global_logger,
+};
+
+/// CaptureWriter records the entries written to it, for a test to assert
+/// on the entries logged rather than read them from stdout. The clones
+/// share the entries.
+///
+/// ```rust
+/// # use logger::level::Level;
+/// let capture = logger::test::with_capture();
+/// logger::warn!("lease renewal failed");
+/// assert!(capture.contains_at(Level::WarnLevel, "lease renewal"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CaptureWriter {
+    buf: Arc<Mutex<Vec<u8>>>,
+}
+
+impl CaptureWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the options of a logger writing uncolored text entries of any level
+    /// to the capture
+    pub fn options(&self) -> Options {
+        Options::new()
+            .with_level(Level::TraceLevel)
+            .with_format(Format::Text)
+            .with_out(Arc::new(Mutex::new(self.clone())))
+    }
+
+    /// the entries written, one per line
+    pub fn entries(&self) -> Vec<String> {
+        let buf = match self.buf.lock() {
+            Ok(buf) => buf,
+            Err(e) => e.into_inner(),
+        };
+        String::from_utf8_lossy(&buf)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// the entries of `level`
+    pub fn entries_at(&self, level: Level) -> Vec<String> {
+        self.entries()
+            .into_iter()
+            .filter(|e| entry_level(e) == Some(level.clone()))
+            .collect()
+    }
+
+    /// returns true if an entry contains `s`
+    pub fn contains(&self, s: &str) -> bool {
+        self.entries().iter().any(|e| e.contains(s))
+    }
+
+    /// returns true if an entry of `level` contains `s`
+    pub fn contains_at(&self, level: Level, s: &str) -> bool {
+        self.entries_at(level).iter().any(|e| e.contains(s))
+    }
+
+    /// forgets the entries written
+    pub fn clear(&self) {
+        if let Ok(mut buf) = self.buf.lock() {
+            buf.clear();
+        }
+    }
+}
+
+impl Write for CaptureWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.buf.lock() {
+            Ok(mut b) => b.extend_from_slice(buf),
+            Err(e) => e.into_inner().extend_from_slice(buf),
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// the level of a text or JSON entry
+fn entry_level(entry: &str) -> Option<Level> {
+    if entry.starts_with('{') {
+        let entry: serde_json::Value = serde_json::from_str(entry).ok()?;
+        return Level::from(entry["level"].as_str()?).ok();
+    }
+    entry
+        .split(' ')
+        .find_map(|field| field.strip_prefix("level="))
+        .and_then(|level| Level::from(level).ok())
+}
+
+/// makes the global logger write to a new [`CaptureWriter`] and returns
+/// it. The global logger is shared by the tests running at the same time,
+/// the capture has their entries too.
+pub fn with_capture() -> CaptureWriter {
+    let capture = CaptureWriter::new();
+    if let Ok(l) = new_logger(Some(capture.options())) {
+        let _ = set_global_logger(Helper::new(l));
+    }
+    capture
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{entry_level, CaptureWriter};
+    use crate::{helper::Helper, level::Level, new_logger, options::Format};
+    use errors::Result;
+
+    #[test]
+    fn test_capture() -> Result<()> {
+        let capture = CaptureWriter::new();
+        let helper = Helper::new(new_logger(Some(capture.options()))?);
+        helper.debug(b"watching");
+        helper.log_target(Level::WarnLevel, "registry", b"lease renewal failed");
+
+        assert_eq!(capture.entries().len(), 2);
+        assert!(capture.contains("watching"));
+        assert!(capture.contains_at(Level::WarnLevel, "lease renewal"));
+        assert!(!capture.contains_at(Level::ErrorLevel, "lease renewal"));
+        assert_eq!(capture.entries_at(Level::DebugLevel).len(), 1);
+
+        capture.clear();
+        assert!(capture.entries().is_empty());
+
+        let json = new_logger(Some(capture.options().with_format(Format::Json)))?;
+        Helper::new(json).error(b"json");
+        assert!(capture.contains_at(Level::ErrorLevel, "json"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_level() {
+        let mut capture = CaptureWriter::new();
+        let _ = capture.write(b"2021-09-30 10:10:10 level=info target=a level=warn b\n");
+        assert_eq!(entry_level(&capture.entries()[0]), Some(Level::InfoLevel));
+        assert_eq!(entry_level("no level"), None);
+        assert_eq!(entry_level(r#"{"level":"warn"}"#), Some(Level::WarnLevel));
+    }
+}
@@ -0,0 +1,133 @@
+use std::{borrow::Cow, fmt};
+
+/// Value is the value of a field. It keeps the type of the value, so the
+/// JSON entries have numbers and booleans where they were logged, and the
+/// text entries show it with `Display`. A value of another type is logged
+/// as its `Display` text with [`Value::display`].
+///
+/// ```rust
+/// # use logger::value::Value;
+/// assert_eq!(Value::from(8080), Value::Int(8080));
+/// assert_eq!(Value::from("vine"), "vine");
+/// assert_eq!(Value::display(std::net::Ipv4Addr::LOCALHOST), "127.0.0.1");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl Value {
+    /// the value as the `Display` text of `v`
+    pub fn display(v: impl fmt::Display) -> Self {
+        Value::Str(v.to_string())
+    }
+
+    /// the text of a `Str` value
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Str(s) => s.as_str().into(),
+            Value::Int(i) => (*i).into(),
+            Value::Uint(u) => (*u).into(),
+            Value::Float(f) => (*f).into(),
+            Value::Bool(b) => (*b).into(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(s) => f.write_str(s),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Uint(u) => write!(f, "{}", u),
+            Value::Float(v) => write!(f, "{}", v),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl PartialEq<str> for Value {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == Some(other)
+    }
+}
+
+impl PartialEq<&str> for Value {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == Some(*other)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Str(s)
+    }
+}
+
+impl From<&String> for Value {
+    fn from(s: &String) -> Self {
+        Value::Str(s.clone())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Str(s.to_string())
+    }
+}
+
+impl From<Cow<'_, str>> for Value {
+    fn from(s: Cow<'_, str>) -> Self {
+        Value::Str(s.into_owned())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+macro_rules! from_number {
+    ($variant:ident($as:ty): $($t:ty),*) => {
+        $(
+            impl From<$t> for Value {
+                fn from(v: $t) -> Self {
+                    Value::$variant(v as $as)
+                }
+            }
+        )*
+    };
+}
+
+from_number!(Int(i64): i8, i16, i32, i64, isize);
+from_number!(Uint(u64): u8, u16, u32, u64, usize);
+from_number!(Float(f64): f32, f64);
+
+#[cfg(test)]
+mod test {
+    use super::Value;
+
+    #[test]
+    fn test_value() {
+        assert_eq!(Value::from(-2i8), Value::Int(-2));
+        assert_eq!(Value::from(7usize), Value::Uint(7));
+        assert_eq!(Value::from(1.5f32), Value::Float(1.5));
+        assert_eq!(Value::from(true).to_string(), "true");
+        assert_eq!(Value::from("a").to_json(), serde_json::json!("a"));
+        assert_eq!(Value::from(8080).to_json(), serde_json::json!(8080));
+        assert_eq!(Value::from(false).to_json(), serde_json::json!(false));
+        assert_eq!(Value::from(0.25).as_str(), None);
+    }
+}
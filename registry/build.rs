@@ -0,0 +1,6 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tonic_build::configure()
+        .compile(&["proto/registry.proto", "proto/health.proto"], &["proto"])?;
+
+    Ok(())
+}
@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use errors::{err, MultiStatus};
+use tokio::task::JoinHandle;
+
+use crate::error::Result;
+use crate::options::{
+    DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+};
+use crate::types::Service;
+use crate::{filter_service, Registry, Watcher};
+
+/// the first and the largest delay before the invalidating watch is restarted
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// name -> read at, services
+type Entries = HashMap<String, (Instant, Vec<Service>)>;
+
+#[derive(Debug, Clone)]
+pub struct CacheOptions {
+    /// how long a service read from the inner registry is reused
+    pub ttl: Duration,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CacheOptions {
+    #[inline]
+    pub fn new() -> Self {
+        CacheOptions {
+            ttl: Duration::from_secs(60),
+        }
+    }
+
+    #[inline]
+    pub fn with_ttl(&mut self, t: Duration) -> &mut Self {
+        self.ttl = t;
+        self
+    }
+}
+
+/// CacheStats counts the answers of a [`CacheRegistry`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// services currently cached
+    pub entries: usize,
+}
+
+/// the implement of [`Registry`] caching the `get_service` answers of
+/// another registry. A background watch drops the services which change,
+/// so entries only outlive a change when the watch is down.
+///
+/// ```rust
+/// # use registry::{cache::CacheRegistry, memory::MemoryRegistry, Registry};
+/// # async fn run() -> errors::Result<()> {
+/// let registry = CacheRegistry::new(Box::new(MemoryRegistry::new(None)), None);
+/// let services = registry.get_service("helloworld".to_string(), None).await?;
+/// println!("{:?}", registry.stats());
+/// # Ok(())
+/// # }
+/// ```
+pub struct CacheRegistry {
+    inner: Arc<Box<dyn Registry + Sync>>,
+    options: CacheOptions,
+    entries: Arc<Mutex<Entries>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    watch: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl CacheRegistry {
+    pub fn new(inner: Box<dyn Registry + Sync>, opt: Option<CacheOptions>) -> Self {
+        CacheRegistry {
+            inner: Arc::new(inner),
+            options: opt.unwrap_or_default(),
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            watch: Mutex::new(None),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: lock(&self.entries).len(),
+        }
+    }
+
+    /// drops every cached service
+    pub fn flush(&self) {
+        lock(&self.entries).clear();
+    }
+
+    fn invalidate(&self, name: &str) {
+        lock(&self.entries).remove(name);
+    }
+
+    /// starts the invalidating watch on first use, from within the runtime
+    fn start_watch(&self) {
+        let mut watch = lock(&self.watch);
+        if watch.is_none() {
+            let task = run(self.inner.clone(), Arc::downgrade(&self.entries));
+            *watch = Some(tokio::spawn(task));
+        }
+    }
+}
+
+impl Drop for CacheRegistry {
+    fn drop(&mut self) {
+        if let Some(task) = lock(&self.watch).take() {
+            task.abort();
+        }
+    }
+}
+
+fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// drops the services named by the watch results of `inner`. Everything is
+/// dropped whenever the watch starts or ends, as changes may have been missed
+/// while it was down.
+async fn run(inner: Arc<Box<dyn Registry + Sync>>, entries: Weak<Mutex<Entries>>) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        match inner.watch(None).await {
+            Ok(w) => {
+                backoff = MIN_BACKOFF;
+                match entries.upgrade() {
+                    Some(entries) => lock(&entries).clear(),
+                    None => return,
+                }
+                loop {
+                    let r = match w.next().await {
+                        Ok(r) => r,
+                        Err(e) => {
+                            logger::warn!("cache watch ended, restarting: {}", e);
+                            break;
+                        }
+                    };
+                    let entries = match entries.upgrade() {
+                        Some(entries) => entries,
+                        None => return,
+                    };
+                    if let Some(s) = r.service {
+                        lock(&entries).remove(&s.name);
+                    }
+                }
+                w.stop().await;
+            }
+            Err(e) => logger::error!("cache could not start watch: {}", e),
+        }
+
+        match entries.upgrade() {
+            Some(entries) => lock(&entries).clear(),
+            None => return,
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+#[async_trait]
+impl Registry for CacheRegistry {
+    /// the inner registry must not be shared to be initialised again
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        match Arc::get_mut(&mut self.inner) {
+            Some(inner) => inner.init(opt).await?,
+            None => return Err(err!("could not init, the cache watch holds the registry").into()),
+        }
+        self.flush();
+        Ok(())
+    }
+
+    #[inline]
+    async fn options(&self) -> Options {
+        self.inner.options().await
+    }
+
+    async fn register(&self, s: &Service, opt: Option<RegisterOptions>) -> Result<()> {
+        self.inner.register(s, opt).await?;
+        self.invalidate(&s.name);
+        Ok(())
+    }
+
+    async fn deregister(&self, s: &Service, opt: Option<DeregisterOptions>) -> Result<()> {
+        self.inner.deregister(s, opt).await?;
+        self.invalidate(&s.name);
+        Ok(())
+    }
+
+    async fn register_many(
+        &self,
+        services: &[Service],
+        opt: Option<RegisterOptions>,
+    ) -> Result<()> {
+        let r = self.inner.register_many(services, opt).await;
+        // some may be registered even when the batch fails
+        for s in services {
+            self.invalidate(&s.name);
+        }
+        r
+    }
+
+    async fn deregister_many(
+        &self,
+        services: &[Service],
+        opt: Option<DeregisterOptions>,
+    ) -> Result<()> {
+        let r = self.inner.deregister_many(services, opt).await;
+        for s in services {
+            self.invalidate(&s.name);
+        }
+        r
+    }
+
+    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> Result<Vec<Service>> {
+        // the watch only covers the default domain
+        if opt.as_ref().is_some_and(|o| o.domain.is_some()) {
+            return self.inner.get_service(s, opt).await;
+        }
+
+        self.start_watch();
+        if let Some((at, services)) = lock(&self.entries).get(&s) {
+            if at.elapsed() < self.options.ttl {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return filter_service(s.clone(), services.clone(), &opt.unwrap_or_default());
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        // the entry holds every node, the filters apply on the way out
+        let mut unfiltered = opt.clone().unwrap_or_default();
+        unfiltered.version = None;
+        unfiltered.metadata_filter.clear();
+        let services = self.inner.get_service(s.clone(), Some(unfiltered)).await?;
+        lock(&self.entries).insert(s.clone(), (Instant::now(), services.clone()));
+        filter_service(s, services, &opt.unwrap_or_default())
+    }
+
+    /// always reads the inner registry, for the statuses of its bad entries
+    async fn get_service_checked(
+        &self,
+        s: String,
+        opt: Option<GetOptions>,
+    ) -> Result<(Vec<Service>, MultiStatus)> {
+        self.inner.get_service_checked(s, opt).await
+    }
+
+    async fn list_service(&self, opt: Option<ListOptions>) -> Result<Vec<Service>> {
+        self.inner.list_service(opt).await
+    }
+
+    async fn watch(&self, opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send + Sync>> {
+        self.inner.watch(opt).await
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        "cache"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use errors::Result;
+
+    use super::{CacheOptions, CacheRegistry, CacheStats};
+    use crate::{
+        memory::MemoryRegistry,
+        options::GetOptions,
+        types::{Node, Service},
+        Registry,
+    };
+
+    fn service(id: &str) -> Service {
+        Service {
+            name: "io.vine.helloworld".to_string(),
+            version: "v1.0.0".to_string(),
+            nodes: vec![Node {
+                id: id.to_string(),
+                address: "127.0.0.1".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        }
+    }
+
+    fn stats(hits: u64, misses: u64, entries: usize) -> CacheStats {
+        CacheStats {
+            hits,
+            misses,
+            entries,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_registry() -> Result<()> {
+        let inner = MemoryRegistry::new(None);
+        inner.register(&service("1"), None).await?;
+        let r = CacheRegistry::new(Box::new(inner.clone()), None);
+        let name = "io.vine.helloworld".to_string();
+
+        r.get_service(name.clone(), None).await?;
+        r.get_service(name.clone(), None).await?;
+        assert_eq!(r.stats(), stats(1, 1, 1));
+
+        // starting the watch drops what was read before it
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(r.stats().entries, 0);
+        r.get_service(name.clone(), None).await?;
+
+        // a change made past the cache is seen through the watch
+        inner.register(&service("2"), None).await?;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(r.stats().entries, 0);
+        let services = r.get_service(name.clone(), None).await?;
+        assert_eq!(services[0].nodes.len(), 2);
+        assert_eq!(r.stats(), stats(1, 3, 1));
+
+        // filters apply to the cached entry
+        let mut gopt = GetOptions::new();
+        gopt.with_version("v0.0.0".to_string());
+        assert!(r.get_service(name.clone(), Some(gopt)).await.is_err());
+        assert_eq!(r.stats(), stats(2, 3, 1));
+
+        // and a change made through it right away
+        r.deregister(&service("2"), None).await?;
+        assert_eq!(r.stats().entries, 0);
+
+        r.get_service(name.clone(), None).await?;
+        r.flush();
+        r.get_service(name, None).await?;
+        assert_eq!(r.stats(), stats(2, 5, 1));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_ttl() -> Result<()> {
+        let inner = MemoryRegistry::new(None);
+        inner.register(&service("1"), None).await?;
+        let mut copt = CacheOptions::new();
+        copt.with_ttl(Duration::from_millis(50));
+        let r = CacheRegistry::new(Box::new(inner), Some(copt));
+        let name = "io.vine.helloworld".to_string();
+
+        r.get_service(name.clone(), None).await?;
+        r.get_service(name.clone(), None).await?;
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        r.get_service(name.clone(), None).await?;
+        assert_eq!(r.stats(), stats(1, 2, 1));
+
+        // errors are not cached
+        assert!(r
+            .get_service("io.vine.missing".to_string(), None)
+            .await
+            .is_err());
+        assert!(r
+            .get_service("io.vine.missing".to_string(), None)
+            .await
+            .is_err());
+        assert_eq!(r.stats(), stats(1, 4, 1));
+
+        Ok(())
+    }
+}
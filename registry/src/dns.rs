@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Local;
+use errors::Status;
+use tokio::sync::{broadcast, RwLock};
+
+use crate::error::{Error, Result};
+use crate::memory::watch::MemoryWatcher;
+use crate::options::{
+    DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+};
+use crate::types::{self, Node, Service};
+use crate::{filter_service, filter_services, Registry, Watcher};
+
+/// how many events a slow watcher may fall behind before losing some
+const EVENT_CAPACITY: usize = 128;
+
+/// name -> the `host:port` upstreams of the service
+type Upstreams = HashMap<String, Vec<String>>;
+
+/// the implement of [`Registry`] over fixed upstream lists, for
+/// environments without a dynamic registry. Host names are resolved by
+/// DNS and, with a refresh interval, resolved again on that interval;
+/// watchers get an update whenever the answers change.
+///
+/// ```rust
+/// # use std::collections::HashMap;
+/// # use std::time::Duration;
+/// # use registry::{dns::StaticRegistry, Registry};
+/// # async fn run() -> errors::Result<()> {
+/// let mut upstreams = HashMap::new();
+/// upstreams.insert("helloworld".to_string(), vec!["localhost:11101".to_string()]);
+/// let registry = StaticRegistry::new(upstreams, Some(Duration::from_secs(30))).await?;
+/// let services = registry.get_service("helloworld".to_string(), None).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct StaticRegistry {
+    options: Options,
+    upstreams: Arc<RwLock<Upstreams>>,
+    services: Arc<RwLock<HashMap<String, Service>>>,
+    events: broadcast::Sender<types::Result>,
+}
+
+impl StaticRegistry {
+    /// resolves every upstream once, failing on one which can't be resolved
+    pub async fn new(upstreams: Upstreams, refresh: Option<Duration>) -> Result<Self> {
+        let mut services = HashMap::new();
+        for (name, addrs) in &upstreams {
+            services.insert(name.clone(), resolve(name, addrs).await?);
+        }
+
+        let registry = StaticRegistry {
+            options: Options::new(),
+            upstreams: Arc::new(RwLock::new(upstreams)),
+            services: Arc::new(RwLock::new(services)),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        };
+        if let Some(interval) = refresh {
+            tokio::spawn(run(
+                Arc::downgrade(&registry.upstreams),
+                Arc::downgrade(&registry.services),
+                registry.events.clone(),
+                interval,
+            ));
+        }
+        Ok(registry)
+    }
+
+    /// replaces the upstreams of `name`, watchers get an update when
+    /// they resolve to other nodes
+    pub async fn set_upstreams(&self, name: &str, addrs: Vec<String>) -> Result<()> {
+        let s = resolve(name, &addrs).await?;
+        self.upstreams.write().await.insert(name.to_string(), addrs);
+        update(&self.services, &self.events, s).await;
+        Ok(())
+    }
+
+    /// resolves every upstream again, keeping the previous answers of
+    /// those which fail
+    pub async fn refresh(&self) {
+        refresh(&self.upstreams, &self.services, &self.events).await
+    }
+}
+
+/// refreshes on `interval` until the registry and all its clones are dropped
+async fn run(
+    upstreams: Weak<RwLock<Upstreams>>,
+    services: Weak<RwLock<HashMap<String, Service>>>,
+    events: broadcast::Sender<types::Result>,
+    interval: Duration,
+) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let (services, upstreams) = match (services.upgrade(), upstreams.upgrade()) {
+            (Some(services), Some(upstreams)) => (services, upstreams),
+            _ => return,
+        };
+        refresh(&upstreams, &services, &events).await;
+    }
+}
+
+async fn refresh(
+    upstreams: &RwLock<Upstreams>,
+    services: &RwLock<HashMap<String, Service>>,
+    events: &broadcast::Sender<types::Result>,
+) {
+    let upstreams = upstreams.read().await.clone();
+    for (name, addrs) in &upstreams {
+        match resolve(name, addrs).await {
+            Ok(s) => update(services, events, s).await,
+            Err(e) => logger::warn!("resolve upstreams of {} failed: {}", name, e),
+        }
+    }
+}
+
+/// stores `s`, emitting an update when its nodes changed
+async fn update(
+    services: &RwLock<HashMap<String, Service>>,
+    events: &broadcast::Sender<types::Result>,
+    s: Service,
+) {
+    let mut services = services.write().await;
+    if services.get(&s.name) == Some(&s) {
+        return;
+    }
+
+    services.insert(s.name.clone(), s.clone());
+    // nobody watching is not an error
+    let _ = events.send(types::Result {
+        action: "update".to_string(),
+        service: Some(s),
+        timestamp: Local::now().timestamp(),
+    });
+}
+
+/// a service holding a node for every address the upstreams resolve to
+async fn resolve(name: &str, addrs: &[String]) -> Result<Service> {
+    let mut nodes = vec![];
+    for addr in addrs {
+        for sa in tokio::net::lookup_host(addr.as_str()).await? {
+            let mut metadata = HashMap::new();
+            metadata.insert("upstream".to_string(), addr.clone());
+            nodes.push(Node {
+                id: sa.to_string(),
+                address: sa.ip().to_string(),
+                port: sa.port() as i64,
+                metadata,
+            });
+        }
+    }
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+    nodes.dedup_by(|a, b| a.id == b.id);
+
+    Ok(Service {
+        name: name.to_string(),
+        nodes,
+        ..Service::new()
+    })
+}
+
+fn read_only() -> Error {
+    Status::method_not_allowed(
+        "io.vine.registry".to_string(),
+        "static registry is read only, set its upstreams instead".to_string(),
+    )
+    .into()
+}
+
+#[async_trait]
+impl Registry for StaticRegistry {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.options = opt.unwrap_or_default();
+        Ok(())
+    }
+
+    #[inline]
+    async fn options(&self) -> Options {
+        self.options.clone()
+    }
+
+    async fn register(&self, _s: &Service, _opt: Option<RegisterOptions>) -> Result<()> {
+        Err(read_only())
+    }
+
+    async fn deregister(&self, _s: &Service, _opt: Option<DeregisterOptions>) -> Result<()> {
+        Err(read_only())
+    }
+
+    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> Result<Vec<Service>> {
+        let opt = opt.unwrap_or_default();
+        let services = match self.services.read().await.get(&s) {
+            Some(service) if !service.nodes.is_empty() => vec![service.clone()],
+            _ => vec![],
+        };
+        filter_service(s, services, &opt)
+    }
+
+    async fn list_service(&self, opt: Option<ListOptions>) -> Result<Vec<Service>> {
+        let opt = opt.unwrap_or_default();
+        let services = self.services.read().await;
+        let mut out: Vec<Service> = services.values().cloned().collect();
+        out.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(filter_services(
+            out,
+            opt.version.as_deref(),
+            &opt.metadata_filter,
+        ))
+    }
+
+    async fn watch(&self, opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send + Sync>> {
+        let watcher = MemoryWatcher::new(self.events.subscribe(), opt);
+        Ok(Box::new(watcher))
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        "static"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use errors::{Code, Result, Status};
+
+    use super::StaticRegistry;
+    use crate::{types::Service, Registry};
+
+    fn upstreams(addrs: &[&str]) -> HashMap<String, Vec<String>> {
+        let mut m = HashMap::new();
+        m.insert(
+            "io.vine.helloworld".to_string(),
+            addrs.iter().map(|a| a.to_string()).collect(),
+        );
+        m
+    }
+
+    #[tokio::test]
+    async fn test_static_registry() -> Result<()> {
+        let r = StaticRegistry::new(upstreams(&["10.0.0.2:8080", "10.0.0.1:8080"]), None).await?;
+        let services = r
+            .get_service("io.vine.helloworld".to_string(), None)
+            .await?;
+        let ids: Vec<&str> = services[0].nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["10.0.0.1:8080", "10.0.0.2:8080"]);
+        assert_eq!(services[0].nodes[0].address, "10.0.0.1");
+        assert_eq!(services[0].nodes[0].port, 8080);
+        assert_eq!(r.list_service(None).await?.len(), 1);
+        assert!(r
+            .get_service("io.vine.other".to_string(), None)
+            .await
+            .is_err());
+
+        let e = r.register(&Service::new(), None).await.unwrap_err();
+        assert_eq!(Status::from(e).code(), Code::MethodNotAllowed);
+
+        // host names resolve through dns
+        let r = StaticRegistry::new(upstreams(&["localhost:8080"]), None).await?;
+        let services = r
+            .get_service("io.vine.helloworld".to_string(), None)
+            .await?;
+        assert!(services[0].nodes.iter().all(|n| n.port == 8080));
+
+        assert!(StaticRegistry::new(upstreams(&["no port"]), None)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_static_watch() -> Result<()> {
+        let r = StaticRegistry::new(upstreams(&["10.0.0.1:8080"]), None).await?;
+        let w = r.watch(None).await?;
+
+        // the same answers are not an update
+        r.refresh().await;
+        r.set_upstreams("io.vine.helloworld", vec!["10.0.0.1:8080".to_string()])
+            .await?;
+        r.set_upstreams(
+            "io.vine.helloworld",
+            vec!["10.0.0.1:8080".to_string(), "10.0.0.3:8080".to_string()],
+        )
+        .await?;
+
+        let event = tokio::time::timeout(Duration::from_secs(1), w.next()).await??;
+        assert_eq!(event.action, "update");
+        assert_eq!(event.service.unwrap().nodes.len(), 2);
+        assert!(tokio::time::timeout(Duration::from_millis(100), w.next())
+            .await
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+
+use errors::{Code, Status};
+
+/// Error is returned by every [`Registry`](crate::Registry) and
+/// [`Watcher`](crate::Watcher) call
+///
+/// ```rust
+/// # use registry::{Error, Registry};
+/// # async fn run(registry: &(dyn Registry + Sync)) -> errors::Result<()> {
+/// match registry.get_service("helloworld".to_string(), None).await {
+///     Ok(services) => println!("{:?}", services),
+///     Err(Error::NotFound(name)) => println!("{} is not registered", name),
+///     Err(e) => return Err(e.into()),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub enum Error {
+    /// no node of the named service is registered
+    NotFound(String),
+    /// the call did not complete in time, the detail names the call
+    Timeout(String),
+    /// the watcher was stopped or its watch ended
+    WatcherStopped,
+    /// any other failure of the backend, a [`Status`] or [`errors::MultiStatus`]
+    /// it failed with can be downcast from the source
+    Backend(anyhow::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// the backend failure, `None` for the other variants
+    pub fn backend(&self) -> Option<&anyhow::Error> {
+        match self {
+            Error::Backend(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(name) => write!(f, "service {} not found", name),
+            Error::Timeout(detail) => write!(f, "{}", detail),
+            Error::WatcherStopped => write!(f, "watcher stopped"),
+            Error::Backend(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Backend(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// sorts an untyped error into a variant, by its status when it carries one
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<Error>() {
+            Ok(e) => return e,
+            Err(e) => e,
+        };
+        match e.downcast_ref::<Status>().map(|s| s.code()) {
+            Some(Code::GatewayTimeout) | Some(Code::RequestTimeout) => {
+                Error::Timeout(e.downcast_ref::<Status>().unwrap().detail().to_string())
+            }
+            _ => Error::Backend(e),
+        }
+    }
+}
+
+macro_rules! backend_from {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Error {
+                fn from(e: $t) -> Self {
+                    Error::Backend(e.into())
+                }
+            }
+        )*
+    };
+}
+
+impl From<Status> for Error {
+    fn from(s: Status) -> Self {
+        anyhow::Error::from(s).into()
+    }
+}
+
+backend_from!(
+    errors::MultiStatus,
+    std::io::Error,
+    std::str::Utf8Error,
+    serde_json::Error,
+    etcd_client::Error,
+    notify::Error,
+    tonic::Status,
+    tonic::transport::Error,
+    tonic::codegen::http::uri::InvalidUri
+);
+
+impl From<Error> for Status {
+    fn from(e: Error) -> Self {
+        let id = "io.vine.registry".to_string();
+        match e {
+            Error::NotFound(name) => Status::not_found(id, format!("service {} not found", name)),
+            Error::Timeout(detail) => Status::timeout(id, detail),
+            Error::WatcherStopped => {
+                Status::new(id, "watcher stopped".to_string(), Code::ServiceUnavailable)
+            }
+            Error::Backend(e) => match e.downcast::<Status>() {
+                Ok(s) => s,
+                Err(e) => Status::internal_server_error(id, e.to_string()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use errors::{Code, Status};
+
+    use super::Error;
+
+    #[test]
+    fn test_from_anyhow() {
+        let e: Error = anyhow::Error::from(Status::gateway_timeout(
+            "io.vine.registry",
+            "get_service timed out after 1s",
+        ))
+        .into();
+        assert!(matches!(&e, Error::Timeout(d) if d == "get_service timed out after 1s"));
+
+        let e: Error = anyhow::Error::from(Error::NotFound("helloworld".to_string())).into();
+        assert!(matches!(e, Error::NotFound(_)));
+
+        let e: Error = errors::err!("boom").into();
+        assert_eq!(e.to_string(), "boom");
+        assert!(e.backend().is_some());
+    }
+
+    #[test]
+    fn test_into_status() {
+        let s = Status::from(Error::NotFound("helloworld".to_string()));
+        assert_eq!(s.code(), Code::NotFound);
+        assert_eq!(s.detail(), "service helloworld not found");
+
+        let s = Status::from(Error::Timeout("get_service timed out".to_string()));
+        assert_eq!(s.code(), Code::RequestTimeout);
+
+        let s = Status::from(Error::WatcherStopped);
+        assert_eq!(s.code(), Code::ServiceUnavailable);
+
+        // a status of the backend is kept as is
+        let s = Status::from(Error::from(Status::method_not_allowed(
+            "io.vine.registry",
+            "read only",
+        )));
+        assert_eq!(s.code(), Code::MethodNotAllowed);
+        assert_eq!(s.detail(), "read only");
+
+        let s = Status::from(Error::Backend(errors::err!("boom")));
+        assert_eq!(s.code(), Code::InternalServerError);
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use errors::err;
+use etcd_client::Client;
+use tokio::sync::Mutex;
+
+use crate::error::Result;
+use crate::with_timeout;
+
+/// the lease a node is written under
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Held {
+    /// the lease id, 0 when the node was written without a lease
+    pub lease: i64,
+    /// the ttl the lease was granted with
+    pub ttl: i64,
+    /// the content hash of the node as written
+    pub hash: u64,
+}
+
+/// LeaseManager keeps the lease each registered node is written under, so a
+/// registration renews the lease of the node rather than granting another
+/// one, and a lease is revoked once the last node holding it is released.
+/// The nodes of a [`register_many`](crate::Registry::register_many) call
+/// share one lease.
+#[derive(Clone, Default)]
+pub(crate) struct LeaseManager {
+    /// node key -> held lease
+    nodes: Arc<Mutex<HashMap<String, Held>>>,
+}
+
+impl LeaseManager {
+    pub async fn get(&self, key: &str) -> Option<Held> {
+        self.nodes.lock().await.get(key).copied()
+    }
+
+    /// records `held` for `key`, returning the lease it replaces when no
+    /// other node holds it anymore
+    pub async fn hold(&self, key: &str, held: Held) -> Option<i64> {
+        let mut nodes = self.nodes.lock().await;
+        let prev = nodes.insert(key.to_string(), held)?;
+        if prev.lease == held.lease {
+            return None;
+        }
+        orphaned(&nodes, prev.lease)
+    }
+
+    /// forgets `key`, returning its lease when no other node holds it
+    pub async fn release(&self, key: &str) -> Option<i64> {
+        let mut nodes = self.nodes.lock().await;
+        let prev = nodes.remove(key)?;
+        orphaned(&nodes, prev.lease)
+    }
+}
+
+/// `lease` when none of `nodes` holds it
+fn orphaned(nodes: &HashMap<String, Held>, lease: i64) -> Option<i64> {
+    if lease == 0 || nodes.values().any(|h| h.lease == lease) {
+        None
+    } else {
+        Some(lease)
+    }
+}
+
+/// grants a lease of `ttl` seconds, 0 when etcd refuses it and the node is
+/// written without one
+pub(crate) async fn grant(client: &mut Client, ttl: i64, timeout: Duration) -> i64 {
+    let rsp = with_timeout("register", timeout, async {
+        Ok(client.lease_grant(ttl, None).await?)
+    })
+    .await;
+    match rsp {
+        Ok(rsp) => rsp.id(),
+        Err(e) => {
+            logger::warn!("lease grant of ttl {} failed: {}", ttl, e);
+            0
+        }
+    }
+}
+
+/// renews `lease` once, false when it has expired
+pub(crate) async fn renew(client: &mut Client, lease: i64, timeout: Duration) -> bool {
+    let renewed = with_timeout("register", timeout, async {
+        let (mut keeper, mut stream) = client.lease_keep_alive(lease).await?;
+        keeper.keep_alive().await?;
+        match stream.message().await? {
+            Some(rsp) if rsp.ttl() > 0 => Ok(()),
+            _ => Err(err!("lease {} expired", lease)),
+        }
+    })
+    .await;
+    if let Err(e) = &renewed {
+        logger::error!("renewing lease {} failed: {}", lease, e);
+    }
+    renewed.is_ok()
+}
+
+/// the ttl `lease` was granted with, 0 when it has expired
+pub(crate) async fn granted_ttl(client: &mut Client, lease: i64, timeout: Duration) -> Result<i64> {
+    let rsp = with_timeout("register", timeout, async {
+        Ok(client.lease_time_to_live(lease, None).await?)
+    })
+    .await?;
+    if rsp.ttl() > 0 {
+        Ok(rsp.granted_ttl())
+    } else {
+        Ok(0)
+    }
+}
+
+/// revokes `lease`, deleting the keys still attached to it. A failure is
+/// only logged, the lease expires on its own.
+pub(crate) async fn revoke(client: &mut Client, lease: i64, timeout: Duration) {
+    logger::debug!("revoking lease {}", lease);
+    let revoked = with_timeout("deregister", timeout, async {
+        Ok(client.lease_revoke(lease).await?)
+    })
+    .await;
+    if let Err(e) = revoked {
+        logger::warn!("revoking lease {} failed: {}", lease, e);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Held, LeaseManager};
+
+    fn held(lease: i64) -> Held {
+        Held {
+            lease,
+            ttl: 30,
+            hash: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hold_release() {
+        let m = LeaseManager::default();
+        assert_eq!(m.hold("a", held(1)).await, None);
+        assert_eq!(m.get("a").await, Some(held(1)));
+        // renewing under the same lease replaces nothing
+        assert_eq!(m.hold("a", held(1)).await, None);
+        // the old lease is returned once nothing holds it
+        assert_eq!(m.hold("a", held(2)).await, Some(1));
+
+        // a shared lease is revoked with the last node holding it
+        m.hold("b", held(2)).await;
+        assert_eq!(m.release("a").await, None);
+        assert_eq!(m.release("b").await, Some(2));
+        assert_eq!(m.release("b").await, None);
+
+        // nodes without a lease have nothing to revoke
+        m.hold("c", held(0)).await;
+        assert_eq!(m.hold("c", held(3)).await, None);
+        assert_eq!(m.release("c").await, Some(3));
+    }
+}
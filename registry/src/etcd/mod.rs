@@ -1,58 +1,135 @@
+mod lease;
 pub mod watch;
 
-use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
 use std::str;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
-use errors::{bail, err, Result};
-use etcd_client::{Client, ConnectOptions, GetOptions as EGetOptions, PutOptions};
+use errors::{err, MultiStatus};
+use etcd_client::{
+    Certificate, Client, ConnectOptions, GetOptions as EGetOptions, Identity, PutOptions,
+    TlsOptions, Txn, TxnOp,
+};
 use itertools::Itertools;
-use tokio::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
 
+use self::lease::{Held, LeaseManager};
 use self::watch::EtcdWatcher;
+use crate::error::{Error, Result};
 use crate::options::{
-    DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+    Codec, DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+    DEFAULT_DOMAIN,
+};
+use crate::types::{Endpoint, Node, OpenApi, Service};
+use crate::{
+    decode_entries, decode_entry, filter_service, filter_services, proto, with_timeout, Registry,
+    Watcher,
 };
-use crate::types::{Node, Service};
-use crate::{Registry, Watcher};
 
 static PREFIX: &str = r"/vine/registry";
+static META_PREFIX: &str = r"/vine/registry-meta";
+
+/// the most operations etcd accepts in one transaction by default
+const MAX_TXN_OPS: usize = 128;
+
+/// the parts of a service version left out of its node values,
+/// stored once under [`meta_path`]
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct VersionMeta {
+    endpoints: Option<Vec<Endpoint>>,
+    apis: Option<OpenApi>,
+}
+
+/// (domain, service name, node id) -> the task registering the node again,
+/// the tasks are aborted once the registry and its clones are dropped
+#[derive(Default)]
+struct KeepAlives(HashMap<(String, String, String), JoinHandle<()>>);
+
+impl Drop for KeepAlives {
+    fn drop(&mut self) {
+        for (_, task) in self.0.drain() {
+            task.abort();
+        }
+    }
+}
+
+/// builds the etcd connection from the tls and auth settings of `opts`
+async fn connect_options(opts: &Options) -> Result<ConnectOptions> {
+    let mut options = ConnectOptions::new();
+
+    if let Some(username) = &opts.username {
+        let password = opts.password.clone().unwrap_or_default();
+        options = options.with_user(username.clone(), password);
+    }
+
+    if opts.tls_enabled() {
+        let mut tls = TlsOptions::new();
+        if let Some(ca) = &opts.tls_ca {
+            let pem = read_pem("ca", ca).await?;
+            tls = tls.ca_certificate(Certificate::from_pem(pem));
+        }
+        match (&opts.tls_cert, &opts.tls_key) {
+            (Some(cert), Some(key)) => {
+                let cert = read_pem("certificate", cert).await?;
+                let key = read_pem("key", key).await?;
+                tls = tls.identity(Identity::from_pem(cert, key));
+            }
+            (None, None) => {}
+            _ => return Err(err!("tls certificate and key must be set together").into()),
+        }
+        options = options.with_tls(tls);
+    }
+
+    Ok(options)
+}
+
+async fn read_pem(kind: &str, path: &std::path::Path) -> Result<Vec<u8>> {
+    tokio::fs::read(path)
+        .await
+        .map_err(|e| err!("read tls {} {}: {}", kind, path.display(), e).into())
+}
 
 /// the implement of [`Registry`] by etcd
 ///
 /// ```rust
-/// let registry = EtcdRegistry::new().await?;
-/// let services = registry.get_service("helloworld", None).await?;
+/// # use registry::{etcd::EtcdRegistry, Registry};
+/// # async fn run() -> errors::Result<()> {
+/// let registry = EtcdRegistry::new(None).await?;
+/// let services = registry.get_service("helloworld".to_string(), None).await?;
+/// # Ok(())
+/// # }
 /// ```
 #[derive(Clone)]
 pub struct EtcdRegistry {
     client: Client,
     options: Options,
 
-    /// 0: registers, 1: leases
-    data: Arc<Mutex<(HashMap<String, u64>, HashMap<String, i64>)>>,
+    leases: LeaseManager,
+    keep_alives: Arc<std::sync::Mutex<KeepAlives>>,
 }
 
 impl EtcdRegistry {
     pub async fn new(opt: Option<Options>) -> Result<Self> {
-        let mut opts = match opt {
-            None => Options::new(),
-            Some(opt) => opt,
-        };
+        let mut opts = opt.unwrap_or_default();
 
         if opts.timeout == 0 {
             opts.timeout = 5;
         }
 
-        let client = Client::connect(&opts.addrs, None).await?;
+        let options = connect_options(&opts).await?;
+        let client = with_timeout("connect", opts.timeout_or(None), async {
+            Ok(Client::connect(&opts.addrs, Some(options)).await?)
+        })
+        .await?;
 
         let eg = EtcdRegistry {
             client,
             options: opts,
-            data: Arc::new(Mutex::new((HashMap::new(), HashMap::new()))),
+            leases: LeaseManager::default(),
+            keep_alives: Arc::default(),
         };
 
         Ok(eg)
@@ -60,27 +137,108 @@ impl EtcdRegistry {
 
     #[inline]
     async fn configure(&mut self, opt: Option<Options>) -> Result<()> {
-        let mut opts = match opt {
-            None => Options::new(),
-            Some(opt) => opt,
-        };
+        let mut opts = opt.unwrap_or_default();
 
         if opts.timeout == 0 {
             opts.timeout = 5;
         }
 
-        let options = {
-            let copts = ConnectOptions::new();
-            copts
-        };
+        let options = connect_options(&opts).await?;
 
-        self.client = Client::connect(&opts.addrs, Some(options)).await?;
+        self.client = with_timeout("connect", opts.timeout_or(None), async {
+            Ok(Client::connect(&opts.addrs, Some(options)).await?)
+        })
+        .await?;
         self.options = opts;
-        self.data = Arc::new(Mutex::new((HashMap::new(), HashMap::new())));
+        self.leases = LeaseManager::default();
 
         Ok(())
     }
 
+    /// the domain named by a call option, the one of the registry options
+    /// when `None`
+    fn domain<'a>(&'a self, d: &'a Option<String>) -> &'a str {
+        match d {
+            Some(d) => d,
+            None => domain(&self.options.domain),
+        }
+    }
+
+    /// registers `node` again on `interval` in the background, replacing
+    /// the task of an earlier registration of the node
+    fn keep_alive(&self, s: &Service, node: &Node, opt: RegisterOptions, interval: Duration) {
+        if interval.as_secs() as i64 >= opt.ttl {
+            logger::warn!(
+                "keep alive interval {:?} of {} {} is not below its ttl {}s",
+                interval,
+                s.name,
+                node.id,
+                opt.ttl
+            );
+        }
+
+        // the task must not own the tasks of the registry, or neither would
+        // go once the registry is dropped
+        let this = EtcdRegistry {
+            keep_alives: Arc::default(),
+            ..self.clone()
+        };
+        let (s, node) = (s.clone(), node.clone());
+        let key = keep_alive_key(self.domain(&opt.domain), &s.name, &node.id);
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // the first tick completes right away, the node was just registered
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(e) = this.register_node(&s, &node, Some(opt.clone())).await {
+                    logger::warn!("keep alive of {} {} failed: {}", s.name, node.id, e);
+                }
+            }
+        });
+
+        let mut keep_alives = self.keep_alives.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(prev) = keep_alives.0.insert(key, task) {
+            prev.abort();
+        }
+    }
+
+    /// stops registering the nodes of `s` again in every domain, all nodes
+    /// when `None`. Call it on shutdown when the nodes should lapse rather
+    /// than be deregistered.
+    pub fn stop_keep_alive(&self, s: Option<&Service>) {
+        let mut keep_alives = self.keep_alives.lock().unwrap_or_else(|e| e.into_inner());
+        match s {
+            Some(s) => {
+                keep_alives.0.retain(|key, task| {
+                    let stop = is_node_of(key, s);
+                    if stop {
+                        task.abort();
+                    }
+                    !stop
+                });
+            }
+            None => {
+                for (_, task) in keep_alives.0.drain() {
+                    task.abort();
+                }
+            }
+        }
+    }
+
+    /// stops registering the nodes of `s` in `domain` again
+    fn stop_domain_keep_alive(&self, domain: &str, s: &Service) {
+        let mut keep_alives = self.keep_alives.lock().unwrap_or_else(|e| e.into_inner());
+        for node in &s.nodes {
+            if let Some(task) = keep_alives
+                .0
+                .remove(&keep_alive_key(domain, &s.name, &node.id))
+            {
+                task.abort();
+            }
+        }
+    }
+
     #[inline]
     async fn register_node(
         &self,
@@ -88,114 +246,237 @@ impl EtcdRegistry {
         node: &Node,
         opt: Option<RegisterOptions>,
     ) -> Result<()> {
-        if s.nodes.len() == 0 {
-            return Err(err!("require at lease one node"));
+        if s.nodes.is_empty() {
+            return Err(err!("require at lease one node").into());
         }
 
+        let opt = opt.unwrap_or_default();
+        let timeout = self.options.timeout_or(opt.timeout);
+        let domain = self.domain(&opt.domain);
+
         let mut client = self.client.clone();
-        let key = format!("{}{}", s.name, node.id);
+        let key = node_key(domain, &s.name, &node.id);
 
-        let data = { self.data.lock().await.clone() };
-        let mut registers = data.0;
-        let mut leases = data.1;
+        let hash = node.content_hash();
 
-        let mut lease_not_found = false;
-        match leases.get(&key) {
-            None => {
-                // renew the lease if it exists
-                let opt = EGetOptions::new().with_serializable();
-                // look for the existing key
-                let rsp = client.get(node_path(&s.name, &node.id), Some(opt)).await?;
-
-                // get the existing lease
-                for kv in rsp.kvs() {
-                    if kv.lease() > 0 {
-                        // decode the existing node
-                        let v = str::from_utf8(kv.value())?;
-                        let svc = decode(v);
-                        if svc.is_none() {
-                            continue;
-                        }
-                        let s = svc.unwrap();
-                        if s.nodes.len() == 0 {
-                            continue;
-                        }
-                        let node = s.nodes.first().unwrap();
-                        let mut h = DefaultHasher::new();
-                        node.hash(&mut h);
-
-                        leases.insert(format!("{}{}", s.name, node.id), kv.lease());
-                        registers.insert(format!("{}{}", s.name, node.id), h.finish());
-                    }
-                }
-            }
-            Some(lease_id) if lease_id > &0 => {
-                logger::debug!("Renewing existing lease for {} {}", s.name, lease_id);
+        // a node unknown to this process may still hold the lease of an
+        // earlier one, which is renewed but never revoked on replacement
+        let (held, owned) = match self.leases.get(&key).await {
+            Some(held) => (Some(held), true),
+            None => (self.adopt(domain, s, node, timeout).await?, false),
+        };
 
-                if let Err(e) = client.lease_keep_alive(lease_id.clone()).await {
-                    logger::error!("Lease not found for {} {} {}", s.name, lease_id, e);
-                    lease_not_found = true;
-                };
+        let mut expired = false;
+        if let Some(held) = held.filter(|h| h.lease > 0 && h.ttl == opt.ttl) {
+            logger::debug!("Renewing existing lease for {} {}", s.name, held.lease);
+            if lease::renew(&mut client, held.lease, timeout).await {
+                if held.hash != hash {
+                    self.put_node(s, node, &opt, held.lease).await?;
+                } else {
+                    logger::debug!(
+                        "Service {} node {} unchanged skipping registration",
+                        s.name,
+                        node.id
+                    );
+                }
+                self.leases.hold(&key, Held { hash, ..held }).await;
+                return Ok(());
             }
-            _ => {}
+            expired = true;
         }
 
-        let mut h = DefaultHasher::new();
-        node.hash(&mut h);
-        let hash = h.finish();
+        let ttl = opt.ttl;
+        let lease_id = lease::grant(&mut client, ttl, timeout).await;
+        logger::info!(
+            "Registering {} id {} with lease {} and ttl {}",
+            s.name,
+            node.id,
+            lease_id,
+            ttl
+        );
+        self.put_node(s, node, &opt, lease_id).await?;
 
-        // let registers = self.registers.lock().await;
-        let v = registers.get(&format!("{}{}", s.name, node.id));
-        if let Some(id) = v {
-            if id == &hash && !lease_not_found {
-                println!(
-                    "Service {} node {} unchanged skipping registration",
-                    s.name, node.id
-                );
-                return Ok(());
+        let held = Held {
+            lease: lease_id,
+            ttl,
+            hash,
+        };
+        if let Some(prev) = self.leases.hold(&key, held).await {
+            if owned && !expired {
+                lease::revoke(&mut client, prev, timeout).await;
             }
         }
 
-        let mut svc = s.clone();
+        Ok(())
+    }
+
+    /// writes the value of `node` under `lease`, none when 0
+    async fn put_node(
+        &self,
+        s: &Service,
+        node: &Node,
+        opt: &RegisterOptions,
+        lease: i64,
+    ) -> Result<()> {
+        let timeout = self.options.timeout_or(opt.timeout);
+        let mut svc = strip(s, opt);
         svc.nodes = vec![node.clone()];
 
-        let mut ttl: i64 = 15;
-        if let Some(o) = opt {
-            ttl = o.ttl;
+        let mut popt = PutOptions::new();
+        if lease != 0 {
+            popt = popt.with_lease(lease);
         }
 
-        let mut popt = PutOptions::new();
-        let lgr = client.lease_grant(ttl, None).await;
-        let mut lease_id: i64 = 0;
-        if let Ok(rsp) = lgr {
-            lease_id = rsp.id();
-            popt = popt.with_lease(rsp.id());
+        let mut client = self.client.clone();
+        let path = node_path(self.domain(&opt.domain), &svc.name, &node.id);
+        with_timeout("register", timeout, async {
+            Ok(client
+                .put(path, encode(&svc, self.options.codec), Some(popt))
+                .await?)
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    /// the lease `node` is stored under in etcd, if it is alive
+    async fn adopt(
+        &self,
+        domain: &str,
+        s: &Service,
+        node: &Node,
+        timeout: Duration,
+    ) -> Result<Option<Held>> {
+        let mut client = self.client.clone();
+        let opt = EGetOptions::new().with_serializable();
+        let path = node_path(domain, &s.name, &node.id);
+        let rsp = with_timeout("register", timeout, async {
+            Ok(client.get(path, Some(opt)).await?)
+        })
+        .await?;
+
+        let kv = match rsp.kvs().first() {
+            Some(kv) if kv.lease() > 0 => kv,
+            _ => return Ok(None),
+        };
+        // the hash of the stored node tells whether it needs writing again
+        let hash = match decode(kv.value()).as_ref().and_then(|s| s.nodes.first()) {
+            Some(node) => node.content_hash(),
+            None => return Ok(None),
+        };
+        let ttl = lease::granted_ttl(&mut client, kv.lease(), timeout).await?;
+        if ttl == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(Held {
+            lease: kv.lease(),
+            ttl,
+            hash,
+        }))
+    }
+}
+
+/// merges the nodes of every decoded value into one service per version,
+/// see [`decode_entries`] for the handling of corrupt values
+fn merge_versions(
+    kvs: &[etcd_client::KeyValue],
+    strict: bool,
+    errs: &mut MultiStatus,
+) -> Result<HashMap<String, Service>> {
+    let mut entries = Vec::with_capacity(kvs.len());
+    for kv in kvs {
+        entries.push((kv.key_str()?, kv.value()));
+    }
+
+    let mut m: HashMap<String, Service> = HashMap::new();
+    for sn in decode_entries(entries, strict, errs)? {
+        match m.get_mut(&sn.version) {
+            Some(s) => s.nodes.extend(sn.nodes),
+            None => {
+                m.insert(sn.version.clone(), sn);
+            }
+        }
+    }
+
+    Ok(m)
+}
+
+impl EtcdRegistry {
+    async fn read_service(
+        &self,
+        s: String,
+        opt: GetOptions,
+        strict: bool,
+    ) -> Result<(Vec<Service>, MultiStatus)> {
+        let timeout = self.options.timeout_or(opt.timeout);
+        let domain = self.domain(&opt.domain);
+
+        let mut client = self.client.clone();
+
+        let opts = EGetOptions::new().with_prefix().with_serializable();
+
+        let key = service_path(domain, &s) + "/";
+        logger::info!("{}", key);
+        let rsp = with_timeout("get_service", timeout, async {
+            Ok(client.get(key, Some(opts)).await?)
+        })
+        .await?;
+        if rsp.kvs().is_empty() {
+            return Err(Error::NotFound(s));
         }
-        logger::info!(
-            "Registering {} id {} with lease {} and ttl {}",
-            svc.name,
-            node.id,
-            lease_id,
-            ttl
-        );
 
-        client
-            .put(
-                node_path(svc.name.to_string(), node.id.to_string()),
-                encode(&svc).into(),
-                Some(popt),
-            )
+        let mut errs = MultiStatus::new();
+        let mut m = merge_versions(rsp.kvs(), strict, &mut errs)?;
+        self.stitch(m.values_mut(), domain, &meta_path(domain, &s, ""), timeout)
             .await?;
+        let services = filter_service(s, m.into_values().collect(), &opt)?;
+
+        Ok((services, errs))
+    }
+
+    /// puts the version meta of `domain` stored under `prefix` back onto
+    /// the services registered without it
+    async fn stitch<'a>(
+        &self,
+        services: impl Iterator<Item = &'a mut Service>,
+        domain: &str,
+        prefix: &str,
+        timeout: Duration,
+    ) -> Result<()> {
+        let mut client = self.client.clone();
+        let opts = EGetOptions::new().with_prefix().with_serializable();
+        let rsp = with_timeout("get_service", timeout, async {
+            Ok(client.get(prefix, Some(opts)).await?)
+        })
+        .await?;
+        if rsp.kvs().is_empty() {
+            return Ok(());
+        }
 
-        registers.insert(format!("{}{}", svc.name, node.id), hash);
-        if lease_id != 0 {
-            leases.insert(format!("{}{}", svc.name, node.id), lease_id);
+        let mut metas = HashMap::new();
+        for kv in rsp.kvs() {
+            match serde_json::from_slice::<VersionMeta>(kv.value()) {
+                Ok(meta) => {
+                    metas.insert(kv.key_str()?.to_string(), meta);
+                }
+                Err(e) => logger::warn!("skipping version meta {}: {}", kv.key_str()?, e),
+            }
         }
 
-        {
-            let mut data = self.data.lock().await;
-            data.0 = registers;
-            data.1 = leases;
+        for svc in services {
+            let meta = match metas.get(&meta_path(domain, &svc.name, &svc.version)) {
+                Some(meta) => meta,
+                None => continue,
+            };
+            if svc.endpoints.is_empty() {
+                if let Some(endpoints) = &meta.endpoints {
+                    svc.endpoints = endpoints.clone();
+                }
+            }
+            if svc.apis.is_none() {
+                svc.apis = meta.apis.clone();
+            }
         }
 
         Ok(())
@@ -216,126 +497,293 @@ impl Registry for EtcdRegistry {
 
     #[inline]
     async fn register(&self, s: &Service, opt: Option<RegisterOptions>) -> Result<()> {
-        if s.nodes.len() == 0 {
-            return Err(err!("require at lease one node"));
+        let popt = opt.unwrap_or_default();
+        if !popt.skip_validation {
+            s.validate()?;
+        }
+
+        if s.nodes.is_empty() {
+            return Err(err!("require at lease one node").into());
+        }
+
+        if let Some(meta) = version_meta(s, &popt) {
+            let timeout = self.options.timeout_or(popt.timeout);
+            let path = meta_path(self.domain(&popt.domain), &s.name, &s.version);
+            let value = serde_json::to_string(&meta)?;
+            let mut client = self.client.clone();
+            with_timeout("register", timeout, async {
+                Ok(client.put(path, value, None).await?)
+            })
+            .await?;
         }
 
-        let popt = match opt {
-            Some(o) => o,
-            None => RegisterOptions::new(),
-        };
         // registry each node individually
         for node in &s.nodes {
             self.register_node(s, node, Some(popt.clone())).await?;
+            if let Some(interval) = popt.interval {
+                self.keep_alive(s, node, popt.clone(), interval);
+            }
         }
 
         Ok(())
     }
 
     #[inline]
-    async fn deregister(&self, s: &Service, _: Option<DeregisterOptions>) -> Result<()> {
-        if s.nodes.len() == 0 {
-            bail!("required at lease one node")
+    async fn deregister(&self, s: &Service, opt: Option<DeregisterOptions>) -> Result<()> {
+        if s.nodes.is_empty() {
+            return Err(err!("required at lease one node").into());
         }
 
+        let opt = opt.unwrap_or_default();
+        let timeout = self.options.timeout_or(opt.timeout);
+        let domain = self.domain(&opt.domain);
+
+        self.stop_domain_keep_alive(domain, s);
         let mut client = self.client.clone();
         for node in &s.nodes {
             logger::info!("Deregistering {} id {}", s.name, node.id);
-            {
-                let key = format!("{}{}", s.name, node.id);
-                let mut data = self.data.lock().await;
-                data.0.remove(&key);
-                data.1.remove(&key);
+            let released = self
+                .leases
+                .release(&node_key(domain, &s.name, &node.id))
+                .await;
+
+            let path = node_path(domain, &s.name, &node.id);
+            with_timeout("deregister", timeout, async {
+                Ok(client.delete(path, None).await?)
+            })
+            .await?;
+            if let Some(lease) = released {
+                lease::revoke(&mut client, lease, timeout).await;
             }
+        }
 
-            client
-                .delete(node_path(s.name.clone(), node.id.clone()), None)
-                .await?;
+        // drop the version meta along with the last node of the version
+        let opts = EGetOptions::new().with_prefix().with_serializable();
+        let key = service_path(domain, &s.name) + "/";
+        let rsp = with_timeout("deregister", timeout, async {
+            Ok(client.get(key, Some(opts)).await?)
+        })
+        .await?;
+        let remaining = rsp
+            .kvs()
+            .iter()
+            .filter_map(|kv| decode(kv.value()))
+            .any(|sn| sn.version == s.version);
+        if !remaining {
+            let path = meta_path(domain, &s.name, &s.version);
+            with_timeout("deregister", timeout, async {
+                Ok(client.delete(path, None).await?)
+            })
+            .await?;
         }
 
         Ok(())
     }
 
-    #[inline]
-    async fn get_service(&self, s: String, _opt: Option<GetOptions>) -> Result<Vec<Service>> {
+    /// writes the nodes and version metas of every service in transactions
+    /// of up to [`MAX_TXN_OPS`] operations under one lease, rather than a
+    /// round trip per node. The nodes are written even when unchanged.
+    async fn register_many(
+        &self,
+        services: &[Service],
+        opt: Option<RegisterOptions>,
+    ) -> Result<()> {
+        let popt = opt.unwrap_or_default();
+        for s in services {
+            if !popt.skip_validation {
+                s.validate()?;
+            }
+            if s.nodes.is_empty() {
+                return Err(err!("require at lease one node").into());
+            }
+        }
+
+        let timeout = self.options.timeout_or(popt.timeout);
+        let domain = self.domain(&popt.domain);
         let mut client = self.client.clone();
 
-        let opts = { EGetOptions::new().with_prefix().with_serializable() };
+        let lease_id = lease::grant(&mut client, popt.ttl, timeout).await;
 
-        let key = service_path(s) + "/";
-        logger::info!("{}", key);
-        let rsp = client.get(key, Some(opts)).await?;
-        if rsp.kvs().len() == 0 {
-            // TODO: registry error
-            bail!("service not found")
+        let mut ops = vec![];
+        let mut hashes = vec![];
+        for s in services {
+            if let Some(meta) = version_meta(s, &popt) {
+                let path = meta_path(domain, &s.name, &s.version);
+                ops.push(TxnOp::put(path, serde_json::to_string(&meta)?, None));
+            }
+            for node in &s.nodes {
+                let mut svc = strip(s, &popt);
+                svc.nodes = vec![node.clone()];
+                let mut put = PutOptions::new();
+                if lease_id != 0 {
+                    put = put.with_lease(lease_id);
+                }
+                let path = node_path(domain, &s.name, &node.id);
+                ops.push(TxnOp::put(
+                    path,
+                    encode(&svc, self.options.codec),
+                    Some(put),
+                ));
+                hashes.push((node_key(domain, &s.name, &node.id), node.content_hash()));
+            }
         }
 
-        let mut m = HashMap::new();
-        for kv in rsp.kvs() {
-            let v = kv.value_str()?;
-            if let Some(sn) = decode(v) {
-                let version = sn.version.clone();
-                let result = m.get(&version);
-                if result.is_none() {
-                    m.insert(version, sn);
-                } else {
-                    let mut s = result.unwrap().clone();
-                    s.nodes = vec![s.nodes, sn.nodes].concat();
-                    m.insert(version, s);
+        logger::info!(
+            "Registering {} nodes of {} services with lease {} and ttl {}",
+            hashes.len(),
+            services.len(),
+            lease_id,
+            popt.ttl
+        );
+        commit(&mut client, "register", timeout, ops).await?;
+
+        let mut replaced = vec![];
+        for (key, hash) in hashes {
+            let held = Held {
+                lease: lease_id,
+                ttl: popt.ttl,
+                hash,
+            };
+            replaced.extend(self.leases.hold(&key, held).await);
+        }
+        for lease in replaced {
+            lease::revoke(&mut client, lease, timeout).await;
+        }
+
+        if let Some(interval) = popt.interval {
+            for s in services {
+                for node in &s.nodes {
+                    self.keep_alive(s, node, popt.clone(), interval);
                 }
             }
         }
 
-        let services = {
-            let mut slice = vec![];
-            for (_, v) in &m {
-                slice.push(v.clone());
+        Ok(())
+    }
+
+    /// deletes the nodes of every service in transactions, then the version
+    /// metas left without nodes with one read per service name
+    async fn deregister_many(
+        &self,
+        services: &[Service],
+        opt: Option<DeregisterOptions>,
+    ) -> Result<()> {
+        if services.iter().any(|s| s.nodes.is_empty()) {
+            return Err(err!("required at lease one node").into());
+        }
+
+        let opt = opt.unwrap_or_default();
+        let timeout = self.options.timeout_or(opt.timeout);
+        let domain = self.domain(&opt.domain);
+        let mut client = self.client.clone();
+
+        let mut ops = vec![];
+        let mut released = vec![];
+        for s in services {
+            self.stop_domain_keep_alive(domain, s);
+            for node in &s.nodes {
+                let key = node_key(domain, &s.name, &node.id);
+                released.extend(self.leases.release(&key).await);
+                ops.push(TxnOp::delete(node_path(domain, &s.name, &node.id), None));
             }
-            slice
-        };
+        }
+        logger::info!(
+            "Deregistering {} nodes of {} services",
+            ops.len(),
+            services.len()
+        );
+        commit(&mut client, "deregister", timeout, ops).await?;
+        for lease in released {
+            lease::revoke(&mut client, lease, timeout).await;
+        }
+
+        let mut versions: HashMap<&str, Vec<&str>> = HashMap::new();
+        for s in services {
+            versions.entry(&s.name).or_default().push(&s.version);
+        }
+
+        let mut ops = vec![];
+        for (name, versions) in versions {
+            let opts = EGetOptions::new().with_prefix().with_serializable();
+            let key = service_path(domain, name) + "/";
+            let rsp = with_timeout("deregister", timeout, async {
+                Ok(client.get(key, Some(opts)).await?)
+            })
+            .await?;
+            let remaining: Vec<String> = rsp
+                .kvs()
+                .iter()
+                .filter_map(|kv| decode(kv.value()))
+                .map(|sn| sn.version)
+                .collect();
+            for version in versions.into_iter().unique() {
+                if !remaining.iter().any(|v| v == version) {
+                    ops.push(TxnOp::delete(meta_path(domain, name, version), None));
+                }
+            }
+        }
+        commit(&mut client, "deregister", timeout, ops).await
+    }
 
+    #[inline]
+    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> Result<Vec<Service>> {
+        let opt = opt.unwrap_or_default();
+        let strict = opt.strict.unwrap_or(self.options.strict_decode);
+        let (services, _) = self.read_service(s, opt, strict).await?;
         Ok(services)
     }
 
     #[inline]
-    async fn list_service(&self, _opt: Option<ListOptions>) -> Result<Vec<Service>> {
+    async fn get_service_checked(
+        &self,
+        s: String,
+        opt: Option<GetOptions>,
+    ) -> Result<(Vec<Service>, MultiStatus)> {
+        self.read_service(s, opt.unwrap_or_default(), false).await
+    }
+
+    #[inline]
+    async fn list_service(&self, opt: Option<ListOptions>) -> Result<Vec<Service>> {
+        let opt = opt.unwrap_or_default();
+        let timeout = self.options.timeout_or(opt.timeout);
+        let domain = self.domain(&opt.domain);
+
         let mut client = self.client.clone();
 
-        let opts = { EGetOptions::new().with_prefix().with_serializable() };
+        let opts = EGetOptions::new().with_prefix().with_serializable();
 
-        let rsp = client.get(PREFIX, Some(opts)).await?;
+        let rsp = with_timeout("list_service", timeout, async {
+            Ok(client.get(domain_path(domain) + "/", Some(opts)).await?)
+        })
+        .await?;
 
         let mut services = Vec::new();
-        if rsp.kvs().len() == 0 {
+        if rsp.kvs().is_empty() {
             return Ok(services);
         }
 
-        let mut m = HashMap::new();
-        for kv in rsp.kvs() {
-            let v = kv.value_str()?;
-            if let Some(sn) = decode(v) {
-                let version = sn.version.clone();
-                let result = m.get(&version);
-                if result.is_none() {
-                    m.insert(version, sn);
-                } else {
-                    let mut s = result.unwrap().clone();
-                    s.nodes = vec![s.nodes, sn.nodes].concat();
-                    m.insert(version, s);
-                }
-            }
-        }
-
+        let mut errs = MultiStatus::new();
+        let mut m = merge_versions(rsp.kvs(), self.options.strict_decode, &mut errs)?;
+        let prefix = meta_domain_path(domain) + "/";
+        self.stitch(m.values_mut(), domain, &prefix, timeout)
+            .await?;
         for v in m.keys().sorted() {
             services.push(m[v].clone());
         }
 
-        Ok(services)
+        Ok(filter_services(
+            services,
+            opt.version.as_deref(),
+            &opt.metadata_filter,
+        ))
     }
 
     #[inline]
     async fn watch(&self, opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send + Sync>> {
-        let watcher = EtcdWatcher::new(self.client.clone(), opt).await?;
+        let mut opt = opt.unwrap_or_default();
+        opt.domain = Some(self.domain(&opt.domain).to_string());
+        let timeout = self.options.timeout_or(opt.timeout);
+        let watcher = EtcdWatcher::new(self.client.clone(), Some(opt), timeout).await?;
         Ok(Box::new(watcher))
     }
 
@@ -345,41 +793,310 @@ impl Registry for EtcdRegistry {
     }
 }
 
-fn encode(s: &Service) -> impl Into<String> {
-    match serde_json::to_string(s) {
-        Ok(s) => s,
-        Err(_) => "".to_string(),
+/// applies `ops` in transactions of up to [`MAX_TXN_OPS`] operations, a
+/// failed transaction stops the call with the ones before it applied
+async fn commit(client: &mut Client, op: &str, timeout: Duration, ops: Vec<TxnOp>) -> Result<()> {
+    let mut ops = ops.into_iter().peekable();
+    while ops.peek().is_some() {
+        let txn = Txn::new().and_then(ops.by_ref().take(MAX_TXN_OPS).collect::<Vec<_>>());
+        with_timeout(op, timeout, async { Ok(client.txn(txn).await?) }).await?;
     }
+    Ok(())
 }
 
-fn decode<T: Into<String>>(data: T) -> Option<Service> {
-    match serde_json::from_str(data.into().as_str()) {
-        Ok(s) => Some(s),
-        Err(_) => None,
+fn encode(s: &Service, codec: Codec) -> Vec<u8> {
+    match codec {
+        Codec::Json => serde_json::to_vec(s).unwrap_or_default(),
+        Codec::Protobuf => prost::Message::encode_to_vec(&proto::Service::from(s)),
     }
 }
 
-fn node_path<T: Into<String>>(s: T, id: T) -> String {
-    let service = s.into().replace("/", "-");
-    let node = id.into().replace("/", "-");
-    PREFIX.to_string() + "/" + service.as_str() + "/" + node.as_str()
+/// decodes a value of either codec, `None` when it is corrupt
+fn decode(data: &[u8]) -> Option<Service> {
+    decode_entry("", data).ok()
+}
+
+/// the domain named by an option, [`DEFAULT_DOMAIN`] when `None`
+fn domain(d: &Option<String>) -> &str {
+    d.as_deref().unwrap_or(DEFAULT_DOMAIN)
 }
 
-fn service_path<T: Into<String>>(s: T) -> String {
-    PREFIX.to_string() + "/" + s.into().replace("/", "-").as_str()
+/// the bookkeeping key of a node, its path as no two nodes share one
+fn node_key(domain: &str, s: &str, id: &str) -> String {
+    node_path(domain, s, id)
+}
+
+/// the key of the keep alive task of a node
+fn keep_alive_key(domain: &str, s: &str, id: &str) -> (String, String, String) {
+    (domain.to_string(), s.to_string(), id.to_string())
+}
+
+/// whether the keep alive task of `key` registers a node of `s`, in any
+/// domain
+fn is_node_of(key: &(String, String, String), s: &Service) -> bool {
+    let (_, name, id) = key;
+    *name == s.name && s.nodes.iter().any(|node| node.id == *id)
+}
+
+fn domain_path(domain: &str) -> String {
+    PREFIX.to_string() + "/" + domain.replace("/", "-").as_str()
+}
+
+fn service_path(domain: &str, s: &str) -> String {
+    domain_path(domain) + "/" + s.replace("/", "-").as_str()
+}
+
+fn node_path(domain: &str, s: &str, id: &str) -> String {
+    service_path(domain, s) + "/" + id.replace("/", "-").as_str()
+}
+
+fn meta_domain_path(domain: &str) -> String {
+    META_PREFIX.to_string() + "/" + domain.replace("/", "-").as_str()
+}
+
+/// the key of the version meta, with an empty version the prefix of
+/// every version of the service
+fn meta_path(domain: &str, s: &str, version: &str) -> String {
+    let service = s.replace("/", "-");
+    let version = version.replace("/", "-");
+    meta_domain_path(domain) + "/" + service.as_str() + "/" + version.as_str()
+}
+
+/// the service as stored in a node value, without the parts `opt` keeps
+/// in the version meta
+fn strip(s: &Service, opt: &RegisterOptions) -> Service {
+    let mut svc = s.clone();
+    if !opt.include_endpoints {
+        svc.endpoints = vec![];
+    }
+    if !opt.include_openapi {
+        svc.apis = None;
+    }
+    svc
+}
+
+/// the version meta of `s`, `None` when the node values keep everything
+fn version_meta(s: &Service, opt: &RegisterOptions) -> Option<VersionMeta> {
+    if opt.include_endpoints && opt.include_openapi {
+        return None;
+    }
+
+    let mut meta = VersionMeta::default();
+    if !opt.include_endpoints {
+        meta.endpoints = Some(s.endpoints.clone());
+    }
+    if !opt.include_openapi {
+        meta.apis = s.apis.clone();
+    }
+    Some(meta)
+}
+
+/// splits a key built by [`node_path`] back into the service name and node id,
+/// leaving out the domain. Both come back as stored, so a `/` replaced by `-`
+/// can't be told apart from a `-` in the original name.
+fn parse_node_path(key: &str) -> Option<(String, String)> {
+    let rest = key.strip_prefix(PREFIX)?.strip_prefix('/')?;
+    let mut parts = rest.splitn(3, '/');
+    parts.next().filter(|d| !d.is_empty())?;
+    let service = parts.next().filter(|s| !s.is_empty())?;
+    let node = parts.next().filter(|s| !s.is_empty() && !s.contains('/'))?;
+    Some((service.to_string(), node.to_string()))
+}
+
+/// builds the minimal service a delete event refers to when only its key is
+/// known. The node carries no address and the service is marked `partial=true`.
+fn partial_service(key: &str) -> Option<Service> {
+    let (name, id) = parse_node_path(key)?;
+    let mut s = Service::new();
+    s.name = name;
+    s.metadata.insert("partial".to_string(), "true".to_string());
+    s.nodes = vec![Node {
+        id,
+        ..Node::default()
+    }];
+    Some(s)
 }
 
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
 
     use crate::{
-        types::{Node, Service},
-        Registry,
+        options::{Codec, DeregisterOptions, GetOptions, Options, RegisterOptions, WatchOptions},
+        types::{Endpoint, Node, OpenApi, Service, Value},
+        Error, Registry,
     };
+    use etcd_client::GetOptions as EGetOptions;
+
+    use super::{
+        connect_options, decode, encode, is_node_of, keep_alive_key, meta_path, node_key,
+        node_path, parse_node_path, partial_service, strip, version_meta, EtcdRegistry, KeepAlives,
+    };
+    use errors::{Code, Result, Status};
+
+    #[tokio::test]
+    async fn test_connect_options() {
+        let mut opts = Options::new();
+        opts.with_auth("root", "secret").with_secure(true);
+        assert!(connect_options(&opts).await.is_ok());
+
+        let mut opts = Options::new();
+        opts.with_tls_ca("/nonexistent/ca.pem");
+        let e = connect_options(&opts).await.unwrap_err();
+        assert!(e.to_string().contains("/nonexistent/ca.pem"), "{}", e);
+
+        let mut opts = Options::new();
+        opts.tls_cert = Some("/nonexistent/cert.pem".into());
+        let e = connect_options(&opts).await.unwrap_err();
+        assert!(e.to_string().contains("set together"), "{}", e);
+    }
+
+    #[test]
+    fn test_keep_alive_keys() {
+        assert_ne!(
+            node_key("vine", "foo", "1bar"),
+            node_key("vine", "foo1", "bar")
+        );
+
+        let s = Service {
+            name: "foo".to_string(),
+            nodes: vec![Node {
+                id: "1bar".to_string(),
+                address: "192.168.1.111".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        };
+        assert!(is_node_of(&keep_alive_key("vine", "foo", "1bar"), &s));
+        assert!(is_node_of(&keep_alive_key("other", "foo", "1bar"), &s));
+        assert!(!is_node_of(&keep_alive_key("vine", "foo1", "bar"), &s));
+        assert!(!is_node_of(&keep_alive_key("vine", "foo", "1"), &s));
+    }
+
+    #[test]
+    fn test_parse_node_path() {
+        let key = node_path("vine", "io.vine.helloworld", "1");
+        assert_eq!(
+            parse_node_path(&key),
+            Some(("io.vine.helloworld".to_string(), "1".to_string()))
+        );
+
+        // `/` in names and ids are stored as `-`
+        let key = node_path("tenant/a", "io/vine/helloworld", "node/1");
+        assert_eq!(key, "/vine/registry/tenant-a/io-vine-helloworld/node-1");
+        assert_eq!(
+            parse_node_path(&key),
+            Some(("io-vine-helloworld".to_string(), "node-1".to_string()))
+        );
+
+        assert_eq!(
+            parse_node_path("/vine/registry/vine/io.vine.helloworld"),
+            None
+        );
+        assert_eq!(
+            parse_node_path("/vine/registry/vine/io.vine.helloworld/"),
+            None
+        );
+        assert_eq!(parse_node_path("/vine/registry/vine//1"), None);
+        assert_eq!(parse_node_path("/vine/registry//a/b"), None);
+        assert_eq!(parse_node_path("/vine/registry/vine/a/b/c"), None);
+        assert_eq!(parse_node_path("/other/a/b"), None);
+    }
+
+    #[test]
+    fn test_partial_service() {
+        let s = partial_service("/vine/registry/vine/io.vine.helloworld/1").unwrap();
+        assert_eq!(s.name, "io.vine.helloworld");
+        assert_eq!(s.nodes.len(), 1);
+        assert_eq!(s.nodes[0].id, "1");
+        assert_eq!(s.metadata.get("partial").map(String::as_str), Some("true"));
+
+        assert!(partial_service("/vine/registry").is_none());
+    }
+
+    /// a service with many endpoints and an openapi document
+    fn heavy_service(name: &str) -> Service {
+        let endpoints = (0..100)
+            .map(|i| Endpoint {
+                name: format!("Helloworld.Call{}", i),
+                request: Some(Value {
+                    name: "Request".to_string(),
+                    rtype: "Request".to_string(),
+                    values: vec![],
+                }),
+                response: None,
+                metadata: HashMap::new(),
+            })
+            .collect();
+        Service {
+            name: name.to_string(),
+            version: "v1.0.0".to_string(),
+            endpoints,
+            nodes: vec![Node {
+                id: "1".to_string(),
+                address: "192.168.1.111".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            apis: Some(OpenApi {
+                openapi: "3.0.1".to_string(),
+                info: None,
+                external_docs: None,
+                servers: vec![],
+                tags: vec![],
+                paths: HashMap::new(),
+                components: None,
+            }),
+            ..Service::new()
+        }
+    }
+
+    #[test]
+    fn test_strip() {
+        let s = heavy_service("io.vine.heavy");
+        let full = RegisterOptions::new();
+        assert_eq!(strip(&s, &full), s);
+        assert!(version_meta(&s, &full).is_none());
+
+        let mut slim = RegisterOptions::new();
+        slim.with_include_endpoints(false)
+            .with_include_openapi(false);
+        let stripped = strip(&s, &slim);
+        assert!(stripped.endpoints.is_empty());
+        assert!(stripped.apis.is_none());
+        let full_len = encode(&s, Codec::Json).len();
+        let slim_len = encode(&stripped, Codec::Json).len();
+        assert!(slim_len * 10 < full_len, "{} vs {}", slim_len, full_len);
+
+        let meta = version_meta(&s, &slim).unwrap();
+        assert_eq!(meta.endpoints.unwrap(), s.endpoints);
+        assert_eq!(meta.apis, s.apis);
+
+        assert_eq!(
+            meta_path("vine", "io/vine", "v1/beta"),
+            "/vine/registry-meta/vine/io-vine/v1-beta"
+        );
+    }
+
+    #[test]
+    fn test_codec() {
+        let mut s = heavy_service("io.vine.helloworld");
+        s.apis = None;
 
-    use super::EtcdRegistry;
-    use errors::Result;
+        let json = encode(&s, Codec::Json);
+        let pb = encode(&s, Codec::Protobuf);
+        assert!(pb.len() < json.len(), "{} vs {}", pb.len(), json.len());
+
+        // either is read back whichever the registry writes
+        assert_eq!(decode(&json).unwrap(), s);
+        assert_eq!(decode(&pb).unwrap(), s);
+
+        assert!(decode(&pb[..pb.len() - 1]).is_none());
+        assert!(decode(b"{").is_none());
+    }
 
     #[tokio::test]
     async fn test_new_etcd_registry() -> Result<()> {
@@ -525,4 +1242,277 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_strict_decode() -> Result<()> {
+        let node = Node {
+            id: "1".to_string(),
+            address: "192.168.1.111".to_string(),
+            port: 11101,
+            metadata: HashMap::new(),
+        };
+        let s = Service {
+            name: "io.vine.corrupt".to_string(),
+            version: "v1.0.0".to_string(),
+            nodes: vec![node],
+            ..Service::new()
+        };
+        let e = EtcdRegistry::new(None).await?;
+        e.register(&s, None).await?;
+        let bad = node_path("vine", "io.vine.corrupt", "bad");
+        e.client.clone().put(bad.clone(), "{garbage", None).await?;
+
+        // lenient reads skip the bad entry
+        let services = e.get_service(s.name.clone(), None).await?;
+        assert_eq!(services[0].nodes.len(), 1);
+
+        let (services, errs) = e.get_service_checked(s.name.clone(), None).await?;
+        assert_eq!(services[0].nodes.len(), 1);
+        assert_eq!(errs.len(), 1);
+        assert!(errs.iter().next().unwrap().detail().contains(&bad));
+
+        let mut gopt = GetOptions::new();
+        gopt.with_strict(true);
+        let err = e.get_service(s.name.clone(), Some(gopt)).await.unwrap_err();
+        let status = Status::from(err);
+        assert_eq!(status.code(), Code::InternalServerError);
+        assert!(status.detail().contains(&bad));
+
+        e.client.clone().delete(bad, None).await?;
+        e.deregister(&s, None).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_register_metadata_change() -> Result<()> {
+        let mut s = Service {
+            name: "io.vine.metadata".to_string(),
+            version: "v1.0.0".to_string(),
+            nodes: vec![Node {
+                id: "1".to_string(),
+                address: "192.168.1.111".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        };
+        let e = EtcdRegistry::new(None).await?;
+        e.register(&s, None).await?;
+
+        s.nodes[0]
+            .metadata
+            .insert("zone".to_string(), "a".to_string());
+        e.register(&s, None).await?;
+
+        let rsp = e
+            .client
+            .clone()
+            .get(node_path("vine", "io.vine.metadata", "1"), None)
+            .await?;
+        let stored: Service = serde_json::from_slice(rsp.kvs()[0].value())?;
+        assert_eq!(stored.nodes[0].metadata, s.nodes[0].metadata);
+
+        e.deregister(&s, None).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_domains() -> Result<()> {
+        let e = EtcdRegistry::new(None).await?;
+        let s = heavy_service("io.vine.tenant");
+
+        let mut ropt = RegisterOptions::new();
+        ropt.with_domain("tenant-a".to_string());
+        e.register(&s, Some(ropt)).await?;
+
+        let mut gopt = GetOptions::new();
+        gopt.with_domain("tenant-a".to_string());
+        let services = e.get_service(s.name.clone(), Some(gopt)).await?;
+        assert_eq!(services[0].nodes, s.nodes);
+
+        // the default domain does not see the tenant
+        assert!(e.get_service(s.name.clone(), None).await.is_err());
+        let mut gopt = GetOptions::new();
+        gopt.with_domain("tenant-b".to_string());
+        assert!(e.get_service(s.name.clone(), Some(gopt)).await.is_err());
+
+        let mut dopt = DeregisterOptions::new();
+        dopt.with_domain("tenant-a".to_string());
+        e.deregister(&s, Some(dopt)).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_register_many() -> Result<()> {
+        let e = EtcdRegistry::new(None).await?;
+        // more nodes than fit one transaction
+        let services: Vec<Service> = (0..40)
+            .map(|i| {
+                let mut s = heavy_service("io.vine.batch");
+                s.version = format!("v{}.0.0", i);
+                s.nodes = (0..4)
+                    .map(|n| Node {
+                        id: format!("{}-{}", i, n),
+                        address: "192.168.1.111".to_string(),
+                        port: 11101,
+                        metadata: HashMap::new(),
+                    })
+                    .collect();
+                s
+            })
+            .collect();
+
+        let mut ropt = RegisterOptions::new();
+        ropt.with_include_endpoints(false);
+        e.register_many(&services, Some(ropt)).await?;
+
+        let got = e.get_service("io.vine.batch".to_string(), None).await?;
+        assert_eq!(got.len(), 40);
+        assert!(got.iter().all(|s| s.nodes.len() == 4));
+        // the endpoints are read back from the version metas
+        assert!(got.iter().all(|s| s.endpoints.len() == 100));
+
+        e.deregister_many(&services, None).await?;
+        let e2 = e.get_service("io.vine.batch".to_string(), None).await;
+        assert!(matches!(e2, Err(Error::NotFound(_))));
+
+        // the version metas went with their last nodes
+        let key = meta_path("vine", "io.vine.batch", "");
+        let opts = EGetOptions::new().with_prefix().with_count_only();
+        let rsp = e.client.clone().get(key, Some(opts)).await?;
+        assert_eq!(rsp.count(), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_register_slim() -> Result<()> {
+        let e = EtcdRegistry::new(None).await?;
+        let mut sizes = vec![];
+        for (name, include) in &[("io.vine.full", true), ("io.vine.slim", false)] {
+            let s = heavy_service(name);
+            let mut ropt = RegisterOptions::new();
+            ropt.with_include_endpoints(*include)
+                .with_include_openapi(*include);
+            e.register(&s, Some(ropt)).await?;
+
+            let rsp = e
+                .client
+                .clone()
+                .get(node_path("vine", name, "1"), None)
+                .await?;
+            sizes.push(rsp.kvs()[0].value().len());
+
+            // get returns the complete service either way
+            let services = e.get_service(name.to_string(), None).await?;
+            assert_eq!(services[0].endpoints, s.endpoints);
+            assert_eq!(services[0].apis, s.apis);
+
+            e.deregister(&s, None).await?;
+            let rsp = e
+                .client
+                .clone()
+                .get(meta_path("vine", name, "v1.0.0"), None)
+                .await?;
+            assert!(rsp.kvs().is_empty());
+        }
+        assert!(sizes[1] * 10 < sizes[0], "{:?}", sizes);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive() -> Result<()> {
+        let s = Service {
+            name: "io.vine.keepalive".to_string(),
+            version: "v1.0.0".to_string(),
+            nodes: vec![Node {
+                id: "1".to_string(),
+                address: "192.168.1.111".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        };
+        let e = EtcdRegistry::new(None).await?;
+        let mut ropt = RegisterOptions::new();
+        ropt.with_ttl(2).with_interval(Duration::from_millis(500));
+        e.register(&s, Some(ropt)).await?;
+
+        // alive well past the ttl
+        tokio::time::sleep(Duration::from_secs(4)).await;
+        assert_eq!(e.get_service(s.name.clone(), None).await?.len(), 1);
+
+        e.stop_keep_alive(None);
+        tokio::time::sleep(Duration::from_secs(4)).await;
+        assert!(e.get_service(s.name.clone(), None).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keep_alives_abort() {
+        let alive = Arc::new(());
+        let held = alive.clone();
+        let mut tasks = KeepAlives::default();
+        tasks.0.insert(
+            keep_alive_key("vine", "io.vine.keepalive", "1"),
+            tokio::spawn(async move {
+                let _held = held;
+                std::future::pending::<()>().await
+            }),
+        );
+        drop(tasks);
+
+        // the future of an aborted task is dropped
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while Arc::strong_count(&alive) > 1 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("the keep alive task outlived its registry");
+    }
+
+    #[tokio::test]
+    async fn test_watch_lease_expiry() -> Result<()> {
+        let e = EtcdRegistry::new(None).await?;
+
+        let mut wopt = WatchOptions::new();
+        wopt.with_service("io.vine.expiry".to_string());
+        let watcher = e.watch(Some(wopt)).await?;
+
+        let s = Service {
+            name: "io.vine.expiry".to_string(),
+            version: "v1.0.0".to_string(),
+            nodes: vec![Node {
+                id: "1".to_string(),
+                address: "192.168.1.111".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        };
+        let mut ropt = RegisterOptions::new();
+        ropt.with_ttl(2);
+        e.register(&s, Some(ropt)).await?;
+
+        let deleted = tokio::time::timeout(tokio::time::Duration::from_secs(10), async {
+            loop {
+                let r = watcher.next().await?;
+                if r.action == "delete" {
+                    return Ok::<_, errors::anyhow::Error>(r);
+                }
+            }
+        })
+        .await??;
+        let svc = deleted.service.unwrap();
+        assert_eq!(svc.name, "io.vine.expiry");
+        assert_eq!(svc.nodes[0].id, "1");
+
+        Ok(())
+    }
 }
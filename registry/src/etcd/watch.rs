@@ -1,20 +1,73 @@
+use std::collections::VecDeque;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use chrono::Local;
-use errors::{bail, Result};
 use etcd_client::{
-    Client, EventType, WatchOptions as EWatchOptions, WatchStream, Watcher as EWatcher,
+    Client, Event, EventType, WatchOptions as EWatchOptions, WatchStream, Watcher as EWatcher,
 };
 use tokio::sync::Mutex;
 
-use crate::{options::WatchOptions, types, Watcher};
+use crate::error::{Error, Result};
+use crate::{options::WatchOptions, types, with_timeout, Watcher};
 
-use super::{decode, service_path, PREFIX};
+use super::{decode, domain, domain_path, partial_service, service_path};
 
 #[derive(Clone)]
 pub struct EtcdWatcher {
-    w: Arc<Mutex<(EWatcher, WatchStream)>>,
+    /// the watch, and the results of a response not returned yet
+    w: Arc<Mutex<(EWatcher, WatchStream, VecDeque<types::Result>)>>,
+    opts: WatchOptions,
+}
+
+/// the service a delete of the node at `key` reports, the one of `prev`,
+/// the value deleted, when the event has it
+fn deleted_service(key: &str, prev: Option<&[u8]>) -> Option<types::Service> {
+    match prev {
+        Some(value) => Some(decode(value).unwrap_or_default()),
+        // without prev_kv only the key tells which node is gone
+        None => partial_service(key),
+    }
+}
+
+/// the result an event reports, `None` for the events naming no node
+fn event_result(event: &Event) -> Result<Option<types::Result>> {
+    let kv = match event.kv() {
+        Some(kv) => kv,
+        None => return Ok(None),
+    };
+
+    let action;
+    let mut service = types::Service::new();
+
+    match event.event_type() {
+        EventType::Put => {
+            if let Some(svc) = decode(kv.value()) {
+                service = svc;
+            };
+            if kv.create_revision() == kv.mod_revision() {
+                action = "create";
+            } else {
+                action = "update";
+            }
+        }
+        EventType::Delete => {
+            action = "delete";
+            let prev = event.prev_kv().map(|kv| kv.value());
+            match deleted_service(kv.key_str()?, prev) {
+                Some(svc) => service = svc,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    logger::debug!("watch event: {} {}", action, service.name);
+    Ok(Some(types::Result {
+        action: action.to_string(),
+        service: Some(service),
+        timestamp: Local::now().timestamp(),
+    }))
 }
 
 #[async_trait]
@@ -22,59 +75,27 @@ impl Watcher for EtcdWatcher {
     async fn next(&self) -> Result<types::Result> {
         let rc = self.w.clone();
         let mut w = rc.lock().await;
-        while let Some(rsp) = w.1.message().await? {
+        loop {
+            if let Some(r) = w.2.pop_front() {
+                return Ok(r);
+            }
+
+            let rsp = match w.1.message().await? {
+                Some(rsp) => rsp,
+                None => return Err(Error::WatcherStopped),
+            };
             if rsp.canceled() {
-                bail!("could not get next, watch is canceled")
+                return Err(Error::WatcherStopped);
             }
 
             for event in rsp.events() {
-                if event.kv().is_none() {
-                    continue;
-                }
-
-                let mut action = "";
-                let mut service = types::Service::new();
-
-                match event.event_type() {
-                    EventType::Put => {
-                        if let Some(kv) = event.kv() {
-                            let value = kv.value_str()?;
-                            if let Some(svc) = decode(value) {
-                                service = svc;
-                            };
-                            if kv.create_revision() == kv.mod_revision() {
-                                action = "create";
-                            } else {
-                                action = "update";
-                            }
-                        } else {
-                            continue;
-                        }
-                    }
-                    EventType::Delete => {
-                        action = "delete";
-                        if let Some(kv) = event.prev_kv() {
-                            let value = kv.value_str()?;
-                            if let Some(svc) = decode(value) {
-                                service = svc;
-                            };
-                        } else {
-                            continue;
-                        }
+                if let Some(r) = event_result(event)? {
+                    if self.opts.matches(&r) {
+                        w.2.push_back(r);
                     }
-                };
-
-                logger::debug!("watch event: {} {}", action, service.name);
-                let event_result = types::Result {
-                    action: action.to_string().clone(),
-                    service: Some(service),
-                    timestamp: Local::now().timestamp(),
-                };
-                return Ok(event_result);
+                }
             }
         }
-
-        bail!("could not get next")
     }
 
     async fn stop(&self) {
@@ -85,25 +106,77 @@ impl Watcher for EtcdWatcher {
 }
 
 impl EtcdWatcher {
-    pub async fn new(client: Client, opt: Option<WatchOptions>) -> Result<Self> {
-        let wopts = {
-            let opts = EWatchOptions::new().with_prev_key().with_prefix();
-            opts
-        };
+    /// starts the watch, which must be set up within `timeout`
+    pub async fn new(client: Client, opt: Option<WatchOptions>, timeout: Duration) -> Result<Self> {
+        let wopts = EWatchOptions::new().with_prev_key().with_prefix();
 
-        let mut watch_path = PREFIX.to_string();
-        if opt.is_some() {
-            let o = opt.unwrap();
-            if o.service != "" {
-                watch_path = service_path(o.service) + "/"
-            }
+        let o = opt.unwrap_or_default();
+        let watch_path = if !o.service.is_empty() {
+            service_path(domain(&o.domain), &o.service) + "/"
+        } else if !o.prefix.is_empty() {
+            // narrows the watch, the names are checked again on the way out
+            service_path(domain(&o.domain), &o.prefix)
+        } else {
+            domain_path(domain(&o.domain)) + "/"
         };
 
-        let w = client.clone().watch(watch_path, Some(wopts)).await?;
+        let mut client = client;
+        let (w, stream) = with_timeout("watch", timeout, async {
+            Ok(client.watch(watch_path, Some(wopts)).await?)
+        })
+        .await?;
 
         let watcher = EtcdWatcher {
-            w: Arc::new(Mutex::new(w)),
+            w: Arc::new(Mutex::new((w, stream, VecDeque::new()))),
+            opts: o,
         };
         Ok(watcher)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::deleted_service;
+    use crate::etcd::{encode, node_path};
+    use crate::{
+        options::Codec,
+        types::{Node, Service},
+    };
+
+    #[test]
+    fn test_deleted_service() {
+        // the delete of a watch without prev_kv
+        let key = node_path("vine", "io.vine.helloworld", "1");
+        let s = deleted_service(&key, None).unwrap();
+        assert_eq!(s.name, "io.vine.helloworld");
+        assert_eq!(s.nodes.len(), 1);
+        assert_eq!(s.nodes[0].id, "1");
+        assert!(s.nodes[0].address.is_empty());
+        assert_eq!(s.metadata.get("partial").map(String::as_str), Some("true"));
+        // a bare key naming no node reports nothing
+        assert!(deleted_service("/vine/registry/vine/io.vine.helloworld", None).is_none());
+
+        let full = Service {
+            name: "io.vine.helloworld".to_string(),
+            version: "v1.0.0".to_string(),
+            nodes: vec![Node {
+                id: "1".to_string(),
+                address: "192.168.1.111".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        };
+        for codec in [Codec::Json, Codec::Protobuf] {
+            let value = encode(&full, codec);
+            assert_eq!(deleted_service(&key, Some(&value)), Some(full.clone()));
+        }
+        // a corrupt value deleted is still a delete
+        assert_eq!(
+            deleted_service(&key, Some(b"corrupt")),
+            Some(Service::new())
+        );
+    }
+}
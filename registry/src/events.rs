@@ -0,0 +1,331 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use broker::Broker;
+use futures_core::Stream;
+use tokio::sync::{broadcast, OnceCell};
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tokio_stream::StreamExt;
+
+use crate::{global_registry, types, SharedRegistry};
+
+/// how many events a subscriber may fall behind before it is lagged
+const DEFAULT_CAPACITY: usize = 256;
+
+/// the topic [`publish_loop`] is usually given
+pub const DEFAULT_TOPIC: &str = "vine.registry.events";
+
+/// the first and the largest delay before the watch is restarted
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// BusEvent is an item delivered to an [`EventReceiver`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum BusEvent {
+    /// a watch result of the underlying registry
+    Update(Box<types::Result>),
+    /// the subscriber fell behind and this many events were dropped
+    Lagged(u64),
+}
+
+/// EventBus runs a single watch against a registry and fans its
+/// results out to any number of subscribers.
+///
+/// ```rust
+/// # use registry::{events::EventBus, global_registry};
+/// # use tokio_stream::StreamExt;
+/// # async fn run() {
+/// let bus = EventBus::new(global_registry().await);
+/// let mut events = bus.subscribe();
+/// while let Some(event) = events.next().await {
+///     println!("{:?}", event);
+/// }
+/// # }
+/// ```
+pub struct EventBus {
+    tx: broadcast::Sender<types::Result>,
+    task: JoinHandle<()>,
+}
+
+impl EventBus {
+    pub fn new(registry: SharedRegistry) -> Self {
+        Self::with_capacity(registry, DEFAULT_CAPACITY)
+    }
+
+    /// `capacity` bounds how far a subscriber can fall behind
+    pub fn with_capacity(registry: SharedRegistry, capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        let task = tokio::spawn(run(registry, tx.clone()));
+        EventBus { tx, task }
+    }
+
+    pub fn subscribe(&self) -> EventReceiver {
+        EventReceiver {
+            inner: BroadcastStream::new(self.tx.subscribe()),
+        }
+    }
+
+    /// stops the underlying watch, subscribers see the end of their stream
+    /// once the bus is dropped
+    pub fn stop(&self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for EventBus {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// watches `registry` forever, restarting the watch with a backoff
+/// whenever it can't be started or stops delivering
+async fn run(registry: SharedRegistry, tx: broadcast::Sender<types::Result>) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let watcher = {
+            let r = registry.lock().await;
+            r.watch(None).await
+        };
+
+        match watcher {
+            Ok(w) => {
+                backoff = MIN_BACKOFF;
+                loop {
+                    match w.next().await {
+                        // no subscribers is not an error
+                        Ok(r) => {
+                            let _ = tx.send(r);
+                        }
+                        Err(e) => {
+                            logger::warn!("event bus watch ended, restarting: {}", e);
+                            break;
+                        }
+                    }
+                }
+                w.stop().await;
+            }
+            Err(e) => logger::error!("event bus could not start watch: {}", e),
+        }
+
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// EventReceiver is the [`Stream`] of events of one [`EventBus`] subscriber
+pub struct EventReceiver {
+    inner: BroadcastStream<types::Result>,
+}
+
+impl Stream for EventReceiver {
+    type Item = BusEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner).poll_next(cx).map(|r| {
+            r.map(|r| match r {
+                Ok(r) => BusEvent::Update(Box::new(r)),
+                Err(BroadcastStreamRecvError::Lagged(n)) => BusEvent::Lagged(n),
+            })
+        })
+    }
+}
+
+/// republishes every event of `events` onto `topic` of `broker` as the JSON
+/// of its [`types::Result`], until the bus of the receiver is dropped. Lost
+/// events and failed publishes are logged and skipped.
+///
+/// ```rust
+/// # use broker::memory::MemoryBroker;
+/// # use registry::events::{global_events, publish_loop, DEFAULT_TOPIC};
+/// # async fn run() {
+/// let events = global_events().await.subscribe();
+/// tokio::spawn(async move {
+///     publish_loop(events, &MemoryBroker::new(None), DEFAULT_TOPIC).await
+/// });
+/// # }
+/// ```
+pub async fn publish_loop(mut events: EventReceiver, broker: &(dyn Broker + Sync), topic: &str) {
+    while let Some(event) = events.next().await {
+        let r = match event {
+            BusEvent::Update(r) => r,
+            BusEvent::Lagged(n) => {
+                logger::warn!("{} registry events not published to {}", n, topic);
+                continue;
+            }
+        };
+        let body = match serde_json::to_vec(&r) {
+            Ok(body) => body,
+            Err(e) => {
+                logger::error!("could not encode registry event: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = broker.publish(topic, &body).await {
+            logger::error!("could not publish registry event to {}: {}", topic, e);
+        }
+    }
+}
+
+static GLOBAL_EVENTS: OnceCell<EventBus> = OnceCell::const_new();
+
+/// returns the process wide [`EventBus`] over the global registry,
+/// starting it on first use
+pub async fn global_events() -> &'static EventBus {
+    GLOBAL_EVENTS
+        .get_or_init(|| async { EventBus::new(global_registry().await) })
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use errors::{bail, Result};
+    use tokio::sync::{mpsc, Mutex};
+    use tokio_stream::StreamExt;
+
+    use broker::{memory::MemoryBroker, Broker};
+
+    use super::{publish_loop, BusEvent, EventBus, DEFAULT_TOPIC};
+    use crate::{
+        memory::MemoryRegistry,
+        types::{self, Node, Service},
+        Registry, SharedRegistry,
+    };
+
+    fn service(id: &str) -> Service {
+        Service {
+            name: "io.vine.helloworld".to_string(),
+            version: "v1.0.0".to_string(),
+            nodes: vec![Node {
+                id: id.to_string(),
+                address: "127.0.0.1".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        }
+    }
+
+    /// registers probes until one reaches a subscriber of `bus`, so the bus
+    /// watches `r` before the test subscribes. The events of the probes
+    /// registered before reach it first.
+    async fn watching(bus: &EventBus, r: &MemoryRegistry) -> Result<()> {
+        let mut probe = bus.subscribe();
+        let wait = async {
+            for i in 0.. {
+                let id = format!("probe-{}", i);
+                let s = Service {
+                    name: "io.vine.probe".to_string(),
+                    ..service(&id)
+                };
+                r.register(&s, None).await?;
+                loop {
+                    let event = tokio::time::timeout(Duration::from_millis(20), probe.next());
+                    match event.await {
+                        Ok(Some(BusEvent::Update(e)))
+                            if e.service.as_ref().is_some_and(|s| s.nodes[0].id == id) =>
+                        {
+                            return Ok(());
+                        }
+                        Ok(Some(_)) => continue,
+                        Ok(None) => bail!("event bus stopped"),
+                        Err(_) => break,
+                    }
+                }
+            }
+            Ok(())
+        };
+        tokio::time::timeout(Duration::from_secs(5), wait).await?
+    }
+
+    #[tokio::test]
+    async fn test_subscribers() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let shared: SharedRegistry = Arc::new(Mutex::new(Box::new(r.clone())));
+        let bus = EventBus::with_capacity(shared, 4);
+        watching(&bus, &r).await?;
+
+        let mut fast = vec![];
+        let (progress, mut received) = mpsc::unbounded_channel();
+        for _ in 0..2 {
+            let mut events = bus.subscribe();
+            let progress = progress.clone();
+            fast.push(tokio::spawn(async move {
+                let mut got = vec![];
+                while got.len() < 10 {
+                    match events.next().await {
+                        Some(BusEvent::Update(r)) => got.push(*r),
+                        other => panic!("unexpected {:?}", other),
+                    }
+                    let _ = progress.send(());
+                }
+                got
+            }));
+        }
+        let mut slow = bus.subscribe();
+
+        // the fast subscribers read each event before the next one
+        for i in 0..10 {
+            r.register(&service(&i.to_string()), None).await?;
+            for _ in 0..fast.len() {
+                tokio::time::timeout(Duration::from_secs(5), received.recv()).await?;
+            }
+        }
+
+        for f in fast {
+            let got = f.await?;
+            let ids: Vec<String> = got
+                .iter()
+                .map(|r| r.service.as_ref().unwrap().nodes[0].id.clone())
+                .collect();
+            assert_eq!(ids, (0..10).map(|i| i.to_string()).collect::<Vec<_>>());
+        }
+
+        assert_eq!(slow.next().await, Some(BusEvent::Lagged(6)));
+        match slow.next().await {
+            Some(BusEvent::Update(r)) => assert_eq!(r.service.unwrap().nodes[0].id, "6"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // the bus keeps serving after a subscriber lagged
+        let mut late = bus.subscribe();
+        r.register(&service("10"), None).await?;
+        match late.next().await {
+            Some(BusEvent::Update(r)) => assert_eq!(r.service.unwrap().nodes[0].id, "10"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_publish_loop() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let shared: SharedRegistry = Arc::new(Mutex::new(Box::new(r.clone())));
+        let bus = EventBus::new(shared);
+        let broker = MemoryBroker::new(None);
+        let sub = broker.subscribe(DEFAULT_TOPIC).await?;
+
+        watching(&bus, &r).await?;
+        let events = bus.subscribe();
+        let b = broker.clone();
+        let publisher = tokio::spawn(async move { publish_loop(events, &b, DEFAULT_TOPIC).await });
+
+        r.register(&service("1"), None).await?;
+        let got: types::Result = serde_json::from_slice(&sub.next().await?)?;
+        assert_eq!(got.action, "create");
+        assert_eq!(got.service.unwrap().nodes[0].id, "1");
+
+        // the loop ends with the bus
+        drop(bus);
+        tokio::time::timeout(Duration::from_secs(1), publisher).await??;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,391 @@
+pub mod watch;
+
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use errors::{err, MultiStatus};
+
+use self::watch::FileWatcher;
+use crate::error::Result;
+use crate::options::{
+    DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+};
+use crate::types::{Node, Service};
+use crate::{decode_entries, filter_service, filter_services, Registry, Watcher};
+
+/// the extension of the node files, anything else in the directory is ignored
+const EXT: &str = "json";
+
+/// the implement of [`Registry`] persisting services as JSON files under a
+/// directory, one file per node at `<dir>/<service>/<node id>.json`. Every
+/// process sharing the directory sees the others' registrations and, through
+/// file system notifications, their changes.
+///
+/// ```rust
+/// # use registry::{file::FileRegistry, Registry};
+/// # async fn run() -> errors::Result<()> {
+/// let registry = FileRegistry::new("/var/lib/vine/registry").await?;
+/// let services = registry.get_service("helloworld".to_string(), None).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct FileRegistry {
+    options: Options,
+    dir: PathBuf,
+}
+
+impl FileRegistry {
+    /// creates `dir` when it doesn't exist
+    pub async fn new(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        tokio::fs::create_dir_all(&dir).await?;
+        Ok(FileRegistry {
+            options: Options::new(),
+            dir,
+        })
+    }
+
+    fn service_dir(&self, name: &str) -> PathBuf {
+        self.dir.join(name.replace("/", "-"))
+    }
+
+    fn node_file(&self, name: &str, id: &str) -> PathBuf {
+        self.service_dir(name)
+            .join(format!("{}.{}", id.replace("/", "-"), EXT))
+    }
+
+    /// the services stored under `name`, `None` when there are none
+    async fn read_service(
+        &self,
+        name: &str,
+        strict: bool,
+        errs: &mut MultiStatus,
+    ) -> Result<Option<Vec<Service>>> {
+        let files = node_files(&self.service_dir(name)).await?;
+        if files.is_empty() {
+            return Ok(None);
+        }
+
+        let mut entries = Vec::with_capacity(files.len());
+        for path in files {
+            // a file removed since the listing is no longer part of the service
+            if let Ok(data) = tokio::fs::read(&path).await {
+                entries.push((path.display().to_string(), data));
+            }
+        }
+
+        let entries = entries.iter().map(|(k, v)| (k.as_str(), v.as_slice()));
+        let mut out: Vec<Service> = vec![];
+        for sn in decode_entries(entries, strict, errs)? {
+            match out.iter_mut().find(|s| s.version == sn.version) {
+                Some(s) => s.nodes.extend(sn.nodes),
+                None => out.push(sn),
+            }
+        }
+        out.sort_by(|a, b| a.version.cmp(&b.version));
+
+        Ok(Some(out).filter(|out| !out.is_empty()))
+    }
+}
+
+/// the node files in `dir`, sorted, none when `dir` doesn't exist
+async fn node_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut rd = match tokio::fs::read_dir(dir).await {
+        Ok(rd) => rd,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = rd.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) == Some(EXT) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// writes `data` next to `path` and renames it into place, so readers
+/// never see a partial file
+async fn write_atomic(path: &Path, data: Vec<u8>) -> Result<()> {
+    let tmp = path.with_extension(format!("{}.tmp", EXT));
+    tokio::fs::write(&tmp, data).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[async_trait]
+impl Registry for FileRegistry {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.options = opt.unwrap_or_default();
+        Ok(())
+    }
+
+    #[inline]
+    async fn options(&self) -> Options {
+        self.options.clone()
+    }
+
+    async fn register(&self, s: &Service, opt: Option<RegisterOptions>) -> Result<()> {
+        if !opt.unwrap_or_default().skip_validation {
+            s.validate()?;
+        }
+
+        if s.nodes.is_empty() {
+            return Err(err!("require at lease one node").into());
+        }
+
+        tokio::fs::create_dir_all(self.service_dir(&s.name)).await?;
+        for node in &s.nodes {
+            let mut svc = s.clone();
+            svc.nodes = vec![node.clone()];
+            let data = serde_json::to_vec(&svc)?;
+            write_atomic(&self.node_file(&s.name, &node.id), data).await?;
+        }
+
+        logger::debug!("Registered {} version {} in files", s.name, s.version);
+        Ok(())
+    }
+
+    async fn deregister(&self, s: &Service, _opt: Option<DeregisterOptions>) -> Result<()> {
+        if s.nodes.is_empty() {
+            return Err(err!("required at lease one node").into());
+        }
+
+        for node in &s.nodes {
+            match tokio::fs::remove_file(self.node_file(&s.name, &node.id)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+
+        // the service directory goes with its last node, it is fine when
+        // another process registered a node meanwhile
+        let _ = tokio::fs::remove_dir(self.service_dir(&s.name)).await;
+
+        Ok(())
+    }
+
+    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> Result<Vec<Service>> {
+        let opt = opt.unwrap_or_default();
+        let strict = opt.strict.unwrap_or(self.options.strict_decode);
+        let services = self
+            .read_service(&s, strict, &mut MultiStatus::new())
+            .await?
+            .unwrap_or_default();
+        filter_service(s, services, &opt)
+    }
+
+    async fn get_service_checked(
+        &self,
+        s: String,
+        opt: Option<GetOptions>,
+    ) -> Result<(Vec<Service>, MultiStatus)> {
+        let opt = opt.unwrap_or_default();
+        let mut errs = MultiStatus::new();
+        let services = self
+            .read_service(&s, false, &mut errs)
+            .await?
+            .unwrap_or_default();
+        Ok((filter_service(s, services, &opt)?, errs))
+    }
+
+    async fn list_service(&self, opt: Option<ListOptions>) -> Result<Vec<Service>> {
+        let opt = opt.unwrap_or_default();
+        let mut names = vec![];
+        let mut rd = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = rd.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        names.sort();
+
+        let mut out = vec![];
+        let mut errs = MultiStatus::new();
+        for name in names {
+            let services = self
+                .read_service(&name, self.options.strict_decode, &mut errs)
+                .await?;
+            out.extend(services.unwrap_or_default());
+        }
+        Ok(filter_services(
+            out,
+            opt.version.as_deref(),
+            &opt.metadata_filter,
+        ))
+    }
+
+    async fn watch(&self, opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send + Sync>> {
+        let watcher = FileWatcher::new(&self.dir, opt).await?;
+        Ok(Box::new(watcher))
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        "file"
+    }
+}
+
+/// splits a node file under `dir` back into the service name and node id
+fn parse_node_file(dir: &Path, path: &Path) -> Option<(String, String)> {
+    let rel = path.strip_prefix(dir).ok()?;
+    let mut parts = rel.iter();
+    let service = parts.next()?.to_str()?;
+    let file = Path::new(parts.next()?);
+    if parts.next().is_some() || file.extension().and_then(|e| e.to_str()) != Some(EXT) {
+        return None;
+    }
+    let id = file.file_stem()?.to_str()?;
+    Some((service.to_string(), id.to_string()))
+}
+
+/// the service a removed node file refers to, like an etcd delete
+/// without its previous value the node carries only its id
+fn partial_service(name: String, id: String) -> Service {
+    let mut s = Service::new();
+    s.name = name;
+    s.metadata.insert("partial".to_string(), "true".to_string());
+    s.nodes = vec![Node {
+        id,
+        ..Node::default()
+    }];
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use errors::Result;
+
+    use super::{parse_node_file, FileRegistry};
+    use crate::{
+        options::{GetOptions, WatchOptions},
+        types::{self, Node, Service},
+        Registry, Watcher,
+    };
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vine-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn service(version: &str, ids: &[&str]) -> Service {
+        let nodes = ids
+            .iter()
+            .map(|id| Node {
+                id: id.to_string(),
+                address: "192.168.1.111".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            })
+            .collect();
+        Service {
+            name: "io.vine.helloworld".to_string(),
+            version: version.to_string(),
+            nodes,
+            ..Service::new()
+        }
+    }
+
+    async fn next(w: &(dyn Watcher + Send + Sync)) -> Result<types::Result> {
+        Ok(tokio::time::timeout(Duration::from_secs(2), w.next()).await??)
+    }
+
+    #[test]
+    fn test_parse_node_file() {
+        let dir = PathBuf::from("/var/lib/vine");
+        assert_eq!(
+            parse_node_file(&dir, &dir.join("io.vine.helloworld").join("1.json")),
+            Some(("io.vine.helloworld".to_string(), "1".to_string()))
+        );
+        assert_eq!(
+            parse_node_file(&dir, &dir.join("io.vine.helloworld").join("1.json.tmp")),
+            None
+        );
+        assert_eq!(parse_node_file(&dir, &dir.join("io.vine.helloworld")), None);
+        assert_eq!(parse_node_file(&dir, &PathBuf::from("/tmp/a/1.json")), None);
+    }
+
+    #[tokio::test]
+    async fn test_file_registry() -> Result<()> {
+        let dir = temp_dir("file-registry");
+        let r = FileRegistry::new(&dir).await?;
+        r.register(&service("v1.0.0", &["1", "2"]), None).await?;
+        r.register(&service("v2.0.0", &["3"]), None).await?;
+
+        let name = "io.vine.helloworld".to_string();
+        let services = r.get_service(name.clone(), None).await?;
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].nodes.len(), 2);
+        assert_eq!(r.list_service(None).await?.len(), 2);
+
+        // registrations outlive the registry
+        drop(r);
+        let r = FileRegistry::new(&dir).await?;
+        assert_eq!(r.get_service(name.clone(), None).await?.len(), 2);
+
+        // a corrupt file fails only strict reads
+        std::fs::write(dir.join("io.vine.helloworld").join("bad.json"), "{garbage")?;
+        let (services, errs) = r.get_service_checked(name.clone(), None).await?;
+        assert_eq!(services.len(), 2);
+        assert_eq!(errs.len(), 1);
+        let mut gopt = GetOptions::new();
+        gopt.with_strict(true);
+        assert!(r.get_service(name.clone(), Some(gopt)).await.is_err());
+        std::fs::remove_file(dir.join("io.vine.helloworld").join("bad.json"))?;
+
+        r.deregister(&service("v1.0.0", &["1", "2"]), None).await?;
+        r.deregister(&service("v2.0.0", &["3"]), None).await?;
+        assert!(r.get_service(name, None).await.is_err());
+        assert!(r.list_service(None).await?.is_empty());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_watch() -> Result<()> {
+        let dir = temp_dir("file-watch");
+        let r = FileRegistry::new(&dir).await?;
+        let mut wopt = WatchOptions::new();
+        wopt.with_service("io.vine.helloworld".to_string());
+        let w = r.watch(Some(wopt)).await?;
+
+        let mut s = service("v1.0.0", &["1"]);
+        r.register(&s, None).await?;
+        let event = next(w.as_ref()).await?;
+        assert_eq!(event.action, "create");
+        assert_eq!(event.service.unwrap().nodes[0].id, "1");
+
+        s.nodes[0].port = 11102;
+        r.register(&s, None).await?;
+        let event = next(w.as_ref()).await?;
+        assert_eq!(event.action, "update");
+        assert_eq!(event.service.unwrap().nodes[0].port, 11102);
+
+        // another process sharing the directory
+        let other = FileRegistry::new(&dir).await?;
+        let mut o = service("v1.0.0", &["1"]);
+        o.name = "io.vine.other".to_string();
+        other.register(&o, None).await?;
+        other.deregister(&s, None).await?;
+        let event = next(w.as_ref()).await?;
+        assert_eq!(event.action, "delete");
+        let deleted = event.service.unwrap();
+        assert_eq!(deleted.version, "v1.0.0");
+        assert_eq!(deleted.nodes[0].id, "1");
+
+        w.stop().await;
+        assert!(w.next().await.is_err());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex as StdMutex;
+
+use async_trait::async_trait;
+use chrono::Local;
+use errors::err;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher as _};
+use tokio::sync::{mpsc, Mutex, Notify};
+
+use super::{node_files, parse_node_file, partial_service};
+use crate::error::{Error, Result};
+use crate::{options::WatchOptions, types, Watcher};
+
+/// name -> node id -> the service as last read from its file
+type Known = HashMap<String, HashMap<String, types::Service>>;
+
+/// the implement of [`Watcher`] over the file system notifications of the
+/// directory of a [`super::FileRegistry`]
+pub struct FileWatcher {
+    dir: PathBuf,
+    opts: WatchOptions,
+    /// kept to keep receiving notifications
+    _watcher: StdMutex<RecommendedWatcher>,
+    rx: Mutex<mpsc::UnboundedReceiver<notify::Result<notify::Event>>>,
+    /// the results not yet returned, and what every node file held last
+    state: Mutex<(Vec<types::Result>, Known)>,
+    stopped: AtomicBool,
+    exit: Notify,
+}
+
+#[async_trait]
+impl Watcher for FileWatcher {
+    async fn next(&self) -> Result<types::Result> {
+        let mut rx = self.rx.lock().await;
+        loop {
+            if self.stopped.load(Ordering::SeqCst) {
+                return Err(Error::WatcherStopped);
+            }
+
+            {
+                let mut state = self.state.lock().await;
+                if !state.0.is_empty() {
+                    return Ok(state.0.remove(0));
+                }
+            }
+
+            let event = tokio::select! {
+                event = rx.recv() => event,
+                _ = self.exit.notified() => continue,
+            };
+
+            match event {
+                Some(Ok(event)) => {
+                    for path in event.paths {
+                        self.changed(&path).await;
+                    }
+                }
+                Some(Err(e)) => return Err(err!("could not get next: {}", e).into()),
+                None => return Err(Error::WatcherStopped),
+            }
+        }
+    }
+
+    async fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.exit.notify_one();
+    }
+}
+
+impl FileWatcher {
+    pub async fn new(dir: &Path, opt: Option<WatchOptions>) -> Result<Self> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // the watcher being gone is not an error
+            let _ = tx.send(event);
+        })?;
+        watcher.watch(dir, RecursiveMode::Recursive)?;
+
+        let w = FileWatcher {
+            dir: dir.to_path_buf(),
+            opts: opt.unwrap_or_default(),
+            _watcher: StdMutex::new(watcher),
+            rx: Mutex::new(rx),
+            state: Mutex::new((vec![], Known::new())),
+            stopped: AtomicBool::new(false),
+            exit: Notify::new(),
+        };
+
+        // what is registered already is not reported as created
+        let mut rd = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = rd.next_entry().await? {
+            for path in node_files(&entry.path()).await? {
+                w.read(&path, false).await;
+            }
+        }
+
+        Ok(w)
+    }
+
+    /// reports the change of `path`, a new service directory reports
+    /// the files written to it before it was watched
+    async fn changed(&self, path: &Path) {
+        if path.parent() == Some(self.dir.as_path()) {
+            if let Ok(files) = node_files(path).await {
+                for file in files {
+                    self.read(&file, true).await;
+                }
+            }
+            return;
+        }
+        self.read(path, true).await;
+    }
+
+    /// reads the node file at `path` again, queueing a result when `report`
+    /// and it changed
+    async fn read(&self, path: &Path, report: bool) {
+        let (name, id) = match parse_node_file(&self.dir, path) {
+            Some(parsed) => parsed,
+            None => return,
+        };
+
+        let current = match tokio::fs::read(path).await {
+            Ok(data) => match serde_json::from_slice::<types::Service>(&data) {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    logger::warn!("skipping node file {}: {}", path.display(), e);
+                    return;
+                }
+            },
+            Err(_) => None,
+        };
+
+        let mut state = self.state.lock().await;
+        let (queue, known) = &mut *state;
+        let nodes = known.entry(name.clone()).or_default();
+        let (action, service) = match current {
+            Some(s) => match nodes.insert(id, s.clone()) {
+                Some(prev) if prev == s => return,
+                Some(_) => ("update", s),
+                None => ("create", s),
+            },
+            None => match nodes.remove(&id) {
+                Some(prev) => ("delete", prev),
+                None if report => ("delete", partial_service(name, id)),
+                None => return,
+            },
+        };
+
+        let r = types::Result {
+            action: action.to_string(),
+            service: Some(service),
+            timestamp: Local::now().timestamp(),
+        };
+        if report && self.opts.matches(&r) {
+            queue.push(r);
+        }
+    }
+}
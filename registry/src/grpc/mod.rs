@@ -0,0 +1,346 @@
+pub mod server;
+pub mod watch;
+
+use std::convert::TryInto;
+
+use async_trait::async_trait;
+use errors::{err, Code, Status};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+
+pub use self::server::serve;
+use self::watch::GrpcWatcher;
+use crate::error::{Error, Result};
+use crate::options::{
+    DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+};
+use crate::proto::{self, registry_client::RegistryClient};
+use crate::types::Service;
+use crate::{filter_service, filter_services, with_timeout, Registry, Watcher};
+
+/// the implement of [`Registry`] over the gRPC registry service of a
+/// remote registry, see [`serve`]
+///
+/// ```rust
+/// # use registry::{grpc::GrpcRegistry, options::Options, Registry};
+/// # async fn run() -> errors::Result<()> {
+/// let mut opts = Options::new();
+/// opts.addrs = vec!["127.0.0.1:11500".to_string()];
+/// let registry = GrpcRegistry::new(Some(opts)).await?;
+/// let services = registry.get_service("helloworld".to_string(), None).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct GrpcRegistry {
+    client: RegistryClient<Channel>,
+    options: Options,
+}
+
+impl GrpcRegistry {
+    pub async fn new(opt: Option<Options>) -> Result<Self> {
+        let mut opts = opt.unwrap_or_default();
+
+        if opts.timeout == 0 {
+            opts.timeout = 5;
+        }
+
+        let client = connect(&opts).await?;
+        Ok(GrpcRegistry {
+            client,
+            options: opts,
+        })
+    }
+}
+
+/// connects to every address of `opts`, balancing the calls between them
+async fn connect(opts: &Options) -> Result<RegistryClient<Channel>> {
+    if opts.addrs.is_empty() {
+        return Err(err!("require at lease one registry address").into());
+    }
+
+    let mut endpoints = Vec::with_capacity(opts.addrs.len());
+    for addr in &opts.addrs {
+        let uri = if addr.contains("://") {
+            addr.clone()
+        } else if opts.secure {
+            format!("https://{}", addr)
+        } else {
+            format!("http://{}", addr)
+        };
+
+        let mut endpoint = Endpoint::from_shared(uri)?;
+        if opts.secure {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new())?;
+        }
+        endpoints.push(endpoint);
+    }
+
+    let channel = if endpoints.len() == 1 {
+        let endpoint = endpoints.remove(0);
+        with_timeout("connect", opts.timeout_or(None), async {
+            Ok(endpoint.connect().await?)
+        })
+        .await?
+    } else {
+        Channel::balance_list(endpoints.into_iter())
+    };
+
+    Ok(RegistryClient::new(channel))
+}
+
+/// the options carrying `domain`, `None` for the default domain
+fn domain_options(domain: &Option<String>) -> Option<proto::Options> {
+    domain.as_ref().map(|d| proto::Options {
+        ttl: 0,
+        domain: d.clone(),
+    })
+}
+
+/// the remote status of a failed call
+fn remote(s: tonic::Status) -> anyhow::Error {
+    Status::from(s).into()
+}
+
+fn is_not_found(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<Status>()
+        .is_some_and(|s| s.code() == Code::NotFound)
+}
+
+#[async_trait]
+impl Registry for GrpcRegistry {
+    #[inline]
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        let mut opts = opt.unwrap_or_default();
+
+        if opts.timeout == 0 {
+            opts.timeout = 5;
+        }
+
+        self.client = connect(&opts).await?;
+        self.options = opts;
+        Ok(())
+    }
+
+    #[inline]
+    async fn options(&self) -> Options {
+        self.options.clone()
+    }
+
+    #[inline]
+    async fn register(&self, s: &Service, opt: Option<RegisterOptions>) -> Result<()> {
+        let opt = opt.unwrap_or_default();
+        if !opt.skip_validation {
+            s.validate()?;
+        }
+
+        // the ttl travels as the service options, like the go registry service
+        let mut service = proto::Service::from(s);
+        service.options = Some(proto::Options {
+            ttl: opt.ttl,
+            domain: opt.domain.clone().unwrap_or_default(),
+        });
+
+        let mut client = self.client.clone();
+        with_timeout("register", self.options.timeout_or(opt.timeout), async {
+            client.register(service).await.map_err(remote)?;
+            Ok(())
+        })
+        .await
+        .map_err(Error::from)
+    }
+
+    #[inline]
+    async fn deregister(&self, s: &Service, opt: Option<DeregisterOptions>) -> Result<()> {
+        let opt = opt.unwrap_or_default();
+        let mut service = proto::Service::from(s);
+        service.options = domain_options(&opt.domain);
+
+        let mut client = self.client.clone();
+        with_timeout("deregister", self.options.timeout_or(opt.timeout), async {
+            client.deregister(service).await.map_err(remote)?;
+            Ok(())
+        })
+        .await
+        .map_err(Error::from)
+    }
+
+    #[inline]
+    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> Result<Vec<Service>> {
+        let opt = opt.unwrap_or_default();
+        let req = proto::GetRequest {
+            service: s.clone(),
+            options: domain_options(&opt.domain),
+        };
+
+        let mut client = self.client.clone();
+        let rsp = with_timeout("get service", self.options.timeout_or(opt.timeout), async {
+            client.get_service(req).await.map_err(remote)
+        })
+        .await
+        .map_err(|e| match Error::from(e) {
+            // the server answers an unknown service with its status
+            Error::Backend(e) if is_not_found(&e) => Error::NotFound(s.clone()),
+            e => e,
+        })?;
+
+        let services = rsp
+            .into_inner()
+            .services
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<std::result::Result<_, Status>>()?;
+        // the filters are not part of the request, the server sends every node
+        filter_service(s, services, &opt)
+    }
+
+    #[inline]
+    async fn list_service(&self, opt: Option<ListOptions>) -> Result<Vec<Service>> {
+        let opt = opt.unwrap_or_default();
+
+        let mut client = self.client.clone();
+        let rsp = with_timeout(
+            "list service",
+            self.options.timeout_or(opt.timeout),
+            async {
+                client
+                    .list_services(proto::ListRequest {
+                        options: domain_options(&opt.domain),
+                    })
+                    .await
+                    .map_err(remote)
+            },
+        )
+        .await?;
+
+        let services = rsp
+            .into_inner()
+            .services
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<std::result::Result<_, Status>>()?;
+        Ok(filter_services(
+            services,
+            opt.version.as_deref(),
+            &opt.metadata_filter,
+        ))
+    }
+
+    #[inline]
+    async fn watch(&self, opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send + Sync>> {
+        let opt = opt.unwrap_or_default();
+        let req = proto::WatchRequest {
+            options: domain_options(&opt.domain),
+            service: opt.service.clone(),
+        };
+
+        let mut client = self.client.clone();
+        let stream = with_timeout("watch", self.options.timeout_or(opt.timeout), async {
+            client.watch(req).await.map_err(remote)
+        })
+        .await?;
+
+        Ok(Box::new(GrpcWatcher::new(stream.into_inner(), opt)))
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        "grpc"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::Duration;
+
+    use errors::Result;
+
+    use super::{serve, GrpcRegistry};
+    use crate::{
+        memory::MemoryRegistry,
+        options::{Options, WatchOptions},
+        types::{Endpoint, Node, Service, Value},
+        Error, Registry,
+    };
+
+    fn service() -> Service {
+        Service {
+            name: "io.vine.helloworld".to_string(),
+            version: "v1.0.0".to_string(),
+            endpoints: vec![Endpoint {
+                name: "Helloworld.Call".to_string(),
+                request: Some(Value {
+                    name: "Request".to_string(),
+                    rtype: "Request".to_string(),
+                    values: vec![],
+                }),
+                response: None,
+                metadata: HashMap::new(),
+            }],
+            nodes: vec![Node {
+                id: "1".to_string(),
+                address: "127.0.0.1".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        }
+    }
+
+    async fn client(addr: SocketAddr) -> GrpcRegistry {
+        let mut opts = Options::new();
+        opts.addrs = vec![addr.to_string()];
+        // the server task may not be listening yet
+        for _ in 0..50 {
+            if let Ok(r) = GrpcRegistry::new(Some(opts.clone())).await {
+                return r;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("could not connect to {}", addr);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_registry() -> Result<()> {
+        let addr = TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+        let server = tokio::spawn(serve(Box::new(MemoryRegistry::new(None)), addr));
+        let r = client(addr).await;
+        assert_eq!(r.string().await, "grpc");
+
+        let mut wopt = WatchOptions::new();
+        wopt.with_service("io.vine.helloworld".to_string());
+        let w = r.watch(Some(wopt)).await?;
+
+        let s = service();
+        r.register(&s, None).await?;
+
+        let event = w.next().await?;
+        assert_eq!(event.action, "create");
+        assert_eq!(event.service.unwrap().nodes, s.nodes);
+
+        let services = r.get_service(s.name.clone(), None).await?;
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].endpoints, s.endpoints);
+        assert_eq!(services[0].nodes, s.nodes);
+
+        let services = r.list_service(None).await?;
+        assert_eq!(services.len(), 1);
+
+        // the server validates as well as the client
+        let mut bad = service();
+        bad.nodes[0].port = 0;
+        assert!(r.register(&bad, None).await.is_err());
+
+        r.deregister(&s, None).await?;
+        assert_eq!(w.next().await?.action, "delete");
+        let e = r.get_service(s.name.clone(), None).await;
+        assert!(matches!(e, Err(Error::NotFound(name)) if name == s.name));
+
+        w.stop().await;
+        assert!(w.next().await.is_err());
+
+        server.abort();
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use std::convert::TryInto;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use errors::{MultiStatus, Result, Status};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Request, Response};
+
+use crate::options::{DeregisterOptions, GetOptions, ListOptions, RegisterOptions, WatchOptions};
+use crate::proto::{
+    self,
+    registry_server::{Registry as RegistryRpc, RegistryServer},
+};
+use crate::{Error, Registry};
+
+/// how many watch results may queue for a slow client
+const WATCH_BUFFER: usize = 64;
+
+/// serves `inner` as the gRPC registry service on `addr` until the server fails
+pub async fn serve(inner: Box<dyn Registry + Sync>, addr: SocketAddr) -> Result<()> {
+    let service = RegistryService {
+        inner: Arc::new(inner),
+    };
+
+    Server::builder()
+        .add_service(RegistryServer::new(service))
+        .serve(addr)
+        .await?;
+
+    Ok(())
+}
+
+/// exposes a [`Registry`] backend over the proto
+struct RegistryService {
+    inner: Arc<Box<dyn Registry + Sync>>,
+}
+
+/// the grpc status of a failed backend call
+fn status(e: Error) -> tonic::Status {
+    if let Some(errs) = e.backend().and_then(|e| e.downcast_ref::<MultiStatus>()) {
+        return match errs.is_empty() {
+            false => Status::from(errs.clone()).into(),
+            true => tonic::Status::unknown(errs.to_string()),
+        };
+    }
+    Status::from(e).into()
+}
+
+/// the domain the request names, `None` for the default domain
+fn domain(options: &Option<proto::Options>) -> Option<String> {
+    options
+        .as_ref()
+        .map(|o| o.domain.clone())
+        .filter(|d| !d.is_empty())
+}
+
+fn services(services: Vec<crate::types::Service>) -> Vec<proto::Service> {
+    services.iter().map(proto::Service::from).collect()
+}
+
+#[tonic::async_trait]
+impl RegistryRpc for RegistryService {
+    async fn get_service(
+        &self,
+        request: Request<proto::GetRequest>,
+    ) -> std::result::Result<Response<proto::GetResponse>, tonic::Status> {
+        let request = request.into_inner();
+        let mut opt = GetOptions::new();
+        opt.domain = domain(&request.options);
+        let services = services(
+            self.inner
+                .get_service(request.service, Some(opt))
+                .await
+                .map_err(status)?,
+        );
+        Ok(Response::new(proto::GetResponse { services }))
+    }
+
+    async fn register(
+        &self,
+        request: Request<proto::Service>,
+    ) -> std::result::Result<Response<proto::EmptyResponse>, tonic::Status> {
+        let service = request.into_inner();
+        let mut opt = RegisterOptions::new();
+        if let Some(o) = &service.options {
+            opt.with_ttl(o.ttl);
+        }
+        opt.domain = domain(&service.options);
+
+        let service: crate::types::Service = service.try_into()?;
+        self.inner
+            .register(&service, Some(opt))
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::EmptyResponse {}))
+    }
+
+    async fn deregister(
+        &self,
+        request: Request<proto::Service>,
+    ) -> std::result::Result<Response<proto::EmptyResponse>, tonic::Status> {
+        let service = request.into_inner();
+        let mut opt = DeregisterOptions::new();
+        opt.domain = domain(&service.options);
+
+        let service: crate::types::Service = service.try_into()?;
+        self.inner
+            .deregister(&service, Some(opt))
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::EmptyResponse {}))
+    }
+
+    async fn list_services(
+        &self,
+        request: Request<proto::ListRequest>,
+    ) -> std::result::Result<Response<proto::ListResponse>, tonic::Status> {
+        let mut opt = ListOptions::new();
+        opt.domain = domain(&request.into_inner().options);
+        let services = services(self.inner.list_service(Some(opt)).await.map_err(status)?);
+        Ok(Response::new(proto::ListResponse { services }))
+    }
+
+    type WatchStream = ReceiverStream<std::result::Result<proto::Result, tonic::Status>>;
+
+    async fn watch(
+        &self,
+        request: Request<proto::WatchRequest>,
+    ) -> std::result::Result<Response<Self::WatchStream>, tonic::Status> {
+        let request = request.into_inner();
+        let mut opt = WatchOptions::new();
+        opt.with_service(request.service);
+        opt.domain = domain(&request.options);
+        let w = self.inner.watch(Some(opt)).await.map_err(status)?;
+
+        let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+        tokio::spawn(async move {
+            loop {
+                // stop watching as soon as the client goes away
+                let r = tokio::select! {
+                    r = w.next() => r,
+                    _ = tx.closed() => break,
+                };
+
+                let failed = r.is_err();
+                let item = r.map(|r| proto::Result::from(&r)).map_err(status);
+                if tx.send(item).await.is_err() || failed {
+                    break;
+                }
+            }
+            w.stop().await;
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
@@ -0,0 +1,62 @@
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use async_trait::async_trait;
+use errors::Status;
+use tokio::sync::{Mutex, Notify};
+use tonic::Streaming;
+
+use crate::error::{Error, Result};
+use crate::{options::WatchOptions, proto, types, Watcher};
+
+/// the implement of [`Watcher`] over the Watch stream of a
+/// [`super::GrpcRegistry`]
+pub struct GrpcWatcher {
+    stream: Mutex<Streaming<proto::Result>>,
+    /// the action and prefix filters are not part of the proto
+    opts: WatchOptions,
+    stopped: AtomicBool,
+    exit: Notify,
+}
+
+#[async_trait]
+impl Watcher for GrpcWatcher {
+    async fn next(&self) -> Result<types::Result> {
+        let mut stream = self.stream.lock().await;
+        loop {
+            if self.stopped.load(Ordering::SeqCst) {
+                return Err(Error::WatcherStopped);
+            }
+
+            let r = tokio::select! {
+                r = stream.message() => r,
+                _ = self.exit.notified() => continue,
+            };
+
+            let r: types::Result = match r {
+                Ok(Some(r)) => r.try_into()?,
+                Ok(None) => return Err(Error::WatcherStopped),
+                Err(e) => return Err(Status::from(e).into()),
+            };
+            if self.opts.matches(&r) {
+                return Ok(r);
+            }
+        }
+    }
+
+    async fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.exit.notify_one();
+    }
+}
+
+impl GrpcWatcher {
+    pub fn new(stream: Streaming<proto::Result>, opts: WatchOptions) -> Self {
+        GrpcWatcher {
+            stream: Mutex::new(stream),
+            opts,
+            stopped: AtomicBool::new(false),
+            exit: Notify::new(),
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use errors::MultiStatus;
+use tokio::net::TcpStream;
+use tonic::transport::Endpoint;
+
+use self::proto::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
+use crate::error::{Error, Result};
+use crate::options::{
+    DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+};
+use crate::types::{Node, Service};
+use crate::{Registry, Watcher};
+
+/// the messages and the client and server of the standard gRPC health service
+pub mod proto {
+    tonic::include_proto!("grpc.health.v1");
+}
+
+/// how a node is probed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    /// the node accepts a TCP connection
+    Tcp,
+    /// the node answers `SERVING` to the gRPC health check of its whole
+    /// server, over a plaintext connection
+    Grpc,
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthOptions {
+    pub probe: Probe,
+    /// how long a node may take to answer before it counts as dead
+    pub timeout: Duration,
+}
+
+impl Default for HealthOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthOptions {
+    #[inline]
+    pub fn new() -> Self {
+        HealthOptions {
+            probe: Probe::Tcp,
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[inline]
+    pub fn with_probe(&mut self, probe: Probe) -> &mut Self {
+        self.probe = probe;
+        self
+    }
+
+    #[inline]
+    pub fn with_timeout(&mut self, t: Duration) -> &mut Self {
+        self.timeout = t;
+        self
+    }
+}
+
+/// the implement of [`Registry`] probing the nodes `get_service` returns
+/// from another registry, dropping the dead ones whose registration has not
+/// expired yet. The nodes are probed concurrently on every call, a service
+/// left without nodes is not found. `list_service` names services rather
+/// than resolving them and is not probed.
+///
+/// ```rust
+/// # use registry::{health::HealthRegistry, memory::MemoryRegistry, Registry};
+/// # async fn run() -> errors::Result<()> {
+/// let registry = HealthRegistry::new(Box::new(MemoryRegistry::new(None)), None);
+/// let services = registry.get_service("helloworld".to_string(), None).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct HealthRegistry {
+    inner: Box<dyn Registry + Sync>,
+    options: HealthOptions,
+}
+
+impl HealthRegistry {
+    pub fn new(inner: Box<dyn Registry + Sync>, opt: Option<HealthOptions>) -> Self {
+        HealthRegistry {
+            inner,
+            options: opt.unwrap_or_default(),
+        }
+    }
+
+    /// drops the nodes failing the probe and the services left without nodes
+    async fn healthy(&self, services: Vec<Service>) -> Vec<Service> {
+        let probes: Vec<_> = services
+            .iter()
+            .flat_map(|s| s.nodes.iter())
+            .map(|n| tokio::spawn(probe(n.clone(), self.options.clone())))
+            .collect();
+        let mut alive = Vec::with_capacity(probes.len());
+        for p in probes {
+            alive.push(p.await.unwrap_or(false));
+        }
+
+        let mut alive = alive.into_iter();
+        services
+            .into_iter()
+            .filter_map(|mut s| {
+                let name = &s.name;
+                s.nodes.retain(|n| {
+                    let ok = alive.next().unwrap_or(false);
+                    if !ok {
+                        logger::debug!("dropping dead node {} of {}", n.id, name);
+                    }
+                    ok
+                });
+                if s.nodes.is_empty() {
+                    None
+                } else {
+                    Some(s)
+                }
+            })
+            .collect()
+    }
+}
+
+/// whether `node` answers the probe within the timeout
+async fn probe(node: Node, opt: HealthOptions) -> bool {
+    let addr = host_port(&node);
+    let check = async {
+        match opt.probe {
+            Probe::Tcp => TcpStream::connect(&addr).await.is_ok(),
+            Probe::Grpc => serving(&addr).await,
+        }
+    };
+    tokio::time::timeout(opt.timeout, check)
+        .await
+        .unwrap_or(false)
+}
+
+async fn serving(addr: &str) -> bool {
+    let endpoint = match Endpoint::from_shared(format!("http://{}", addr)) {
+        Ok(endpoint) => endpoint,
+        Err(_) => return false,
+    };
+    let channel = match endpoint.connect().await {
+        Ok(channel) => channel,
+        Err(_) => return false,
+    };
+    let req = HealthCheckRequest {
+        service: String::new(),
+    };
+    match HealthClient::new(channel).check(req).await {
+        Ok(rsp) => rsp.into_inner().status == ServingStatus::Serving as i32,
+        Err(_) => false,
+    }
+}
+
+/// the `host:port` of a node, bracketing an IPv6 address
+fn host_port(node: &Node) -> String {
+    if node.address.contains(':') && !node.address.starts_with('[') {
+        format!("[{}]:{}", node.address, node.port)
+    } else {
+        format!("{}:{}", node.address, node.port)
+    }
+}
+
+#[async_trait]
+impl Registry for HealthRegistry {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.inner.init(opt).await
+    }
+
+    #[inline]
+    async fn options(&self) -> Options {
+        self.inner.options().await
+    }
+
+    async fn register(&self, s: &Service, opt: Option<RegisterOptions>) -> Result<()> {
+        self.inner.register(s, opt).await
+    }
+
+    async fn deregister(&self, s: &Service, opt: Option<DeregisterOptions>) -> Result<()> {
+        self.inner.deregister(s, opt).await
+    }
+
+    async fn register_many(
+        &self,
+        services: &[Service],
+        opt: Option<RegisterOptions>,
+    ) -> Result<()> {
+        self.inner.register_many(services, opt).await
+    }
+
+    async fn deregister_many(
+        &self,
+        services: &[Service],
+        opt: Option<DeregisterOptions>,
+    ) -> Result<()> {
+        self.inner.deregister_many(services, opt).await
+    }
+
+    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> Result<Vec<Service>> {
+        let services = self.inner.get_service(s.clone(), opt).await?;
+        let services = self.healthy(services).await;
+        if services.is_empty() {
+            return Err(Error::NotFound(s));
+        }
+        Ok(services)
+    }
+
+    async fn get_service_checked(
+        &self,
+        s: String,
+        opt: Option<GetOptions>,
+    ) -> Result<(Vec<Service>, MultiStatus)> {
+        let (services, errs) = self.inner.get_service_checked(s.clone(), opt).await?;
+        let services = self.healthy(services).await;
+        if services.is_empty() {
+            return Err(Error::NotFound(s));
+        }
+        Ok((services, errs))
+    }
+
+    async fn list_service(&self, opt: Option<ListOptions>) -> Result<Vec<Service>> {
+        self.inner.list_service(opt).await
+    }
+
+    async fn watch(&self, opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send + Sync>> {
+        self.inner.watch(opt).await
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        "health"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::Duration;
+
+    use errors::Result;
+    use tonic::{transport::Server, Request, Response};
+
+    use super::proto::{
+        health_check_response::ServingStatus,
+        health_server::{Health, HealthServer},
+        HealthCheckRequest, HealthCheckResponse,
+    };
+    use super::{host_port, HealthOptions, HealthRegistry, Probe};
+    use crate::{
+        memory::MemoryRegistry,
+        types::{Node, Service},
+        Error, Registry,
+    };
+
+    fn node(id: &str, port: u16) -> Node {
+        Node {
+            id: id.to_string(),
+            address: "127.0.0.1".to_string(),
+            port: port as i64,
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn service(nodes: Vec<Node>) -> Service {
+        Service {
+            name: "io.vine.helloworld".to_string(),
+            version: "v1.0.0".to_string(),
+            nodes,
+            ..Service::new()
+        }
+    }
+
+    /// a port nothing listens on
+    fn closed_port() -> u16 {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    struct Serving(ServingStatus);
+
+    #[tonic::async_trait]
+    impl Health for Serving {
+        async fn check(
+            &self,
+            _req: Request<HealthCheckRequest>,
+        ) -> std::result::Result<Response<HealthCheckResponse>, tonic::Status> {
+            Ok(Response::new(HealthCheckResponse {
+                status: self.0 as i32,
+            }))
+        }
+    }
+
+    fn serve(status: ServingStatus) -> u16 {
+        let port = closed_port();
+        let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(HealthServer::new(Serving(status)))
+                .serve(addr),
+        );
+        port
+    }
+
+    #[tokio::test]
+    async fn test_tcp_probe() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let alive = listener.local_addr()?.port();
+
+        let inner = MemoryRegistry::new(None);
+        inner
+            .register(
+                &service(vec![node("1", alive), node("2", closed_port())]),
+                None,
+            )
+            .await?;
+        let r = HealthRegistry::new(Box::new(inner.clone()), None);
+        let name = "io.vine.helloworld".to_string();
+
+        let services = r.get_service(name.clone(), None).await?;
+        assert_eq!(services.len(), 1);
+        let ids: Vec<&str> = services[0].nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["1"]);
+
+        // the registrations are left alone
+        assert_eq!(
+            inner.get_service(name.clone(), None).await?[0].nodes.len(),
+            2
+        );
+
+        drop(listener);
+        let e = r.get_service(name.clone(), None).await;
+        assert!(matches!(e, Err(Error::NotFound(n)) if n == name));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grpc_probe() -> Result<()> {
+        let serving = serve(ServingStatus::Serving);
+        let not_serving = serve(ServingStatus::NotServing);
+        // accepts connections but does not speak gRPC
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let other = listener.local_addr()?.port();
+
+        let inner = MemoryRegistry::new(None);
+        inner
+            .register(
+                &service(vec![
+                    node("1", serving),
+                    node("2", not_serving),
+                    node("3", other),
+                ]),
+                None,
+            )
+            .await?;
+        let mut hopt = HealthOptions::new();
+        hopt.with_probe(Probe::Grpc)
+            .with_timeout(Duration::from_millis(200));
+        let r = HealthRegistry::new(Box::new(inner), Some(hopt));
+        let name = "io.vine.helloworld".to_string();
+
+        // the servers may not be listening yet
+        let mut ids = vec![];
+        for _ in 0..50 {
+            if let Ok(services) = r.get_service(name.clone(), None).await {
+                ids = services[0].nodes.iter().map(|n| n.id.clone()).collect();
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(ids, vec!["1"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_host_port() {
+        assert_eq!(host_port(&node("1", 8080)), "127.0.0.1:8080");
+
+        let mut n = node("1", 8080);
+        n.address = "::1".to_string();
+        assert_eq!(host_port(&n), "[::1]:8080");
+        n.address = "[::1]".to_string();
+        assert_eq!(host_port(&n), "[::1]:8080");
+    }
+}
@@ -1,73 +1,365 @@
 pub mod options;
 
+pub mod cache;
+
+pub mod dns;
+
 /// #[cfg(feature = "registry-etcd")]
 pub mod etcd;
 
+pub mod error;
+
+pub mod events;
+
+pub mod file;
+
+pub mod grpc;
+
+pub mod health;
+
+pub mod memory;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+pub mod mock;
+
+pub mod multi;
+
+pub mod plugins;
+
+pub mod proto;
+
+pub mod registration;
+
+pub mod selector;
+
+pub mod stream;
+
 pub mod types;
 
+mod uri;
+
 use self::options::{
     DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
 };
 use self::types::Service;
+pub use error::Error;
 use etcd::EtcdRegistry;
-use std::sync::Arc;
-use tokio::sync::{Mutex, OnceCell};
+use memory::MemoryRegistry;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Mutex;
+pub use uri::from_uri;
 
 use async_trait::async_trait;
-use errors::Result;
+use errors::{bail, MultiStatus, Result, Status};
+
+pub type SharedRegistry = Arc<Mutex<Box<dyn Registry + Sync + 'static>>>;
+
+/// constructs the backend named by `kind`: `etcd`, `memory`,
+/// `static:<path>`, a memory registry seeded from a JSON file holding
+/// a list of services, `file:<dir>`, see [`file::FileRegistry`],
+/// `grpc:<addr>[,<addr>...]`, a registry served by [`grpc::serve`], a
+/// connection URI, see [`from_uri`], or `<name>[:<config>]` of a backend
+/// registered with [`plugins::register`].
+pub async fn new_registry(kind: &str) -> Result<Box<dyn Registry + Sync + 'static>> {
+    match kind {
+        "" | "memory" => Ok(Box::new(MemoryRegistry::new(None))),
+        "etcd" => Ok(Box::new(EtcdRegistry::new(None).await?)),
+        _ if kind.contains("://") => from_uri(kind).await,
+        _ => {
+            if let Some(path) = kind.strip_prefix("static:") {
+                Ok(Box::new(MemoryRegistry::from_file(path).await?))
+            } else if let Some(dir) = kind.strip_prefix("file:") {
+                Ok(Box::new(file::FileRegistry::new(dir).await?))
+            } else if let Some(addrs) = kind.strip_prefix("grpc:") {
+                let mut opts = Options::new();
+                opts.addrs = addrs.split(',').map(String::from).collect();
+                Ok(Box::new(grpc::GrpcRegistry::new(Some(opts)).await?))
+            } else {
+                let name = kind.split(':').next().unwrap_or(kind);
+                match plugins::build(name, kind).await {
+                    Some(registry) => registry,
+                    None => bail!("unknown registry '{}'", kind),
+                }
+            }
+        }
+    }
+}
 
-async fn init_registry() -> Arc<Mutex<Box<dyn Registry + Sync + Send + 'static>>> {
-    println!("init");
-    let registry = EtcdRegistry::new(None).await.unwrap();
-    Arc::new(Mutex::new(Box::new(registry)))
+/// selects the default backend from `VINE_REGISTRY`, falling back to
+/// the memory registry when nothing is configured or it can't be built
+async fn init_registry() -> SharedRegistry {
+    let kind = std::env::var("VINE_REGISTRY").unwrap_or_default();
+    let registry = match new_registry(&kind).await {
+        Ok(registry) => registry,
+        Err(e) => {
+            logger::error!("init registry '{}' failed, using memory: {}", kind, e);
+            Box::new(MemoryRegistry::new(None))
+        }
+    };
+    #[cfg(feature = "metrics")]
+    let registry = Box::new(metrics::MetricsRegistry::new(registry).await);
+    Arc::new(Mutex::new(registry))
 }
 
-static DEFAULT_REGISTRY: OnceCell<Arc<Mutex<Box<dyn Registry + Sync + Send + 'static>>>> =
-    OnceCell::const_new();
-pub async fn global_registry() -> &'static Arc<Mutex<Box<dyn Registry + Sync + Send + 'static>>> {
-    let out = DEFAULT_REGISTRY.get_or_init(init_registry).await;
-    out
+static DEFAULT_REGISTRY: RwLock<Option<SharedRegistry>> = RwLock::new(None);
+
+fn current_registry() -> Option<SharedRegistry> {
+    let slot = DEFAULT_REGISTRY.read().unwrap_or_else(|e| e.into_inner());
+    slot.clone()
 }
 
-pub fn set_global_registry(reg: impl Registry + Sync + Send + 'static) -> Result<()> {
-    match DEFAULT_REGISTRY.set(Arc::new(Mutex::new(Box::new(reg)))) {
-        Ok(()) => Ok(()),
-        Err(_) => Err(errors::err!("set global logger failed")),
+/// returns the registry used by the module level functions,
+/// initialising it from the environment on first use
+pub async fn global_registry() -> SharedRegistry {
+    if let Some(registry) = current_registry() {
+        return registry;
     }
+
+    let registry = init_registry().await;
+    let mut slot = DEFAULT_REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    slot.get_or_insert(registry).clone()
+}
+
+/// replaces the registry used by the module level functions.
+/// Calls already holding the previous registry finish against it.
+pub fn set_global_registry(reg: impl Registry + Sync + 'static) {
+    let mut slot = DEFAULT_REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    *slot = Some(Arc::new(Mutex::new(Box::new(reg))));
 }
 
 #[async_trait]
 pub trait Registry: Send {
-    async fn init(&mut self, opt: Option<Options>) -> Result<()>;
+    async fn init(&mut self, opt: Option<Options>) -> error::Result<()>;
     async fn options(&self) -> Options;
-    async fn register(&self, s: &Service, opt: Option<RegisterOptions>) -> Result<()>;
-    async fn deregister(&self, s: &Service, opt: Option<DeregisterOptions>) -> Result<()>;
-    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> Result<Vec<Service>>;
-    async fn list_service(&self, opt: Option<ListOptions>) -> Result<Vec<Service>>;
+    async fn register(&self, s: &Service, opt: Option<RegisterOptions>) -> error::Result<()>;
+    async fn deregister(&self, s: &Service, opt: Option<DeregisterOptions>) -> error::Result<()>;
+    /// registers every service of `services` with the same options, one
+    /// after another unless the backend batches them. The first failure
+    /// stops the call, the services before it stay registered.
+    async fn register_many(
+        &self,
+        services: &[Service],
+        opt: Option<RegisterOptions>,
+    ) -> error::Result<()>
+    where
+        Self: Sync,
+    {
+        for s in services {
+            self.register(s, opt.clone()).await?;
+        }
+        Ok(())
+    }
+    /// deregisters every service of `services`, like `register_many`
+    async fn deregister_many(
+        &self,
+        services: &[Service],
+        opt: Option<DeregisterOptions>,
+    ) -> error::Result<()>
+    where
+        Self: Sync,
+    {
+        for s in services {
+            self.deregister(s, opt.clone()).await?;
+        }
+        Ok(())
+    }
+    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> error::Result<Vec<Service>>;
+    /// like `get_service` but entries which can't be decoded don't fail the call,
+    /// the services read are returned along with a status for every bad entry
+    async fn get_service_checked(
+        &self,
+        s: String,
+        opt: Option<GetOptions>,
+    ) -> error::Result<(Vec<Service>, MultiStatus)>
+    where
+        Self: Sync,
+    {
+        Ok((self.get_service(s, opt).await?, MultiStatus::new()))
+    }
+    /// like `get_service` but returns the highest version of the service
+    /// matching the semver requirement `req`, such as `^1.2` or
+    /// `>=1.0, <2.0`. A leading `v` of the registered versions is ignored,
+    /// the versions which don't parse never match.
+    async fn get_service_matching(
+        &self,
+        s: String,
+        req: &str,
+        opt: Option<GetOptions>,
+    ) -> error::Result<Vec<Service>>
+    where
+        Self: Sync,
+    {
+        let req = semver::VersionReq::parse(req)
+            .map_err(|e| errors::err!("bad version requirement '{}': {}", req, e))?;
+        let services = self.get_service(s.clone(), opt).await?;
+        best_matching(s, services, &req)
+    }
+    async fn list_service(&self, opt: Option<ListOptions>) -> error::Result<Vec<Service>>;
     async fn watch(
         &self,
         opt: Option<WatchOptions>,
-    ) -> Result<Box<dyn Watcher + Sync + Send + Sync>>;
+    ) -> error::Result<Box<dyn Watcher + Send + Sync>>;
     async fn string(&self) -> &'static str;
 }
 
 #[async_trait]
 pub trait Watcher {
-    async fn next(&self) -> Result<crate::types::Result>;
+    async fn next(&self) -> error::Result<crate::types::Result>;
     async fn stop(&self);
 }
 
+/// runs `fut` with a deadline of `dur`, converting an elapse into a
+/// `Status::timeout` (408) which names the timed out operation. `fut` is
+/// dropped on the elapse, so is a call whose caller drops it, cancelling
+/// the request in flight.
+pub async fn with_timeout<T, F>(op: &str, dur: Duration, fut: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    match tokio::time::timeout(dur, fut).await {
+        Ok(out) => out,
+        Err(_) => Err(Status::timeout(
+            "io.vine.registry".to_string(),
+            format!("{} timed out after {:?}", op, dur),
+        )
+        .into()),
+    }
+}
+
+/// decodes a stored service value of either [`options::Codec`], the error
+/// names the offending key
+pub(crate) fn decode_entry(key: &str, value: &[u8]) -> std::result::Result<Service, Status> {
+    let corrupt = |e: String| {
+        Status::internal_server_error(
+            "io.vine.registry".to_string(),
+            format!("corrupt registry entry {}: {}", key, e),
+        )
+    };
+
+    // a JSON value opens with a brace, a protobuf one with a field tag
+    match value.iter().find(|b| !b.is_ascii_whitespace()) {
+        None | Some(b'{') => serde_json::from_slice(value).map_err(|e| corrupt(e.to_string())),
+        Some(_) => {
+            let s = <proto::Service as prost::Message>::decode(value)
+                .map_err(|e| corrupt(e.to_string()))?;
+            Service::try_from(s).map_err(|e| corrupt(e.detail().to_string()))
+        }
+    }
+}
+
+/// decodes every `(key, value)` entry. When `strict` the first corrupt entry
+/// fails the call, otherwise it is logged, recorded in `errs` and skipped.
+pub(crate) fn decode_entries<'a>(
+    entries: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+    strict: bool,
+    errs: &mut MultiStatus,
+) -> Result<Vec<Service>> {
+    let mut services = vec![];
+    for (key, value) in entries {
+        match decode_entry(key, value) {
+            Ok(s) => services.push(s),
+            Err(e) if strict => return Err(e.into()),
+            Err(e) => {
+                logger::warn!("skipping {}", e.detail());
+                errs.push(e);
+            }
+        }
+    }
+
+    Ok(services)
+}
+
+/// keeps the services of `version` and of them the nodes whose metadata
+/// holds every pair of `metadata`, dropping the services left without nodes
+pub(crate) fn filter_services(
+    services: Vec<Service>,
+    version: Option<&str>,
+    metadata: &HashMap<String, String>,
+) -> Vec<Service> {
+    services
+        .into_iter()
+        .filter(|s| version.is_none_or(|v| s.version == v))
+        .filter_map(|mut s| {
+            if metadata.is_empty() {
+                return Some(s);
+            }
+            s.nodes
+                .retain(|n| metadata.iter().all(|(k, v)| n.metadata.get(k) == Some(v)));
+            if s.nodes.is_empty() {
+                None
+            } else {
+                Some(s)
+            }
+        })
+        .collect()
+}
+
+/// [`filter_services`] by the filters of `opt` for the services of `name`,
+/// [`Error::NotFound`] when no node is left
+pub(crate) fn filter_service(
+    name: String,
+    services: Vec<Service>,
+    opt: &GetOptions,
+) -> error::Result<Vec<Service>> {
+    let services = filter_services(services, opt.version.as_deref(), &opt.metadata_filter);
+    if services.is_empty() {
+        return Err(Error::NotFound(name));
+    }
+    Ok(services)
+}
+
+/// the services of the highest version matching `req`, more than one when
+/// versions such as `1.2.0` and `v1.2.0` parse the same
+pub(crate) fn best_matching(
+    name: String,
+    services: Vec<Service>,
+    req: &semver::VersionReq,
+) -> error::Result<Vec<Service>> {
+    let mut matching: Vec<(semver::Version, Service)> = services
+        .into_iter()
+        .filter_map(|s| {
+            let v = s.version.strip_prefix('v').unwrap_or(&s.version);
+            let v = semver::Version::parse(v).ok()?;
+            Some((v, s))
+        })
+        .filter(|(v, _)| req.matches(v))
+        .collect();
+
+    let best = match matching.iter().map(|(v, _)| v).max() {
+        Some(best) => best.clone(),
+        None => return Err(Error::NotFound(name)),
+    };
+    matching.retain(|(v, _)| *v == best);
+    Ok(matching.into_iter().map(|(_, s)| s).collect())
+}
+
 /// register a service node. Additionally supply options such as TTL.
 pub async fn register(s: &Service, opt: Option<RegisterOptions>) -> Result<()> {
-    let rc = global_registry().await.clone();
+    let rc = global_registry().await;
     let m = rc.lock().await;
     m.register(s, opt).await?;
     Ok(())
 }
 
+/// register a service node, deregistering it when the returned
+/// [`registration::Registration`] is dropped or the process is signalled.
+pub async fn register_guarded(
+    s: &Service,
+    opt: Option<RegisterOptions>,
+) -> Result<registration::Registration> {
+    let rc = global_registry().await;
+    Ok(registration::Registration::new(rc, s, opt).await?)
+}
+
 /// deregister a service node
 pub async fn deregister(s: &Service, opt: Option<DeregisterOptions>) -> Result<()> {
-    let rc = global_registry().await.clone();
+    let rc = global_registry().await;
     let m = rc.lock().await;
     m.deregister(s, opt).await?;
     Ok(())
@@ -75,15 +367,40 @@ pub async fn deregister(s: &Service, opt: Option<DeregisterOptions>) -> Result<(
 
 /// get_service retrieve a service. A slice is returned since we separate Name/Version.
 pub async fn get_service(s: String, opt: Option<GetOptions>) -> Result<Vec<Service>> {
-    let rc = global_registry().await.clone();
+    let rc = global_registry().await;
     let m = rc.lock().await;
     let services = m.get_service(s, opt).await?;
     Ok(services)
 }
 
+/// get_service_checked retrieve a service, returning the entries which could not be
+/// decoded as a [`MultiStatus`] instead of failing or dropping them silently.
+pub async fn get_service_checked(
+    s: String,
+    opt: Option<GetOptions>,
+) -> Result<(Vec<Service>, MultiStatus)> {
+    let rc = global_registry().await;
+    let m = rc.lock().await;
+    let out = m.get_service_checked(s, opt).await?;
+    Ok(out)
+}
+
+/// get_service_matching retrieve the highest version of a service matching a
+/// semver requirement, see [`Registry::get_service_matching`].
+pub async fn get_service_matching(
+    s: String,
+    req: &str,
+    opt: Option<GetOptions>,
+) -> Result<Vec<Service>> {
+    let rc = global_registry().await;
+    let m = rc.lock().await;
+    let services = m.get_service_matching(s, req, opt).await?;
+    Ok(services)
+}
+
 /// list_services list the services. Only returns service names
 pub async fn list_service(opt: Option<ListOptions>) -> Result<Vec<Service>> {
-    let rc = global_registry().await.clone();
+    let rc = global_registry().await;
     let m = rc.lock().await;
     let services = m.list_service(opt).await?;
     Ok(services)
@@ -91,7 +408,7 @@ pub async fn list_service(opt: Option<ListOptions>) -> Result<Vec<Service>> {
 
 /// watch returns a watcher which allows you to track updates to the registry.
 pub async fn watch(opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send + Sync>> {
-    let rc = global_registry().await.clone();
+    let rc = global_registry().await;
     let m = rc.lock().await;
     let watcher = m.watch(opt).await?;
     Ok(watcher)
@@ -99,7 +416,7 @@ pub async fn watch(opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send +
 
 /// returns the name of DEFAULT_REGISTRY
 pub async fn get_name() -> &'static str {
-    let rc = global_registry().await.clone();
+    let rc = global_registry().await;
     let m = rc.lock().await;
     m.string().await
 }
@@ -107,28 +424,215 @@ pub async fn get_name() -> &'static str {
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    use std::time::Duration;
 
-    use errors::Result;
+    use async_trait::async_trait;
+    use errors::{Code, Result, Status};
 
     use crate::{
-        deregister,
+        deregister, error,
         etcd::EtcdRegistry,
-        global_registry, list_service, register, set_global_registry,
+        get_name, get_service, global_registry, list_service,
+        memory::MemoryRegistry,
+        new_registry,
+        options::{
+            DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+        },
+        register, set_global_registry,
         types::{Node, Service},
+        with_timeout, Error, Registry, Watcher,
     };
 
+    /// serialises the tests swapping the global registry
+    static GLOBAL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// a registry whose backend never answers in time
+    struct SleepyRegistry {
+        options: Options,
+        delay: Duration,
+    }
+
+    impl SleepyRegistry {
+        async fn backend<T: Default>(&self, op: &str, t: Option<Duration>) -> error::Result<T> {
+            let delay = self.delay;
+            with_timeout(op, self.options.timeout_or(t), async move {
+                tokio::time::sleep(delay).await;
+                Ok(T::default())
+            })
+            .await
+            .map_err(Error::from)
+        }
+    }
+
+    #[async_trait]
+    impl Registry for SleepyRegistry {
+        async fn init(&mut self, _opt: Option<Options>) -> error::Result<()> {
+            Ok(())
+        }
+
+        async fn options(&self) -> Options {
+            self.options.clone()
+        }
+
+        async fn register(&self, _s: &Service, opt: Option<RegisterOptions>) -> error::Result<()> {
+            self.backend("register", opt.and_then(|o| o.timeout)).await
+        }
+
+        async fn deregister(
+            &self,
+            _s: &Service,
+            opt: Option<DeregisterOptions>,
+        ) -> error::Result<()> {
+            self.backend("deregister", opt.and_then(|o| o.timeout))
+                .await
+        }
+
+        async fn get_service(
+            &self,
+            _s: String,
+            opt: Option<GetOptions>,
+        ) -> error::Result<Vec<Service>> {
+            self.backend("get_service", opt.and_then(|o| o.timeout))
+                .await
+        }
+
+        async fn list_service(&self, opt: Option<ListOptions>) -> error::Result<Vec<Service>> {
+            self.backend("list_service", opt.and_then(|o| o.timeout))
+                .await
+        }
+
+        async fn watch(
+            &self,
+            _opt: Option<WatchOptions>,
+        ) -> error::Result<Box<dyn Watcher + Send + Sync>> {
+            Err(errors::err!("watch is not supported").into())
+        }
+
+        async fn string(&self) -> &'static str {
+            "sleepy"
+        }
+    }
+
+    fn timeout_code(e: Error) -> (Code, String) {
+        assert!(matches!(e, Error::Timeout(_)), "{}", e);
+        let status = Status::from(e);
+        (status.code(), status.detail().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_call_timeout() {
+        let mut options = Options::new();
+        options.with_timeout(60);
+        let r = SleepyRegistry {
+            options,
+            delay: Duration::from_secs(5),
+        };
+
+        let mut opt = GetOptions::new();
+        opt.with_timeout(Duration::from_millis(10));
+        let e = r
+            .get_service("io.vine.helloworld".to_string(), Some(opt))
+            .await;
+        let (code, detail) = timeout_code(e.unwrap_err());
+        assert_eq!(code, Code::RequestTimeout);
+        assert!(detail.contains("get_service"));
+
+        let mut opt = ListOptions::new();
+        opt.with_timeout(Duration::from_millis(10));
+        let (code, detail) = timeout_code(r.list_service(Some(opt)).await.unwrap_err());
+        assert_eq!(code, Code::RequestTimeout);
+        assert!(detail.contains("list_service"));
+
+        let mut opt = DeregisterOptions::new();
+        opt.with_timeout(Duration::from_millis(10));
+        let e = r.deregister(&Service::new(), Some(opt)).await;
+        let (code, detail) = timeout_code(e.unwrap_err());
+        assert_eq!(code, Code::RequestTimeout);
+        assert!(detail.contains("deregister"));
+    }
+
+    #[tokio::test]
+    async fn test_options_timeout_fallback() {
+        let mut options = Options::new();
+        options.with_timeout(0);
+        let r = SleepyRegistry {
+            options,
+            delay: Duration::from_secs(5),
+        };
+
+        let e = r.register(&Service::new(), None).await;
+        let (code, detail) = timeout_code(e.unwrap_err());
+        assert_eq!(code, Code::RequestTimeout);
+        assert!(detail.contains("register"));
+
+        let r = SleepyRegistry {
+            options: Options::new(),
+            delay: Duration::from_millis(1),
+        };
+        let mut opt = GetOptions::new();
+        opt.with_timeout(Duration::from_secs(5));
+        assert!(r.get_service("".to_string(), Some(opt)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_get_service_matching() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let name = "io.vine.helloworld".to_string();
+        for (version, id) in [
+            ("v1.1.0", "1"),
+            ("v1.2.3", "2"),
+            ("1.2.3", "3"),
+            ("v2.0.0", "4"),
+        ] {
+            let s = Service {
+                name: name.clone(),
+                version: version.to_string(),
+                nodes: vec![Node {
+                    id: id.to_string(),
+                    address: "127.0.0.1".to_string(),
+                    port: 11101,
+                    metadata: HashMap::new(),
+                }],
+                ..Service::new()
+            };
+            r.register(&s, None).await?;
+        }
+
+        let mut versions: Vec<String> = r
+            .get_service_matching(name.clone(), "^1.1", None)
+            .await?
+            .into_iter()
+            .map(|s| s.version)
+            .collect();
+        versions.sort();
+        assert_eq!(versions, vec!["1.2.3", "v1.2.3"]);
+
+        let services = r.get_service_matching(name.clone(), "~1.1", None).await?;
+        assert_eq!(services[0].version, "v1.1.0");
+        let services = r.get_service_matching(name.clone(), "*", None).await?;
+        assert_eq!(services[0].version, "v2.0.0");
+
+        let e = r.get_service_matching(name.clone(), "^3", None).await;
+        assert!(matches!(e, Err(Error::NotFound(n)) if n == name));
+        let e = r.get_service_matching(name, "one", None).await;
+        assert!(matches!(e, Err(Error::Backend(_))));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_global_registry() {
-        let rc = global_registry().await.clone();
+        let rc = global_registry().await;
         let m = rc.lock().await;
-        assert!(m.string().await != "");
+        assert!(!m.string().await.is_empty());
     }
 
     #[tokio::test]
     async fn test_set_global_registry() -> Result<()> {
+        let _global = GLOBAL.lock().await;
         let registry = EtcdRegistry::new(None).await?;
-        set_global_registry(registry)?;
-        let rc = global_registry().await.clone();
+        set_global_registry(registry);
+        let rc = global_registry().await;
         let m = rc.lock().await;
         assert_eq!(m.string().await, "etcd".to_string());
 
@@ -153,6 +657,7 @@ mod tests {
             apis: None,
         };
 
+        let _global = GLOBAL.lock().await;
         register(&s, None).await?;
 
         let services = list_service(None).await?;
@@ -164,4 +669,57 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_swap_global_registry() -> Result<()> {
+        let _global = GLOBAL.lock().await;
+        let mut s = Service::new();
+        s.name = "io.vine.swap".to_string();
+        s.nodes = vec![Node {
+            id: "1".to_string(),
+            address: "127.0.0.1".to_string(),
+            port: 11101,
+            metadata: HashMap::new(),
+        }];
+
+        set_global_registry(MemoryRegistry::new(None));
+        register(&s, None).await?;
+        assert_eq!(get_service(s.name.clone(), None).await?.len(), 1);
+
+        set_global_registry(MemoryRegistry::new(None));
+        assert_eq!(get_name().await, "memory");
+        assert!(get_service(s.name.clone(), None).await.is_err());
+        assert!(list_service(None).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_new_registry() -> Result<()> {
+        assert_eq!(new_registry("").await?.string().await, "memory");
+        assert_eq!(new_registry("memory").await?.string().await, "memory");
+        assert!(new_registry("consul").await.is_err());
+        assert!(new_registry("static:/nonexistent/services.json")
+            .await
+            .is_err());
+
+        let path = std::env::temp_dir().join("vine-static-registry.json");
+        std::fs::write(
+            &path,
+            r#"[{"name":"io.vine.static","version":"v1","metadata":{},"endpoints":[],
+            "nodes":[{"id":"1","address":"10.0.0.1","port":8080,"metadata":{}}],
+            "options":null,"apis":null}]"#,
+        )?;
+        let r = new_registry(&format!("static:{}", path.display())).await?;
+        let services = r.get_service("io.vine.static".to_string(), None).await?;
+        assert_eq!(services[0].nodes[0].address, "10.0.0.1");
+        std::fs::remove_file(path)?;
+
+        let dir = std::env::temp_dir().join("vine-file-registry-kind");
+        let r = new_registry(&format!("file:{}", dir.display())).await?;
+        assert_eq!(r.string().await, "file");
+        std::fs::remove_dir_all(dir)?;
+
+        Ok(())
+    }
 }
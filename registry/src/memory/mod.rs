@@ -0,0 +1,709 @@
+pub mod watch;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, Once};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::Local;
+use errors::{err, MultiStatus};
+use tokio::sync::{broadcast, RwLock};
+
+use self::watch::MemoryWatcher;
+use crate::error::Result;
+use crate::options::{
+    DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+};
+use crate::types::{self, Service};
+use crate::{decode_entries, filter_service, filter_services, Registry, Watcher};
+
+/// name -> version -> service
+type Services = HashMap<String, HashMap<String, Service>>;
+
+/// name -> (key, value) of encoded entries injected by tests
+type RawEntries = HashMap<String, Vec<(String, Vec<u8>)>>;
+
+/// (name, version, node id) -> when the node expires
+type Deadlines = HashMap<(String, String, String), Instant>;
+
+/// how many events a slow watcher may fall behind before losing some
+const EVENT_CAPACITY: usize = 128;
+
+/// how often expired nodes are looked for while nothing reads the registry
+const SWEEP_INTERVAL: Duration = Duration::from_millis(500);
+
+/// the implement of [`Registry`] kept in process memory. Like the etcd
+/// leases, a node registered with a positive ttl is removed once the ttl
+/// passes without it being registered again.
+///
+/// ```rust
+/// # use registry::{memory::MemoryRegistry, types::Service, Registry};
+/// # async fn run(service: Service) -> errors::Result<()> {
+/// let registry = MemoryRegistry::new(None);
+/// registry.register(&service, None).await?;
+/// let services = registry.get_service("helloworld".to_string(), None).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MemoryRegistry {
+    options: Options,
+    services: Arc<RwLock<Services>>,
+    raw: Arc<RwLock<RawEntries>>,
+    deadlines: Arc<Mutex<Deadlines>>,
+    events: broadcast::Sender<types::Result>,
+    sweeper: Arc<Once>,
+}
+
+impl Default for MemoryRegistry {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl MemoryRegistry {
+    pub fn new(opt: Option<Options>) -> Self {
+        MemoryRegistry {
+            options: opt.unwrap_or_default(),
+            services: Arc::new(RwLock::new(HashMap::new())),
+            raw: Arc::new(RwLock::new(HashMap::new())),
+            deadlines: Arc::new(Mutex::new(HashMap::new())),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            sweeper: Arc::new(Once::new()),
+        }
+    }
+
+    /// stores an encoded entry for `service` under `key` as a backend like etcd
+    /// would, so tests can exercise how reads deal with corrupt values
+    pub async fn inject_raw(
+        &self,
+        service: impl Into<String>,
+        key: impl Into<String>,
+        value: impl Into<Vec<u8>>,
+    ) {
+        let mut raw = self.raw.write().await;
+        raw.entry(service.into())
+            .or_insert_with(Vec::new)
+            .push((key.into(), value.into()));
+    }
+
+    /// the services stored under `name` merged with its decoded raw entries
+    async fn read_service(
+        &self,
+        name: &str,
+        strict: bool,
+        errs: &mut MultiStatus,
+    ) -> Result<Option<Vec<Service>>> {
+        let services = self.services.read().await;
+        let raw = self.raw.read().await;
+
+        let stored = services.get(name).filter(|versions| !versions.is_empty());
+        let entries = raw.get(name).filter(|entries| !entries.is_empty());
+        if stored.is_none() && entries.is_none() {
+            return Ok(None);
+        }
+
+        let mut out: Vec<Service> = stored
+            .map(|versions| versions.values().cloned().collect())
+            .unwrap_or_default();
+        let entries = entries
+            .into_iter()
+            .flatten()
+            .map(|(k, v)| (k.as_str(), v.as_slice()));
+        for sn in decode_entries(entries, strict, errs)? {
+            match out.iter_mut().find(|s| s.version == sn.version) {
+                Some(s) => s.nodes.extend(sn.nodes),
+                None => out.push(sn),
+            }
+        }
+        out.sort_by(|a, b| a.version.cmp(&b.version));
+
+        Ok(Some(out))
+    }
+
+    fn notify(&self, action: &str, s: &Service) {
+        notify(&self.events, action, s)
+    }
+
+    /// removes the expired nodes before a read
+    async fn expire(&self) {
+        let mut services = self.services.write().await;
+        expire(&mut services, &self.deadlines, &self.events);
+    }
+
+    /// starts removing expired nodes in the background, so watchers see
+    /// them go even when nothing reads the registry. The task ends once
+    /// the registry and all its clones are dropped.
+    fn start_sweeper(&self) {
+        let services = Arc::downgrade(&self.services);
+        let deadlines = Arc::downgrade(&self.deadlines);
+        let events = self.events.clone();
+        self.sweeper.call_once(move || {
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(SWEEP_INTERVAL).await;
+                    let (services, deadlines) = match (services.upgrade(), deadlines.upgrade()) {
+                        (Some(services), Some(deadlines)) => (services, deadlines),
+                        _ => return,
+                    };
+                    let mut services = services.write().await;
+                    expire(&mut services, &deadlines, &events);
+                }
+            });
+        });
+    }
+
+    /// builds a registry holding the services listed in the JSON file at `path`,
+    /// they never expire
+    pub async fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let data = tokio::fs::read(path.as_ref()).await?;
+        let services: Vec<Service> = serde_json::from_slice(&data)?;
+
+        let registry = MemoryRegistry::new(None);
+        let mut ropt = RegisterOptions::new();
+        ropt.with_ttl(0);
+        for s in &services {
+            registry.register(s, Some(ropt.clone())).await?;
+        }
+        Ok(registry)
+    }
+}
+
+fn notify(events: &broadcast::Sender<types::Result>, action: &str, s: &Service) {
+    // nobody watching is not an error
+    let _ = events.send(types::Result {
+        action: action.to_string(),
+        service: Some(s.clone()),
+        timestamp: Local::now().timestamp(),
+    });
+}
+
+/// removes every node past its deadline from `services`, emitting a delete
+/// for each. Called with the services locked, so a node registered again
+/// meanwhile keeps its new deadline.
+fn expire(
+    services: &mut Services,
+    deadlines: &Mutex<Deadlines>,
+    events: &broadcast::Sender<types::Result>,
+) {
+    let now = Instant::now();
+    let expired: Vec<(String, String, String)> = {
+        let mut deadlines = deadlines.lock().unwrap_or_else(|e| e.into_inner());
+        let keys: Vec<_> = deadlines
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(k, _)| k.clone())
+            .collect();
+        for k in &keys {
+            deadlines.remove(k);
+        }
+        keys
+    };
+
+    for (name, version, id) in expired {
+        let versions = match services.get_mut(&name) {
+            Some(versions) => versions,
+            None => continue,
+        };
+        if let Some(stored) = versions.get_mut(&version) {
+            if let Some(i) = stored.nodes.iter().position(|n| n.id == id) {
+                let node = stored.nodes.remove(i);
+                let s = Service {
+                    nodes: vec![node],
+                    ..stored.clone()
+                };
+                if stored.nodes.is_empty() {
+                    versions.remove(&version);
+                }
+                logger::debug!("Expired {} id {} in memory", name, id);
+                notify(events, "delete", &s);
+            }
+        }
+        if versions.is_empty() {
+            services.remove(&name);
+        }
+    }
+}
+
+#[async_trait]
+impl Registry for MemoryRegistry {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.options = opt.unwrap_or_default();
+        Ok(())
+    }
+
+    #[inline]
+    async fn options(&self) -> Options {
+        self.options.clone()
+    }
+
+    async fn register(&self, s: &Service, opt: Option<RegisterOptions>) -> Result<()> {
+        let opt = opt.unwrap_or_default();
+        if !opt.skip_validation {
+            s.validate()?;
+        }
+
+        if s.nodes.is_empty() {
+            return Err(err!("require at lease one node").into());
+        }
+
+        let mut services = self.services.write().await;
+        expire(&mut services, &self.deadlines, &self.events);
+        {
+            let mut deadlines = self.deadlines.lock().unwrap_or_else(|e| e.into_inner());
+            for node in &s.nodes {
+                let key = (s.name.clone(), s.version.clone(), node.id.clone());
+                if opt.ttl > 0 {
+                    deadlines.insert(key, Instant::now() + Duration::from_secs(opt.ttl as u64));
+                } else {
+                    deadlines.remove(&key);
+                }
+            }
+        }
+        if opt.ttl > 0 {
+            self.start_sweeper();
+        }
+
+        let versions = services.entry(s.name.clone()).or_insert_with(HashMap::new);
+        let action = match versions.get_mut(&s.version) {
+            None => {
+                versions.insert(s.version.clone(), s.clone());
+                "create"
+            }
+            Some(stored) => {
+                let mut nodes = std::mem::take(&mut stored.nodes);
+                for node in &s.nodes {
+                    match nodes.iter_mut().find(|n| n.id == node.id) {
+                        Some(n) => *n = node.clone(),
+                        None => nodes.push(node.clone()),
+                    }
+                }
+                *stored = Service { nodes, ..s.clone() };
+                "update"
+            }
+        };
+        self.notify(action, s);
+
+        logger::debug!("Registered {} version {} in memory", s.name, s.version);
+        Ok(())
+    }
+
+    async fn deregister(&self, s: &Service, _opt: Option<DeregisterOptions>) -> Result<()> {
+        if s.nodes.is_empty() {
+            return Err(err!("required at lease one node").into());
+        }
+
+        let mut services = self.services.write().await;
+        {
+            let mut deadlines = self.deadlines.lock().unwrap_or_else(|e| e.into_inner());
+            for node in &s.nodes {
+                deadlines.remove(&(s.name.clone(), s.version.clone(), node.id.clone()));
+            }
+        }
+        if let Some(versions) = services.get_mut(&s.name) {
+            if let Some(stored) = versions.get_mut(&s.version) {
+                stored
+                    .nodes
+                    .retain(|n| !s.nodes.iter().any(|node| node.id == n.id));
+                if stored.nodes.is_empty() {
+                    versions.remove(&s.version);
+                }
+            }
+            if versions.is_empty() {
+                services.remove(&s.name);
+            }
+            self.notify("delete", s);
+        }
+
+        Ok(())
+    }
+
+    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> Result<Vec<Service>> {
+        let opt = opt.unwrap_or_default();
+        let strict = opt.strict.unwrap_or(self.options.strict_decode);
+        self.expire().await;
+        let services = self
+            .read_service(&s, strict, &mut MultiStatus::new())
+            .await?
+            .unwrap_or_default();
+        filter_service(s, services, &opt)
+    }
+
+    async fn get_service_checked(
+        &self,
+        s: String,
+        opt: Option<GetOptions>,
+    ) -> Result<(Vec<Service>, MultiStatus)> {
+        let opt = opt.unwrap_or_default();
+        let mut errs = MultiStatus::new();
+        self.expire().await;
+        let services = self
+            .read_service(&s, false, &mut errs)
+            .await?
+            .unwrap_or_default();
+        Ok((filter_service(s, services, &opt)?, errs))
+    }
+
+    async fn list_service(&self, opt: Option<ListOptions>) -> Result<Vec<Service>> {
+        let opt = opt.unwrap_or_default();
+        self.expire().await;
+        let mut names: Vec<String> = self.services.read().await.keys().cloned().collect();
+        names.extend(self.raw.read().await.keys().cloned());
+        names.sort();
+        names.dedup();
+
+        let mut out = vec![];
+        let mut errs = MultiStatus::new();
+        for name in names {
+            let services = self
+                .read_service(&name, self.options.strict_decode, &mut errs)
+                .await?;
+            out.extend(services.unwrap_or_default());
+        }
+        Ok(filter_services(
+            out,
+            opt.version.as_deref(),
+            &opt.metadata_filter,
+        ))
+    }
+
+    async fn watch(&self, opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send + Sync>> {
+        let watcher = MemoryWatcher::new(self.events.subscribe(), opt);
+        Ok(Box::new(watcher))
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        "memory"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use super::MemoryRegistry;
+    use crate::{
+        options::{GetOptions, ListOptions, Options, RegisterOptions, WatchOptions},
+        types::{Node, Service},
+        Error, Registry,
+    };
+    use errors::{Code, MultiStatus, Result, Status};
+
+    fn service(version: &str, ids: &[&str]) -> Service {
+        let nodes = ids
+            .iter()
+            .map(|id| Node {
+                id: id.to_string(),
+                address: "192.168.1.111".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            })
+            .collect();
+        Service {
+            name: "io.vine.helloworld".to_string(),
+            version: version.to_string(),
+            nodes,
+            ..Service::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_service() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        r.register(&service("v1.0.0", &["1"]), None).await?;
+        r.register(&service("v1.0.0", &["2"]), None).await?;
+        r.register(&service("v2.0.0", &["3"]), None).await?;
+
+        let services = r
+            .get_service("io.vine.helloworld".to_string(), None)
+            .await?;
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].nodes.len(), 2);
+        assert_eq!(services[1].nodes[0].id, "3");
+
+        assert_eq!(r.list_service(None).await?.len(), 2);
+
+        r.deregister(&service("v1.0.0", &["1", "2"]), None).await?;
+        let services = r
+            .get_service("io.vine.helloworld".to_string(), None)
+            .await?;
+        assert_eq!(services.len(), 1);
+
+        r.deregister(&service("v2.0.0", &["3"]), None).await?;
+        let e = r.get_service("io.vine.helloworld".to_string(), None).await;
+        assert!(matches!(e, Err(Error::NotFound(name)) if name == "io.vine.helloworld"));
+        assert!(r.list_service(None).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_register_many() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let services = vec![service("v1.0.0", &["1", "2"]), service("v2.0.0", &["3"])];
+        r.register_many(&services, None).await?;
+
+        let got = r
+            .get_service("io.vine.helloworld".to_string(), None)
+            .await?;
+        assert_eq!(got.len(), 2);
+
+        // the first invalid service stops the batch
+        let bad = vec![service("v3.0.0", &["4"]), service("v4.0.0", &[])];
+        assert!(r.register_many(&bad, None).await.is_err());
+        assert_eq!(r.list_service(None).await?.len(), 3);
+
+        r.deregister_many(&services, None).await?;
+        r.deregister_many(&bad[..1], None).await?;
+        assert!(r.list_service(None).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_register_metadata_change() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let mut s = service("v1.0.0", &["1"]);
+        r.register(&s, None).await?;
+
+        s.nodes[0]
+            .metadata
+            .insert("zone".to_string(), "a".to_string());
+        r.register(&s, None).await?;
+
+        let services = r.get_service(s.name.clone(), None).await?;
+        assert_eq!(services[0].nodes[0].metadata, s.nodes[0].metadata);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_filters() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let mut eu = service("v1.0.0", &["1", "2"]);
+        eu.nodes[0]
+            .metadata
+            .insert("region".to_string(), "eu".to_string());
+        r.register(&eu, None).await?;
+        r.register(&service("v2.0.0", &["3"]), None).await?;
+        let name = "io.vine.helloworld".to_string();
+
+        let mut gopt = GetOptions::new();
+        gopt.with_version("v2.0.0".to_string());
+        let services = r.get_service(name.clone(), Some(gopt)).await?;
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].nodes[0].id, "3");
+
+        let mut gopt = GetOptions::new();
+        gopt.with_metadata_filter("region".to_string(), "eu".to_string());
+        let services = r.get_service(name.clone(), Some(gopt)).await?;
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].version, "v1.0.0");
+        assert_eq!(services[0].nodes.len(), 1);
+        assert_eq!(services[0].nodes[0].id, "1");
+
+        // no node left is not found
+        let mut gopt = GetOptions::new();
+        gopt.with_version("v2.0.0".to_string())
+            .with_metadata_filter("region".to_string(), "eu".to_string());
+        let e = r.get_service(name.clone(), Some(gopt)).await;
+        assert!(matches!(e, Err(Error::NotFound(_))));
+
+        let mut lopt = ListOptions::new();
+        lopt.with_metadata_filter("region".to_string(), "us".to_string());
+        assert!(r.list_service(Some(lopt)).await?.is_empty());
+        let mut lopt = ListOptions::new();
+        lopt.with_version("v1.0.0".to_string());
+        let services = r.list_service(Some(lopt)).await?;
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].nodes.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_register_validation() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let mut s = service("v1.0.0", &["1"]);
+        s.nodes[0].port = 0;
+
+        let e = r.register(&s, None).await.unwrap_err();
+        let errs = e
+            .backend()
+            .and_then(|e| e.downcast_ref::<MultiStatus>())
+            .unwrap();
+        assert_eq!(errs.len(), 1);
+        assert!(r.list_service(None).await?.is_empty());
+
+        let mut ropt = RegisterOptions::new();
+        ropt.with_skip_validation(true);
+        r.register(&s, Some(ropt)).await?;
+        assert_eq!(r.list_service(None).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_strict_decode() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        r.register(&service("v1.0.0", &["1"]), None).await?;
+        let good = serde_json::to_vec(&service("v1.0.0", &["2"]))?;
+        r.inject_raw(
+            "io.vine.helloworld",
+            "/vine/registry/io.vine.helloworld/2",
+            good,
+        )
+        .await;
+        r.inject_raw(
+            "io.vine.helloworld",
+            "/vine/registry/io.vine.helloworld/bad",
+            "{garbage",
+        )
+        .await;
+
+        let name = "io.vine.helloworld".to_string();
+        let services = r.get_service(name.clone(), None).await?;
+        assert_eq!(services.len(), 1);
+        assert_eq!(services[0].nodes.len(), 2);
+        assert_eq!(r.list_service(None).await?[0].nodes.len(), 2);
+
+        let (services, errs) = r.get_service_checked(name.clone(), None).await?;
+        assert_eq!(services[0].nodes.len(), 2);
+        assert_eq!(errs.len(), 1);
+        let e = errs.iter().next().unwrap();
+        assert_eq!(e.code(), Code::InternalServerError);
+        assert!(e.detail().contains("/vine/registry/io.vine.helloworld/bad"));
+
+        let mut gopt = GetOptions::new();
+        gopt.with_strict(true);
+        let e = r.get_service(name.clone(), Some(gopt)).await.unwrap_err();
+        let status = Status::from(e);
+        assert_eq!(status.code(), Code::InternalServerError);
+        assert!(status
+            .detail()
+            .contains("/vine/registry/io.vine.helloworld/bad"));
+
+        let mut options = Options::new();
+        options.with_strict_decode(true);
+        let strict = MemoryRegistry {
+            options,
+            ..r.clone()
+        };
+        assert!(strict.get_service(name.clone(), None).await.is_err());
+        assert!(strict.list_service(None).await.is_err());
+        assert!(strict.get_service_checked(name, None).await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let all = r.watch(None).await?;
+        let mut wopt = WatchOptions::new();
+        wopt.with_service("io.vine.other".to_string());
+        let other = r.watch(Some(wopt)).await?;
+
+        r.register(&service("v1.0.0", &["1"]), None).await?;
+        r.register(&service("v1.0.0", &["2"]), None).await?;
+        r.deregister(&service("v1.0.0", &["1", "2"]), None).await?;
+
+        let mut actions = vec![];
+        for _ in 0..3 {
+            let event = all.next().await?;
+            assert_eq!(event.service.unwrap().name, "io.vine.helloworld");
+            actions.push(event.action);
+        }
+        assert_eq!(actions, vec!["create", "update", "delete"]);
+
+        let mut s = service("v1.0.0", &["3"]);
+        s.name = "io.vine.other".to_string();
+        r.register(&s, None).await?;
+        assert_eq!(other.next().await?.service.unwrap().name, "io.vine.other");
+
+        other.stop().await;
+        assert!(matches!(other.next().await, Err(Error::WatcherStopped)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch_filters() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let mut wopt = WatchOptions::new();
+        wopt.with_action("delete".to_string())
+            .with_prefix("io.vine.hello".to_string());
+        let deletes = r.watch(Some(wopt)).await?;
+
+        let mut other = service("v1.0.0", &["9"]);
+        other.name = "io.vine.other".to_string();
+        r.register(&service("v1.0.0", &["1"]), None).await?;
+        r.register(&service("v1.0.0", &["1", "2"]), None).await?;
+        r.register(&other, None).await?;
+        r.deregister(&other, None).await?;
+        r.deregister(&service("v1.0.0", &["1"]), None).await?;
+
+        let event = deletes.next().await?;
+        assert_eq!(event.action, "delete");
+        assert_eq!(event.service.unwrap().name, "io.vine.helloworld");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ttl_expiry() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let w = r.watch(None).await?;
+
+        let mut ropt = RegisterOptions::new();
+        ropt.with_ttl(1);
+        r.register(&service("v1.0.0", &["1"]), Some(ropt.clone()))
+            .await?;
+        ropt.with_ttl(0);
+        r.register(&service("v1.0.0", &["2"]), Some(ropt)).await?;
+        assert_eq!(w.next().await?.action, "create");
+        assert_eq!(w.next().await?.action, "update");
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let services = r
+            .get_service("io.vine.helloworld".to_string(), None)
+            .await?;
+        assert_eq!(services[0].nodes.len(), 1);
+        assert_eq!(services[0].nodes[0].id, "2");
+
+        let event = w.next().await?;
+        assert_eq!(event.action, "delete");
+        assert_eq!(event.service.unwrap().nodes[0].id, "1");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ttl_sweeper() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let w = r.watch(None).await?;
+
+        let mut ropt = RegisterOptions::new();
+        ropt.with_ttl(1);
+        let s = service("v1.0.0", &["1"]);
+        r.register(&s, Some(ropt.clone())).await?;
+        assert_eq!(w.next().await?.action, "create");
+
+        // registering again renews the ttl
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        r.register(&s, Some(ropt)).await?;
+        assert_eq!(w.next().await?.action, "update");
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(r.list_service(None).await?.len(), 1);
+
+        // nothing reads, the delete still arrives
+        let event = tokio::time::timeout(Duration::from_secs(2), w.next()).await??;
+        assert_eq!(event.action, "delete");
+        assert!(r.list_service(None).await?.is_empty());
+
+        Ok(())
+    }
+}
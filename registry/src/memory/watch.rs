@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use async_trait::async_trait;
+use tokio::sync::{broadcast, Mutex, Notify};
+
+use crate::error::{Error, Result};
+use crate::{options::WatchOptions, types, Watcher};
+
+/// the implement of [`Watcher`] over the events of a [`super::MemoryRegistry`]
+pub struct MemoryWatcher {
+    opts: WatchOptions,
+    rx: Mutex<broadcast::Receiver<types::Result>>,
+    stopped: AtomicBool,
+    exit: Notify,
+}
+
+#[async_trait]
+impl Watcher for MemoryWatcher {
+    async fn next(&self) -> Result<types::Result> {
+        let mut rx = self.rx.lock().await;
+        loop {
+            if self.stopped.load(Ordering::SeqCst) {
+                return Err(Error::WatcherStopped);
+            }
+
+            let r = tokio::select! {
+                r = rx.recv() => r,
+                _ = self.exit.notified() => continue,
+            };
+
+            match r {
+                Ok(r) if self.opts.matches(&r) => return Ok(r),
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    logger::warn!("memory watcher lagged, {} events dropped", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => return Err(Error::WatcherStopped),
+            }
+        }
+    }
+
+    async fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.exit.notify_one();
+    }
+}
+
+impl MemoryWatcher {
+    pub fn new(rx: broadcast::Receiver<types::Result>, opt: Option<WatchOptions>) -> Self {
+        MemoryWatcher {
+            opts: opt.unwrap_or_default(),
+            rx: Mutex::new(rx),
+            stopped: AtomicBool::new(false),
+            exit: Notify::new(),
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use errors::MultiStatus;
+
+use crate::error::{Error, Result};
+use crate::options::{
+    DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+};
+use crate::types::Service;
+use crate::{Registry, Watcher};
+
+/// the upper bounds of the latency buckets, in seconds
+const BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// the `result` label of a call, by the error it failed with
+const RESULTS: [&str; 4] = ["ok", "not_found", "timeout", "error"];
+
+/// the calls of one operation of one backend
+#[derive(Default)]
+struct Series {
+    /// by the index of the result in [`RESULTS`]
+    results: [u64; 4],
+    /// by the index of the bound in [`BUCKETS`], not cumulative
+    buckets: [u64; 12],
+    sum: f64,
+    count: u64,
+}
+
+/// (backend, operation) -> calls
+static SERIES: Mutex<BTreeMap<(&'static str, &'static str), Series>> = Mutex::new(BTreeMap::new());
+
+fn series() -> MutexGuard<'static, BTreeMap<(&'static str, &'static str), Series>> {
+    SERIES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn observe<T>(backend: &'static str, op: &'static str, start: Instant, out: &Result<T>) {
+    let elapsed = start.elapsed().as_secs_f64();
+    let result = match out {
+        Ok(_) => 0,
+        Err(Error::NotFound(_)) => 1,
+        Err(Error::Timeout(_)) => 2,
+        Err(_) => 3,
+    };
+
+    let mut series = series();
+    let s = series.entry((backend, op)).or_default();
+    s.results[result] += 1;
+    if let Some(i) = BUCKETS.iter().position(|b| elapsed <= *b) {
+        s.buckets[i] += 1;
+    }
+    s.sum += elapsed;
+    s.count += 1;
+}
+
+/// the counters and latency histograms of every instrumented registry call
+/// in the Prometheus text exposition format, to serve on a `/metrics`
+/// endpoint:
+///
+/// - `vine_registry_requests_total{backend, op, result}`, the calls by
+///   result: `ok`, `not_found`, `timeout` or `error`
+/// - `vine_registry_request_duration_seconds{backend, op}`, their latency
+pub fn gather() -> String {
+    let series = series();
+    let mut out = String::new();
+
+    out.push_str(
+        "# HELP vine_registry_requests_total Registry calls by backend, operation and result.\n",
+    );
+    out.push_str("# TYPE vine_registry_requests_total counter\n");
+    for ((backend, op), s) in series.iter() {
+        for (result, n) in RESULTS.iter().zip(s.results.iter()) {
+            let _ = writeln!(
+                out,
+                "vine_registry_requests_total{{backend=\"{}\",op=\"{}\",result=\"{}\"}} {}",
+                backend, op, result, n
+            );
+        }
+    }
+
+    out.push_str("# HELP vine_registry_request_duration_seconds Latency of the registry calls.\n");
+    out.push_str("# TYPE vine_registry_request_duration_seconds histogram\n");
+    for ((backend, op), s) in series.iter() {
+        let labels = format!("backend=\"{}\",op=\"{}\"", backend, op);
+        let mut cumulative = 0;
+        for (bound, n) in BUCKETS.iter().zip(s.buckets.iter()) {
+            cumulative += n;
+            let _ = writeln!(
+                out,
+                "vine_registry_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                labels, bound, cumulative
+            );
+        }
+        let _ = writeln!(
+            out,
+            "vine_registry_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+            labels, s.count
+        );
+        let _ = writeln!(
+            out,
+            "vine_registry_request_duration_seconds_sum{{{}}} {}",
+            labels, s.sum
+        );
+        let _ = writeln!(
+            out,
+            "vine_registry_request_duration_seconds_count{{{}}} {}",
+            labels, s.count
+        );
+    }
+
+    out
+}
+
+/// the implement of [`Registry`] counting and timing the calls of another
+/// registry for [`gather`], labelled with the name of the inner registry it
+/// also reports as its own. The default registry is instrumented already.
+///
+/// ```rust
+/// # use registry::{memory::MemoryRegistry, metrics::{self, MetricsRegistry}, Registry};
+/// # async fn run() -> errors::Result<()> {
+/// let registry = MetricsRegistry::new(Box::new(MemoryRegistry::new(None))).await;
+/// let services = registry.list_service(None).await?;
+/// println!("{}", metrics::gather());
+/// # Ok(())
+/// # }
+/// ```
+pub struct MetricsRegistry {
+    inner: Box<dyn Registry + Sync>,
+    backend: &'static str,
+}
+
+impl MetricsRegistry {
+    pub async fn new(inner: Box<dyn Registry + Sync>) -> Self {
+        let backend = inner.string().await;
+        MetricsRegistry { inner, backend }
+    }
+
+    async fn timed<T, F>(&self, op: &'static str, call: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let start = Instant::now();
+        let out = call.await;
+        observe(self.backend, op, start, &out);
+        out
+    }
+}
+
+#[async_trait]
+impl Registry for MetricsRegistry {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.inner.init(opt).await
+    }
+
+    #[inline]
+    async fn options(&self) -> Options {
+        self.inner.options().await
+    }
+
+    async fn register(&self, s: &Service, opt: Option<RegisterOptions>) -> Result<()> {
+        self.timed("register", self.inner.register(s, opt)).await
+    }
+
+    async fn deregister(&self, s: &Service, opt: Option<DeregisterOptions>) -> Result<()> {
+        self.timed("deregister", self.inner.deregister(s, opt))
+            .await
+    }
+
+    async fn register_many(
+        &self,
+        services: &[Service],
+        opt: Option<RegisterOptions>,
+    ) -> Result<()> {
+        self.timed("register_many", self.inner.register_many(services, opt))
+            .await
+    }
+
+    async fn deregister_many(
+        &self,
+        services: &[Service],
+        opt: Option<DeregisterOptions>,
+    ) -> Result<()> {
+        self.timed("deregister_many", self.inner.deregister_many(services, opt))
+            .await
+    }
+
+    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> Result<Vec<Service>> {
+        self.timed("get_service", self.inner.get_service(s, opt))
+            .await
+    }
+
+    async fn get_service_checked(
+        &self,
+        s: String,
+        opt: Option<GetOptions>,
+    ) -> Result<(Vec<Service>, MultiStatus)> {
+        self.timed("get_service", self.inner.get_service_checked(s, opt))
+            .await
+    }
+
+    async fn list_service(&self, opt: Option<ListOptions>) -> Result<Vec<Service>> {
+        self.timed("list_service", self.inner.list_service(opt))
+            .await
+    }
+
+    /// times starting the watch, not the results
+    async fn watch(&self, opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send + Sync>> {
+        self.timed("watch", self.inner.watch(opt)).await
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        self.backend
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use errors::Result;
+
+    use super::{gather, MetricsRegistry};
+    use crate::{mock::MockRegistry, Error, Registry};
+
+    #[tokio::test]
+    async fn test_gather() -> Result<()> {
+        let mock = MockRegistry::new();
+        let r = MetricsRegistry::new(Box::new(mock.clone())).await;
+        assert_eq!(r.string().await, "mock");
+
+        mock.expect_get_service("io.vine.slow")
+            .return_err(Error::Timeout("get_service timed out".to_string()));
+        assert!(r
+            .get_service("io.vine.slow".to_string(), None)
+            .await
+            .is_err());
+        assert!(r
+            .get_service("io.vine.none".to_string(), None)
+            .await
+            .is_err());
+        r.list_service(None).await?;
+
+        let out = gather();
+        let labels = "backend=\"mock\",op=\"get_service\"";
+        for result in &["timeout", "not_found"] {
+            let line = format!(
+                "vine_registry_requests_total{{{},result=\"{}\"}} 1",
+                labels, result
+            );
+            assert!(out.contains(&line), "{}", out);
+        }
+        assert!(out.contains(
+            "vine_registry_requests_total{backend=\"mock\",op=\"list_service\",result=\"ok\"} 1"
+        ));
+        let count = format!(
+            "vine_registry_request_duration_seconds_count{{{}}} 2",
+            labels
+        );
+        assert!(out.contains(&count), "{}", out);
+        let inf = format!(
+            "vine_registry_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 2",
+            labels
+        );
+        assert!(out.contains(&inf), "{}", out);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::error::{Error, Result};
+use crate::memory::MemoryRegistry;
+use crate::options::{
+    DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+};
+use crate::types::Service;
+use crate::{Registry, Watcher};
+
+/// what a scripted call answers
+enum Reply<T> {
+    Ok(T),
+    Err(Error),
+    /// answered by the memory registry behind the mock
+    Pass,
+}
+
+struct Scripted<T> {
+    /// the service name the call must name, any when `None`
+    name: Option<String>,
+    latency: Duration,
+    reply: Reply<T>,
+}
+
+type Queue<T> = VecDeque<Scripted<T>>;
+
+#[derive(Default)]
+struct Script {
+    register: Queue<()>,
+    deregister: Queue<()>,
+    get_service: Queue<Vec<Service>>,
+    list_service: Queue<Vec<Service>>,
+    watch: Queue<Box<dyn Watcher + Send + Sync>>,
+    /// method -> how many times it was called
+    calls: HashMap<&'static str, usize>,
+}
+
+impl Script {
+    fn pending(&self) -> usize {
+        self.register.len()
+            + self.deregister.len()
+            + self.get_service.len()
+            + self.list_service.len()
+            + self.watch.len()
+    }
+}
+
+fn lock(m: &Mutex<Script>) -> MutexGuard<'_, Script> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// the answer of a call scripted by a `MockRegistry::expect_*` method, in
+/// the queue of its method once `return_ok`, `return_err` or `pass` is called
+#[must_use = "the expectation is scripted by return_ok, return_err or pass"]
+pub struct Expectation<T> {
+    script: Arc<Mutex<Script>>,
+    queue: fn(&mut Script) -> &mut Queue<T>,
+    name: Option<String>,
+    latency: Duration,
+}
+
+impl<T> Expectation<T> {
+    /// delays the answer by `d`, so deadlines and timeouts can be tested
+    pub fn with_latency(mut self, d: Duration) -> Self {
+        self.latency = d;
+        self
+    }
+
+    /// answers the call with `v`
+    pub fn return_ok(self, v: T) {
+        self.push(Reply::Ok(v))
+    }
+
+    /// fails the call with `e`
+    pub fn return_err(self, e: impl Into<Error>) {
+        self.push(Reply::Err(e.into()))
+    }
+
+    /// lets the memory registry behind the mock answer the call, after the
+    /// latency
+    pub fn pass(self) {
+        self.push(Reply::Pass)
+    }
+
+    fn push(self, reply: Reply<T>) {
+        let mut script = lock(&self.script);
+        (self.queue)(&mut script).push_back(Scripted {
+            name: self.name,
+            latency: self.latency,
+            reply,
+        });
+    }
+}
+
+/// the implement of [`Registry`] answering from a script, for the tests of
+/// the retry, caching and failover logic built on a registry.
+///
+/// Every `expect_*` call queues the answer of one call of its method; a call
+/// takes the first answer queued for its service name, and the calls nothing
+/// is queued for are answered by a [`MemoryRegistry`] behind the mock. Clones
+/// share the script, so a test can keep one after boxing another.
+///
+/// ```rust
+/// # use registry::{mock::MockRegistry, Error, Registry};
+/// # async fn run() -> errors::Result<()> {
+/// let mock = MockRegistry::new();
+/// mock.expect_get_service("helloworld")
+///     .return_err(Error::Timeout("get_service timed out".to_string()));
+///
+/// let name = "helloworld".to_string();
+/// assert!(mock.get_service(name.clone(), None).await.is_err());
+/// // nothing is scripted anymore, helloworld is not registered
+/// assert!(matches!(mock.get_service(name, None).await, Err(Error::NotFound(_))));
+/// assert_eq!(mock.calls("get_service"), 2);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct MockRegistry {
+    inner: MemoryRegistry,
+    script: Arc<Mutex<Script>>,
+}
+
+impl MockRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn expect<T>(
+        &self,
+        name: Option<String>,
+        queue: fn(&mut Script) -> &mut Queue<T>,
+    ) -> Expectation<T> {
+        Expectation {
+            script: self.script.clone(),
+            queue,
+            name,
+            latency: Duration::from_secs(0),
+        }
+    }
+
+    /// scripts the next registration of the service named `name`
+    pub fn expect_register(&self, name: impl Into<String>) -> Expectation<()> {
+        self.expect(Some(name.into()), |s| &mut s.register)
+    }
+
+    /// scripts the next deregistration of the service named `name`
+    pub fn expect_deregister(&self, name: impl Into<String>) -> Expectation<()> {
+        self.expect(Some(name.into()), |s| &mut s.deregister)
+    }
+
+    /// scripts the next lookup of the service named `name`, which
+    /// `get_service_checked` goes through as well
+    pub fn expect_get_service(&self, name: impl Into<String>) -> Expectation<Vec<Service>> {
+        self.expect(Some(name.into()), |s| &mut s.get_service)
+    }
+
+    pub fn expect_list_service(&self) -> Expectation<Vec<Service>> {
+        self.expect(None, |s| &mut s.list_service)
+    }
+
+    pub fn expect_watch(&self) -> Expectation<Box<dyn Watcher + Send + Sync>> {
+        self.expect(None, |s| &mut s.watch)
+    }
+
+    /// how many times `method` was called, the name of the [`Registry`]
+    /// method such as `"get_service"`
+    pub fn calls(&self, method: &str) -> usize {
+        lock(&self.script).calls.get(method).copied().unwrap_or(0)
+    }
+
+    /// how many scripted answers no call has taken yet
+    pub fn pending(&self) -> usize {
+        lock(&self.script).pending()
+    }
+
+    /// forgets the answers no call has taken and the call counts
+    pub fn reset(&self) {
+        *lock(&self.script) = Script::default();
+    }
+
+    /// counts a call of `method` and takes the answer queued for `name`
+    async fn answer<T>(
+        &self,
+        method: &'static str,
+        name: Option<&str>,
+        queue: fn(&mut Script) -> &mut Queue<T>,
+    ) -> Reply<T> {
+        let scripted = {
+            let mut script = lock(&self.script);
+            *script.calls.entry(method).or_default() += 1;
+            let queue = queue(&mut script);
+            let i = queue
+                .iter()
+                .position(|e| e.name.is_none() || e.name.as_deref() == name);
+            i.and_then(|i| queue.remove(i))
+        };
+
+        match scripted {
+            Some(scripted) => {
+                if scripted.latency > Duration::from_secs(0) {
+                    tokio::time::sleep(scripted.latency).await;
+                }
+                scripted.reply
+            }
+            None => Reply::Pass,
+        }
+    }
+}
+
+#[async_trait]
+impl Registry for MockRegistry {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        self.inner.init(opt).await
+    }
+
+    #[inline]
+    async fn options(&self) -> Options {
+        self.inner.options().await
+    }
+
+    async fn register(&self, s: &Service, opt: Option<RegisterOptions>) -> Result<()> {
+        match self
+            .answer("register", Some(&s.name), |s| &mut s.register)
+            .await
+        {
+            Reply::Ok(()) => Ok(()),
+            Reply::Err(e) => Err(e),
+            Reply::Pass => self.inner.register(s, opt).await,
+        }
+    }
+
+    async fn deregister(&self, s: &Service, opt: Option<DeregisterOptions>) -> Result<()> {
+        match self
+            .answer("deregister", Some(&s.name), |s| &mut s.deregister)
+            .await
+        {
+            Reply::Ok(()) => Ok(()),
+            Reply::Err(e) => Err(e),
+            Reply::Pass => self.inner.deregister(s, opt).await,
+        }
+    }
+
+    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> Result<Vec<Service>> {
+        match self
+            .answer("get_service", Some(&s), |s| &mut s.get_service)
+            .await
+        {
+            Reply::Ok(services) => Ok(services),
+            Reply::Err(e) => Err(e),
+            Reply::Pass => self.inner.get_service(s, opt).await,
+        }
+    }
+
+    async fn list_service(&self, opt: Option<ListOptions>) -> Result<Vec<Service>> {
+        match self
+            .answer("list_service", None, |s| &mut s.list_service)
+            .await
+        {
+            Reply::Ok(services) => Ok(services),
+            Reply::Err(e) => Err(e),
+            Reply::Pass => self.inner.list_service(opt).await,
+        }
+    }
+
+    async fn watch(&self, opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send + Sync>> {
+        match self.answer("watch", None, |s| &mut s.watch).await {
+            Reply::Ok(watcher) => Ok(watcher),
+            Reply::Err(e) => Err(e),
+            Reply::Pass => self.inner.watch(opt).await,
+        }
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        "mock"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    use errors::Result;
+
+    use super::MockRegistry;
+    use crate::{
+        types::{Node, Service},
+        Error, Registry,
+    };
+
+    fn service(name: &str) -> Service {
+        Service {
+            name: name.to_string(),
+            version: "v1.0.0".to_string(),
+            nodes: vec![Node {
+                id: "1".to_string(),
+                address: "127.0.0.1".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_script() -> Result<()> {
+        let mock = MockRegistry::new();
+        let hello = "io.vine.helloworld".to_string();
+        mock.register(&service(&hello), None).await?;
+
+        mock.expect_get_service(hello.clone())
+            .return_err(Error::Timeout("get_service timed out".to_string()));
+        mock.expect_get_service(hello.clone())
+            .with_latency(Duration::from_millis(50))
+            .pass();
+        mock.expect_get_service("io.vine.other")
+            .return_ok(vec![service("io.vine.other")]);
+        assert_eq!(mock.pending(), 3);
+
+        // the answers scripted for another name are left alone
+        let e = mock.get_service(hello.clone(), None).await;
+        assert!(matches!(e, Err(Error::Timeout(_))));
+        let start = Instant::now();
+        assert_eq!(mock.get_service(hello.clone(), None).await?[0].name, hello);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        let other = mock.get_service("io.vine.other".to_string(), None).await?;
+        assert_eq!(other[0].name, "io.vine.other");
+        assert_eq!(mock.pending(), 0);
+
+        // the default methods go through the scripted ones
+        mock.expect_register("io.vine.fail")
+            .return_err(errors::err!("unavailable"));
+        let services = vec![service("io.vine.ok"), service("io.vine.fail")];
+        assert!(mock.register_many(&services, None).await.is_err());
+        assert_eq!(mock.calls("register"), 3);
+        assert_eq!(mock.get_service_checked(hello, None).await?.0.len(), 1);
+        assert_eq!(mock.calls("get_service"), 4);
+
+        mock.expect_list_service().return_ok(vec![]);
+        mock.reset();
+        assert_eq!(mock.pending(), 0);
+        assert_eq!(mock.list_service(None).await?.len(), 2);
+
+        Ok(())
+    }
+}
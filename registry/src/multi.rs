@@ -0,0 +1,396 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use errors::{err, MultiStatus, Status};
+use tokio::sync::{mpsc, Mutex};
+
+use crate::error::{Error, Result};
+use crate::options::{
+    DeregisterOptions, GetOptions, ListOptions, Options, RegisterOptions, WatchOptions,
+};
+use crate::types::{self, Service};
+use crate::{Registry, Watcher};
+
+/// the future of a call to one of the registries
+type Call<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// how many watch results of the underlying registries may queue up
+const WATCH_BUFFER: usize = 128;
+
+/// Reads is how a [`MultiRegistry`] answers get and list
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reads {
+    /// the first registry which answers, in the order they were given
+    Fallback,
+    /// the services of every registry which answers, merged
+    Merge,
+}
+
+/// the implement of [`Registry`] writing to several registries at once,
+/// e.g. to migrate from one backend to another without downtime
+///
+/// ```rust
+/// # use registry::{memory::MemoryRegistry, multi::{MultiRegistry, Reads}, Registry};
+/// # async fn run() -> errors::Result<()> {
+/// let mut registry = MultiRegistry::new(vec![
+///     Box::new(MemoryRegistry::new(None)),
+///     Box::new(MemoryRegistry::new(None)),
+/// ]);
+/// registry.with_reads(Reads::Merge);
+/// let services = registry.list_service(None).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MultiRegistry {
+    registries: Vec<Box<dyn Registry + Sync>>,
+    reads: Reads,
+}
+
+impl MultiRegistry {
+    /// the first of `registries` is the primary, reads fall back to the
+    /// others in order
+    pub fn new(registries: Vec<Box<dyn Registry + Sync>>) -> Self {
+        MultiRegistry {
+            registries,
+            reads: Reads::Fallback,
+        }
+    }
+
+    #[inline]
+    pub fn with_reads(&mut self, r: Reads) -> &mut Self {
+        self.reads = r;
+        self
+    }
+
+    /// the services read from every registry, by `reads`. Fails only when
+    /// no registry answers, with the error of the primary.
+    async fn read<'a, F>(&'a self, read: F) -> Result<Vec<Service>>
+    where
+        F: Fn(&'a (dyn Registry + Sync)) -> Call<'a, Vec<Service>>,
+    {
+        let mut first_err = None;
+        let mut out: Vec<Service> = vec![];
+        let mut answered = false;
+        for r in &self.registries {
+            match read(r.as_ref()).await {
+                Ok(services) => {
+                    answered = true;
+                    merge(&mut out, services);
+                    if self.reads == Reads::Fallback {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    let name = r.string().await;
+                    logger::debug!("read from {} registry failed: {}", name, e);
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+
+        match (answered, first_err) {
+            (false, Some(e)) => Err(e),
+            (false, None) => Err(err!("no registry to read from").into()),
+            (true, _) => Ok(out),
+        }
+    }
+
+    /// runs `write` against every registry, the error holds a status for
+    /// every registry which failed
+    async fn write<'a, F>(&'a self, op: &str, write: F) -> Result<()>
+    where
+        F: Fn(&'a (dyn Registry + Sync)) -> Call<'a, ()>,
+    {
+        let mut errs = MultiStatus::new();
+        for r in &self.registries {
+            if let Err(e) = write(r.as_ref()).await {
+                let prefix = format!("{} on {} registry", op, r.string().await);
+                let s = Status::from(e);
+                let status = Status::new(
+                    "io.vine.registry".to_string(),
+                    format!("{}: {}", prefix, s.detail()),
+                    s.code(),
+                );
+                logger::error!("{}", status.detail());
+                errs.push(status);
+            }
+        }
+
+        if errs.is_empty() {
+            Ok(())
+        } else {
+            Err(errs.into())
+        }
+    }
+}
+
+/// merges `services` into `out` by name and version, nodes by id
+fn merge(out: &mut Vec<Service>, services: Vec<Service>) {
+    for s in services {
+        match out
+            .iter_mut()
+            .find(|o| o.name == s.name && o.version == s.version)
+        {
+            Some(o) => {
+                for node in s.nodes {
+                    if !o.nodes.iter().any(|n| n.id == node.id) {
+                        o.nodes.push(node);
+                    }
+                }
+            }
+            None => out.push(s),
+        }
+    }
+}
+
+#[async_trait]
+impl Registry for MultiRegistry {
+    async fn init(&mut self, opt: Option<Options>) -> Result<()> {
+        for r in &mut self.registries {
+            r.init(opt.clone()).await?;
+        }
+        Ok(())
+    }
+
+    /// the options of the primary
+    async fn options(&self) -> Options {
+        match self.registries.first() {
+            Some(r) => r.options().await,
+            None => Options::new(),
+        }
+    }
+
+    async fn register(&self, s: &Service, opt: Option<RegisterOptions>) -> Result<()> {
+        self.write("register", |r| r.register(s, opt.clone())).await
+    }
+
+    async fn deregister(&self, s: &Service, opt: Option<DeregisterOptions>) -> Result<()> {
+        self.write("deregister", |r| r.deregister(s, opt.clone()))
+            .await
+    }
+
+    async fn register_many(
+        &self,
+        services: &[Service],
+        opt: Option<RegisterOptions>,
+    ) -> Result<()> {
+        self.write("register", |r| r.register_many(services, opt.clone()))
+            .await
+    }
+
+    async fn deregister_many(
+        &self,
+        services: &[Service],
+        opt: Option<DeregisterOptions>,
+    ) -> Result<()> {
+        self.write("deregister", |r| r.deregister_many(services, opt.clone()))
+            .await
+    }
+
+    async fn get_service(&self, s: String, opt: Option<GetOptions>) -> Result<Vec<Service>> {
+        self.read(|r| r.get_service(s.clone(), opt.clone())).await
+    }
+
+    async fn list_service(&self, opt: Option<ListOptions>) -> Result<Vec<Service>> {
+        self.read(|r| r.list_service(opt.clone())).await
+    }
+
+    /// watches every registry when reads merge, the first which can be
+    /// watched otherwise
+    async fn watch(&self, opt: Option<WatchOptions>) -> Result<Box<dyn Watcher + Send + Sync>> {
+        let mut watchers = vec![];
+        let mut first_err = None;
+        for r in &self.registries {
+            match r.watch(opt.clone()).await {
+                Ok(w) => {
+                    watchers.push(w);
+                    if self.reads == Reads::Fallback {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+
+        match (watchers.is_empty(), first_err) {
+            (true, Some(e)) => Err(e),
+            (true, None) => return Err(err!("no registry to watch").into()),
+            (false, _) => Ok(Box::new(MultiWatcher::new(watchers))),
+        }
+    }
+
+    #[inline]
+    async fn string(&self) -> &'static str {
+        "multi"
+    }
+}
+
+/// the implement of [`Watcher`] forwarding the results of several watchers
+pub struct MultiWatcher {
+    watchers: Vec<Arc<Box<dyn Watcher + Send + Sync>>>,
+    rx: Mutex<mpsc::Receiver<Result<types::Result>>>,
+}
+
+impl MultiWatcher {
+    pub fn new(watchers: Vec<Box<dyn Watcher + Send + Sync>>) -> Self {
+        let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+        let watchers: Vec<_> = watchers.into_iter().map(Arc::new).collect();
+        for w in &watchers {
+            let w = w.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                loop {
+                    let r = w.next().await;
+                    let failed = r.is_err();
+                    if tx.send(r).await.is_err() || failed {
+                        return;
+                    }
+                }
+            });
+        }
+        MultiWatcher {
+            watchers,
+            rx: Mutex::new(rx),
+        }
+    }
+}
+
+#[async_trait]
+impl Watcher for MultiWatcher {
+    /// the next result of any watcher, the first error ends the watch
+    async fn next(&self) -> Result<types::Result> {
+        match self.rx.lock().await.recv().await {
+            Some(r) => r,
+            None => return Err(Error::WatcherStopped),
+        }
+    }
+
+    async fn stop(&self) {
+        for w in &self.watchers {
+            w.stop().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use errors::{MultiStatus, Result};
+
+    use super::{MultiRegistry, Reads};
+    use crate::{
+        dns::StaticRegistry,
+        memory::MemoryRegistry,
+        types::{Node, Service},
+        Registry,
+    };
+
+    fn service(name: &str, id: &str) -> Service {
+        Service {
+            name: name.to_string(),
+            version: "v1.0.0".to_string(),
+            nodes: vec![Node {
+                id: id.to_string(),
+                address: "127.0.0.1".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multi_registry() -> Result<()> {
+        let (a, b) = (MemoryRegistry::new(None), MemoryRegistry::new(None));
+        let mut r = MultiRegistry::new(vec![Box::new(a.clone()), Box::new(b.clone())]);
+
+        r.register(&service("io.vine.helloworld", "1"), None)
+            .await?;
+        assert_eq!(a.list_service(None).await?.len(), 1);
+        assert_eq!(b.list_service(None).await?.len(), 1);
+
+        // only the secondary knows the service, reads fall back to it
+        b.register(&service("io.vine.other", "2"), None).await?;
+        b.register(&service("io.vine.helloworld", "3"), None)
+            .await?;
+        let services = r.get_service("io.vine.other".to_string(), None).await?;
+        assert_eq!(services[0].nodes[0].id, "2");
+        let services = r
+            .get_service("io.vine.helloworld".to_string(), None)
+            .await?;
+        assert_eq!(services[0].nodes.len(), 1);
+        assert_eq!(r.list_service(None).await?.len(), 1);
+
+        r.with_reads(Reads::Merge);
+        let services = r
+            .get_service("io.vine.helloworld".to_string(), None)
+            .await?;
+        let ids: Vec<&str> = services[0].nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "3"]);
+        assert_eq!(r.list_service(None).await?.len(), 2);
+
+        r.deregister(&service("io.vine.helloworld", "1"), None)
+            .await?;
+        assert!(a
+            .get_service("io.vine.helloworld".to_string(), None)
+            .await
+            .is_err());
+        assert!(r
+            .get_service("io.vine.missing".to_string(), None)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_multi_partial_failure() -> Result<()> {
+        let a = MemoryRegistry::new(None);
+        let b = StaticRegistry::new(HashMap::new(), None).await?;
+        let r = MultiRegistry::new(vec![Box::new(b), Box::new(a.clone())]);
+
+        let e = r
+            .register(&service("io.vine.helloworld", "1"), None)
+            .await
+            .unwrap_err();
+        let errs = e
+            .backend()
+            .and_then(|e| e.downcast_ref::<MultiStatus>())
+            .unwrap();
+        assert_eq!(errs.len(), 1);
+        assert!(errs.iter().next().unwrap().detail().contains("static"));
+        // the healthy registry was still written
+        assert_eq!(a.list_service(None).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_multi_watch() -> Result<()> {
+        let (a, b) = (MemoryRegistry::new(None), MemoryRegistry::new(None));
+        let mut r = MultiRegistry::new(vec![Box::new(a.clone()), Box::new(b.clone())]);
+        r.with_reads(Reads::Merge);
+        let w = r.watch(None).await?;
+
+        a.register(&service("io.vine.helloworld", "1"), None)
+            .await?;
+        b.register(&service("io.vine.helloworld", "2"), None)
+            .await?;
+        let mut ids = vec![];
+        for _ in 0..2 {
+            ids.push(w.next().await?.service.unwrap().nodes[0].id.clone());
+        }
+        ids.sort();
+        assert_eq!(ids, vec!["1", "2"]);
+
+        w.stop().await;
+        assert!(w.next().await.is_err());
+
+        Ok(())
+    }
+}
@@ -1,8 +1,73 @@
-#[derive(Debug, Clone)]
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::types;
+
+/// the domain of the calls which don't name one
+pub const DEFAULT_DOMAIN: &str = "vine";
+
+/// the encoding of the values a backend stores, either is read back
+/// whichever is configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    #[default]
+    Json,
+    /// the `Service` message of registry.proto, as written by the vine Go
+    /// project, the openapi documents of a service are not stored
+    Protobuf,
+}
+
+#[derive(Clone)]
 pub struct Options {
     pub addrs: Vec<String>,
+    /// default timeout in seconds applied to every backend call
+    /// that does not carry its own timeout
     pub timeout: i64,
+    /// connect over TLS, also implied by any of the `tls_*` paths
     pub secure: bool,
+    /// PEM file of the CA verifying the servers
+    pub tls_ca: Option<PathBuf>,
+    /// PEM files of the client certificate and its key, set together
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// credentials authenticating to the backend
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// fail reads on values which can't be decoded instead of skipping them
+    pub strict_decode: bool,
+    /// the domain of the calls which don't name one, for the backends
+    /// keeping domains apart, [`DEFAULT_DOMAIN`] when `None`
+    pub domain: Option<String>,
+    /// the encoding of the values written
+    pub codec: Codec,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// the credentials are redacted, the options end up in logs
+impl fmt::Debug for Options {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redact = |s: &Option<String>| s.as_ref().map(|_| "***");
+        f.debug_struct("Options")
+            .field("addrs", &self.addrs)
+            .field("timeout", &self.timeout)
+            .field("secure", &self.secure)
+            .field("tls_ca", &self.tls_ca)
+            .field("tls_cert", &self.tls_cert)
+            .field("tls_key", &self.tls_key)
+            .field("username", &redact(&self.username))
+            .field("password", &redact(&self.password))
+            .field("strict_decode", &self.strict_decode)
+            .field("domain", &self.domain)
+            .field("codec", &self.codec)
+            .finish()
+    }
 }
 
 impl Options {
@@ -12,38 +77,165 @@ impl Options {
             addrs: vec![String::from("127.0.0.1:2379")],
             timeout: 15,
             secure: false,
+            tls_ca: None,
+            tls_cert: None,
+            tls_key: None,
+            username: None,
+            password: None,
+            strict_decode: false,
+            domain: None,
+            codec: Codec::default(),
         }
     }
 
     #[inline]
-    pub fn with_timeout(&mut self, t: i64) -> &Self {
+    pub fn with_timeout(&mut self, t: i64) -> &mut Self {
         self.timeout = t;
         self
     }
 
     #[inline]
-    pub fn with_secure(&mut self, b: bool) -> &Self {
+    pub fn with_secure(&mut self, b: bool) -> &mut Self {
         self.secure = b;
         self
     }
+
+    #[inline]
+    pub fn with_tls_ca(&mut self, ca: impl Into<PathBuf>) -> &mut Self {
+        self.tls_ca = Some(ca.into());
+        self
+    }
+
+    #[inline]
+    pub fn with_tls_identity(
+        &mut self,
+        cert: impl Into<PathBuf>,
+        key: impl Into<PathBuf>,
+    ) -> &mut Self {
+        self.tls_cert = Some(cert.into());
+        self.tls_key = Some(key.into());
+        self
+    }
+
+    #[inline]
+    pub fn with_auth(
+        &mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> &mut Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+
+    /// whether the connection should use TLS
+    #[inline]
+    pub fn tls_enabled(&self) -> bool {
+        self.secure || self.tls_ca.is_some() || self.tls_cert.is_some() || self.tls_key.is_some()
+    }
+
+    #[inline]
+    pub fn with_strict_decode(&mut self, b: bool) -> &mut Self {
+        self.strict_decode = b;
+        self
+    }
+
+    #[inline]
+    pub fn with_codec(&mut self, codec: Codec) -> &mut Self {
+        self.codec = codec;
+        self
+    }
+
+    /// returns the per-call timeout if given, otherwise `Options.timeout`
+    #[inline]
+    pub fn timeout_or(&self, t: Option<Duration>) -> Duration {
+        match t {
+            Some(d) => d,
+            None => Duration::from_secs(self.timeout.max(0) as u64),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct RegisterOptions {
     pub ttl: i64,
+    /// overrides `Options.timeout` for this call
+    pub timeout: Option<Duration>,
+    /// register without checking `Service::validate`
+    pub skip_validation: bool,
+    /// store the endpoints in every node value, otherwise once per version
+    pub include_endpoints: bool,
+    /// store the openapi document in every node value, otherwise once per version
+    pub include_openapi: bool,
+    /// registers again on this interval until deregistered, keeping the
+    /// registration alive past its ttl
+    pub interval: Option<Duration>,
+    /// the tenant the call belongs to, [`DEFAULT_DOMAIN`] when `None`.
+    /// Backends without tenants ignore it.
+    pub domain: Option<String>,
+}
+
+impl Default for RegisterOptions {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RegisterOptions {
     #[inline]
     pub fn new() -> Self {
-        RegisterOptions { ttl: 15 }
+        RegisterOptions {
+            ttl: 15,
+            timeout: None,
+            skip_validation: false,
+            include_endpoints: true,
+            include_openapi: true,
+            interval: None,
+            domain: None,
+        }
     }
 
     #[inline]
-    pub fn with_ttl(&mut self, ttl: i64) -> &Self {
+    pub fn with_ttl(&mut self, ttl: i64) -> &mut Self {
         self.ttl = ttl;
         self
     }
+
+    #[inline]
+    pub fn with_timeout(&mut self, t: Duration) -> &mut Self {
+        self.timeout = Some(t);
+        self
+    }
+
+    #[inline]
+    pub fn with_skip_validation(&mut self, b: bool) -> &mut Self {
+        self.skip_validation = b;
+        self
+    }
+
+    #[inline]
+    pub fn with_include_endpoints(&mut self, b: bool) -> &mut Self {
+        self.include_endpoints = b;
+        self
+    }
+
+    #[inline]
+    pub fn with_include_openapi(&mut self, b: bool) -> &mut Self {
+        self.include_openapi = b;
+        self
+    }
+
+    #[inline]
+    pub fn with_interval(&mut self, t: Duration) -> &mut Self {
+        self.interval = Some(t);
+        self
+    }
+
+    #[inline]
+    pub fn with_domain(&mut self, d: String) -> &mut Self {
+        self.domain = Some(d);
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +243,23 @@ pub struct WatchOptions {
     // Specify a service to watch
     // If blank, the watch is for all services
     pub service: String,
+    /// the tenant the call belongs to, [`DEFAULT_DOMAIN`] when `None`.
+    /// Backends without tenants ignore it.
+    pub domain: Option<String>,
+    /// the actions to return, `create`, `update` or `delete`, every action
+    /// when empty
+    pub actions: Vec<String>,
+    /// only the services whose name starts with it, every service when blank
+    pub prefix: String,
+    /// overrides `Options.timeout` for starting the watch, the results are
+    /// waited for without a deadline
+    pub timeout: Option<Duration>,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WatchOptions {
@@ -58,24 +267,204 @@ impl WatchOptions {
     pub fn new() -> Self {
         WatchOptions {
             service: String::new(),
+            domain: None,
+            actions: vec![],
+            prefix: String::new(),
+            timeout: None,
         }
     }
 
     #[inline]
-    pub fn with_service(&mut self, s: String) -> &Self {
+    pub fn with_service(&mut self, s: String) -> &mut Self {
         self.service = s;
         self
     }
+
+    #[inline]
+    pub fn with_domain(&mut self, d: String) -> &mut Self {
+        self.domain = Some(d);
+        self
+    }
+
+    #[inline]
+    pub fn with_action(&mut self, a: String) -> &mut Self {
+        self.actions.push(a);
+        self
+    }
+
+    #[inline]
+    pub fn with_prefix(&mut self, p: String) -> &mut Self {
+        self.prefix = p;
+        self
+    }
+
+    #[inline]
+    pub fn with_timeout(&mut self, t: Duration) -> &mut Self {
+        self.timeout = Some(t);
+        self
+    }
+
+    /// whether a watch result passes the service, prefix and action filters
+    pub fn matches(&self, r: &types::Result) -> bool {
+        let name = r.service.as_ref().map_or("", |s| s.name.as_str());
+        (self.service.is_empty() || name == self.service)
+            && name.starts_with(self.prefix.as_str())
+            && (self.actions.is_empty() || self.actions.contains(&r.action))
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct DeregisterOptions {}
+#[derive(Debug, Clone, Default)]
+pub struct DeregisterOptions {
+    /// overrides `Options.timeout` for this call
+    pub timeout: Option<Duration>,
+    /// the tenant the call belongs to, [`DEFAULT_DOMAIN`] when `None`.
+    /// Backends without tenants ignore it.
+    pub domain: Option<String>,
+}
 
-#[derive(Debug, Clone)]
-pub struct GetOptions {}
+impl DeregisterOptions {
+    #[inline]
+    pub fn new() -> Self {
+        DeregisterOptions {
+            timeout: None,
+            domain: None,
+        }
+    }
 
-#[derive(Debug, Clone)]
-pub struct ListOptions {}
+    #[inline]
+    pub fn with_timeout(&mut self, t: Duration) -> &mut Self {
+        self.timeout = Some(t);
+        self
+    }
+
+    #[inline]
+    pub fn with_domain(&mut self, d: String) -> &mut Self {
+        self.domain = Some(d);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GetOptions {
+    /// overrides `Options.timeout` for this call
+    pub timeout: Option<Duration>,
+    /// overrides `Options.strict_decode` for this call
+    pub strict: Option<bool>,
+    /// the tenant the call belongs to, [`DEFAULT_DOMAIN`] when `None`.
+    /// Backends without tenants ignore it.
+    pub domain: Option<String>,
+    /// only the services of this version
+    pub version: Option<String>,
+    /// only the nodes whose metadata holds every one of these pairs
+    pub metadata_filter: HashMap<String, String>,
+}
+
+impl GetOptions {
+    #[inline]
+    pub fn new() -> Self {
+        GetOptions {
+            timeout: None,
+            strict: None,
+            domain: None,
+            version: None,
+            metadata_filter: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn with_timeout(&mut self, t: Duration) -> &mut Self {
+        self.timeout = Some(t);
+        self
+    }
+
+    #[inline]
+    pub fn with_strict(&mut self, b: bool) -> &mut Self {
+        self.strict = Some(b);
+        self
+    }
+
+    #[inline]
+    pub fn with_domain(&mut self, d: String) -> &mut Self {
+        self.domain = Some(d);
+        self
+    }
+
+    #[inline]
+    pub fn with_version(&mut self, v: String) -> &mut Self {
+        self.version = Some(v);
+        self
+    }
+
+    #[inline]
+    pub fn with_metadata_filter(&mut self, k: String, v: String) -> &mut Self {
+        self.metadata_filter.insert(k, v);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    /// overrides `Options.timeout` for this call
+    pub timeout: Option<Duration>,
+    /// the tenant the call belongs to, [`DEFAULT_DOMAIN`] when `None`.
+    /// Backends without tenants ignore it.
+    pub domain: Option<String>,
+    /// only the services of this version
+    pub version: Option<String>,
+    /// only the nodes whose metadata holds every one of these pairs
+    pub metadata_filter: HashMap<String, String>,
+}
+
+impl ListOptions {
+    #[inline]
+    pub fn new() -> Self {
+        ListOptions {
+            timeout: None,
+            domain: None,
+            version: None,
+            metadata_filter: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn with_timeout(&mut self, t: Duration) -> &mut Self {
+        self.timeout = Some(t);
+        self
+    }
+
+    #[inline]
+    pub fn with_domain(&mut self, d: String) -> &mut Self {
+        self.domain = Some(d);
+        self
+    }
+
+    #[inline]
+    pub fn with_version(&mut self, v: String) -> &mut Self {
+        self.version = Some(v);
+        self
+    }
+
+    #[inline]
+    pub fn with_metadata_filter(&mut self, k: String, v: String) -> &mut Self {
+        self.metadata_filter.insert(k, v);
+        self
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct OpenapiAPIOptions {}
+
+#[cfg(test)]
+mod test {
+    use super::Options;
+
+    #[test]
+    fn test_debug_redacts_credentials() {
+        let mut opt = Options::new();
+        opt.with_auth("root", "s3cret");
+        let s = format!("{:?}", opt);
+        assert!(!s.contains("root") && !s.contains("s3cret"), "{}", s);
+        assert!(s.contains(r#"password: Some("***")"#), "{}", s);
+        assert!(format!("{:?}", Options::new()).contains("password: None"));
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+
+use errors::Result;
+
+use crate::Registry;
+
+/// the future building the backend of a plugin
+pub type Building =
+    Pin<Box<dyn Future<Output = Result<Box<dyn Registry + Sync + 'static>>> + Send>>;
+
+/// builds the backend of a plugin from the whole kind it was selected by,
+/// such as `mdns`, `mdns:<config>` or `mdns://<host>`
+pub type Factory = dyn Fn(String) -> Building + Send + Sync;
+
+/// name -> factory
+static PLUGINS: RwLock<BTreeMap<String, Arc<Factory>>> = RwLock::new(BTreeMap::new());
+
+/// makes the backend built by `factory` available by `name` to
+/// [`new_registry`](crate::new_registry), [`from_uri`](crate::from_uri) and
+/// so `VINE_REGISTRY`, replacing a plugin of the same name. The built in
+/// backends can't be replaced. Call it before the global registry is first
+/// used.
+///
+/// ```rust
+/// # use registry::{memory::MemoryRegistry, plugins, Registry};
+/// # async fn run() -> errors::Result<()> {
+/// plugins::register("mdns", |kind| async move {
+///     // parse `kind` and look the services up over multicast dns
+///     Ok(Box::new(MemoryRegistry::new(None)) as Box<dyn Registry + Sync>)
+/// });
+/// let registry = registry::new_registry("mdns").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn register<F, Fut>(name: &str, factory: F)
+where
+    F: Fn(String) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Box<dyn Registry + Sync + 'static>>> + Send + 'static,
+{
+    let factory: Arc<Factory> = Arc::new(move |kind| Box::pin(factory(kind)) as Building);
+    let mut plugins = PLUGINS.write().unwrap_or_else(|e| e.into_inner());
+    plugins.insert(name.to_string(), factory);
+}
+
+/// removes the plugin of `name`, false when there was none
+pub fn deregister(name: &str) -> bool {
+    let mut plugins = PLUGINS.write().unwrap_or_else(|e| e.into_inner());
+    plugins.remove(name).is_some()
+}
+
+/// the names of the registered plugins, sorted
+pub fn names() -> Vec<String> {
+    let plugins = PLUGINS.read().unwrap_or_else(|e| e.into_inner());
+    plugins.keys().cloned().collect()
+}
+
+/// builds the backend of the plugin `name` for `kind`, `None` when no
+/// plugin of the name is registered
+pub(crate) async fn build(
+    name: &str,
+    kind: &str,
+) -> Option<Result<Box<dyn Registry + Sync + 'static>>> {
+    let factory = {
+        let plugins = PLUGINS.read().unwrap_or_else(|e| e.into_inner());
+        plugins.get(name).cloned()
+    }?;
+    Some(factory(kind.to_string()).await)
+}
+
+#[cfg(test)]
+mod test {
+    use errors::Result;
+
+    use super::{deregister, names, register};
+    use crate::{from_uri, memory::MemoryRegistry, new_registry, Registry};
+
+    #[tokio::test]
+    async fn test_plugins() -> Result<()> {
+        register("test-plugin", |kind| async move {
+            assert!(kind.starts_with("test-plugin"), "{}", kind);
+            Ok(Box::new(MemoryRegistry::new(None)) as Box<dyn Registry + Sync>)
+        });
+        assert!(names().contains(&"test-plugin".to_string()));
+
+        for kind in &["test-plugin", "test-plugin:config", "test-plugin://host"] {
+            let r = new_registry(kind).await?;
+            assert_eq!(r.string().await, "memory");
+        }
+        assert!(from_uri("test-plugin://host").await.is_ok());
+
+        // the built in backends win
+        register("memory", |_| async move { errors::bail!("replaced") });
+        assert!(new_registry("memory").await.is_ok());
+        assert!(deregister("memory"));
+
+        assert!(deregister("test-plugin"));
+        assert!(!deregister("test-plugin"));
+        assert!(new_registry("test-plugin").await.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,307 @@
+//! the protobuf messages of the vine registry, wire compatible with the
+//! registry.proto of the vine Go project, along with the gRPC client
+//! and server of its `Registry` service
+
+use std::convert::{TryFrom, TryInto};
+
+use errors::Status;
+
+use crate::types;
+
+tonic::include_proto!("registry");
+
+fn invalid(detail: String) -> Status {
+    Status::bad_request("io.vine.registry".to_string(), detail)
+}
+
+impl From<&types::Service> for Service {
+    fn from(s: &types::Service) -> Self {
+        Service {
+            name: s.name.clone(),
+            version: s.version.clone(),
+            metadata: s.metadata.clone(),
+            endpoints: s.endpoints.iter().map(Endpoint::from).collect(),
+            nodes: s.nodes.iter().map(Node::from).collect(),
+            options: s.options.as_ref().map(|o| Options {
+                ttl: o.ttl,
+                domain: String::new(),
+            }),
+        }
+    }
+}
+
+/// the openapi documents of a service are not part of the proto,
+/// `apis` is always `None`
+impl TryFrom<Service> for types::Service {
+    type Error = Status;
+
+    fn try_from(s: Service) -> std::result::Result<Self, Self::Error> {
+        Ok(types::Service {
+            name: s.name,
+            version: s.version,
+            metadata: s.metadata,
+            endpoints: s
+                .endpoints
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<std::result::Result<_, _>>()?,
+            nodes: s
+                .nodes
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<std::result::Result<_, _>>()?,
+            options: s.options.map(|o| types::Options { ttl: o.ttl }),
+            apis: None,
+        })
+    }
+}
+
+impl From<&types::Node> for Node {
+    fn from(n: &types::Node) -> Self {
+        Node {
+            id: n.id.clone(),
+            address: n.address.clone(),
+            port: n.port,
+            metadata: n.metadata.clone(),
+        }
+    }
+}
+
+impl TryFrom<Node> for types::Node {
+    type Error = Status;
+
+    fn try_from(n: Node) -> std::result::Result<Self, Self::Error> {
+        Ok(types::Node {
+            id: n.id,
+            address: n.address,
+            port: n.port,
+            metadata: n.metadata,
+        })
+    }
+}
+
+impl From<&types::Endpoint> for Endpoint {
+    fn from(e: &types::Endpoint) -> Self {
+        Endpoint {
+            name: e.name.clone(),
+            request: e.request.as_ref().map(Value::from),
+            response: e.response.as_ref().map(Value::from),
+            metadata: e.metadata.clone(),
+        }
+    }
+}
+
+impl TryFrom<Endpoint> for types::Endpoint {
+    type Error = Status;
+
+    fn try_from(e: Endpoint) -> std::result::Result<Self, Self::Error> {
+        Ok(types::Endpoint {
+            name: e.name,
+            request: e.request.map(TryInto::try_into).transpose()?,
+            response: e.response.map(TryInto::try_into).transpose()?,
+            metadata: e.metadata,
+        })
+    }
+}
+
+impl From<&types::Value> for Value {
+    fn from(v: &types::Value) -> Self {
+        Value {
+            name: v.name.clone(),
+            r#type: v.rtype.clone(),
+            values: v.values.iter().map(Value::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<Value> for types::Value {
+    type Error = Status;
+
+    fn try_from(v: Value) -> std::result::Result<Self, Self::Error> {
+        Ok(types::Value {
+            name: v.name,
+            rtype: v.r#type,
+            values: v
+                .values
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<std::result::Result<_, _>>()?,
+        })
+    }
+}
+
+impl From<&types::Result> for Result {
+    fn from(r: &types::Result) -> Self {
+        Result {
+            action: r.action.clone(),
+            service: r.service.as_ref().map(Service::from),
+            timestamp: r.timestamp,
+        }
+    }
+}
+
+/// the action must be one of create, update or delete
+impl TryFrom<Result> for types::Result {
+    type Error = Status;
+
+    fn try_from(r: Result) -> std::result::Result<Self, Self::Error> {
+        match r.action.as_str() {
+            "create" | "update" | "delete" => {}
+            action => return Err(invalid(format!("unknown watch action '{}'", action))),
+        }
+
+        Ok(types::Result {
+            action: r.action,
+            service: r.service.map(TryInto::try_into).transpose()?,
+            timestamp: r.timestamp,
+        })
+    }
+}
+
+impl From<&types::Event> for Event {
+    fn from(e: &types::Event) -> Self {
+        Event {
+            id: e.id.clone(),
+            r#type: e.r#type,
+            timestamp: e.timestamp,
+            service: e.service.as_ref().map(Service::from),
+        }
+    }
+}
+
+/// the type must be a known [`EventType`]
+impl TryFrom<Event> for types::Event {
+    type Error = Status;
+
+    fn try_from(e: Event) -> std::result::Result<Self, Self::Error> {
+        if EventType::from_i32(e.r#type).is_none() {
+            return Err(invalid(format!("unknown event type {}", e.r#type)));
+        }
+
+        Ok(types::Event {
+            id: e.id,
+            r#type: e.r#type,
+            timestamp: e.timestamp,
+            service: e.service.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
+
+    use prost::Message;
+
+    use super::{EventType, Service};
+    use crate::types;
+
+    fn metadata(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn service() -> types::Service {
+        let value = |name: &str, rtype: &str, values| types::Value {
+            name: name.to_string(),
+            rtype: rtype.to_string(),
+            values,
+        };
+
+        types::Service {
+            name: "io.vine.helloworld".to_string(),
+            version: "v1.0.0".to_string(),
+            metadata: metadata(&[("env", "test"), ("zone", "a")]),
+            endpoints: vec![
+                types::Endpoint {
+                    name: "Helloworld.Call".to_string(),
+                    request: Some(value(
+                        "Request",
+                        "Request",
+                        vec![value("name", "string", vec![])],
+                    )),
+                    response: Some(value(
+                        "Response",
+                        "Response",
+                        vec![value("items", "Item", vec![value("id", "int64", vec![])])],
+                    )),
+                    metadata: metadata(&[("stream", "false")]),
+                },
+                types::Endpoint {
+                    name: "Helloworld.Ping".to_string(),
+                    request: None,
+                    response: None,
+                    metadata: HashMap::new(),
+                },
+            ],
+            nodes: vec![
+                types::Node {
+                    id: "1".to_string(),
+                    address: "192.168.1.111".to_string(),
+                    port: 11101,
+                    metadata: metadata(&[("protocol", "grpc")]),
+                },
+                types::Node {
+                    id: "2".to_string(),
+                    address: "192.168.1.112".to_string(),
+                    port: 11101,
+                    metadata: HashMap::new(),
+                },
+            ],
+            options: Some(types::Options { ttl: 30 }),
+            apis: None,
+        }
+    }
+
+    #[test]
+    fn test_service_round_trip() {
+        let s = service();
+        let p = Service::from(&s);
+        assert_eq!(
+            p.endpoints[0].request.as_ref().unwrap().values[0].r#type,
+            "string"
+        );
+        assert_eq!(types::Service::try_from(p.clone()).unwrap(), s);
+
+        // and through the wire
+        let decoded = Service::decode(p.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(types::Service::try_from(decoded).unwrap(), s);
+
+        let empty = types::Service::new();
+        assert_eq!(
+            types::Service::try_from(Service::from(&empty)).unwrap(),
+            empty
+        );
+    }
+
+    #[test]
+    fn test_result_and_event() {
+        let mut r = types::Result::new();
+        r.set_action("update".to_string());
+        r.set_service(service());
+        r.set_timestamp(1633024800);
+        let p = super::Result::from(&r);
+        assert_eq!(types::Result::try_from(p.clone()).unwrap(), r);
+
+        let bad = super::Result {
+            action: "rename".to_string(),
+            ..p
+        };
+        assert!(types::Result::try_from(bad).is_err());
+
+        let e = types::Event {
+            id: "1".to_string(),
+            r#type: EventType::Delete as i32,
+            timestamp: 1633024800,
+            service: Some(service()),
+        };
+        let p = super::Event::from(&e);
+        assert_eq!(types::Event::try_from(p.clone()).unwrap(), e);
+
+        let bad = super::Event { r#type: 7, ..p };
+        assert!(types::Event::try_from(bad).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, Once};
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::options::{DeregisterOptions, RegisterOptions};
+use crate::types::Service;
+use crate::SharedRegistry;
+
+/// how long the deregistrations on a signal may take before the process exits
+const SIGNAL_GRACE: Duration = Duration::from_secs(5);
+
+/// a live registration to deregister on a signal
+struct Entry {
+    id: u64,
+    registry: SharedRegistry,
+    service: Service,
+    opt: DeregisterOptions,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static LIVE: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+static SIGNALS: Once = Once::new();
+
+fn live() -> MutexGuard<'static, Vec<Entry>> {
+    LIVE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Registration keeps a service registered until it is dropped or
+/// [`deregistered`](Registration::deregister).
+///
+/// Dropping it deregisters the service in the background, on a best effort
+/// basis as the runtime may be shutting down; await `deregister` where that
+/// matters. The first registration also handles SIGTERM and ctrl-c: every
+/// live registration is deregistered and the process exits, as the handler
+/// replaces the default behaviour of the signals.
+///
+/// ```rust
+/// # use registry::{registration::Registration, types::Service};
+/// # async fn run(s: Service) -> errors::Result<()> {
+/// let registration = registry::register_guarded(&s, None).await?;
+/// // serve until shutdown
+/// registration.deregister().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[must_use = "the service is deregistered when the registration is dropped"]
+pub struct Registration {
+    id: u64,
+    registry: SharedRegistry,
+    service: Service,
+    opt: DeregisterOptions,
+    done: bool,
+}
+
+impl Registration {
+    /// registers `s` with `registry`, the deregistration uses the domain
+    /// and timeout of `opt`
+    pub async fn new(
+        registry: SharedRegistry,
+        s: &Service,
+        opt: Option<RegisterOptions>,
+    ) -> Result<Self> {
+        let mut dopt = DeregisterOptions::new();
+        if let Some(opt) = &opt {
+            dopt.timeout = opt.timeout;
+            dopt.domain = opt.domain.clone();
+        }
+        registry.lock().await.register(s, opt).await?;
+
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        live().push(Entry {
+            id,
+            registry: registry.clone(),
+            service: s.clone(),
+            opt: dopt.clone(),
+        });
+        SIGNALS.call_once(|| {
+            tokio::spawn(on_signal());
+        });
+
+        Ok(Registration {
+            id,
+            registry,
+            service: s.clone(),
+            opt: dopt,
+            done: false,
+        })
+    }
+
+    /// the registered service
+    pub fn service(&self) -> &Service {
+        &self.service
+    }
+
+    /// deregisters the service now
+    pub async fn deregister(mut self) -> Result<()> {
+        self.done = true;
+        live().retain(|e| e.id != self.id);
+        let registry = self.registry.lock().await;
+        registry
+            .deregister(&self.service, Some(self.opt.clone()))
+            .await
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        live().retain(|e| e.id != self.id);
+
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                logger::warn!(
+                    "{} dropped outside of a runtime, not deregistered",
+                    self.service.name
+                );
+                return;
+            }
+        };
+        let (registry, s, opt) = (
+            self.registry.clone(),
+            self.service.clone(),
+            self.opt.clone(),
+        );
+        handle.spawn(async move {
+            let registry = registry.lock().await;
+            if let Err(e) = registry.deregister(&s, Some(opt)).await {
+                logger::error!("deregister {} on drop failed: {}", s.name, e);
+            }
+        });
+    }
+}
+
+/// waits for SIGTERM or ctrl-c, deregisters every live registration and
+/// exits with the conventional status of the signal
+async fn on_signal() {
+    let code = wait_signal().await;
+    let entries: Vec<Entry> = live().drain(..).collect();
+    logger::info!("deregistering {} services on signal", entries.len());
+
+    let deregister = async {
+        for e in entries {
+            let registry = e.registry.lock().await;
+            if let Err(err) = registry.deregister(&e.service, Some(e.opt)).await {
+                logger::error!("deregister {} on signal failed: {}", e.service.name, err);
+            }
+        }
+    };
+    if tokio::time::timeout(SIGNAL_GRACE, deregister)
+        .await
+        .is_err()
+    {
+        logger::warn!("deregistering on signal timed out after {:?}", SIGNAL_GRACE);
+    }
+    std::process::exit(code);
+}
+
+#[cfg(unix)]
+async fn wait_signal() -> i32 {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut term = match signal(SignalKind::terminate()) {
+        Ok(term) => term,
+        Err(e) => {
+            logger::warn!("could not handle SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return 130;
+        }
+    };
+    tokio::select! {
+        _ = term.recv() => 143,
+        _ = tokio::signal::ctrl_c() => 130,
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_signal() -> i32 {
+    let _ = tokio::signal::ctrl_c().await;
+    130
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use errors::Result;
+    use tokio::sync::Mutex;
+
+    use super::{live, Registration};
+    use crate::{
+        memory::MemoryRegistry,
+        types::{Node, Service},
+        Registry, SharedRegistry,
+    };
+
+    fn service(id: &str) -> Service {
+        Service {
+            name: "io.vine.helloworld".to_string(),
+            version: "v1.0.0".to_string(),
+            nodes: vec![Node {
+                id: id.to_string(),
+                address: "127.0.0.1".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registration() -> Result<()> {
+        let inner = MemoryRegistry::new(None);
+        let shared: SharedRegistry = Arc::new(Mutex::new(Box::new(inner.clone())));
+        let name = "io.vine.helloworld".to_string();
+
+        let first = Registration::new(shared.clone(), &service("1"), None).await?;
+        let second = Registration::new(shared.clone(), &service("2"), None).await?;
+        let services = inner.get_service(name.clone(), None).await?;
+        assert_eq!(services[0].nodes.len(), 2);
+
+        first.deregister().await?;
+        let services = inner.get_service(name.clone(), None).await?;
+        assert_eq!(services[0].nodes[0].id, "2");
+
+        // dropping deregisters in the background
+        drop(second);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(inner.get_service(name, None).await.is_err());
+        assert!(live().is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,348 @@
+mod random;
+mod round_robin;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use errors::{Result, Status};
+
+pub use self::random::Random;
+pub use self::round_robin::RoundRobin;
+use crate::types::{Node, Service};
+use crate::SharedRegistry;
+
+/// Selector picks a node of a service for every call, skipping the
+/// nodes recently marked as failed
+#[async_trait]
+pub trait Selector: Send + Sync {
+    /// a node of `service` which survives the filters of `opts`,
+    /// `Status::not_found` when there is none
+    async fn select(&self, service: &str, opts: Option<SelectOptions>) -> Result<Node>;
+    /// reports the outcome of a call to `node`, an error blacklists
+    /// the node for `SelectorOptions.cooldown`
+    fn mark(&self, service: &str, node: &Node, err: Option<&Status>);
+    /// forgets the services read and the nodes marked of `service`, the
+    /// next selection reads the registry again
+    fn reset(&self, service: &str);
+}
+
+#[derive(Debug, Clone)]
+pub struct SelectorOptions {
+    /// how long the services read from the registry are reused
+    pub ttl: Duration,
+    /// how long a node marked as failed is not selected
+    pub cooldown: Duration,
+}
+
+impl Default for SelectorOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelectorOptions {
+    #[inline]
+    pub fn new() -> Self {
+        SelectorOptions {
+            ttl: Duration::from_secs(60),
+            cooldown: Duration::from_secs(30),
+        }
+    }
+
+    #[inline]
+    pub fn with_ttl(&mut self, t: Duration) -> &mut Self {
+        self.ttl = t;
+        self
+    }
+
+    #[inline]
+    pub fn with_cooldown(&mut self, t: Duration) -> &mut Self {
+        self.cooldown = t;
+        self
+    }
+}
+
+/// Filter narrows the nodes a [`Selector`] may pick
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// only nodes of this service version
+    Version(String),
+    /// only nodes carrying this metadata key and value
+    Metadata(String, String),
+}
+
+impl Filter {
+    fn version(&self, s: &Service) -> bool {
+        match self {
+            Filter::Version(v) => &s.version == v,
+            Filter::Metadata(..) => true,
+        }
+    }
+
+    fn node(&self, n: &Node) -> bool {
+        match self {
+            Filter::Version(_) => true,
+            Filter::Metadata(k, v) => n.metadata.get(k) == Some(v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SelectOptions {
+    /// every filter must match for a node to be selected
+    pub filters: Vec<Filter>,
+}
+
+impl SelectOptions {
+    #[inline]
+    pub fn new() -> Self {
+        SelectOptions { filters: vec![] }
+    }
+
+    #[inline]
+    pub fn with_version(&mut self, v: String) -> &mut Self {
+        self.filters.push(Filter::Version(v));
+        self
+    }
+
+    #[inline]
+    pub fn with_metadata(&mut self, k: String, v: String) -> &mut Self {
+        self.filters.push(Filter::Metadata(k, v));
+        self
+    }
+}
+
+/// the services cache and the blacklist shared by the selectors
+struct Nodes {
+    registry: SharedRegistry,
+    options: SelectorOptions,
+    /// service name -> read at, services
+    cache: Mutex<HashMap<String, (Instant, Vec<Service>)>>,
+    /// (service name, node id) -> blacklisted until
+    blacklist: Mutex<HashMap<(String, String), Instant>>,
+}
+
+impl Nodes {
+    fn new(registry: SharedRegistry, opts: Option<SelectorOptions>) -> Self {
+        Nodes {
+            registry,
+            options: opts.unwrap_or_default(),
+            cache: Mutex::new(HashMap::new()),
+            blacklist: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn services(&self, service: &str) -> Result<Vec<Service>> {
+        {
+            let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some((at, services)) = cache.get(service) {
+                if at.elapsed() < self.options.ttl {
+                    return Ok(services.clone());
+                }
+            }
+        }
+
+        let services = {
+            let r = self.registry.lock().await;
+            r.get_service(service.to_string(), None).await?
+        };
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.insert(service.to_string(), (Instant::now(), services.clone()));
+        Ok(services)
+    }
+
+    /// the nodes of `service` which pass the filters and aren't blacklisted
+    async fn candidates(&self, service: &str, opts: Option<SelectOptions>) -> Result<Vec<Node>> {
+        let filters = opts.unwrap_or_default().filters;
+        let services = self.services(service).await?;
+
+        let now = Instant::now();
+        let mut blacklist = self.blacklist.lock().unwrap_or_else(|e| e.into_inner());
+        blacklist.retain(|_, until| *until > now);
+
+        let nodes: Vec<Node> = services
+            .into_iter()
+            .filter(|s| filters.iter().all(|f| f.version(s)))
+            .flat_map(|s| s.nodes)
+            .filter(|n| filters.iter().all(|f| f.node(n)))
+            .filter(|n| !blacklist.contains_key(&(service.to_string(), n.id.clone())))
+            .collect();
+
+        if nodes.is_empty() {
+            return Err(Status::not_found(
+                "io.vine.registry".to_string(),
+                format!("no node of service {} is available", service),
+            )
+            .into());
+        }
+        Ok(nodes)
+    }
+
+    fn mark(&self, service: &str, node: &Node, err: Option<&Status>) {
+        let key = (service.to_string(), node.id.clone());
+        let mut blacklist = self.blacklist.lock().unwrap_or_else(|e| e.into_inner());
+        match err {
+            Some(_) => {
+                blacklist.insert(key, Instant::now() + self.options.cooldown);
+            }
+            None => {
+                blacklist.remove(&key);
+            }
+        }
+    }
+
+    fn reset(&self, service: &str) {
+        self.cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(service);
+        self.blacklist
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(s, _), _| s != service);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use errors::{Code, Result, Status};
+    use tokio::sync::Mutex;
+
+    use super::{Random, RoundRobin, SelectOptions, Selector, SelectorOptions};
+    use crate::{
+        memory::MemoryRegistry,
+        types::{Node, Service},
+        Registry, SharedRegistry,
+    };
+
+    fn node(id: &str, zone: &str) -> Node {
+        let mut metadata = HashMap::new();
+        metadata.insert("zone".to_string(), zone.to_string());
+        Node {
+            id: id.to_string(),
+            address: "127.0.0.1".to_string(),
+            port: 11101,
+            metadata,
+        }
+    }
+
+    async fn registry() -> Result<SharedRegistry> {
+        let r = MemoryRegistry::new(None);
+        for (version, nodes) in &[
+            ("v1", vec![node("1", "a"), node("2", "b")]),
+            ("v2", vec![node("3", "a")]),
+        ] {
+            let s = Service {
+                name: "io.vine.helloworld".to_string(),
+                version: version.to_string(),
+                nodes: nodes.clone(),
+                ..Service::new()
+            };
+            r.register(&s, None).await?;
+        }
+        Ok(Arc::new(Mutex::new(Box::new(r))))
+    }
+
+    async fn ids(s: &dyn Selector, n: usize, opts: Option<SelectOptions>) -> Result<Vec<String>> {
+        let mut ids = vec![];
+        for _ in 0..n {
+            ids.push(s.select("io.vine.helloworld", opts.clone()).await?.id);
+        }
+        Ok(ids)
+    }
+
+    #[tokio::test]
+    async fn test_round_robin() -> Result<()> {
+        let s = RoundRobin::new(registry().await?, None);
+        assert_eq!(ids(&s, 4, None).await?, vec!["1", "2", "3", "1"]);
+
+        let mut opts = SelectOptions::new();
+        opts.with_version("v1".to_string());
+        assert!(ids(&s, 10, Some(opts.clone()))
+            .await?
+            .iter()
+            .all(|id| id != "3"));
+
+        opts.with_metadata("zone".to_string(), "b".to_string());
+        assert_eq!(ids(&s, 2, Some(opts)).await?, vec!["2", "2"]);
+
+        let mut opts = SelectOptions::new();
+        opts.with_metadata("zone".to_string(), "c".to_string());
+        let e = s
+            .select("io.vine.helloworld", Some(opts))
+            .await
+            .unwrap_err();
+        assert_eq!(e.downcast::<Status>()?.code(), Code::NotFound);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_random() -> Result<()> {
+        let s = Random::new(registry().await?, None);
+        let mut opts = SelectOptions::new();
+        opts.with_metadata("zone".to_string(), "a".to_string());
+        let got = ids(&s, 20, Some(opts)).await?;
+        assert!(got.iter().all(|id| id == "1" || id == "3"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_blacklist() -> Result<()> {
+        let mut sopts = SelectorOptions::new();
+        sopts.with_cooldown(Duration::from_millis(100));
+        let s = RoundRobin::new(registry().await?, Some(sopts));
+
+        let failed = Status::internal_server_error("io.vine.helloworld", "broken");
+        s.mark("io.vine.helloworld", &node("2", "b"), Some(&failed));
+        assert!(ids(&s, 6, None).await?.iter().all(|id| id != "2"));
+
+        // marking every node fails the selection
+        s.mark("io.vine.helloworld", &node("1", "a"), Some(&failed));
+        s.mark("io.vine.helloworld", &node("3", "a"), Some(&failed));
+        assert!(s.select("io.vine.helloworld", None).await.is_err());
+
+        // a success clears the mark before the cooldown
+        s.mark("io.vine.helloworld", &node("3", "a"), None);
+        assert_eq!(ids(&s, 2, None).await?, vec!["3", "3"]);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let mut got = ids(&s, 3, None).await?;
+        got.sort();
+        assert_eq!(got, vec!["1", "2", "3"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reset() -> Result<()> {
+        let registry = registry().await?;
+        let s = RoundRobin::new(registry.clone(), None);
+        assert_eq!(ids(&s, 3, None).await?, vec!["1", "2", "3"]);
+
+        let failed = Status::internal_server_error("io.vine.helloworld", "broken");
+        s.mark("io.vine.helloworld", &node("2", "b"), Some(&failed));
+        let v3 = Service {
+            name: "io.vine.helloworld".to_string(),
+            version: "v3".to_string(),
+            nodes: vec![node("4", "c")],
+            ..Service::new()
+        };
+        registry.lock().await.register(&v3, None).await?;
+        // the services read before are reused until the ttl
+        assert!(ids(&s, 6, None).await?.iter().all(|id| id != "4"));
+
+        s.reset("io.vine.helloworld");
+        let mut got = ids(&s, 4, None).await?;
+        got.sort();
+        assert_eq!(got, vec!["1", "2", "3", "4"]);
+
+        Ok(())
+    }
+}
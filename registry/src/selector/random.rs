@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+use errors::{Result, Status};
+use rand::Rng;
+
+use super::{Nodes, SelectOptions, Selector, SelectorOptions};
+use crate::types::Node;
+use crate::SharedRegistry;
+
+/// the [`Selector`] picking any of the available nodes of a service
+pub struct Random {
+    nodes: Nodes,
+}
+
+impl Random {
+    pub fn new(registry: SharedRegistry, opts: Option<SelectorOptions>) -> Self {
+        Random {
+            nodes: Nodes::new(registry, opts),
+        }
+    }
+}
+
+#[async_trait]
+impl Selector for Random {
+    async fn select(&self, service: &str, opts: Option<SelectOptions>) -> Result<Node> {
+        let mut nodes = self.nodes.candidates(service, opts).await?;
+        let i = rand::thread_rng().gen_range(0..nodes.len());
+        Ok(nodes.swap_remove(i))
+    }
+
+    fn mark(&self, service: &str, node: &Node, err: Option<&Status>) {
+        self.nodes.mark(service, node, err)
+    }
+
+    fn reset(&self, service: &str) {
+        self.nodes.reset(service)
+    }
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use errors::{Result, Status};
+
+use super::{Nodes, SelectOptions, Selector, SelectorOptions};
+use crate::types::Node;
+use crate::SharedRegistry;
+
+/// the [`Selector`] rotating through the available nodes of a service
+pub struct RoundRobin {
+    nodes: Nodes,
+    /// service name -> the count of selections
+    next: Mutex<HashMap<String, usize>>,
+}
+
+impl RoundRobin {
+    pub fn new(registry: SharedRegistry, opts: Option<SelectorOptions>) -> Self {
+        RoundRobin {
+            nodes: Nodes::new(registry, opts),
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl Selector for RoundRobin {
+    async fn select(&self, service: &str, opts: Option<SelectOptions>) -> Result<Node> {
+        let mut nodes = self.nodes.candidates(service, opts).await?;
+
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+        let i = next.entry(service.to_string()).or_insert(0);
+        let node = nodes.swap_remove(*i % nodes.len());
+        *i = i.wrapping_add(1);
+        Ok(node)
+    }
+
+    fn mark(&self, service: &str, node: &Node, err: Option<&Status>) {
+        self.nodes.mark(service, node, err)
+    }
+
+    fn reset(&self, service: &str) {
+        self.nodes.reset(service);
+        self.next
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(service);
+    }
+}
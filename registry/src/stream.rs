@@ -0,0 +1,143 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+use crate::error::Result;
+use crate::{types, Watcher};
+
+type Next = Pin<Box<dyn Future<Output = Result<types::Result>> + Send>>;
+
+/// WatchStream adapts a [`Watcher`] into a [`Stream`] of its results.
+///
+/// The stream yields the error the watch ends with and then ends.
+/// Dropping the stream does not stop the watch, call [`WatchStream::stop`].
+///
+/// ```rust
+/// # use registry::{stream::WatchStream, Registry};
+/// # use tokio_stream::StreamExt;
+/// # async fn run(registry: &(dyn Registry + Sync), mut shutdown: tokio::sync::oneshot::Receiver<()>) -> errors::Result<()> {
+/// let mut events = WatchStream::new(registry.watch(None).await?);
+/// loop {
+///     tokio::select! {
+///         Some(event) = events.next() => println!("{:?}", event?),
+///         _ = &mut shutdown => break,
+///     }
+/// }
+/// events.stop().await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct WatchStream {
+    watcher: Arc<dyn Watcher + Send + Sync>,
+    next: Option<Next>,
+    done: bool,
+}
+
+impl WatchStream {
+    pub fn new(watcher: Box<dyn Watcher + Send + Sync>) -> Self {
+        WatchStream {
+            watcher: Arc::from(watcher),
+            next: None,
+            done: false,
+        }
+    }
+
+    /// stops the underlying watch, the stream ends after the pending result
+    pub async fn stop(&self) {
+        self.watcher.stop().await;
+    }
+}
+
+impl From<Box<dyn Watcher + Send + Sync>> for WatchStream {
+    fn from(watcher: Box<dyn Watcher + Send + Sync>) -> Self {
+        WatchStream::new(watcher)
+    }
+}
+
+impl Stream for WatchStream {
+    type Item = Result<types::Result>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        let watcher = self.watcher.clone();
+        let next = self
+            .next
+            .get_or_insert_with(|| Box::pin(async move { watcher.next().await }));
+
+        match next.as_mut().poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(r) => {
+                self.next = None;
+                self.done = r.is_err();
+                Poll::Ready(Some(r))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use errors::Result;
+    use tokio_stream::StreamExt;
+
+    use super::WatchStream;
+    use crate::{
+        memory::MemoryRegistry,
+        types::{Node, Service},
+        Registry,
+    };
+
+    fn service(id: &str) -> Service {
+        Service {
+            name: "io.vine.helloworld".to_string(),
+            version: "v1.0.0".to_string(),
+            nodes: vec![Node {
+                id: id.to_string(),
+                address: "127.0.0.1".to_string(),
+                port: 11101,
+                metadata: HashMap::new(),
+            }],
+            ..Service::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_stream() -> Result<()> {
+        let r = MemoryRegistry::new(None);
+        let mut events = WatchStream::new(r.watch(None).await?);
+
+        let writer = r.clone();
+        tokio::spawn(async move {
+            for i in 0..3 {
+                writer
+                    .register(&service(&i.to_string()), None)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        let ids: Vec<String> = (&mut events)
+            .take(3)
+            .map(|r| r.unwrap().service.unwrap().nodes[0].id.clone())
+            .collect()
+            .await;
+        assert_eq!(ids, vec!["0", "1", "2"]);
+
+        // the stream ends with the error of the stopped watch
+        events.stop().await;
+        let last = tokio::time::timeout(Duration::from_secs(1), events.next()).await?;
+        assert!(matches!(last, Some(Err(_))));
+        assert!(events.next().await.is_none());
+
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
+use errors::{MultiStatus, Status};
 use serde::{Deserialize, Serialize};
 use std::collections::{hash_map::DefaultHasher, HashMap};
 use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
 
 /// Service represents a vine service
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -14,6 +16,12 @@ pub struct Service {
     pub apis: Option<OpenApi>,
 }
 
+impl Default for Service {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Service {
     pub fn new() -> Self {
         Service {
@@ -26,10 +34,34 @@ impl Service {
             apis: None,
         }
     }
+
+    /// checks the service and all of its nodes can be registered, the error
+    /// is a [`MultiStatus`] holding a bad request for every violation
+    pub fn validate(&self) -> errors::Result<()> {
+        let mut errs = MultiStatus::new();
+        self.violations(&mut errs);
+        for node in &self.nodes {
+            node.violations(&mut errs);
+        }
+        into_result(errs)
+    }
+
+    fn violations(&self, errs: &mut MultiStatus) {
+        if self.name.is_empty() {
+            errs.push(violation("service name is empty"));
+        }
+        if self.name.contains('/') {
+            errs.push(violation(format!(
+                "service name {} must not contain '/', it is stored as '-'",
+                self.name
+            )));
+        }
+        metadata_violations(&format!("service {}", self.name), &self.metadata, errs);
+    }
 }
 
 /// Node represents the node the service is on
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Node {
     pub id: String,
     pub address: String,
@@ -37,16 +69,103 @@ pub struct Node {
     pub metadata: HashMap<String, String>,
 }
 
+impl Node {
+    /// checks the node can be registered, the error is a [`MultiStatus`]
+    /// holding a bad request for every violation
+    pub fn validate(&self) -> errors::Result<()> {
+        let mut errs = MultiStatus::new();
+        self.violations(&mut errs);
+        into_result(errs)
+    }
+
+    fn violations(&self, errs: &mut MultiStatus) {
+        if self.id.is_empty() {
+            errs.push(violation("node id is empty"));
+        }
+        if self.id.contains('/') {
+            errs.push(violation(format!(
+                "node id {} must not contain '/', it is stored as '-'",
+                self.id
+            )));
+        }
+        if !valid_address(&self.address) {
+            errs.push(violation(format!(
+                "node {} address '{}' is not a host or ip",
+                self.id, self.address
+            )));
+        }
+        if !(1..=65535).contains(&self.port) {
+            errs.push(violation(format!(
+                "node {} port {} is out of range 1..=65535",
+                self.id, self.port
+            )));
+        }
+        metadata_violations(&format!("node {}", self.id), &self.metadata, errs);
+    }
+}
+
+fn violation(detail: impl Into<String>) -> Status {
+    Status::bad_request("io.vine.registry".to_string(), detail.into())
+}
+
+fn into_result(errs: MultiStatus) -> errors::Result<()> {
+    if errs.is_empty() {
+        Ok(())
+    } else {
+        Err(errs.into())
+    }
+}
+
+fn metadata_violations(owner: &str, metadata: &HashMap<String, String>, errs: &mut MultiStatus) {
+    let mut keys: Vec<&String> = metadata.keys().collect();
+    keys.sort();
+    for k in keys {
+        if k.is_empty() || k.chars().any(char::is_whitespace) {
+            errs.push(violation(format!(
+                "{} metadata key '{}' is empty or contains whitespace",
+                owner, k
+            )));
+        }
+    }
+}
+
+/// an ip, optionally in brackets, or a RFC 1123 host name
+fn valid_address(addr: &str) -> bool {
+    let ip = addr.trim_start_matches('[').trim_end_matches(']');
+    if ip.parse::<IpAddr>().is_ok() {
+        return true;
+    }
+
+    !addr.is_empty()
+        && addr.len() <= 253
+        && addr.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
 impl Hash for Node {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    fn hash<H: Hasher>(&self, state: &mut H) {
         self.id.hash(state);
         self.address.hash(state);
         self.port.hash(state);
-        let mut hasher = DefaultHasher::new();
-        for (k, v) in &self.metadata {
-            hasher.write(k.as_bytes());
-            hasher.write(v.as_bytes());
-        }
+        // the order of a HashMap is random, hash the entries by key
+        let mut metadata: Vec<(&String, &String)> = self.metadata.iter().collect();
+        metadata.sort();
+        metadata.hash(state);
+    }
+}
+
+impl Node {
+    /// a digest of everything registered for the node, used to tell
+    /// whether a registration changed anything
+    pub fn content_hash(&self) -> u64 {
+        let mut h = DefaultHasher::new();
+        self.hash(&mut h);
+        h.finish()
     }
 }
 
@@ -83,6 +202,12 @@ pub struct Result {
     pub timestamp: i64,
 }
 
+impl Default for Result {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Result {
     pub fn new() -> Self {
         Result {
@@ -92,15 +217,15 @@ impl Result {
         }
     }
 
-    pub fn set_action(&mut self, action: String)  {
+    pub fn set_action(&mut self, action: String) {
         self.action = action;
     }
 
-    pub fn set_service(&mut self, service: Service)  {
+    pub fn set_service(&mut self, service: Service) {
         self.service = Some(service);
     }
 
-    pub fn set_timestamp(&mut self, timestamp: i64)  {
+    pub fn set_timestamp(&mut self, timestamp: i64) {
         self.timestamp = timestamp;
     }
 }
@@ -323,3 +448,137 @@ pub struct Schema {
     pub parameters: Vec<PathParameters>,
     pub additional_properties: Option<Box<Schema>>,
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use errors::{Code, MultiStatus};
+
+    use super::{Node, Service};
+
+    fn node() -> Node {
+        Node {
+            id: "1".to_string(),
+            address: "192.168.1.111".to_string(),
+            port: 11101,
+            metadata: HashMap::new(),
+        }
+    }
+
+    fn violations(r: errors::Result<()>) -> Vec<String> {
+        let errs = r.unwrap_err().downcast::<MultiStatus>().unwrap();
+        errs.iter()
+            .map(|s| {
+                assert_eq!(s.code(), Code::BadRequest);
+                s.detail().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_node_validate() {
+        assert!(node().validate().is_ok());
+        for address in &[
+            "localhost",
+            "io-vine.example.com",
+            "::1",
+            "[::1]",
+            "10.0.0.1",
+        ] {
+            let n = Node {
+                address: address.to_string(),
+                ..node()
+            };
+            assert!(n.validate().is_ok(), "{}", address);
+        }
+
+        let n = Node {
+            id: "".to_string(),
+            ..node()
+        };
+        assert_eq!(violations(n.validate()), vec!["node id is empty"]);
+
+        let n = Node {
+            id: "a/b".to_string(),
+            ..node()
+        };
+        assert!(violations(n.validate())[0].contains("'/'"));
+
+        for address in &["", "-bad.host", "bad host", "a..b", "10.0.0.1:80"] {
+            let n = Node {
+                address: address.to_string(),
+                ..node()
+            };
+            assert!(
+                violations(n.validate())[0].contains("address"),
+                "{}",
+                address
+            );
+        }
+
+        for port in &[0, -1, 65536] {
+            let n = Node {
+                port: *port,
+                ..node()
+            };
+            assert!(violations(n.validate())[0].contains("port"));
+        }
+
+        let mut n = node();
+        n.metadata.insert("has space".to_string(), "v".to_string());
+        assert!(violations(n.validate())[0].contains("metadata key 'has space'"));
+    }
+
+    #[test]
+    fn test_service_validate() {
+        let mut s = Service::new();
+        s.name = "io.vine.helloworld".to_string();
+        s.nodes = vec![node()];
+        assert!(s.validate().is_ok());
+
+        s.name = "io/vine".to_string();
+        assert!(violations(s.validate())[0].contains("'/'"));
+
+        s.name = "".to_string();
+        s.metadata.insert("\tkey".to_string(), "v".to_string());
+        s.metadata.insert("key\n".to_string(), "v".to_string());
+        s.nodes = vec![
+            node(),
+            Node {
+                id: "2".to_string(),
+                address: "".to_string(),
+                port: 0,
+                metadata: HashMap::new(),
+            },
+        ];
+        let details = violations(s.validate());
+        assert_eq!(details.len(), 5);
+        assert_eq!(details[0], "service name is empty");
+        assert!(details[3].starts_with("node 2 address"));
+        assert!(details[4].starts_with("node 2 port"));
+    }
+
+    #[test]
+    fn test_node_content_hash() {
+        let mut a = node();
+        let mut b = node();
+        assert_eq!(a.content_hash(), b.content_hash());
+
+        a.metadata.insert("zone".to_string(), "a".to_string());
+        assert_ne!(a.content_hash(), b.content_hash());
+
+        b.metadata.insert("zone".to_string(), "b".to_string());
+        assert_ne!(a.content_hash(), b.content_hash());
+
+        for i in 0..16 {
+            a.metadata.insert(format!("k{}", i), i.to_string());
+        }
+        let mut c = node();
+        for i in (0..16).rev() {
+            c.metadata.insert(format!("k{}", i), i.to_string());
+        }
+        c.metadata.insert("zone".to_string(), "a".to_string());
+        assert_eq!(a.content_hash(), c.content_hash());
+    }
+}
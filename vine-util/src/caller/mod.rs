@@ -8,14 +8,8 @@ pub fn caller(skip: usize) -> String {
         return out;
     }
     backtrace::resolve(frame.unwrap().ip(), |cb| {
-        let filename = cb.filename();
-        let lineno = cb.lineno();
-        if filename.is_some() && lineno.is_some() {
-            out = format!(
-                "{}:{}",
-                filename.unwrap().to_path_buf().to_str().unwrap(),
-                lineno.unwrap()
-            );
+        if let (Some(filename), Some(lineno)) = (cb.filename(), cb.lineno()) {
+            out = format!("{}:{}", filename.to_path_buf().to_str().unwrap(), lineno);
         }
     });
 